@@ -0,0 +1,447 @@
+use crate::gg18_key_gen::GG18SignContext;
+use crate::gg18_reshare::{deal, GG18ReshareDealers, GG18ReshareMsg1, GG18ReshareMsg2};
+use crate::identity::IdentityKey;
+use crate::li17_key_gen::{
+    li17_p1_key_gen1_with_share, li17_p1_key_gen2, li17_p2_key_gen1_with_share, li17_p2_key_gen2,
+    Li17KeyGenP1Context1, Li17KeyGenP1Msg2, Li17KeyGenP2Context1, Li17KeyGenP2Msg1,
+    Li17SignP1Context, Li17SignP2Context,
+};
+use crate::secret::Secret;
+use crate::session::{Envelope, SessionId, SessionMsg, SignedMsg};
+use crate::wire::MessageKind;
+use crate::Error;
+use curv::cryptographic_primitives::secret_sharing::feldman_vss::VerifiableSS;
+use curv::elliptic::curves::{p256::Secp256r1, Point, Scalar};
+use multi_party_ecdsa::protocols::multi_party_ecdsa::gg_2018::party_i::Keys;
+use multi_party_ecdsa::protocols::two_party_ecdsa::lindell_2017::party_one;
+use multi_party_ecdsa::utilities::mta::{MessageA, MessageB};
+use paillier::{DecryptionKey, EncryptionKey, KeyGeneration, Paillier};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::collections::BTreeMap;
+use zk_paillier::zkproofs::{NiCorrectKeyProof, SALT_STRING};
+
+/*
+Conversion between Li17 and GG18 keys, the public key stays the same.
+
+Li17 to GG18: the Li17 key is x1·x2·G. P1 and P2 turn their multiplicative
+shares into additive ones with an MtA under a fresh Paillier key of P1, then
+both deal their additive share to the new parties as dealers 1 and 2 of
+gg18_reshare. The new parties run gg18_reshare_receiver_1 and
+gg18_reshare_receiver_2 with li17_to_gg18_dealers of the Li17 public key and
+the identity keys of P1 and P2, which sign their deals as any dealer does. The
+receivers cannot know the public points of the MtA shares, so only the sum of
+the two deals is checked and a wrong deal is not blamed on P1 or P2. The MtA
+messages between P1 and P2 are not signed, P1 checks the response against the
+public share of P2 and a forged request to P2 only makes the sum check fail.
+
+    P1: li17_p1_to_gg18_1 -> P2: li17_p2_to_gg18 -> P1: li17_p1_to_gg18_2
+
+GG18 to Li17: two parties of a GG18 key with threshold 2 hold the additive
+shares w1 + w2 of the key. P1 picks a random x1 and the parties compute
+x2 = x1^-1·(w1 + w2) with an MtA under the GG18 Paillier key of P1, only P2
+learns x2. Li17 key generation is then run with the fixed shares x1 and x2.
+The messages before the Li17 key generation are signed with the GG18 identity
+keys of the two parties. The GG18 parties take the roles of P1 and P2 in the
+order of the calls:
+
+    P1: gg18_to_li17_p1_1 -> P2: gg18_to_li17_p2_1 -> P1: gg18_to_li17_p1_2
+    -> P2: gg18_to_li17_p2_2 -> P1: gg18_to_li17_p1_3 -> P2: gg18_to_li17_p2_3
+
+Both directions end with a check against the old public key, a party that
+cheats in the MtA makes the conversion fail but is not identified.
+*/
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Li17ToGg18P1Context1 {
+    session: SessionId,
+    identity: IdentityKey,
+    public_p2: Point<Secp256r1>,
+    x1: Secret<Scalar<Secp256r1>>,
+    dk: Secret<DecryptionKey>,
+}
+
+impl_clone_secret!(Li17ToGg18P1Context1 {
+    session,
+    identity,
+    public_p2,
+    x1,
+    dk,
+});
+
+pub type Li17ToGg18Msg1 = SessionMsg<(EncryptionKey, NiCorrectKeyProof, MessageA)>;
+
+pub type Li17ToGg18Msg2 = SessionMsg<MessageB>;
+
+/*
+The dealers P1 and P2 for gg18_reshare_receiver_1, pk is the Li17 public key
+and p1_identity and p2_identity the identity public keys P1 and P2 sign their
+deals with
+*/
+pub fn li17_to_gg18_dealers(
+    pk: Point<Secp256r1>,
+    p1_identity: Point<Secp256r1>,
+    p2_identity: Point<Secp256r1>,
+) -> GG18ReshareDealers {
+    let identities = vec![(1, p1_identity), (2, p2_identity)]
+        .into_iter()
+        .collect();
+    GG18ReshareDealers::with_sum(identities, pk)
+}
+
+// identity is the key P1 signs its deal with
+pub fn li17_p1_to_gg18_1(
+    context: Li17SignP1Context,
+    identity: IdentityKey,
+    session: SessionId,
+) -> Result<(Li17ToGg18Msg1, Li17ToGg18P1Context1), Error> {
+    let (ek, dk) = Paillier::keypair().keys();
+    let correct_key_proof = NiCorrectKeyProof::proof(&dk, None);
+    let (m_a, _) = MessageA::a(&context.x1, &ek, &[]);
+
+    let context1 = Li17ToGg18P1Context1 {
+        session,
+        identity,
+        public_p2: context.public_p2,
+        x1: context.x1,
+        dk: Secret::new(dk),
+    };
+    Ok((
+        SessionMsg::new(session, (ek, correct_key_proof, m_a)),
+        context1,
+    ))
+}
+
+/*
+keys are the round 1 messages of the new parties (gg18_reshare_receiver_1) and
+identities their identity public keys. The deal, signed with identity, goes to
+all new parties, the MtA response to P1.
+*/
+pub fn li17_p2_to_gg18(
+    msg: Li17ToGg18Msg1,
+    keys: BTreeMap<u16, GG18ReshareMsg1>,
+    context: Li17SignP2Context,
+    identity: &IdentityKey,
+    identities: &[Point<Secp256r1>],
+    threshold: u16,
+    session: SessionId,
+) -> Result<(Li17ToGg18Msg2, GG18ReshareMsg2), Error> {
+    let (ek, correct_key_proof, m_a) = msg.open(&session, 1, Some(1))?;
+    if correct_key_proof.verify(&ek, SALT_STRING).is_err() {
+        return Err(Error::InvalidPaillierKey {
+            round: 1,
+            party: Some(1),
+        });
+    }
+
+    let (m_b, beta, _, _) =
+        MessageB::b(&context.x2, &ek, m_a, &[]).map_err(|_| Error::InvalidProof {
+            round: 1,
+            party: Some(1),
+        })?;
+    let beta = Secret::new(beta);
+
+    let shares = deal(keys, 2, &beta, identity, identities, threshold, session)?;
+    Ok((SessionMsg::new(session, m_b), shares))
+}
+
+pub fn li17_p1_to_gg18_2(
+    msg: Li17ToGg18Msg2,
+    keys: BTreeMap<u16, GG18ReshareMsg1>,
+    context: Li17ToGg18P1Context1,
+    identities: &[Point<Secp256r1>],
+    threshold: u16,
+) -> Result<GG18ReshareMsg2, Error> {
+    let m_b = msg.open(&context.session, 2, Some(2))?;
+
+    // the MtA must use the share behind the Li17 public share of P2
+    let alpha = match m_b.verify_proofs_get_alpha(&context.dk, &context.x1) {
+        Ok((alpha, _)) if m_b.b_proof.pk == context.public_p2 => Secret::new(alpha),
+        _ => {
+            return Err(Error::InvalidProof {
+                round: 2,
+                party: Some(2),
+            })
+        }
+    };
+
+    deal(
+        keys,
+        1,
+        &alpha,
+        &context.identity,
+        identities,
+        threshold,
+        context.session,
+    )
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Gg18ToLi17P1Context1 {
+    session: SessionId,
+    identity: IdentityKey,
+    other_identity: Point<Secp256r1>,
+    pk: Point<Secp256r1>,
+    x1_inv: Secret<Scalar<Secp256r1>>,
+    w1: Secret<Scalar<Secp256r1>>,
+    dk: Secret<DecryptionKey>,
+    public_w2: Point<Secp256r1>,
+    key_gen: Li17KeyGenP1Context1,
+}
+
+impl_clone_secret!(Gg18ToLi17P1Context1 {
+    session,
+    identity,
+    other_identity,
+    pk,
+    x1_inv,
+    w1,
+    dk,
+    public_w2,
+    key_gen,
+});
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Gg18ToLi17P1Context2 {
+    pk: Point<Secp256r1>,
+    key_gen: Li17KeyGenP1Context1,
+}
+
+impl_clone_secret!(Gg18ToLi17P1Context2 { pk, key_gen });
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Gg18ToLi17P2Context1 {
+    session: SessionId,
+    other_identity: Point<Secp256r1>,
+    pk: Point<Secp256r1>,
+    beta: Secret<Scalar<Secp256r1>>,
+    p1_msg1: party_one::KeyGenFirstMsg,
+}
+
+impl_clone_secret!(Gg18ToLi17P2Context1 {
+    session,
+    other_identity,
+    pk,
+    beta,
+    p1_msg1,
+});
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Gg18ToLi17P2Context2 {
+    pk: Point<Secp256r1>,
+    key_gen: Li17KeyGenP2Context1,
+}
+
+impl_clone_secret!(Gg18ToLi17P2Context2 { pk, key_gen });
+
+pub type Gg18ToLi17Msg1 = SignedMsg<(MessageA, party_one::KeyGenFirstMsg)>;
+
+pub type Gg18ToLi17Msg2 = SignedMsg<MessageB>;
+
+// P1's part of x2, P2 adds its MtA share
+pub type Gg18ToLi17Msg3 = SignedMsg<Scalar<Secp256r1>>;
+
+// other is the GG18 index of the party that becomes P2
+pub fn gg18_to_li17_p1_1(
+    context: GG18SignContext,
+    other: u16,
+    session: SessionId,
+) -> Result<(Gg18ToLi17Msg1, Gg18ToLi17P1Context1), Error> {
+    check_pair(&context, other)?;
+
+    let w1 = Secret::new(lagrange_weight(&context, other) * &context.shared_keys.x_i);
+    let public_w2 = Keys::update_commitments_to_xi(
+        &Keys::get_commitments_to_xi(&context.vss_scheme_vec)[other as usize],
+        &context.vss_scheme_vec[other as usize],
+        other,
+        &[context.index, other],
+    );
+
+    // any x1 works, as after a Li17 refresh, see li17_p1_key_gen1
+    let x1 = Secret::new(Scalar::<Secp256r1>::random());
+    let x1_inv = Secret::new(x1.invert().ok_or(Error::MalformedInput {
+        round: 1,
+        party: None,
+    })?);
+    let (m_a, _) = MessageA::a(&x1_inv, &context.party_keys.ek, &[]);
+    let (p1_msg1, key_gen) = li17_p1_key_gen1_with_share(session, x1);
+
+    let msg1 = SignedMsg::sign(
+        session,
+        (m_a, p1_msg1),
+        &context.identity,
+        &pair_envelope(MessageKind::GG18_TO_LI17_P1_MSG1),
+    )?;
+    let context1 = Gg18ToLi17P1Context1 {
+        session,
+        other_identity: context.identities[other as usize].clone(),
+        identity: context.identity,
+        pk: context.pk,
+        x1_inv,
+        w1,
+        dk: Secret::new(context.party_keys.dk.clone()),
+        public_w2,
+        key_gen,
+    };
+    Ok((msg1, context1))
+}
+
+// other is the GG18 index of the party that becomes P1
+pub fn gg18_to_li17_p2_1(
+    msg: Gg18ToLi17Msg1,
+    context: GG18SignContext,
+    other: u16,
+    session: SessionId,
+) -> Result<(Gg18ToLi17Msg2, Gg18ToLi17P2Context1), Error> {
+    check_pair(&context, other)?;
+    let envelope = pair_envelope(MessageKind::GG18_TO_LI17_P1_MSG1);
+    msg.check(
+        &session,
+        context.identities.get(other as usize),
+        &envelope,
+        1,
+    )?;
+    let (m_a, p1_msg1) = msg.body;
+
+    let w2 = Secret::new(lagrange_weight(&context, other) * &context.shared_keys.x_i);
+    let (m_b, beta, _, _) = MessageB::b(&w2, &context.paillier_key_vec[other as usize], m_a, &[])
+        .map_err(|_| Error::InvalidProof {
+        round: 1,
+        party: Some(1),
+    })?;
+
+    let msg2 = SignedMsg::sign(
+        session,
+        m_b,
+        &context.identity,
+        &pair_envelope(MessageKind::GG18_TO_LI17_P2_MSG1),
+    )?;
+    let context1 = Gg18ToLi17P2Context1 {
+        session,
+        other_identity: context.identities[other as usize].clone(),
+        pk: context.pk,
+        beta: Secret::new(beta),
+        p1_msg1,
+    };
+    Ok((msg2, context1))
+}
+
+pub fn gg18_to_li17_p1_2(
+    msg: Gg18ToLi17Msg2,
+    context: Gg18ToLi17P1Context1,
+) -> Result<(Gg18ToLi17Msg3, Gg18ToLi17P1Context2), Error> {
+    let envelope = pair_envelope(MessageKind::GG18_TO_LI17_P2_MSG1);
+    msg.check(
+        &context.session,
+        Some(&context.other_identity),
+        &envelope,
+        2,
+    )?;
+    let m_b = msg.body;
+
+    // the MtA must use the weighted GG18 share of P2
+    let alpha = match m_b.verify_proofs_get_alpha(&context.dk, &context.x1_inv) {
+        Ok((alpha, _)) if m_b.b_proof.pk == context.public_w2 => Secret::new(alpha),
+        _ => {
+            return Err(Error::InvalidProof {
+                round: 2,
+                party: Some(2),
+            })
+        }
+    };
+    let part = &*context.x1_inv * &*context.w1 + &*alpha;
+
+    let msg3 = SignedMsg::sign(
+        context.session,
+        part,
+        &context.identity,
+        &pair_envelope(MessageKind::GG18_TO_LI17_P1_MSG2),
+    )?;
+    let context2 = Gg18ToLi17P1Context2 {
+        pk: context.pk,
+        key_gen: context.key_gen,
+    };
+    Ok((msg3, context2))
+}
+
+pub fn gg18_to_li17_p2_2(
+    msg: Gg18ToLi17Msg3,
+    context: Gg18ToLi17P2Context1,
+) -> Result<(Li17KeyGenP2Msg1, Gg18ToLi17P2Context2), Error> {
+    let envelope = pair_envelope(MessageKind::GG18_TO_LI17_P1_MSG2);
+    msg.check(
+        &context.session,
+        Some(&context.other_identity),
+        &envelope,
+        2,
+    )?;
+    let x2 = Secret::new(msg.body + &*context.beta);
+    let (msg, key_gen) = li17_p2_key_gen1_with_share(context.p1_msg1, context.session, x2);
+    let context2 = Gg18ToLi17P2Context2 {
+        pk: context.pk,
+        key_gen,
+    };
+    Ok((msg, context2))
+}
+
+pub fn gg18_to_li17_p1_3(
+    msg: Li17KeyGenP2Msg1,
+    context: Gg18ToLi17P1Context2,
+) -> Result<(Li17KeyGenP1Msg2, Li17SignP1Context), Error> {
+    let (msg, sign_context) = li17_p1_key_gen2(msg, context.key_gen)?;
+    if sign_context.public != context.pk {
+        return Err(Error::MalformedInput {
+            round: 3,
+            party: Some(2),
+        });
+    }
+    Ok((msg, sign_context))
+}
+
+pub fn gg18_to_li17_p2_3(
+    msg: Li17KeyGenP1Msg2,
+    context: Gg18ToLi17P2Context2,
+) -> Result<Li17SignP2Context, Error> {
+    let (_, sign_context) = li17_p2_key_gen2(msg, context.key_gen)?;
+    if sign_context.public != context.pk {
+        return Err(Error::MalformedInput {
+            round: 3,
+            party: Some(1),
+        });
+    }
+    Ok(sign_context)
+}
+
+fn check_pair(context: &GG18SignContext, other: u16) -> Result<(), Error> {
+    if context.threshold != 2
+        || other == context.index
+        || other as usize >= context.vss_scheme_vec.len()
+        || other as usize >= context.identities.len()
+    {
+        return Err(Error::MalformedInput {
+            round: 1,
+            party: None,
+        });
+    }
+    Ok(())
+}
+
+// a message between P1 and P2, whose roles are its sender and receiver
+fn pair_envelope(kind: MessageKind) -> Envelope<'static> {
+    let sender = kind.sender as u16;
+    Envelope {
+        kind,
+        sender,
+        receiver: Some(3 - sender),
+        binding: &[],
+    }
+}
+
+// the weight of our share when the two parties of the conversion sign
+fn lagrange_weight(context: &GG18SignContext, other: u16) -> Scalar<Secp256r1> {
+    VerifiableSS::<Secp256r1, Sha256>::map_share_to_new_params(
+        &context.vss_scheme_vec[context.index as usize].parameters,
+        context.index,
+        &[context.index, other],
+    )
+}
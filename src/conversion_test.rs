@@ -0,0 +1,183 @@
+use crate::conversion::*;
+use crate::gg18_reshare::{deal, gg18_reshare_receiver_1, gg18_reshare_receiver_2};
+use crate::gg18_test::{check_sig, identities, keygen, SID};
+use crate::identity::IdentityKey;
+use crate::li17_state::{Li17P1Output, Li17P2Output};
+use crate::secret::CloneSecret;
+use crate::simulation::Simulation;
+use crate::Error;
+use curv::elliptic::curves::{p256::Secp256r1, Scalar};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+
+#[test]
+fn li17_to_gg18_2_of_3() {
+    let (p1, p2) = crate::li17_test::run_keygen();
+    let pk = p1.public.clone();
+    let (p1_key, p2_key) = (IdentityKey::random(), IdentityKey::random());
+
+    let (identity_keys, ids) = identities(3);
+    let (keys, receivers): (Vec<_>, Vec<_>) = identity_keys
+        .into_iter()
+        .enumerate()
+        .map(|(i, k)| {
+            let dealers = li17_to_gg18_dealers(pk.clone(), p1_key.public(), p2_key.public());
+            gg18_reshare_receiver_1(2, i as u16, dealers, SID, k, ids.clone()).unwrap()
+        })
+        .unzip();
+    let keys: BTreeMap<u16, _> = keys
+        .into_iter()
+        .enumerate()
+        .map(|(i, m)| (i as u16, m))
+        .collect();
+
+    let (msg1, p1) = li17_p1_to_gg18_1(p1, p1_key, SID).unwrap();
+    let (msg2, deal2) = li17_p2_to_gg18(msg1, keys.clone(), p2, &p2_key, &ids, 2, SID).unwrap();
+    let deal1 = li17_p1_to_gg18_2(msg2, keys.clone(), p1, &ids, 2).unwrap();
+    let deals: BTreeMap<u16, _> = vec![(1, deal1), (2, deal2)].into_iter().collect();
+
+    // a deal as P2 under an identity key the receivers do not know
+    let share = Scalar::<Secp256r1>::random();
+    let forged = deal(
+        keys.clone(),
+        2,
+        &share,
+        &IdentityKey::random(),
+        &ids,
+        2,
+        SID,
+    )
+    .unwrap();
+    let mut forged_deals = deals.clone();
+    forged_deals.insert(2, forged);
+    let mut others = keys.clone();
+    others.remove(&0);
+    assert_eq!(
+        gg18_reshare_receiver_2(others, forged_deals, receivers[0].clone_secret()).unwrap_err(),
+        Error::InvalidIdentitySignature {
+            round: 3,
+            party: Some(2)
+        }
+    );
+
+    let contexts: Vec<_> = receivers
+        .into_iter()
+        .enumerate()
+        .map(|(i, c)| {
+            let mut others = keys.clone();
+            others.remove(&(i as u16));
+            gg18_reshare_receiver_2(others, deals.clone(), c).unwrap()
+        })
+        .collect();
+    assert!(contexts.iter().all(|c| c.pk == pk));
+
+    // the recovery party signs with either of the old parties
+    let message = b"converted";
+    let hash = Sha256::digest(message);
+    let signers = vec![contexts[0].clone_secret(), contexts[2].clone_secret()];
+    let outcomes = Simulation::new(SID).gg18_sign(signers, &hash).unwrap();
+    for outcome in outcomes {
+        check_sig(&outcome.output().unwrap(), message, &pk);
+    }
+}
+
+#[test]
+fn li17_to_gg18_rejects_foreign_mta() {
+    let (p1, _) = crate::li17_test::run_keygen();
+    let (_, other_p2) = crate::li17_test::run_keygen();
+
+    let (p1_key, p2_key) = (IdentityKey::random(), IdentityKey::random());
+    let (identity_keys, ids) = identities(2);
+    let (keys, _): (Vec<_>, Vec<_>) = identity_keys
+        .into_iter()
+        .enumerate()
+        .map(|(i, k)| {
+            let dealers = li17_to_gg18_dealers(p1.public.clone(), p1_key.public(), p2_key.public());
+            gg18_reshare_receiver_1(2, i as u16, dealers, SID, k, ids.clone()).unwrap()
+        })
+        .unzip();
+    let keys: BTreeMap<u16, _> = keys
+        .into_iter()
+        .enumerate()
+        .map(|(i, m)| (i as u16, m))
+        .collect();
+
+    // P2 of another key answers the MtA
+    let (msg1, p1) = li17_p1_to_gg18_1(p1, p1_key, SID).unwrap();
+    let (msg2, _) = li17_p2_to_gg18(msg1, keys.clone(), other_p2, &p2_key, &ids, 2, SID).unwrap();
+    assert_eq!(
+        li17_p1_to_gg18_2(msg2, keys, p1, &ids, 2).unwrap_err(),
+        Error::InvalidProof {
+            round: 2,
+            party: Some(2)
+        }
+    );
+}
+
+#[test]
+fn gg18_to_li17() {
+    let contexts = keygen(3, 2);
+    let pk = contexts[0].pk.clone();
+
+    // party 1 cannot step in for P1 of the pair 2 and 0
+    let (msg1, _) = gg18_to_li17_p1_1(contexts[1].clone_secret(), 0, SID).unwrap();
+    assert_eq!(
+        gg18_to_li17_p2_1(msg1, contexts[0].clone_secret(), 2, SID).unwrap_err(),
+        Error::InvalidIdentitySignature {
+            round: 1,
+            party: Some(1)
+        }
+    );
+
+    // parties 2 and 0 become P1 and P2
+    let (msg1, p1) = gg18_to_li17_p1_1(contexts[2].clone_secret(), 0, SID).unwrap();
+    let (msg2, p2) = gg18_to_li17_p2_1(msg1, contexts[0].clone_secret(), 2, SID).unwrap();
+    let (msg3, p1) = gg18_to_li17_p1_2(msg2, p1).unwrap();
+    let (msg4, p2) = gg18_to_li17_p2_2(msg3, p2).unwrap();
+    let (msg5, p1) = gg18_to_li17_p1_3(msg4, p1).unwrap();
+    let p2 = gg18_to_li17_p2_3(msg5, p2).unwrap();
+    assert_eq!(p1.public, pk);
+    assert_eq!(p2.public, pk);
+
+    let message = b"converted";
+    let hash = Sha256::digest(message);
+    let (sig, _) = Simulation::new(SID).li17_sign(p1, p2, &hash).unwrap();
+    match sig.output() {
+        Some(Li17P1Output::Signature(sig)) => check_sig(&sig, message, &pk),
+        _ => panic!("expected a signature"),
+    }
+
+    // only 2-of-n keys collapse into a pair
+    let contexts = keygen(3, 3);
+    assert!(gg18_to_li17_p1_1(contexts[0].clone_secret(), 1, SID).is_err());
+}
+
+#[test]
+fn gg18_to_li17_refresh_and_sign() {
+    let contexts = keygen(3, 2);
+    let pk = contexts[0].pk.clone();
+
+    let (msg1, p1) = gg18_to_li17_p1_1(contexts[0].clone_secret(), 1, SID).unwrap();
+    let (msg2, p2) = gg18_to_li17_p2_1(msg1, contexts[1].clone_secret(), 0, SID).unwrap();
+    let (msg3, p1) = gg18_to_li17_p1_2(msg2, p1).unwrap();
+    let (msg4, p2) = gg18_to_li17_p2_2(msg3, p2).unwrap();
+    let (msg5, p1) = gg18_to_li17_p1_3(msg4, p1).unwrap();
+    let p2 = gg18_to_li17_p2_3(msg5, p2).unwrap();
+
+    // the converted shares refresh like shares of a Li17 key generation
+    let (p1, p2) = Simulation::new(SID).li17_refresh(p1, p2).unwrap();
+    let (p1, p2) = match (p1.output(), p2.output()) {
+        (Some(Li17P1Output::KeyShare(p1)), Some(Li17P2Output::KeyShare(p2))) => (p1, p2),
+        _ => panic!("expected key shares"),
+    };
+    assert_eq!(p1.public, pk);
+    assert_eq!(p2.public, pk);
+
+    let message = b"converted and refreshed";
+    let hash = Sha256::digest(message);
+    let (sig, _) = Simulation::new(SID).li17_sign(p1, p2, &hash).unwrap();
+    match sig.output() {
+        Some(Li17P1Output::Signature(sig)) => check_sig(&sig, message, &pk),
+        _ => panic!("expected a signature"),
+    }
+}
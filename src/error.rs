@@ -0,0 +1,204 @@
+use crate::evidence::Evidence;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+
+/*
+Errors returned by the protocol rounds.
+
+`round` is the number of the round function that failed (3 for gg18_sign3,
+2 for li17_p1_key_gen2, ...). `party` is the index of the party whose message
+caused the failure, if it can be determined: Li17 parties are 1 and 2, GG18
+parties are identified by their key generation index. GG18 rounds take their
+messages keyed by sender and return WrongMessageCount or UnexpectedSender if
+the senders are not exactly the other participants of the round. InvalidEncoding,
+UnsupportedVersion and DecryptionFailed are not tied to a round, they are
+returned when decoding signatures, stored contexts and sealed key shares.
+Transport is returned when a connection to another party fails or is closed,
+UnexpectedOutput when a state machine finishes without the output of its run.
+Storage and PresignatureUnavailable are returned by the presignature pool.
+Misbehavior blames a GG18 party and carries the evidence, see evidence.rs.
+InvalidIdentitySignature is returned when a GG18 message is not signed by the
+identity key of its sender for what the receiver expects, it does not prove
+that the sender misbehaved.
+*/
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Error {
+    CommitmentMismatch {
+        round: u16,
+        party: Option<u16>,
+    },
+    InvalidProof {
+        round: u16,
+        party: Option<u16>,
+    },
+    InvalidPaillierKey {
+        round: u16,
+        party: Option<u16>,
+    },
+    InvalidVss {
+        round: u16,
+        party: Option<u16>,
+    },
+    WrongMessageCount {
+        round: u16,
+        expected: usize,
+        received: usize,
+    },
+    MalformedInput {
+        round: u16,
+        party: Option<u16>,
+    },
+    InvalidSignature {
+        round: u16,
+    },
+    InvalidEphemeralKey {
+        round: u16,
+        party: Option<u16>,
+    },
+    SessionMismatch {
+        round: u16,
+        party: Option<u16>,
+    },
+    UnexpectedSender {
+        round: u16,
+        party: u16,
+    },
+    InvalidIdentitySignature {
+        round: u16,
+        party: Option<u16>,
+    },
+    Misbehavior {
+        evidence: Evidence,
+    },
+    InvalidEncoding,
+    UnsupportedVersion {
+        version: u16,
+    },
+    DecryptionFailed,
+    Transport,
+    Storage,
+    PresignatureUnavailable,
+    UnexpectedOutput,
+}
+
+impl Error {
+    pub fn round(&self) -> Option<u16> {
+        match *self {
+            Error::CommitmentMismatch { round, .. }
+            | Error::InvalidProof { round, .. }
+            | Error::InvalidPaillierKey { round, .. }
+            | Error::InvalidVss { round, .. }
+            | Error::WrongMessageCount { round, .. }
+            | Error::MalformedInput { round, .. }
+            | Error::InvalidSignature { round }
+            | Error::InvalidEphemeralKey { round, .. }
+            | Error::SessionMismatch { round, .. }
+            | Error::UnexpectedSender { round, .. }
+            | Error::InvalidIdentitySignature { round, .. } => Some(round),
+            Error::Misbehavior { ref evidence } => Some(evidence.round),
+            Error::InvalidEncoding
+            | Error::UnsupportedVersion { .. }
+            | Error::DecryptionFailed
+            | Error::Transport
+            | Error::Storage
+            | Error::PresignatureUnavailable
+            | Error::UnexpectedOutput => None,
+        }
+    }
+
+    pub fn party(&self) -> Option<u16> {
+        match *self {
+            Error::CommitmentMismatch { party, .. }
+            | Error::InvalidProof { party, .. }
+            | Error::InvalidPaillierKey { party, .. }
+            | Error::InvalidVss { party, .. }
+            | Error::MalformedInput { party, .. }
+            | Error::InvalidEphemeralKey { party, .. }
+            | Error::SessionMismatch { party, .. }
+            | Error::InvalidIdentitySignature { party, .. } => party,
+            Error::UnexpectedSender { party, .. } => Some(party),
+            Error::Misbehavior { ref evidence } => Some(evidence.party),
+            Error::WrongMessageCount { .. }
+            | Error::InvalidSignature { .. }
+            | Error::InvalidEncoding
+            | Error::UnsupportedVersion { .. }
+            | Error::DecryptionFailed
+            | Error::Transport
+            | Error::Storage
+            | Error::PresignatureUnavailable
+            | Error::UnexpectedOutput => None,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (round, what) = match *self {
+            Error::CommitmentMismatch { round, .. } => (round, "commitment mismatch"),
+            Error::InvalidProof { round, .. } => (round, "invalid zero-knowledge proof"),
+            Error::InvalidPaillierKey { round, .. } => (round, "invalid Paillier key"),
+            Error::InvalidVss { round, .. } => (round, "invalid verifiable secret sharing"),
+            Error::MalformedInput { round, .. } => (round, "malformed input"),
+            Error::InvalidSignature { round } => (round, "final signature is invalid"),
+            Error::InvalidEphemeralKey { round, .. } => {
+                (round, "invalid ephemeral key decommitment")
+            }
+            Error::SessionMismatch { round, .. } => (round, "message from another session"),
+            Error::UnexpectedSender { round, .. } => (round, "unexpected message"),
+            Error::InvalidIdentitySignature { round, .. } => (round, "invalid identity signature"),
+            Error::Misbehavior { ref evidence } => (evidence.round, "misbehavior"),
+            Error::WrongMessageCount {
+                round,
+                expected,
+                received,
+            } => {
+                return write!(
+                    f,
+                    "round {}: expected {} messages, received {}",
+                    round, expected, received
+                );
+            }
+            Error::InvalidEncoding => return write!(f, "invalid encoding"),
+            Error::DecryptionFailed => {
+                return write!(f, "wrong password or key, or tampered key share");
+            }
+            Error::UnsupportedVersion { version } => {
+                return write!(f, "unsupported format version {}", version);
+            }
+            Error::Transport => return write!(f, "connection to another party failed"),
+            Error::Storage => return write!(f, "presignature storage failed"),
+            Error::PresignatureUnavailable => {
+                return write!(f, "presignature is not available or already used");
+            }
+            Error::UnexpectedOutput => {
+                return write!(f, "protocol run finished without the expected output");
+            }
+        };
+        match self.party() {
+            Some(party) => write!(f, "round {}: {} from party {}", round, what, party),
+            None => write!(f, "round {}: {}", round, what),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+// checks that exactly the expected senders delivered a message
+pub(crate) fn check_senders<T>(
+    round: u16,
+    messages: &BTreeMap<u16, T>,
+    expected: &[u16],
+) -> Result<(), Error> {
+    if messages.len() != expected.len() {
+        return Err(Error::WrongMessageCount {
+            round,
+            expected: expected.len(),
+            received: messages.len(),
+        });
+    }
+    if let Some(&party) = messages.keys().find(|j| !expected.contains(j)) {
+        return Err(Error::UnexpectedSender { round, party });
+    }
+    Ok(())
+}
@@ -0,0 +1,487 @@
+use crate::gg18_key_gen::{
+    open_share, GG18KeyGenMsg1, GG18KeyGenMsg2, GG18KeyGenMsg3, GG18KeyGenMsg4, GG18KeyGenMsg5,
+};
+use crate::gg18_sign::{
+    GG18SignMsg1, GG18SignMsg2, GG18SignMsg4, GG18SignMsg5, GG18SignMsg6, GG18SignMsg7,
+    GG18SignMsg8,
+};
+use crate::session::{Envelope, SessionId, SignedMsg};
+use crate::wire::{MessageKind, Wire, MAX_MESSAGE_LEN};
+use crate::Error;
+use bincode::Options;
+use curv::arithmetic::traits::*;
+use curv::cryptographic_primitives::commitments::hash_commitment::HashCommitment;
+use curv::cryptographic_primitives::commitments::traits::Commitment;
+use curv::cryptographic_primitives::hashing::DigestExt;
+use curv::cryptographic_primitives::proofs::sigma_correct_homomorphic_elgamal_enc::{
+    HomoELGamalProof, HomoElGamalStatement,
+};
+use curv::cryptographic_primitives::proofs::sigma_dlog::DLogProof;
+use curv::cryptographic_primitives::secret_sharing::feldman_vss::{
+    ShamirSecretSharing, VerifiableSS,
+};
+use curv::elliptic::curves::{p256::Secp256r1, Point, Scalar};
+use curv::BigInt;
+use multi_party_ecdsa::protocols::multi_party_ecdsa::gg_2018::party_i::{
+    KeyGenBroadcastMessage1, KeyGenDecommitMessage1, Phase5ADecom1, Phase5Com1, Phase5Com2,
+    Phase5DDecom2, SignBroadcastPhase1, SignDecommitPhase1,
+};
+use multi_party_ecdsa::utilities::mta::MessageB;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use zk_paillier::zkproofs::SALT_STRING;
+
+/*
+Evidence of a misbehaving GG18 party.
+
+When a check of gg18_key_gen or gg18_sign fails for the data of a single party,
+the round returns Error::Misbehavior with the messages that failed the check.
+Evidence::verify repeats the check without any secret of the accusing party,
+so other parties and operators can confirm the accusation before excluding
+the party.
+
+The messages are recorded as the accused party signed them with its identity
+key, see SignedMsg. verify takes the identity public key of the accused party
+and checks the signatures first, an accuser cannot make up messages the party
+did not send. The session of the evidence is the session the messages were
+signed for, for batch signing the sub-session of the instance. Evidence of
+round 4 and 5 of key generation reveals the ephemeral ECDH key of
+the accusing party and with it every share it received, the aborted key
+generation must not be continued.
+
+Evidence of round 3 of signing also records the signers and the public key
+share of the accused party, the MtA proofs are checked against its weighted
+share. They are not signed by the accused, public_data returns them and the
+verifier compares them with the signing run and the key before trusting verify.
+
+Failures of checks over the sum of all contributions (delta in round 4 and the
+final check of round 9 of signing, the final signature) do not identify a
+party and keep their error without party.
+*/
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Evidence {
+    pub session: SessionId,
+    pub round: u16,
+    pub party: u16,
+    claim: Vec<u8>,
+}
+
+impl Evidence {
+    pub(crate) fn new(
+        session: SessionId,
+        round: u16,
+        party: u16,
+        claim: &Claim,
+    ) -> Result<Self, Error> {
+        let claim = claim_options()
+            .serialize(claim)
+            .map_err(|_| Error::InvalidEncoding)?;
+        Ok(Evidence {
+            session,
+            round,
+            party,
+            claim,
+        })
+    }
+
+    // the signers and the public key share of the accused that the claim is checked against
+    pub fn public_data(&self) -> Option<(Vec<u16>, Point<Secp256r1>)> {
+        match claim_options().deserialize(&self.claim) {
+            Ok(Claim::SignMtaProof {
+                indices,
+                public_share,
+                ..
+            }) => Some((indices, public_share)),
+            _ => None,
+        }
+    }
+
+    // true if party signed the recorded messages and they fail the check of the round
+    pub fn verify(&self, identity: &Point<Secp256r1>) -> bool {
+        let claim: Claim = match claim_options().deserialize(&self.claim) {
+            Ok(claim) => claim,
+            Err(_) => return false,
+        };
+        match claim {
+            Claim::KeyGenDecommitment {
+                commitment,
+                decommitment,
+            } => {
+                self.round == 3
+                    && self.signed(
+                        identity,
+                        &commitment,
+                        MessageKind::GG18_KEY_GEN_MSG1,
+                        None,
+                        &[],
+                    )
+                    && self.signed(
+                        identity,
+                        &decommitment,
+                        MessageKind::GG18_KEY_GEN_MSG2,
+                        None,
+                        &[],
+                    )
+                    && !key_gen_decommitment_valid(&commitment.body.0, &decommitment.body)
+            }
+            Claim::KeyGenShare {
+                receiver,
+                receiver_dh_secret,
+                dh_public,
+                sealed_share,
+                commitments,
+            } => {
+                // the party sealed the share for the ECDH key of receiver_dh_secret
+                let binding = (Point::generator() * &receiver_dh_secret).to_bytes(true);
+                if !self.signed(
+                    identity,
+                    &dh_public,
+                    MessageKind::GG18_KEY_GEN_MSG1,
+                    None,
+                    &[],
+                ) || !self.signed(
+                    identity,
+                    &sealed_share,
+                    MessageKind::GG18_KEY_GEN_MSG3,
+                    Some(receiver),
+                    binding.as_ref(),
+                ) {
+                    return false;
+                }
+                let share = open_share(
+                    &self.session,
+                    self.party,
+                    receiver,
+                    &receiver_dh_secret,
+                    &dh_public.body.1,
+                    &sealed_share.body,
+                );
+                match (share, commitments) {
+                    (None, None) => self.round == 4,
+                    (Some(share), Some((decommitment, vss_scheme))) => {
+                        self.round == 5
+                            && self.signed(
+                                identity,
+                                &decommitment,
+                                MessageKind::GG18_KEY_GEN_MSG2,
+                                None,
+                                &[],
+                            )
+                            && self.signed(
+                                identity,
+                                &vss_scheme,
+                                MessageKind::GG18_KEY_GEN_MSG4,
+                                None,
+                                &[],
+                            )
+                            && !key_gen_share_valid(
+                                &decommitment.body.y_i,
+                                &vss_scheme.body,
+                                &share,
+                                receiver,
+                            )
+                    }
+                    _ => false,
+                }
+            }
+            Claim::KeyGenDlogProof { proof } => {
+                self.round == 6
+                    && self.signed(identity, &proof, MessageKind::GG18_KEY_GEN_MSG5, None, &[])
+                    && DLogProof::verify(&proof.body).is_err()
+            }
+            Claim::SignMtaProof {
+                presign,
+                receiver,
+                indices,
+                parameters,
+                public_share,
+                proofs,
+            } => {
+                let mut sorted = indices.clone();
+                sorted.sort_unstable();
+                sorted.dedup();
+                if sorted.len() != indices.len()
+                    || receiver == self.party
+                    || !indices.contains(&receiver)
+                    || !indices.contains(&self.party)
+                {
+                    return false;
+                }
+                let lambda = VerifiableSS::<Secp256r1, Sha256>::map_share_to_new_params(
+                    &parameters,
+                    self.party,
+                    &indices,
+                );
+                self.round == 3
+                    && self.signed(
+                        identity,
+                        &proofs,
+                        MessageKind::gg18_sign_round(presign, 2),
+                        Some(receiver),
+                        &[],
+                    )
+                    && !sign_mta_proofs_valid(
+                        &proofs.body.0,
+                        &proofs.body.1,
+                        &(&public_share * &lambda),
+                    )
+            }
+            Claim::SignDecommitment {
+                presign,
+                receiver,
+                commitment,
+                b_proof,
+                decommitment,
+            } => {
+                self.round == 5
+                    && self.signed(
+                        identity,
+                        &commitment,
+                        MessageKind::gg18_sign_round(presign, 1),
+                        None,
+                        &[],
+                    )
+                    && self.signed(
+                        identity,
+                        &b_proof,
+                        MessageKind::gg18_sign_round(presign, 2),
+                        Some(receiver),
+                        &[],
+                    )
+                    && self.signed(
+                        identity,
+                        &decommitment,
+                        MessageKind::gg18_sign_round(presign, 4),
+                        None,
+                        &[],
+                    )
+                    && !sign_decommitment_valid(
+                        &commitment.body.0,
+                        &decommitment.body,
+                        &b_proof.body.0.b_proof,
+                    )
+            }
+            Claim::SignPhase5A {
+                r,
+                commitment,
+                decommitment,
+            } => {
+                let binding = r.to_bytes(true);
+                let (decommitment_5a, elgamal_proof, dlog_proof) = &decommitment.body;
+                self.round == 7
+                    && self.signed(
+                        identity,
+                        &commitment,
+                        MessageKind::GG18_SIGN_MSG5,
+                        None,
+                        &[],
+                    )
+                    && self.signed(
+                        identity,
+                        &decommitment,
+                        MessageKind::GG18_SIGN_MSG6,
+                        None,
+                        binding.as_ref(),
+                    )
+                    && !sign_phase5a_valid(
+                        &r,
+                        &commitment.body,
+                        decommitment_5a,
+                        elgamal_proof,
+                        dlog_proof,
+                    )
+            }
+            Claim::SignPhase5D {
+                commitment,
+                decommitment,
+            } => {
+                self.round == 9
+                    && self.signed(
+                        identity,
+                        &commitment,
+                        MessageKind::GG18_SIGN_MSG7,
+                        None,
+                        &[],
+                    )
+                    && self.signed(
+                        identity,
+                        &decommitment,
+                        MessageKind::GG18_SIGN_MSG8,
+                        None,
+                        &[],
+                    )
+                    && !sign_phase5d_valid(&commitment.body, &decommitment.body)
+            }
+        }
+    }
+
+    // msg was signed by party in the session of the evidence
+    fn signed<T: Wire>(
+        &self,
+        identity: &Point<Secp256r1>,
+        msg: &SignedMsg<T>,
+        kind: MessageKind,
+        receiver: Option<u16>,
+        binding: &[u8],
+    ) -> bool {
+        let envelope = Envelope {
+            kind,
+            sender: self.party,
+            receiver,
+            binding,
+        };
+        msg.session == self.session && msg.verify(identity, &envelope)
+    }
+}
+
+// the error blaming party, used by the rounds of key generation and signing
+pub(crate) fn misbehavior(session: &SessionId, round: u16, party: u16, claim: Claim) -> Error {
+    match Evidence::new(*session, round, party, &claim) {
+        Ok(evidence) => Error::Misbehavior { evidence },
+        Err(e) => e,
+    }
+}
+
+// the signed messages of the accused party, with what is needed to check them
+#[derive(Serialize, Deserialize)]
+pub(crate) enum Claim {
+    KeyGenDecommitment {
+        commitment: GG18KeyGenMsg1,
+        decommitment: GG18KeyGenMsg2,
+    },
+    // commitments is None if the share could not be opened in round 4
+    KeyGenShare {
+        receiver: u16,
+        receiver_dh_secret: Scalar<Secp256r1>,
+        dh_public: GG18KeyGenMsg1,
+        sealed_share: GG18KeyGenMsg3,
+        commitments: Option<(GG18KeyGenMsg2, GG18KeyGenMsg4)>,
+    },
+    KeyGenDlogProof {
+        proof: GG18KeyGenMsg5,
+    },
+    // receiver is the accusing party, public_share the unweighted share of the accused
+    SignMtaProof {
+        presign: bool,
+        receiver: u16,
+        indices: Vec<u16>,
+        parameters: ShamirSecretSharing,
+        public_share: Point<Secp256r1>,
+        proofs: GG18SignMsg2,
+    },
+    // receiver is the accusing party, the b proof was sent to it
+    SignDecommitment {
+        presign: bool,
+        receiver: u16,
+        commitment: GG18SignMsg1,
+        b_proof: GG18SignMsg2,
+        decommitment: GG18SignMsg4,
+    },
+    SignPhase5A {
+        r: Point<Secp256r1>,
+        commitment: GG18SignMsg5,
+        decommitment: GG18SignMsg6,
+    },
+    SignPhase5D {
+        commitment: GG18SignMsg7,
+        decommitment: GG18SignMsg8,
+    },
+}
+
+// the claim is only read back by Evidence::verify, the messages in it keep their signatures
+fn claim_options() -> impl Options {
+    bincode::DefaultOptions::new()
+        .with_big_endian()
+        .with_fixint_encoding()
+        .with_limit(MAX_MESSAGE_LEN)
+        .reject_trailing_bytes()
+}
+
+/*
+The per-party parts of the upstream batch checks. The rounds run them only
+after the batch check failed, to find the party to blame.
+*/
+
+pub(crate) fn key_gen_decommitment_valid(
+    commitment: &KeyGenBroadcastMessage1,
+    decommitment: &KeyGenDecommitMessage1,
+) -> bool {
+    HashCommitment::<Sha256>::create_commitment_with_user_defined_randomness(
+        &BigInt::from_bytes(&decommitment.y_i.to_bytes(true)),
+        &decommitment.blind_factor,
+    ) == commitment.com
+        && commitment
+            .correct_key_proof
+            .verify(&commitment.e, SALT_STRING)
+            .is_ok()
+}
+
+pub(crate) fn key_gen_share_valid(
+    y_i: &Point<Secp256r1>,
+    vss_scheme: &VerifiableSS<Secp256r1, Sha256>,
+    share: &Scalar<Secp256r1>,
+    receiver: u16,
+) -> bool {
+    vss_scheme.validate_share(share, receiver + 1).is_ok() && &vss_scheme.commitments[0] == y_i
+}
+
+// the public part of the MtA checks, the consistency with alpha needs the decryption key
+pub(crate) fn sign_mta_proofs_valid(
+    m_b_gamma: &MessageB,
+    m_b_w: &MessageB,
+    g_w_i: &Point<Secp256r1>,
+) -> bool {
+    DLogProof::verify(&m_b_gamma.b_proof).is_ok()
+        && DLogProof::verify(&m_b_gamma.beta_tag_proof).is_ok()
+        && DLogProof::verify(&m_b_w.b_proof).is_ok()
+        && DLogProof::verify(&m_b_w.beta_tag_proof).is_ok()
+        && &m_b_w.b_proof.pk == g_w_i
+}
+
+pub(crate) fn sign_decommitment_valid(
+    commitment: &SignBroadcastPhase1,
+    decommitment: &SignDecommitPhase1,
+    b_proof: &DLogProof<Secp256r1, Sha256>,
+) -> bool {
+    b_proof.pk == decommitment.g_gamma_i
+        && HashCommitment::<Sha256>::create_commitment_with_user_defined_randomness(
+            &BigInt::from_bytes(&decommitment.g_gamma_i.to_bytes(true)),
+            &decommitment.blind_factor,
+        ) == commitment.com
+}
+
+pub(crate) fn sign_phase5a_valid(
+    r: &Point<Secp256r1>,
+    commitment: &Phase5Com1,
+    decommitment: &Phase5ADecom1,
+    elgamal_proof: &HomoELGamalProof<Secp256r1, Sha256>,
+    dlog_proof: &DLogProof<Secp256r1, Sha256>,
+) -> bool {
+    let statement = HomoElGamalStatement {
+        G: decommitment.A_i.clone(),
+        H: r.clone(),
+        Y: Point::generator().to_point(),
+        D: decommitment.V_i.clone(),
+        E: decommitment.B_i.clone(),
+    };
+    let input_hash = Sha256::new()
+        .chain_points([&decommitment.V_i, &decommitment.A_i, &decommitment.B_i])
+        .result_bigint();
+    HashCommitment::<Sha256>::create_commitment_with_user_defined_randomness(
+        &input_hash,
+        &decommitment.blind_factor,
+    ) == commitment.com
+        && elgamal_proof.verify(&statement).is_ok()
+        && DLogProof::verify(dlog_proof).is_ok()
+        && dlog_proof.pk == decommitment.A_i
+}
+
+pub(crate) fn sign_phase5d_valid(commitment: &Phase5Com2, decommitment: &Phase5DDecom2) -> bool {
+    let input_hash = Sha256::new()
+        .chain_points([&decommitment.u_i, &decommitment.t_i])
+        .result_bigint();
+    HashCommitment::<Sha256>::create_commitment_with_user_defined_randomness(
+        &input_hash,
+        &decommitment.blind_factor,
+    ) == commitment.com
+}
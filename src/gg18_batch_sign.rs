@@ -0,0 +1,265 @@
+use crate::gg18_key_gen::GG18SignContext;
+use crate::gg18_sign::{
+    gg18_sign1, gg18_sign10, gg18_sign2, gg18_sign3, gg18_sign4, gg18_sign5, gg18_sign6,
+    gg18_sign7, gg18_sign8, gg18_sign9, other_signers, GG18SignContext1, GG18SignContext2,
+    GG18SignContext3, GG18SignContext4, GG18SignContext5, GG18SignContext6, GG18SignContext7,
+    GG18SignContext8, GG18SignContext9, GG18SignMsg1, GG18SignMsg2, GG18SignMsg3, GG18SignMsg4,
+    GG18SignMsg5, GG18SignMsg6, GG18SignMsg7, GG18SignMsg8, GG18SignMsg9,
+};
+use crate::secret::CloneSecret;
+use crate::session::{open_all, SessionId, SessionMsg};
+use crate::signature::Signature;
+use crate::Error;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/*
+GG18 signing of many hashes in one session.
+
+Every hash is signed by its own instance of gg18_sign with its own nonce, the
+instances run in lockstep: round k of the batch runs round k of every instance
+and its messages carry the messages of all instances in the order of the
+hashes. Signing N hashes takes the same ten rounds as signing one, round 2 is
+P2P as in gg18_sign.
+
+Instance i runs in its own sub-session derived from the batch session and i,
+its messages are signed for it, so a signed message of one instance is not
+accepted in the place of another. Evidence of a failed instance is for its
+sub-session.
+
+The key context is borrowed, every instance works on its own copy. All signers
+must pass the same hashes in the same order. A failure in any instance fails
+the whole batch: GG18 aborts on a misbehaving party, its other instances are
+not trusted either.
+*/
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GG18BatchSignContext<C> {
+    session: SessionId,
+    senders: Vec<u16>,
+    instances: Vec<C>,
+}
+
+pub type GG18BatchSignContext1 = GG18BatchSignContext<GG18SignContext1>;
+pub type GG18BatchSignContext2 = GG18BatchSignContext<GG18SignContext2>;
+pub type GG18BatchSignContext3 = GG18BatchSignContext<GG18SignContext3>;
+pub type GG18BatchSignContext4 = GG18BatchSignContext<GG18SignContext4>;
+pub type GG18BatchSignContext5 = GG18BatchSignContext<GG18SignContext5>;
+pub type GG18BatchSignContext6 = GG18BatchSignContext<GG18SignContext6>;
+pub type GG18BatchSignContext7 = GG18BatchSignContext<GG18SignContext7>;
+pub type GG18BatchSignContext8 = GG18BatchSignContext<GG18SignContext8>;
+pub type GG18BatchSignContext9 = GG18BatchSignContext<GG18SignContext9>;
+
+pub type GG18BatchSignMsg1 = SessionMsg<Vec<GG18SignMsg1>>;
+pub type GG18BatchSignMsg2 = SessionMsg<Vec<GG18SignMsg2>>;
+pub type GG18BatchSignMsg3 = SessionMsg<Vec<GG18SignMsg3>>;
+pub type GG18BatchSignMsg4 = SessionMsg<Vec<GG18SignMsg4>>;
+pub type GG18BatchSignMsg5 = SessionMsg<Vec<GG18SignMsg5>>;
+pub type GG18BatchSignMsg6 = SessionMsg<Vec<GG18SignMsg6>>;
+pub type GG18BatchSignMsg7 = SessionMsg<Vec<GG18SignMsg7>>;
+pub type GG18BatchSignMsg8 = SessionMsg<Vec<GG18SignMsg8>>;
+pub type GG18BatchSignMsg9 = SessionMsg<Vec<GG18SignMsg9>>;
+
+// the session of instance i of a batch
+fn instance_session(session: &SessionId, i: usize) -> SessionId {
+    SessionId::derive(&[
+        session.as_bytes(),
+        b"gg18 batch sign",
+        &(i as u64).to_be_bytes(),
+    ])
+}
+
+pub fn gg18_batch_sign1(
+    context: &GG18SignContext,
+    indices: Vec<u16>,
+    threshold_index: usize,
+    message_hashes: &[[u8; 32]],
+    session: SessionId,
+) -> Result<(GG18BatchSignMsg1, GG18BatchSignContext1), Error> {
+    if message_hashes.is_empty() {
+        return Err(Error::MalformedInput {
+            round: 1,
+            party: None,
+        });
+    }
+
+    let (messages, instances): (Vec<_>, Vec<_>) = message_hashes
+        .iter()
+        .enumerate()
+        .map(|(i, hash)| {
+            gg18_sign1(
+                context.clone_secret(),
+                indices.clone(),
+                threshold_index,
+                hash.to_vec(),
+                instance_session(&session, i),
+            )
+        })
+        .collect::<Result<Vec<_>, Error>>()?
+        .into_iter()
+        .unzip();
+
+    let context1 = GG18BatchSignContext {
+        session,
+        senders: other_signers(&indices, threshold_index),
+        instances,
+    };
+    Ok((SessionMsg::new(session, messages), context1))
+}
+
+// the MtA responses are keyed by their recipient
+pub fn gg18_batch_sign2(
+    messages: BTreeMap<u16, GG18BatchSignMsg1>,
+    context: GG18BatchSignContext1,
+) -> Result<(BTreeMap<u16, GG18BatchSignMsg2>, GG18BatchSignContext2), Error> {
+    let GG18BatchSignContext {
+        session,
+        senders,
+        instances,
+    } = context;
+    let outputs = run_instances(&session, &senders, instances, messages, 2, gg18_sign2)?;
+
+    let mut send_vec: BTreeMap<u16, Vec<_>> = BTreeMap::new();
+    let mut instances = Vec::with_capacity(outputs.len());
+    for (output, instance) in outputs {
+        for (j, msg) in output {
+            send_vec.entry(j).or_default().push(msg);
+        }
+        instances.push(instance);
+    }
+    let send_vec = send_vec
+        .into_iter()
+        .map(|(j, m)| (j, SessionMsg::new(session, m)))
+        .collect();
+    let context2 = GG18BatchSignContext {
+        session,
+        senders,
+        instances,
+    };
+    Ok((send_vec, context2))
+}
+
+pub fn gg18_batch_sign3(
+    messages: BTreeMap<u16, GG18BatchSignMsg2>,
+    context: GG18BatchSignContext2,
+) -> Result<(GG18BatchSignMsg3, GG18BatchSignContext3), Error> {
+    broadcast(messages, context, 3, gg18_sign3)
+}
+
+pub fn gg18_batch_sign4(
+    messages: BTreeMap<u16, GG18BatchSignMsg3>,
+    context: GG18BatchSignContext3,
+) -> Result<(GG18BatchSignMsg4, GG18BatchSignContext4), Error> {
+    broadcast(messages, context, 4, gg18_sign4)
+}
+
+pub fn gg18_batch_sign5(
+    messages: BTreeMap<u16, GG18BatchSignMsg4>,
+    context: GG18BatchSignContext4,
+) -> Result<(GG18BatchSignMsg5, GG18BatchSignContext5), Error> {
+    broadcast(messages, context, 5, gg18_sign5)
+}
+
+pub fn gg18_batch_sign6(
+    messages: BTreeMap<u16, GG18BatchSignMsg5>,
+    context: GG18BatchSignContext5,
+) -> Result<(GG18BatchSignMsg6, GG18BatchSignContext6), Error> {
+    broadcast(messages, context, 6, gg18_sign6)
+}
+
+pub fn gg18_batch_sign7(
+    messages: BTreeMap<u16, GG18BatchSignMsg6>,
+    context: GG18BatchSignContext6,
+) -> Result<(GG18BatchSignMsg7, GG18BatchSignContext7), Error> {
+    broadcast(messages, context, 7, gg18_sign7)
+}
+
+pub fn gg18_batch_sign8(
+    messages: BTreeMap<u16, GG18BatchSignMsg7>,
+    context: GG18BatchSignContext7,
+) -> Result<(GG18BatchSignMsg8, GG18BatchSignContext8), Error> {
+    broadcast(messages, context, 8, gg18_sign8)
+}
+
+pub fn gg18_batch_sign9(
+    messages: BTreeMap<u16, GG18BatchSignMsg8>,
+    context: GG18BatchSignContext8,
+) -> Result<(GG18BatchSignMsg9, GG18BatchSignContext9), Error> {
+    broadcast(messages, context, 9, gg18_sign9)
+}
+
+// one signature per hash, in the order of the hashes
+pub fn gg18_batch_sign10(
+    messages: BTreeMap<u16, GG18BatchSignMsg9>,
+    context: GG18BatchSignContext9,
+) -> Result<Vec<Signature>, Error> {
+    run_instances(
+        &context.session,
+        &context.senders,
+        context.instances,
+        messages,
+        10,
+        gg18_sign10,
+    )
+}
+
+fn broadcast<C, I, M, D>(
+    messages: BTreeMap<u16, SessionMsg<Vec<I>>>,
+    context: GG18BatchSignContext<C>,
+    round: u16,
+    step: fn(BTreeMap<u16, I>, C) -> Result<(M, D), Error>,
+) -> Result<(SessionMsg<Vec<M>>, GG18BatchSignContext<D>), Error> {
+    let GG18BatchSignContext {
+        session,
+        senders,
+        instances,
+    } = context;
+    let outputs = run_instances(&session, &senders, instances, messages, round, step)?;
+    let (messages, instances): (Vec<_>, Vec<_>) = outputs.into_iter().unzip();
+    let next = GG18BatchSignContext {
+        session,
+        senders,
+        instances,
+    };
+    Ok((SessionMsg::new(session, messages), next))
+}
+
+// runs the round on every instance with its part of the batched messages
+fn run_instances<C, I, O>(
+    session: &SessionId,
+    senders: &[u16],
+    instances: Vec<C>,
+    messages: BTreeMap<u16, SessionMsg<Vec<I>>>,
+    round: u16,
+    step: fn(BTreeMap<u16, I>, C) -> Result<O, Error>,
+) -> Result<Vec<O>, Error> {
+    let parts = split(session, round, messages, senders, instances.len())?;
+    instances
+        .into_iter()
+        .zip(parts)
+        .map(|(instance, part)| step(part, instance))
+        .collect()
+}
+
+// splits the batched messages into one message map per instance
+fn split<I>(
+    session: &SessionId,
+    round: u16,
+    messages: BTreeMap<u16, SessionMsg<Vec<I>>>,
+    senders: &[u16],
+    instances: usize,
+) -> Result<Vec<BTreeMap<u16, I>>, Error> {
+    let messages = open_all(session, round, messages, senders)?;
+    let mut parts: Vec<BTreeMap<u16, I>> = (0..instances).map(|_| BTreeMap::new()).collect();
+    for (&j, bodies) in senders.iter().zip(messages) {
+        if bodies.len() != instances {
+            return Err(Error::MalformedInput {
+                round,
+                party: Some(j),
+            });
+        }
+        for (part, body) in parts.iter_mut().zip(bodies) {
+            part.insert(j, body);
+        }
+    }
+    Ok(parts)
+}
@@ -15,169 +15,411 @@ You should have received a copy of the GNU General Public License
 along with this program.  If not, see <https://www.gnu.org/licenses/>.
 */
 
+use crate::evidence::{key_gen_decommitment_valid, key_gen_share_valid, misbehavior, Claim};
+use crate::identity::{check_identities, IdentityKey};
+use crate::persist::{Persist, Protocol};
+use crate::secret::Secret;
+use crate::session::{open_signed_all, Envelope, SessionId, SignedMsg};
+use crate::wire::MessageKind;
+use crate::Error;
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use curv::arithmetic::traits::*;
 use curv::{
     cryptographic_primitives::{
         proofs::sigma_dlog::DLogProof, secret_sharing::feldman_vss::VerifiableSS,
     },
     elliptic::curves::{p256::Secp256r1, Point, Scalar},
+    BigInt,
 };
 use multi_party_ecdsa::protocols::multi_party_ecdsa::gg_2018::party_i::{
     KeyGenBroadcastMessage1, KeyGenDecommitMessage1, Keys, Parameters, SharedKeys,
 };
 use paillier::EncryptionKey;
 use serde::{Deserialize, Serialize};
-use sha2::Sha256;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use zeroize::Zeroize;
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+/*
+Round 1 also broadcasts an ephemeral ECDH key of every party. The secret shares
+of round 3 are sealed with ChaCha20-Poly1305 under a key derived from the ECDH
+secret of sender and receiver, so round 3 messages can be sent over the same
+bus as the broadcasts. The ephemeral keys are as authentic as the round 1
+broadcast itself.
+
+Every message is signed with the identity key of its sender, a sealed share
+also with the ECDH key of its receiver as binding. The signed messages of the
+other parties are kept until the last round that checks them, a failed check
+returns them as evidence.
+*/
+
+#[derive(Debug, Serialize, Deserialize)]
 pub struct GG18KeyGenContext1 {
     threshold: u16,
     parties: u16,
     index: u16,
-    party_keys: Keys,
+    session: SessionId,
+    identity: IdentityKey,
+    identities: Vec<Point<Secp256r1>>,
+    party_keys: Secret<Keys>,
     bc_i: KeyGenBroadcastMessage1,
     decom_i: KeyGenDecommitMessage1,
+    dh_secret: Secret<Scalar<Secp256r1>>,
 }
 
-pub type GG18KeyGenMsg1 = KeyGenBroadcastMessage1;
-
-#[derive(Clone, Debug, Serialize, Deserialize)]
+impl_clone_secret!(GG18KeyGenContext1 {
+    threshold,
+    parties,
+    index,
+    session,
+    identity,
+    identities,
+    party_keys,
+    bc_i,
+    decom_i,
+    dh_secret,
+});
+
+pub type GG18KeyGenMsg1 = SignedMsg<(KeyGenBroadcastMessage1, Point<Secp256r1>)>;
+
+#[derive(Debug, Serialize, Deserialize)]
 pub struct GG18KeyGenContext2 {
     threshold: u16,
     parties: u16,
     index: u16,
-    party_keys: Keys,
+    session: SessionId,
+    identity: IdentityKey,
+    identities: Vec<Point<Secp256r1>>,
+    party_keys: Secret<Keys>,
     bc1_vec: Vec<KeyGenBroadcastMessage1>,
     decom_i: KeyGenDecommitMessage1,
+    dh_secret: Secret<Scalar<Secp256r1>>,
+    dh_public_vec: Vec<Point<Secp256r1>>,
+    signed1: BTreeMap<u16, GG18KeyGenMsg1>,
 }
 
-pub type GG18KeyGenMsg2 = KeyGenDecommitMessage1;
-
-#[derive(Clone, Debug, Serialize, Deserialize)]
+impl_clone_secret!(GG18KeyGenContext2 {
+    threshold,
+    parties,
+    index,
+    session,
+    identity,
+    identities,
+    party_keys,
+    bc1_vec,
+    decom_i,
+    dh_secret,
+    dh_public_vec,
+    signed1,
+});
+
+pub type GG18KeyGenMsg2 = SignedMsg<KeyGenDecommitMessage1>;
+
+#[derive(Debug, Serialize, Deserialize)]
 pub struct GG18KeyGenContext3 {
     threshold: u16,
     parties: u16,
     index: u16,
-    party_keys: Keys,
+    session: SessionId,
+    identity: IdentityKey,
+    identities: Vec<Point<Secp256r1>>,
+    party_keys: Secret<Keys>,
     bc1_vec: Vec<KeyGenBroadcastMessage1>,
     vss_scheme: VerifiableSS<Secp256r1, Sha256>,
-    secret_shares: Vec<Scalar<Secp256r1>>,
+    secret_shares: Secret<Vec<Scalar<Secp256r1>>>,
     y_sum: Point<Secp256r1>,
     point_vec: Vec<Point<Secp256r1>>,
+    dh_secret: Secret<Scalar<Secp256r1>>,
+    dh_public_vec: Vec<Point<Secp256r1>>,
+    signed1: BTreeMap<u16, GG18KeyGenMsg1>,
+    signed2: BTreeMap<u16, GG18KeyGenMsg2>,
 }
 
-pub type GG18KeyGenMsg3 = Scalar<Secp256r1>;
-
-#[derive(Clone, Debug, Serialize, Deserialize)]
+impl_clone_secret!(GG18KeyGenContext3 {
+    threshold,
+    parties,
+    index,
+    session,
+    identity,
+    identities,
+    party_keys,
+    bc1_vec,
+    vss_scheme,
+    secret_shares,
+    y_sum,
+    point_vec,
+    dh_secret,
+    dh_public_vec,
+    signed1,
+    signed2,
+});
+
+// a secret share sealed for its receiver
+pub type GG18KeyGenMsg3 = SignedMsg<Vec<u8>>;
+
+#[derive(Debug, Serialize, Deserialize)]
 pub struct GG18KeyGenContext4 {
     threshold: u16,
     parties: u16,
     index: u16,
-    party_keys: Keys,
+    session: SessionId,
+    identity: IdentityKey,
+    identities: Vec<Point<Secp256r1>>,
+    party_keys: Secret<Keys>,
     bc1_vec: Vec<KeyGenBroadcastMessage1>,
     vss_scheme: VerifiableSS<Secp256r1, Sha256>,
     y_sum: Point<Secp256r1>,
     point_vec: Vec<Point<Secp256r1>>,
-    party_shares: Vec<Scalar<Secp256r1>>,
+    party_shares: Secret<Vec<Scalar<Secp256r1>>>,
+    // kept as evidence against the senders of invalid shares
+    dh_secret: Secret<Scalar<Secp256r1>>,
+    signed1: BTreeMap<u16, GG18KeyGenMsg1>,
+    signed2: BTreeMap<u16, GG18KeyGenMsg2>,
+    signed3: BTreeMap<u16, GG18KeyGenMsg3>,
 }
 
-pub type GG18KeyGenMsg4 = VerifiableSS<Secp256r1, Sha256>;
-
-#[derive(Clone, Debug, Serialize, Deserialize)]
+impl_clone_secret!(GG18KeyGenContext4 {
+    threshold,
+    parties,
+    index,
+    session,
+    identity,
+    identities,
+    party_keys,
+    bc1_vec,
+    vss_scheme,
+    y_sum,
+    point_vec,
+    party_shares,
+    dh_secret,
+    signed1,
+    signed2,
+    signed3,
+});
+
+pub type GG18KeyGenMsg4 = SignedMsg<VerifiableSS<Secp256r1, Sha256>>;
+
+#[derive(Debug, Serialize, Deserialize)]
 pub struct GG18KeyGenContext5 {
     threshold: u16,
     parties: u16,
     index: u16,
-    party_keys: Keys,
+    session: SessionId,
+    identity: IdentityKey,
+    identities: Vec<Point<Secp256r1>>,
+    party_keys: Secret<Keys>,
     bc1_vec: Vec<KeyGenBroadcastMessage1>,
     vss_scheme_vec: Vec<VerifiableSS<Secp256r1, Sha256>>,
     y_sum: Point<Secp256r1>,
     point_vec: Vec<Point<Secp256r1>>,
-    shared_keys: SharedKeys,
+    shared_keys: Secret<SharedKeys>,
     dlog_proof: DLogProof<Secp256r1, Sha256>,
 }
 
-pub type GG18KeyGenMsg5 = DLogProof<Secp256r1, Sha256>;
+impl_clone_secret!(GG18KeyGenContext5 {
+    threshold,
+    parties,
+    index,
+    session,
+    identity,
+    identities,
+    party_keys,
+    bc1_vec,
+    vss_scheme_vec,
+    y_sum,
+    point_vec,
+    shared_keys,
+    dlog_proof,
+});
+
+pub type GG18KeyGenMsg5 = SignedMsg<DLogProof<Secp256r1, Sha256>>;
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+/*
+The key share of a party. identity is its identity key and identities are the
+identity public keys of all parties by index, GG18 signing signs its messages
+with them as key generation does.
+*/
+#[derive(Debug, Serialize, Deserialize)]
 pub struct GG18SignContext {
     pub threshold: u16,
     pub index: u16,
-    pub party_keys: Keys,
+    pub party_keys: Secret<Keys>,
     pub vss_scheme_vec: Vec<VerifiableSS<Secp256r1, Sha256>>,
-    pub shared_keys: SharedKeys,
+    pub shared_keys: Secret<SharedKeys>,
     pub paillier_key_vec: Vec<EncryptionKey>,
     pub pk: Point<Secp256r1>,
+    pub identity: IdentityKey,
+    pub identities: Vec<Point<Secp256r1>>,
+}
+
+impl_clone_secret!(GG18SignContext {
+    threshold,
+    index,
+    party_keys,
+    vss_scheme_vec,
+    shared_keys,
+    paillier_key_vec,
+    pk,
+    identity,
+    identities,
+});
+
+impl Persist for GG18SignContext {
+    const PROTOCOL: Protocol = Protocol::Gg18;
+    const KIND: &'static str = "sign_context";
 }
 
 /*
 Generate keys
+
+identities are the identity public keys of all parties by index, their number
+is the number of parties. identity is the key of this party, it must match
+identities[index].
 */
 
 pub fn gg18_key_gen_1(
-    parties: u16,
     threshold: u16,
     index: u16,
-) -> Result<(GG18KeyGenMsg1, GG18KeyGenContext1), &'static str> {
+    session: SessionId,
+    identity: IdentityKey,
+    identities: Vec<Point<Secp256r1>>,
+) -> Result<(GG18KeyGenMsg1, GG18KeyGenContext1), Error> {
+    if !check_identities(&identity, &identities, index) {
+        return Err(Error::MalformedInput {
+            round: 1,
+            party: None,
+        });
+    }
+    let parties = identities.len() as u16;
+    if threshold == 0 || threshold > parties {
+        return Err(Error::MalformedInput {
+            round: 1,
+            party: None,
+        });
+    }
+
     let party_keys = Keys::create(index);
     let (bc_i, decom_i) = party_keys.phase1_broadcast_phase3_proof_of_correct_key();
-
+    let dh_secret = Scalar::<Secp256r1>::random();
+    let dh_public = Point::generator() * &dh_secret;
+
+    let msg1 = SignedMsg::sign(
+        session,
+        (bc_i.clone(), dh_public),
+        &identity,
+        &Envelope::broadcast(MessageKind::GG18_KEY_GEN_MSG1, index),
+    )?;
     let context1 = GG18KeyGenContext1 {
         threshold,
         parties,
         index,
-        party_keys,
-        bc_i: bc_i.clone(),
+        session,
+        identity,
+        identities,
+        party_keys: Secret::new(party_keys),
+        bc_i,
         decom_i,
+        dh_secret: Secret::new(dh_secret),
     };
-    Ok((bc_i, context1))
+    Ok((msg1, context1))
 }
 
 pub fn gg18_key_gen_2(
-    messages: Vec<GG18KeyGenMsg1>,
+    messages: BTreeMap<u16, GG18KeyGenMsg1>,
     context: GG18KeyGenContext1,
-) -> Result<(GG18KeyGenMsg2, GG18KeyGenContext2), &'static str> {
+) -> Result<(GG18KeyGenMsg2, GG18KeyGenContext2), Error> {
+    let senders = other_parties(context.parties, context.index);
+    let signed1 = open_signed_all(
+        &context.session,
+        MessageKind::GG18_KEY_GEN_MSG1,
+        messages,
+        &senders,
+        &context.identities,
+        None,
+        &[],
+    )?;
+
+    for (j, m) in senders.iter().zip(signed1.iter()) {
+        if m.body.1.is_zero() {
+            return Err(Error::MalformedInput {
+                round: 2,
+                party: Some(*j),
+            });
+        }
+    }
+
     let (bc_i, decom_i) = (context.bc_i, context.decom_i);
 
-    let mut bc1_vec = messages;
+    let (mut bc1_vec, mut dh_public_vec): (Vec<_>, Vec<_>) =
+        signed1.iter().map(|m| m.body.clone()).unzip();
 
     bc1_vec.insert(context.index as usize, bc_i);
+    dh_public_vec.insert(
+        context.index as usize,
+        Point::generator() * &*context.dh_secret,
+    );
 
+    let msg2 = SignedMsg::sign(
+        context.session,
+        decom_i.clone(),
+        &context.identity,
+        &Envelope::broadcast(MessageKind::GG18_KEY_GEN_MSG2, context.index),
+    )?;
     let context2 = GG18KeyGenContext2 {
         threshold: context.threshold,
         parties: context.parties,
         index: context.index,
+        session: context.session,
+        identity: context.identity,
+        identities: context.identities,
         party_keys: context.party_keys,
         bc1_vec,
-        decom_i: decom_i.clone(),
+        decom_i,
+        dh_secret: context.dh_secret,
+        dh_public_vec,
+        signed1: senders.iter().copied().zip(signed1).collect(),
     };
-    Ok((decom_i, context2))
+    Ok((msg2, context2))
 }
 
 /*
-Messages from this function should be sent over an encrypted channel
+Messages from this function are keyed by their recipient, every share is
+sealed for its recipient
 */
 pub fn gg18_key_gen_3(
-    messages: Vec<GG18KeyGenMsg2>,
+    messages: BTreeMap<u16, GG18KeyGenMsg2>,
     context: GG18KeyGenContext2,
-) -> Result<(Vec<GG18KeyGenMsg3>, GG18KeyGenContext3), &'static str> {
+) -> Result<(BTreeMap<u16, GG18KeyGenMsg3>, GG18KeyGenContext3), Error> {
+    let senders = other_parties(context.parties, context.index);
+    let signed2: BTreeMap<u16, GG18KeyGenMsg2> = senders
+        .iter()
+        .copied()
+        .zip(open_signed_all(
+            &context.session,
+            MessageKind::GG18_KEY_GEN_MSG2,
+            messages,
+            &senders,
+            &context.identities,
+            None,
+            &[],
+        )?)
+        .collect();
+
     let params = Parameters {
         threshold: context.threshold - 1,
         share_count: context.parties,
     };
 
-    let mut j = 0;
     let mut point_vec: Vec<Point<Secp256r1>> = Vec::new();
     let mut decom_vec: Vec<KeyGenDecommitMessage1> = Vec::new();
     for i in 0..params.share_count {
-        if i == context.index {
-            point_vec.push(context.decom_i.y_i.clone());
-            decom_vec.push(context.decom_i.clone());
-        } else {
-            let decom_j = &messages[j];
-            point_vec.push(decom_j.y_i.clone());
-            decom_vec.push(decom_j.clone());
-            j += 1;
-        }
+        let decom = match signed2.get(&i) {
+            Some(m) => &m.body,
+            None => &context.decom_i,
+        };
+        point_vec.push(decom.y_i.clone());
+        decom_vec.push(decom.clone());
     }
 
     let (head, tail) = point_vec.split_at(1);
@@ -192,64 +434,198 @@ pub fn gg18_key_gen_3(
         );
 
     if result.is_err() {
-        return Err("invalid key");
+        let culprit = senders.iter().find(|&&j| {
+            !key_gen_decommitment_valid(&context.bc1_vec[j as usize], &decom_vec[j as usize])
+        });
+        return Err(match culprit {
+            Some(&j) => misbehavior(
+                &context.session,
+                3,
+                j,
+                Claim::KeyGenDecommitment {
+                    commitment: context.signed1[&j].clone(),
+                    decommitment: signed2[&j].clone(),
+                },
+            ),
+            None => Error::CommitmentMismatch {
+                round: 3,
+                party: None,
+            },
+        });
     }
 
     let (vss_scheme, secret_shares, _index) = result.unwrap();
 
-    let mut messages_output = secret_shares.clone();
-
-    messages_output.remove(context.index as usize);
+    // shares keyed by the receiving party
+    let messages_output = senders
+        .iter()
+        .map(|&j| {
+            let receiver_dh_public = &context.dh_public_vec[j as usize];
+            let sealed = seal_share(
+                &context.session,
+                context.index,
+                j,
+                &context.dh_secret,
+                receiver_dh_public,
+                &secret_shares[j as usize],
+            )?;
+            let binding = receiver_dh_public.to_bytes(true);
+            let envelope = Envelope {
+                kind: MessageKind::GG18_KEY_GEN_MSG3,
+                sender: context.index,
+                receiver: Some(j),
+                binding: binding.as_ref(),
+            };
+            let msg = SignedMsg::sign(context.session, sealed, &context.identity, &envelope)?;
+            Ok((j, msg))
+        })
+        .collect::<Result<BTreeMap<u16, GG18KeyGenMsg3>, Error>>()?;
 
     let context3 = GG18KeyGenContext3 {
         threshold: context.threshold,
         parties: context.parties,
         index: context.index,
+        session: context.session,
+        identity: context.identity,
+        identities: context.identities,
         party_keys: context.party_keys,
         bc1_vec: context.bc1_vec,
         vss_scheme,
-        secret_shares,
+        secret_shares: Secret::new(secret_shares),
         y_sum,
         point_vec,
+        dh_secret: context.dh_secret,
+        dh_public_vec: context.dh_public_vec,
+        signed1: context.signed1,
+        signed2,
     };
     Ok((messages_output, context3))
 }
 
 pub fn gg18_key_gen_4(
-    messages: Vec<GG18KeyGenMsg3>,
+    messages: BTreeMap<u16, GG18KeyGenMsg3>,
     context: GG18KeyGenContext3,
-) -> Result<(GG18KeyGenMsg4, GG18KeyGenContext4), &'static str> {
-    let mut party_shares = messages;
+) -> Result<(GG18KeyGenMsg4, GG18KeyGenContext4), Error> {
+    let senders = other_parties(context.parties, context.index);
+    // the senders sign the ECDH key they sealed the share for
+    let binding = context.dh_public_vec[context.index as usize].to_bytes(true);
+    let signed3: BTreeMap<u16, GG18KeyGenMsg3> = senders
+        .iter()
+        .copied()
+        .zip(open_signed_all(
+            &context.session,
+            MessageKind::GG18_KEY_GEN_MSG3,
+            messages,
+            &senders,
+            &context.identities,
+            Some(context.index),
+            binding.as_ref(),
+        )?)
+        .collect();
+
+    let mut party_shares = senders
+        .iter()
+        .map(|&j| {
+            open_share(
+                &context.session,
+                j,
+                context.index,
+                &context.dh_secret,
+                &context.dh_public_vec[j as usize],
+                &signed3[&j].body,
+            )
+            .ok_or_else(|| {
+                misbehavior(
+                    &context.session,
+                    4,
+                    j,
+                    Claim::KeyGenShare {
+                        receiver: context.index,
+                        receiver_dh_secret: (*context.dh_secret).clone(),
+                        dh_public: context.signed1[&j].clone(),
+                        sealed_share: signed3[&j].clone(),
+                        commitments: None,
+                    },
+                )
+            })
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
     party_shares.insert(
         context.index as usize,
         context.secret_shares[context.index as usize].clone(),
     );
 
+    let msg4 = SignedMsg::sign(
+        context.session,
+        context.vss_scheme.clone(),
+        &context.identity,
+        &Envelope::broadcast(MessageKind::GG18_KEY_GEN_MSG4, context.index),
+    )?;
     let context4 = GG18KeyGenContext4 {
         threshold: context.threshold,
         parties: context.parties,
         index: context.index,
+        session: context.session,
+        identity: context.identity,
+        identities: context.identities,
         party_keys: context.party_keys,
         bc1_vec: context.bc1_vec,
         vss_scheme: context.vss_scheme,
         y_sum: context.y_sum,
         point_vec: context.point_vec,
-        party_shares,
+        party_shares: Secret::new(party_shares),
+        dh_secret: context.dh_secret,
+        signed1: context.signed1,
+        signed2: context.signed2,
+        signed3,
     };
 
-    Ok((context4.vss_scheme.clone(), context4))
+    Ok((msg4, context4))
 }
 
 pub fn gg18_key_gen_5(
-    messages: Vec<GG18KeyGenMsg4>,
+    messages: BTreeMap<u16, GG18KeyGenMsg4>,
     context: GG18KeyGenContext4,
-) -> Result<(GG18KeyGenMsg5, GG18KeyGenContext5), &'static str> {
+) -> Result<(GG18KeyGenMsg5, GG18KeyGenContext5), Error> {
+    let senders = other_parties(context.parties, context.index);
+    let signed4: BTreeMap<u16, GG18KeyGenMsg4> = senders
+        .iter()
+        .copied()
+        .zip(open_signed_all(
+            &context.session,
+            MessageKind::GG18_KEY_GEN_MSG4,
+            messages,
+            &senders,
+            &context.identities,
+            None,
+            &[],
+        )?)
+        .collect();
+
     let params = Parameters {
         threshold: context.threshold - 1,
         share_count: context.parties,
     };
-    let mut vss_scheme_vec: Vec<VerifiableSS<Secp256r1, Sha256>> = messages;
-    vss_scheme_vec.insert(context.index as usize, context.vss_scheme.clone());
+
+    for (&j, m) in signed4.iter() {
+        let vss_scheme = &m.body;
+        if vss_scheme.parameters.threshold != params.threshold
+            || vss_scheme.parameters.share_count != params.share_count
+            || vss_scheme.commitments.len() != context.threshold as usize
+        {
+            return Err(Error::InvalidVss {
+                round: 5,
+                party: Some(j),
+            });
+        }
+    }
+
+    let vss_scheme_vec: Vec<VerifiableSS<Secp256r1, Sha256>> = (0..context.parties)
+        .map(|i| match signed4.get(&i) {
+            Some(m) => m.body.clone(),
+            None => context.vss_scheme.clone(),
+        })
+        .collect();
 
     let result = context
         .party_keys
@@ -262,43 +638,112 @@ pub fn gg18_key_gen_5(
         );
 
     if result.is_err() {
-        return Err("invalid vss");
+        let culprit = senders.iter().find(|&&j| {
+            !key_gen_share_valid(
+                &context.point_vec[j as usize],
+                &vss_scheme_vec[j as usize],
+                &context.party_shares[j as usize],
+                context.index,
+            )
+        });
+        return Err(match culprit {
+            Some(&j) => misbehavior(
+                &context.session,
+                5,
+                j,
+                Claim::KeyGenShare {
+                    receiver: context.index,
+                    receiver_dh_secret: (*context.dh_secret).clone(),
+                    dh_public: context.signed1[&j].clone(),
+                    sealed_share: context.signed3[&j].clone(),
+                    commitments: Some((context.signed2[&j].clone(), signed4[&j].clone())),
+                },
+            ),
+            None => Error::InvalidVss {
+                round: 5,
+                party: None,
+            },
+        });
     }
 
     let (shared_keys, dlog_proof) = result.unwrap();
 
+    let msg5 = SignedMsg::sign(
+        context.session,
+        dlog_proof.clone(),
+        &context.identity,
+        &Envelope::broadcast(MessageKind::GG18_KEY_GEN_MSG5, context.index),
+    )?;
     let context5 = GG18KeyGenContext5 {
         threshold: context.threshold,
         parties: context.parties,
         index: context.index,
+        session: context.session,
+        identity: context.identity,
+        identities: context.identities,
         party_keys: context.party_keys,
         bc1_vec: context.bc1_vec,
         vss_scheme_vec,
         y_sum: context.y_sum,
         point_vec: context.point_vec,
-        shared_keys,
+        shared_keys: Secret::new(shared_keys),
         dlog_proof,
     };
 
-    Ok((context5.dlog_proof.clone(), context5))
+    Ok((msg5, context5))
 }
 
 pub fn gg18_key_gen_6(
-    messages: Vec<GG18KeyGenMsg5>,
+    messages: BTreeMap<u16, GG18KeyGenMsg5>,
     context: GG18KeyGenContext5,
-) -> Result<GG18SignContext, &'static str> {
+) -> Result<GG18SignContext, Error> {
+    let senders = other_parties(context.parties, context.index);
+    let signed5: BTreeMap<u16, GG18KeyGenMsg5> = senders
+        .iter()
+        .copied()
+        .zip(open_signed_all(
+            &context.session,
+            MessageKind::GG18_KEY_GEN_MSG5,
+            messages,
+            &senders,
+            &context.identities,
+            None,
+            &[],
+        )?)
+        .collect();
+
     let params = Parameters {
         threshold: context.threshold - 1,
         share_count: context.parties,
     };
 
     let bc1_vec = context.bc1_vec;
-    let mut dlog_proof_vec: Vec<DLogProof<Secp256r1, Sha256>> = messages;
-    dlog_proof_vec.insert(context.index as usize, context.dlog_proof.clone());
+    let dlog_proof_vec: Vec<DLogProof<Secp256r1, Sha256>> = (0..context.parties)
+        .map(|i| match signed5.get(&i) {
+            Some(m) => m.body.clone(),
+            None => context.dlog_proof.clone(),
+        })
+        .collect();
 
     let result = Keys::verify_dlog_proofs(&params, &dlog_proof_vec, &context.point_vec);
     if result.is_err() {
-        return Err("bad dlog proof");
+        let culprit = senders
+            .iter()
+            .find(|&&j| DLogProof::verify(&dlog_proof_vec[j as usize]).is_err());
+        return Err(match culprit {
+            Some(&j) => misbehavior(
+                &context.session,
+                6,
+                j,
+                Claim::KeyGenDlogProof {
+                    proof: signed5[&j].clone(),
+                },
+            ),
+            None => Error::InvalidProof {
+                round: 6,
+                party: None,
+            },
+        });
     }
 
     let paillier_key_vec = (0..params.share_count)
@@ -313,6 +758,97 @@ pub fn gg18_key_gen_6(
         shared_keys: context.shared_keys,
         paillier_key_vec,
         pk: context.y_sum,
+        identity: context.identity,
+        identities: context.identities,
     };
     Ok(sign_context)
 }
+
+// every other party sends one message per round
+pub(crate) fn other_parties(parties: u16, index: u16) -> Vec<u16> {
+    (0..parties).filter(|&j| j != index).collect()
+}
+
+// the ECDH keys of all receivers, signed by the broadcasts of sealed shares
+pub(crate) fn dh_binding(dh_public_vec: &[Point<Secp256r1>]) -> Vec<u8> {
+    dh_public_vec
+        .iter()
+        .flat_map(|p| p.to_bytes(true).to_vec())
+        .collect()
+}
+
+// pairwise key for the round 3 shares, both directions use the same key
+fn share_cipher(
+    session: &SessionId,
+    dh_secret: &Scalar<Secp256r1>,
+    dh_public: &Point<Secp256r1>,
+) -> ChaCha20Poly1305 {
+    let shared = dh_public * dh_secret;
+    let mut key: [u8; 32] = Sha256::new()
+        .chain(b"mpecdsa gg18 share key")
+        .chain(session.as_bytes())
+        .chain(shared.to_bytes(true).as_ref())
+        .finalize()
+        .into();
+    let cipher = ChaCha20Poly1305::new(Key::from_slice(&key));
+    key.zeroize();
+    cipher
+}
+
+// every share is sealed once per session and direction
+fn share_nonce(sender: u16, receiver: u16) -> [u8; 12] {
+    let mut nonce = [0u8; 12];
+    nonce[..2].copy_from_slice(&sender.to_be_bytes());
+    nonce[2..4].copy_from_slice(&receiver.to_be_bytes());
+    nonce
+}
+
+pub(crate) fn seal_share(
+    session: &SessionId,
+    sender: u16,
+    receiver: u16,
+    dh_secret: &Scalar<Secp256r1>,
+    dh_public: &Point<Secp256r1>,
+    share: &Scalar<Secp256r1>,
+) -> Result<Vec<u8>, Error> {
+    let bytes = share.to_bigint().to_bytes();
+    let mut plaintext = [0u8; 32];
+    plaintext[32 - bytes.len()..].copy_from_slice(&bytes);
+    let sealed = share_cipher(session, dh_secret, dh_public)
+        .encrypt(
+            Nonce::from_slice(&share_nonce(sender, receiver)),
+            Payload {
+                msg: &plaintext,
+                aad: session.as_bytes(),
+            },
+        )
+        .map_err(|_| Error::InvalidEncoding);
+    plaintext.zeroize();
+    sealed
+}
+
+pub(crate) fn open_share(
+    session: &SessionId,
+    sender: u16,
+    receiver: u16,
+    dh_secret: &Scalar<Secp256r1>,
+    dh_public: &Point<Secp256r1>,
+    sealed: &[u8],
+) -> Option<Scalar<Secp256r1>> {
+    let mut plaintext = share_cipher(session, dh_secret, dh_public)
+        .decrypt(
+            Nonce::from_slice(&share_nonce(sender, receiver)),
+            Payload {
+                msg: sealed,
+                aad: session.as_bytes(),
+            },
+        )
+        .ok()?;
+    if plaintext.len() != 32 {
+        plaintext.zeroize();
+        return None;
+    }
+    let share = Scalar::from_bigint(&BigInt::from_bytes(&plaintext));
+    plaintext.zeroize();
+    Some(share)
+}
@@ -0,0 +1,257 @@
+use crate::gg18_key_gen::*;
+use crate::identity::IdentityKey;
+use crate::persist::{Persist, Protocol};
+use crate::session::SessionId;
+use crate::state_machine::{take_round, Msg, StateMachine};
+use crate::wire::{read_kind, write_message, MessageKind, Reader, Wire};
+use crate::Error;
+use curv::elliptic::curves::{p256::Secp256r1, Point};
+use serde::{Deserialize, Serialize};
+use std::mem;
+
+/*
+State machine for GG18 key generation.
+
+Round 3 sends every party its own secret share, sealed for the receiver, its
+messages are P2P. All other rounds are broadcasts.
+*/
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum Gg18KeyGenMsg {
+    Round1(GG18KeyGenMsg1),
+    Round2(GG18KeyGenMsg2),
+    Round3(GG18KeyGenMsg3),
+    Round4(GG18KeyGenMsg4),
+    Round5(GG18KeyGenMsg5),
+}
+
+impl Gg18KeyGenMsg {
+    pub fn round(&self) -> u16 {
+        match self {
+            Gg18KeyGenMsg::Round1(_) => 1,
+            Gg18KeyGenMsg::Round2(_) => 2,
+            Gg18KeyGenMsg::Round3(_) => 3,
+            Gg18KeyGenMsg::Round4(_) => 4,
+            Gg18KeyGenMsg::Round5(_) => 5,
+        }
+    }
+
+    pub fn is_p2p(&self) -> bool {
+        self.round() == 3
+    }
+}
+
+impl Wire for Gg18KeyGenMsg {
+    fn write(&self, out: &mut Vec<u8>) -> Result<(), Error> {
+        match self {
+            Gg18KeyGenMsg::Round1(m) => write_message(MessageKind::GG18_KEY_GEN_MSG1, m, out),
+            Gg18KeyGenMsg::Round2(m) => write_message(MessageKind::GG18_KEY_GEN_MSG2, m, out),
+            Gg18KeyGenMsg::Round3(m) => write_message(MessageKind::GG18_KEY_GEN_MSG3, m, out),
+            Gg18KeyGenMsg::Round4(m) => write_message(MessageKind::GG18_KEY_GEN_MSG4, m, out),
+            Gg18KeyGenMsg::Round5(m) => write_message(MessageKind::GG18_KEY_GEN_MSG5, m, out),
+        }
+    }
+
+    fn read(input: &mut Reader<'_>) -> Result<Self, Error> {
+        Ok(match read_kind(input)? {
+            MessageKind::GG18_KEY_GEN_MSG1 => Gg18KeyGenMsg::Round1(Wire::read(input)?),
+            MessageKind::GG18_KEY_GEN_MSG2 => Gg18KeyGenMsg::Round2(Wire::read(input)?),
+            MessageKind::GG18_KEY_GEN_MSG3 => Gg18KeyGenMsg::Round3(Wire::read(input)?),
+            MessageKind::GG18_KEY_GEN_MSG4 => Gg18KeyGenMsg::Round4(Wire::read(input)?),
+            MessageKind::GG18_KEY_GEN_MSG5 => Gg18KeyGenMsg::Round5(Wire::read(input)?),
+            _ => return Err(Error::InvalidEncoding),
+        })
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+enum KeyGenRound {
+    Round1(GG18KeyGenContext1),
+    Round2(GG18KeyGenContext2),
+    Round3(GG18KeyGenContext3),
+    Round4(GG18KeyGenContext4),
+    Round5(GG18KeyGenContext5),
+    Finished,
+    Failed,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Gg18KeyGen {
+    parties: u16,
+    index: u16,
+    round: KeyGenRound,
+    incoming: Vec<Msg<Gg18KeyGenMsg>>,
+    queue: Vec<Msg<Gg18KeyGenMsg>>,
+    output: Option<GG18SignContext>,
+}
+
+impl Persist for Gg18KeyGen {
+    const PROTOCOL: Protocol = Protocol::Gg18;
+    const KIND: &'static str = "key_gen_state";
+}
+
+impl Gg18KeyGen {
+    // see gg18_key_gen_1, the number of parties is the number of identities
+    pub fn new(
+        threshold: u16,
+        index: u16,
+        session: SessionId,
+        identity: IdentityKey,
+        identities: Vec<Point<Secp256r1>>,
+    ) -> Result<Self, Error> {
+        let parties = identities.len() as u16;
+        let (msg1, context1) = gg18_key_gen_1(threshold, index, session, identity, identities)?;
+        let mut key_gen = Gg18KeyGen {
+            parties,
+            index,
+            round: KeyGenRound::Round1(context1),
+            incoming: Vec::new(),
+            queue: Vec::new(),
+            output: None,
+        };
+        key_gen.broadcast(Gg18KeyGenMsg::Round1(msg1));
+        Ok(key_gen)
+    }
+
+    fn broadcast(&mut self, body: Gg18KeyGenMsg) {
+        self.queue.push(Msg {
+            sender: self.index,
+            receiver: None,
+            body,
+        });
+    }
+}
+
+impl StateMachine for Gg18KeyGen {
+    type MessageBody = Gg18KeyGenMsg;
+    type Output = GG18SignContext;
+
+    fn handle_incoming(&mut self, msg: Msg<Gg18KeyGenMsg>) -> Result<(), Error> {
+        // messages of round r are taken by gg18_key_gen_{r + 1}
+        let round = msg.body.round() + 1;
+        if msg.sender >= self.parties || msg.sender == self.index {
+            return Err(Error::UnexpectedSender {
+                round,
+                party: msg.sender,
+            });
+        }
+        let receiver = if msg.body.is_p2p() {
+            Some(self.index)
+        } else {
+            None
+        };
+        let duplicate = self
+            .incoming
+            .iter()
+            .any(|m| m.sender == msg.sender && m.body.round() == msg.body.round());
+        if msg.receiver != receiver || msg.body.round() < self.current_round() || duplicate {
+            return Err(Error::MalformedInput {
+                round,
+                party: Some(msg.sender),
+            });
+        }
+        self.incoming.push(msg);
+        Ok(())
+    }
+
+    fn message_queue(&mut self) -> &mut Vec<Msg<Gg18KeyGenMsg>> {
+        &mut self.queue
+    }
+
+    fn wants_to_proceed(&self) -> bool {
+        let round = self.current_round();
+        match self.round {
+            KeyGenRound::Finished | KeyGenRound::Failed => false,
+            _ => {
+                self.incoming
+                    .iter()
+                    .filter(|m| m.body.round() == round)
+                    .count()
+                    == self.parties as usize - 1
+            }
+        }
+    }
+
+    fn proceed(&mut self) -> Result<(), Error> {
+        if !self.wants_to_proceed() {
+            return Ok(());
+        }
+        self.round = match mem::replace(&mut self.round, KeyGenRound::Failed) {
+            KeyGenRound::Round1(context) => {
+                let messages = take_round(&mut self.incoming, |m| match m {
+                    Gg18KeyGenMsg::Round1(m) => Ok(m),
+                    m => Err(m),
+                });
+                let (msg, context) = gg18_key_gen_2(messages, context)?;
+                self.broadcast(Gg18KeyGenMsg::Round2(msg));
+                KeyGenRound::Round2(context)
+            }
+            KeyGenRound::Round2(context) => {
+                let messages = take_round(&mut self.incoming, |m| match m {
+                    Gg18KeyGenMsg::Round2(m) => Ok(m),
+                    m => Err(m),
+                });
+                let (shares, context) = gg18_key_gen_3(messages, context)?;
+                for (j, share) in shares {
+                    self.queue.push(Msg {
+                        sender: self.index,
+                        receiver: Some(j),
+                        body: Gg18KeyGenMsg::Round3(share),
+                    });
+                }
+                KeyGenRound::Round3(context)
+            }
+            KeyGenRound::Round3(context) => {
+                let messages = take_round(&mut self.incoming, |m| match m {
+                    Gg18KeyGenMsg::Round3(m) => Ok(m),
+                    m => Err(m),
+                });
+                let (msg, context) = gg18_key_gen_4(messages, context)?;
+                self.broadcast(Gg18KeyGenMsg::Round4(msg));
+                KeyGenRound::Round4(context)
+            }
+            KeyGenRound::Round4(context) => {
+                let messages = take_round(&mut self.incoming, |m| match m {
+                    Gg18KeyGenMsg::Round4(m) => Ok(m),
+                    m => Err(m),
+                });
+                let (msg, context) = gg18_key_gen_5(messages, context)?;
+                self.broadcast(Gg18KeyGenMsg::Round5(msg));
+                KeyGenRound::Round5(context)
+            }
+            KeyGenRound::Round5(context) => {
+                let messages = take_round(&mut self.incoming, |m| match m {
+                    Gg18KeyGenMsg::Round5(m) => Ok(m),
+                    m => Err(m),
+                });
+                self.output = Some(gg18_key_gen_6(messages, context)?);
+                KeyGenRound::Finished
+            }
+            KeyGenRound::Finished | KeyGenRound::Failed => unreachable!(),
+        };
+        Ok(())
+    }
+
+    fn current_round(&self) -> u16 {
+        match self.round {
+            KeyGenRound::Round1(_) => 1,
+            KeyGenRound::Round2(_) => 2,
+            KeyGenRound::Round3(_) => 3,
+            KeyGenRound::Round4(_) => 4,
+            KeyGenRound::Round5(_) => 5,
+            KeyGenRound::Finished | KeyGenRound::Failed => 6,
+        }
+    }
+
+    fn is_finished(&self) -> bool {
+        matches!(self.round, KeyGenRound::Finished)
+    }
+
+    fn pick_output(&mut self) -> Option<GG18SignContext> {
+        self.output.take()
+    }
+
+    fn party_index(&self) -> u16 {
+        self.index
+    }
+}
@@ -0,0 +1,331 @@
+use crate::gg18_key_gen::{dh_binding, open_share, other_parties, seal_share, GG18SignContext};
+use crate::identity::{check_identities, IdentityKey};
+use crate::secret::Secret;
+use crate::session::{open_signed_all, Envelope, SessionId, SignedMsg};
+use crate::wire::MessageKind;
+use crate::Error;
+use curv::cryptographic_primitives::secret_sharing::feldman_vss::VerifiableSS;
+use curv::elliptic::curves::{p256::Secp256r1, Point, Scalar};
+use multi_party_ecdsa::protocols::multi_party_ecdsa::gg_2018::party_i::{Keys, SharedKeys};
+use paillier::EncryptionKey;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::collections::BTreeMap;
+use zk_paillier::zkproofs::{NiCorrectKeyProof, SALT_STRING};
+
+/*
+Refresh of GG18 key shares.
+
+All parties of the key generation take part with their sign contexts. Every
+party generates a new Paillier key and reshares its share, weighted with its
+Lagrange coefficient, under a fresh polynomial. The weighted shares sum up to
+the secret key, so the new shares belong to the same pk but are unrelated to
+the old ones: shares of different generations do not combine and signing with
+a mix of old and new contexts fails. The old contexts must still be deleted,
+t old shares keep reconstructing the key.
+
+Round 1 broadcasts the new Paillier key and an ephemeral ECDH key. Round 2
+broadcasts the new VSS scheme of every party together with the shares of all
+other parties, sealed for their receivers as in key generation, so that every
+party checks the same schemes. The constant term of a scheme must be the
+weighted public share of its dealer, which is known from the old schemes.
+
+Both rounds are signed with the identity keys of the parties. A round 2
+message also signs the ECDH keys of all parties, so a receiver only opens
+shares that were sealed for the key it broadcast itself.
+*/
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GG18RefreshContext1 {
+    threshold: u16,
+    index: u16,
+    session: SessionId,
+    party_keys: Secret<Keys>,
+    shared_keys: Secret<SharedKeys>,
+    vss_scheme_vec: Vec<VerifiableSS<Secp256r1, Sha256>>,
+    pk: Point<Secp256r1>,
+    identity: IdentityKey,
+    identities: Vec<Point<Secp256r1>>,
+    dh_secret: Secret<Scalar<Secp256r1>>,
+}
+
+impl_clone_secret!(GG18RefreshContext1 {
+    threshold,
+    index,
+    session,
+    party_keys,
+    shared_keys,
+    vss_scheme_vec,
+    pk,
+    identity,
+    identities,
+    dh_secret,
+});
+
+pub type GG18RefreshMsg1 = SignedMsg<(EncryptionKey, NiCorrectKeyProof, Point<Secp256r1>)>;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GG18RefreshContext2 {
+    threshold: u16,
+    index: u16,
+    session: SessionId,
+    party_keys: Secret<Keys>,
+    vss_scheme_vec: Vec<VerifiableSS<Secp256r1, Sha256>>,
+    pk: Point<Secp256r1>,
+    identity: IdentityKey,
+    identities: Vec<Point<Secp256r1>>,
+    paillier_key_vec: Vec<EncryptionKey>,
+    dh_secret: Secret<Scalar<Secp256r1>>,
+    dh_public_vec: Vec<Point<Secp256r1>>,
+    vss_scheme: VerifiableSS<Secp256r1, Sha256>,
+    share: Secret<Scalar<Secp256r1>>,
+}
+
+impl_clone_secret!(GG18RefreshContext2 {
+    threshold,
+    index,
+    session,
+    party_keys,
+    vss_scheme_vec,
+    pk,
+    identity,
+    identities,
+    paillier_key_vec,
+    dh_secret,
+    dh_public_vec,
+    vss_scheme,
+    share,
+});
+
+// the new vss scheme and the sealed shares keyed by their receiver
+pub type GG18RefreshMsg2 = SignedMsg<(VerifiableSS<Secp256r1, Sha256>, BTreeMap<u16, Vec<u8>>)>;
+
+pub fn gg18_refresh_1(
+    context: GG18SignContext,
+    session: SessionId,
+) -> Result<(GG18RefreshMsg1, GG18RefreshContext1), Error> {
+    let parties = context.paillier_key_vec.len();
+    if context.vss_scheme_vec.len() != parties
+        || context.identities.len() != parties
+        || !check_identities(&context.identity, &context.identities, context.index)
+    {
+        return Err(Error::MalformedInput {
+            round: 1,
+            party: None,
+        });
+    }
+
+    let party_keys = Keys::create(context.index);
+    let correct_key_proof = NiCorrectKeyProof::proof(&party_keys.dk, None);
+    let dh_secret = Scalar::<Secp256r1>::random();
+    let dh_public = Point::generator() * &dh_secret;
+    let msg1 = SignedMsg::sign(
+        session,
+        (party_keys.ek.clone(), correct_key_proof, dh_public),
+        &context.identity,
+        &Envelope::broadcast(MessageKind::GG18_REFRESH_MSG1, context.index),
+    )?;
+
+    let context1 = GG18RefreshContext1 {
+        threshold: context.threshold,
+        index: context.index,
+        session,
+        party_keys: Secret::new(party_keys),
+        shared_keys: context.shared_keys,
+        vss_scheme_vec: context.vss_scheme_vec,
+        pk: context.pk,
+        identity: context.identity,
+        identities: context.identities,
+        dh_secret: Secret::new(dh_secret),
+    };
+    Ok((msg1, context1))
+}
+
+pub fn gg18_refresh_2(
+    messages: BTreeMap<u16, GG18RefreshMsg1>,
+    context: GG18RefreshContext1,
+) -> Result<(GG18RefreshMsg2, GG18RefreshContext2), Error> {
+    let parties = context.vss_scheme_vec.len() as u16;
+    let senders = other_parties(parties, context.index);
+    let messages = open_signed_all(
+        &context.session,
+        MessageKind::GG18_REFRESH_MSG1,
+        messages,
+        &senders,
+        &context.identities,
+        None,
+        &[],
+    )?;
+
+    for (&j, m) in senders.iter().zip(messages.iter()) {
+        let (ek, correct_key_proof, dh_public) = &m.body;
+        if correct_key_proof.verify(ek, SALT_STRING).is_err() {
+            return Err(Error::InvalidPaillierKey {
+                round: 2,
+                party: Some(j),
+            });
+        }
+        if dh_public.is_zero() {
+            return Err(Error::MalformedInput {
+                round: 2,
+                party: Some(j),
+            });
+        }
+    }
+
+    let (mut paillier_key_vec, mut dh_public_vec): (Vec<_>, Vec<_>) =
+        messages.into_iter().map(|m| (m.body.0, m.body.2)).unzip();
+    paillier_key_vec.insert(context.index as usize, context.party_keys.ek.clone());
+    dh_public_vec.insert(
+        context.index as usize,
+        Point::generator() * &*context.dh_secret,
+    );
+
+    let weighted =
+        lagrange_coefficient(&context.vss_scheme_vec, context.index) * &context.shared_keys.x_i;
+    let (vss_scheme, shares) =
+        VerifiableSS::<Secp256r1, Sha256>::share(context.threshold - 1, parties, &weighted);
+    let shares = Secret::new(shares.to_vec());
+
+    let sealed_shares = senders
+        .iter()
+        .map(|&j| {
+            let sealed = seal_share(
+                &context.session,
+                context.index,
+                j,
+                &context.dh_secret,
+                &dh_public_vec[j as usize],
+                &shares[j as usize],
+            )?;
+            Ok((j, sealed))
+        })
+        .collect::<Result<BTreeMap<u16, Vec<u8>>, Error>>()?;
+
+    let msg2 = SignedMsg::sign(
+        context.session,
+        (vss_scheme.clone(), sealed_shares),
+        &context.identity,
+        &Envelope {
+            kind: MessageKind::GG18_REFRESH_MSG2,
+            sender: context.index,
+            receiver: None,
+            binding: &dh_binding(&dh_public_vec),
+        },
+    )?;
+
+    let context2 = GG18RefreshContext2 {
+        threshold: context.threshold,
+        index: context.index,
+        session: context.session,
+        party_keys: context.party_keys,
+        vss_scheme_vec: context.vss_scheme_vec,
+        pk: context.pk,
+        identity: context.identity,
+        identities: context.identities,
+        paillier_key_vec,
+        dh_secret: context.dh_secret,
+        dh_public_vec,
+        vss_scheme,
+        share: Secret::new(shares[context.index as usize].clone()),
+    };
+    Ok((msg2, context2))
+}
+
+pub fn gg18_refresh_3(
+    messages: BTreeMap<u16, GG18RefreshMsg2>,
+    context: GG18RefreshContext2,
+) -> Result<GG18SignContext, Error> {
+    let parties = context.vss_scheme_vec.len() as u16;
+    let senders = other_parties(parties, context.index);
+    // the senders sign the ECDH keys they sealed the shares for
+    let messages = open_signed_all(
+        &context.session,
+        MessageKind::GG18_REFRESH_MSG2,
+        messages,
+        &senders,
+        &context.identities,
+        None,
+        &dh_binding(&context.dh_public_vec),
+    )?;
+
+    let public_shares = Keys::get_commitments_to_xi(&context.vss_scheme_vec);
+    let mut x_i = (*context.share).clone();
+    let mut vss_scheme_vec = Vec::with_capacity(parties as usize);
+    for (&j, m) in senders.iter().zip(messages.into_iter()) {
+        let (vss_scheme, sealed_shares) = m.body;
+        // the dealer reshares exactly its weighted old share
+        let weighted =
+            &public_shares[j as usize] * &lagrange_coefficient(&context.vss_scheme_vec, j);
+        if vss_scheme.parameters.threshold != context.threshold - 1
+            || vss_scheme.parameters.share_count != parties
+            || vss_scheme.commitments.len() != context.threshold as usize
+            || vss_scheme.commitments[0] != weighted
+        {
+            return Err(Error::InvalidVss {
+                round: 3,
+                party: Some(j),
+            });
+        }
+
+        let share = sealed_shares
+            .get(&context.index)
+            .and_then(|sealed| {
+                open_share(
+                    &context.session,
+                    j,
+                    context.index,
+                    &context.dh_secret,
+                    &context.dh_public_vec[j as usize],
+                    sealed,
+                )
+            })
+            .ok_or(Error::MalformedInput {
+                round: 3,
+                party: Some(j),
+            })?;
+        if vss_scheme
+            .validate_share(&share, context.index + 1)
+            .is_err()
+        {
+            return Err(Error::InvalidVss {
+                round: 3,
+                party: Some(j),
+            });
+        }
+
+        x_i = x_i + share;
+        vss_scheme_vec.push(vss_scheme);
+    }
+    vss_scheme_vec.insert(context.index as usize, context.vss_scheme);
+
+    let shared_keys = SharedKeys {
+        y: context.pk.clone(),
+        x_i,
+    };
+
+    Ok(GG18SignContext {
+        threshold: context.threshold,
+        index: context.index,
+        party_keys: context.party_keys,
+        vss_scheme_vec,
+        shared_keys: Secret::new(shared_keys),
+        paillier_key_vec: context.paillier_key_vec,
+        pk: context.pk,
+        identity: context.identity,
+        identities: context.identities,
+    })
+}
+
+// the weight of a share when interpolating the secret from all shares
+fn lagrange_coefficient(
+    vss_scheme_vec: &[VerifiableSS<Secp256r1, Sha256>],
+    index: u16,
+) -> Scalar<Secp256r1> {
+    let parties: Vec<u16> = (0..vss_scheme_vec.len() as u16).collect();
+    VerifiableSS::<Secp256r1, Sha256>::map_share_to_new_params(
+        &vss_scheme_vec[index as usize].parameters,
+        index,
+        &parties,
+    )
+}
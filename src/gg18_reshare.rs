@@ -0,0 +1,526 @@
+use crate::error::check_senders;
+use crate::gg18_key_gen::{dh_binding, open_share, other_parties, seal_share, GG18SignContext};
+use crate::identity::{check_identities, IdentityKey};
+use crate::secret::Secret;
+use crate::session::{Envelope, SessionId, SignedMsg};
+use crate::wire::{MessageKind, Wire};
+use crate::Error;
+use curv::cryptographic_primitives::secret_sharing::feldman_vss::VerifiableSS;
+use curv::elliptic::curves::{p256::Secp256r1, Point, Scalar};
+use curv::BigInt;
+use multi_party_ecdsa::protocols::multi_party_ecdsa::gg_2018::party_i::{Keys, SharedKeys};
+use paillier::EncryptionKey;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::collections::BTreeMap;
+use zk_paillier::zkproofs::{NiCorrectKeyProof, SALT_STRING};
+
+/*
+Resharing of a GG18 key to a new committee.
+
+A quorum of old parties, the dealers, hands the key to a new set of parties
+with a new threshold, the public key stays the same. Dealers are identified by
+their old index and receivers by their new index; a party that stays in the
+committee takes part twice, once with its old context as dealer and once as a
+receiver.
+
+Round 1: every receiver broadcasts a new Paillier key with its proof and an
+ephemeral ECDH key, signed with its identity key (gg18_reshare_receiver_1).
+
+Round 2: every dealer checks the signatures of all receivers, reshares its
+share, weighted with its Lagrange coefficient in the quorum, with a fresh
+polynomial of the new threshold and broadcasts the VSS scheme with the shares
+sealed for every receiver (gg18_reshare_dealer). The message is signed with the
+identity key of the dealer together with the ECDH keys of all receivers, so a
+receiver only opens shares sealed for the key it broadcast itself.
+
+Round 3: every receiver checks the signatures of the other receivers and of the
+dealers, the Paillier keys of the other receivers and its shares against the
+schemes of the dealers. The constant term of each scheme must be the weighted
+public share of its dealer, computed from the old vss_scheme_vec by
+GG18ReshareDealers::from_key, so a dealer that deals anything else is blamed
+(gg18_reshare_receiver_2).
+
+Signing only uses the sum of the schemes in GG18SignContext::vss_scheme_vec, the
+summed scheme of the dealers is split evenly over the new parties. The entries
+of the new vss_scheme_vec are therefore all sum/n and no longer the scheme a
+party dealt itself, only their sum and the public shares derived from it are
+meaningful. As with refresh, the old contexts must be deleted afterwards.
+*/
+
+/*
+The dealers of a resharing keyed by old index with the constant term each must
+deal, and the public key these add up to. None where the receivers only know
+the sum, as for converted Li17 keys. identities are the identity public keys
+of the dealers, their round 2 messages are checked against them.
+*/
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct GG18ReshareDealers {
+    pub(crate) shares: BTreeMap<u16, Option<Point<Secp256r1>>>,
+    pub(crate) identities: BTreeMap<u16, Point<Secp256r1>>,
+    pub(crate) pk: Point<Secp256r1>,
+}
+
+impl GG18ReshareDealers {
+    /*
+    The quorum dealers of a GG18 key, vss_scheme_vec and identities are the
+    public GG18SignContext::vss_scheme_vec and GG18SignContext::identities of
+    the old key.
+    */
+    pub fn from_key(
+        vss_scheme_vec: &[VerifiableSS<Secp256r1, Sha256>],
+        identities: &[Point<Secp256r1>],
+        dealers: &[u16],
+    ) -> Result<Self, Error> {
+        let malformed = Error::MalformedInput {
+            round: 1,
+            party: None,
+        };
+        let old_parties = vss_scheme_vec.len();
+        if !is_set(dealers)
+            || dealers.iter().any(|&d| d as usize >= old_parties)
+            || identities.len() != old_parties
+            || identities.iter().any(|p| p.is_zero())
+        {
+            return Err(malformed);
+        }
+        if dealers.len() != vss_scheme_vec[0].parameters.threshold as usize + 1 {
+            return Err(malformed);
+        }
+
+        let public_shares = Keys::get_commitments_to_xi(vss_scheme_vec);
+        let shares = dealers
+            .iter()
+            .map(|&d| {
+                let lambda = VerifiableSS::<Secp256r1, Sha256>::map_share_to_new_params(
+                    &vss_scheme_vec[d as usize].parameters,
+                    d,
+                    dealers,
+                );
+                (d, Some(&public_shares[d as usize] * &lambda))
+            })
+            .collect();
+        let identities = dealers
+            .iter()
+            .map(|&d| (d, identities[d as usize].clone()))
+            .collect();
+        let pk = vss_scheme_vec.iter().fold(Point::zero(), |pk, vss_scheme| {
+            pk + &vss_scheme.commitments[0]
+        });
+        Ok(GG18ReshareDealers {
+            shares,
+            identities,
+            pk,
+        })
+    }
+
+    // dealers whose constant terms are only known to add up to pk
+    pub(crate) fn with_sum(
+        identities: BTreeMap<u16, Point<Secp256r1>>,
+        pk: Point<Secp256r1>,
+    ) -> Self {
+        GG18ReshareDealers {
+            shares: identities.keys().map(|&d| (d, None)).collect(),
+            identities,
+            pk,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GG18ReshareContext1 {
+    parties: u16,
+    threshold: u16,
+    index: u16,
+    dealers: GG18ReshareDealers,
+    session: SessionId,
+    identity: IdentityKey,
+    identities: Vec<Point<Secp256r1>>,
+    party_keys: Secret<Keys>,
+    dh_secret: Secret<Scalar<Secp256r1>>,
+}
+
+impl_clone_secret!(GG18ReshareContext1 {
+    parties,
+    threshold,
+    index,
+    dealers,
+    session,
+    identity,
+    identities,
+    party_keys,
+    dh_secret,
+});
+
+pub type GG18ReshareMsg1 = SignedMsg<(EncryptionKey, NiCorrectKeyProof, Point<Secp256r1>)>;
+
+// the vss scheme of a dealer, its ephemeral ECDH key and the sealed shares keyed by receiver
+pub type GG18ReshareMsg2 = SignedMsg<(
+    VerifiableSS<Secp256r1, Sha256>,
+    Point<Secp256r1>,
+    BTreeMap<u16, Vec<u8>>,
+)>;
+
+/*
+Starts a new party with index, threshold is the new number of signers. dealers
+are the old quorum and what each of them must deal, see
+GG18ReshareDealers::from_key. identities are the identity public keys of the
+new parties, as for gg18_key_gen_1 their number is the number of parties.
+*/
+pub fn gg18_reshare_receiver_1(
+    threshold: u16,
+    index: u16,
+    dealers: GG18ReshareDealers,
+    session: SessionId,
+    identity: IdentityKey,
+    identities: Vec<Point<Secp256r1>>,
+) -> Result<(GG18ReshareMsg1, GG18ReshareContext1), Error> {
+    let parties = identities.len() as u16;
+    if threshold == 0
+        || threshold > parties
+        || !check_identities(&identity, &identities, index)
+        || dealers.shares.is_empty()
+        || dealers.identities.values().any(|p| p.is_zero())
+    {
+        return Err(Error::MalformedInput {
+            round: 1,
+            party: None,
+        });
+    }
+
+    let party_keys = Keys::create(index);
+    let correct_key_proof = NiCorrectKeyProof::proof(&party_keys.dk, None);
+    let dh_secret = Scalar::<Secp256r1>::random();
+    let dh_public = Point::generator() * &dh_secret;
+    let msg1 = SignedMsg::sign(
+        session,
+        (party_keys.ek.clone(), correct_key_proof, dh_public),
+        &identity,
+        &Envelope::broadcast(MessageKind::GG18_RESHARE_MSG1, index),
+    )?;
+
+    let context1 = GG18ReshareContext1 {
+        parties,
+        threshold,
+        index,
+        dealers,
+        session,
+        identity,
+        identities,
+        party_keys: Secret::new(party_keys),
+        dh_secret: Secret::new(dh_secret),
+    };
+    Ok((msg1, context1))
+}
+
+/*
+Deals the share of an old party to the receivers, messages are the round 1
+messages of all receivers keyed by their new index and identities the identity
+public keys of the receivers, as passed to gg18_reshare_receiver_1. The deal is
+signed with the identity key of the old context.
+*/
+pub fn gg18_reshare_dealer(
+    messages: BTreeMap<u16, GG18ReshareMsg1>,
+    context: GG18SignContext,
+    dealers: Vec<u16>,
+    identities: &[Point<Secp256r1>],
+    threshold: u16,
+    session: SessionId,
+) -> Result<GG18ReshareMsg2, Error> {
+    let old_parties = context.vss_scheme_vec.len() as u16;
+    if dealers.len() != context.threshold as usize
+        || !is_set(&dealers)
+        || !dealers.contains(&context.index)
+        || dealers.iter().any(|&i| i >= old_parties)
+    {
+        return Err(Error::MalformedInput {
+            round: 2,
+            party: None,
+        });
+    }
+
+    let lambda = VerifiableSS::<Secp256r1, Sha256>::map_share_to_new_params(
+        &context.vss_scheme_vec[context.index as usize].parameters,
+        context.index,
+        &dealers,
+    );
+    let weighted = Secret::new(lambda * &context.shared_keys.x_i);
+    deal(
+        messages,
+        context.index,
+        &weighted,
+        &context.identity,
+        identities,
+        threshold,
+        session,
+    )
+}
+
+/*
+Deals an additive share of the secret key as dealer index, the shares of all
+dealers must add up to the key. identity is the key the receivers know the
+dealer by, identities those of the receivers. Used by gg18_reshare_dealer and
+to convert Li17 keys.
+*/
+pub(crate) fn deal(
+    messages: BTreeMap<u16, GG18ReshareMsg1>,
+    index: u16,
+    share: &Scalar<Secp256r1>,
+    identity: &IdentityKey,
+    identities: &[Point<Secp256r1>],
+    threshold: u16,
+    session: SessionId,
+) -> Result<GG18ReshareMsg2, Error> {
+    let parties = identities.len() as u16;
+    if identities.len() > u16::MAX as usize
+        || threshold == 0
+        || threshold > parties
+        || identities.iter().any(|p| p.is_zero())
+    {
+        return Err(Error::MalformedInput {
+            round: 2,
+            party: None,
+        });
+    }
+
+    let receivers: Vec<u16> = (0..parties).collect();
+    let messages = open_signed(
+        &session,
+        MessageKind::GG18_RESHARE_MSG1,
+        messages,
+        &receivers,
+        |j| identities.get(j as usize),
+        &[],
+        2,
+    )?;
+    for (&j, (_, _, dh_public)) in receivers.iter().zip(messages.iter()) {
+        if dh_public.is_zero() {
+            return Err(Error::MalformedInput {
+                round: 2,
+                party: Some(j),
+            });
+        }
+    }
+
+    let (vss_scheme, shares) =
+        VerifiableSS::<Secp256r1, Sha256>::share(threshold - 1, parties, share);
+    let shares = Secret::new(shares.to_vec());
+
+    let dh_secret = Secret::new(Scalar::<Secp256r1>::random());
+    let dh_public = Point::generator() * &*dh_secret;
+    let dh_public_vec: Vec<Point<Secp256r1>> = messages.into_iter().map(|(_, _, p)| p).collect();
+    let sealed_shares = receivers
+        .iter()
+        .zip(dh_public_vec.iter())
+        .map(|(&j, receiver_dh_public)| {
+            let sealed = seal_share(
+                &session,
+                index,
+                j,
+                &dh_secret,
+                receiver_dh_public,
+                &shares[j as usize],
+            )?;
+            Ok((j, sealed))
+        })
+        .collect::<Result<BTreeMap<u16, Vec<u8>>, Error>>()?;
+
+    SignedMsg::sign(
+        session,
+        (vss_scheme, dh_public, sealed_shares),
+        identity,
+        &Envelope {
+            kind: MessageKind::GG18_RESHARE_MSG2,
+            sender: index,
+            receiver: None,
+            binding: &dh_binding(&dh_public_vec),
+        },
+    )
+}
+
+/*
+keys are the round 1 messages of the other receivers keyed by new index,
+shares the round 2 messages of the dealers keyed by old index
+*/
+pub fn gg18_reshare_receiver_2(
+    keys: BTreeMap<u16, GG18ReshareMsg1>,
+    shares: BTreeMap<u16, GG18ReshareMsg2>,
+    context: GG18ReshareContext1,
+) -> Result<GG18SignContext, Error> {
+    let receivers = other_parties(context.parties, context.index);
+    let keys = open_signed(
+        &context.session,
+        MessageKind::GG18_RESHARE_MSG1,
+        keys,
+        &receivers,
+        |j| context.identities.get(j as usize),
+        &[],
+        3,
+    )?;
+
+    for (&j, (ek, correct_key_proof, dh_public)) in receivers.iter().zip(keys.iter()) {
+        if correct_key_proof.verify(ek, SALT_STRING).is_err() {
+            return Err(Error::InvalidPaillierKey {
+                round: 3,
+                party: Some(j),
+            });
+        }
+        if dh_public.is_zero() {
+            return Err(Error::MalformedInput {
+                round: 3,
+                party: Some(j),
+            });
+        }
+    }
+    let (mut paillier_key_vec, mut dh_public_vec): (Vec<EncryptionKey>, Vec<_>) =
+        keys.into_iter().map(|(ek, _, p)| (ek, p)).unzip();
+    paillier_key_vec.insert(context.index as usize, context.party_keys.ek.clone());
+    dh_public_vec.insert(
+        context.index as usize,
+        Point::generator() * &*context.dh_secret,
+    );
+
+    // the dealers sign the ECDH keys they sealed the shares for
+    let dealers: Vec<u16> = context.dealers.shares.keys().copied().collect();
+    let shares = open_signed(
+        &context.session,
+        MessageKind::GG18_RESHARE_MSG2,
+        shares,
+        &dealers,
+        |d| context.dealers.identities.get(&d),
+        &dh_binding(&dh_public_vec),
+        3,
+    )?;
+
+    let mut x_i = Scalar::<Secp256r1>::zero();
+    let mut combined: Option<VerifiableSS<Secp256r1, Sha256>> = None;
+    for (&d, (vss_scheme, dh_public, sealed_shares)) in dealers.iter().zip(shares) {
+        let expected = &context.dealers.shares[&d];
+        if vss_scheme.parameters.threshold != context.threshold - 1
+            || vss_scheme.parameters.share_count != context.parties
+            || vss_scheme.commitments.len() != context.threshold as usize
+            || expected
+                .as_ref()
+                .map_or(false, |share| vss_scheme.commitments[0] != *share)
+        {
+            return Err(Error::InvalidVss {
+                round: 3,
+                party: Some(d),
+            });
+        }
+
+        let share = sealed_shares
+            .get(&context.index)
+            .filter(|_| !dh_public.is_zero())
+            .and_then(|sealed| {
+                open_share(
+                    &context.session,
+                    d,
+                    context.index,
+                    &context.dh_secret,
+                    &dh_public,
+                    sealed,
+                )
+            })
+            .ok_or(Error::MalformedInput {
+                round: 3,
+                party: Some(d),
+            })?;
+        if vss_scheme
+            .validate_share(&share, context.index + 1)
+            .is_err()
+        {
+            return Err(Error::InvalidVss {
+                round: 3,
+                party: Some(d),
+            });
+        }
+
+        x_i = x_i + share;
+        combined = Some(match combined {
+            None => vss_scheme,
+            Some(mut sum) => {
+                sum.commitments = sum
+                    .commitments
+                    .iter()
+                    .zip(vss_scheme.commitments.iter())
+                    .map(|(a, b)| a + b)
+                    .collect();
+                sum
+            }
+        });
+    }
+
+    // without the constant terms of the dealers only the sum can be checked
+    let mut vss_scheme = combined.unwrap();
+    if vss_scheme.commitments[0] != context.dealers.pk {
+        return Err(Error::InvalidVss {
+            round: 3,
+            party: None,
+        });
+    }
+
+    let part = Scalar::<Secp256r1>::from_bigint(&BigInt::from(context.parties as u64))
+        .invert()
+        .ok_or(Error::MalformedInput {
+            round: 3,
+            party: None,
+        })?;
+    vss_scheme.commitments = vss_scheme.commitments.iter().map(|c| c * &part).collect();
+    let vss_scheme_vec = vec![vss_scheme; context.parties as usize];
+
+    let shared_keys = SharedKeys {
+        y: context.dealers.pk.clone(),
+        x_i,
+    };
+
+    Ok(GG18SignContext {
+        threshold: context.threshold,
+        index: context.index,
+        party_keys: context.party_keys,
+        vss_scheme_vec,
+        shared_keys: Secret::new(shared_keys),
+        paillier_key_vec,
+        pk: context.dealers.pk,
+        identity: context.identity,
+        identities: context.identities,
+    })
+}
+
+/*
+Opens the signed messages of exactly the given senders and returns their bodies
+in that order, identity gives the identity key of a sender
+*/
+fn open_signed<'a, T: Wire>(
+    session: &SessionId,
+    kind: MessageKind,
+    mut messages: BTreeMap<u16, SignedMsg<T>>,
+    senders: &[u16],
+    identity: impl Fn(u16) -> Option<&'a Point<Secp256r1>>,
+    binding: &[u8],
+    round: u16,
+) -> Result<Vec<T>, Error> {
+    check_senders(round, &messages, senders)?;
+    let mut opened = Vec::with_capacity(senders.len());
+    for &j in senders {
+        if let Some(m) = messages.remove(&j) {
+            let envelope = Envelope {
+                kind,
+                sender: j,
+                receiver: None,
+                binding,
+            };
+            m.check(session, identity(j), &envelope, round)?;
+            opened.push(m.body);
+        }
+    }
+    Ok(opened)
+}
+
+// non-empty and without duplicates
+fn is_set(indices: &[u16]) -> bool {
+    let mut sorted = indices.to_vec();
+    sorted.sort_unstable();
+    sorted.dedup();
+    !indices.is_empty() && sorted.len() == indices.len()
+}
@@ -15,7 +15,18 @@ You should have received a copy of the GNU General Public License
 along with this program.  If not, see <https://www.gnu.org/licenses/>.
 */
 
+use crate::evidence::{
+    misbehavior, sign_decommitment_valid, sign_mta_proofs_valid, sign_phase5a_valid,
+    sign_phase5d_valid, Claim,
+};
 use crate::gg18_key_gen::GG18SignContext;
+use crate::identity::{check_identities, IdentityKey};
+use crate::persist::{Persist, Protocol};
+use crate::secret::Secret;
+use crate::session::{open_signed_all, Envelope, SessionId, SignedMsg};
+use crate::signature::Signature;
+use crate::wire::MessageKind;
+use crate::Error;
 use curv::{
     arithmetic::traits::*,
     cryptographic_primitives::{
@@ -33,92 +44,218 @@ use multi_party_ecdsa::utilities::mta::*;
 use paillier::EncryptionKey;
 use serde::{Deserialize, Serialize};
 use sha2::Sha256;
+use std::collections::BTreeMap;
+
+// rounds 1 to 4 prepare the signature of a hash or a presignature
+#[derive(Clone, Debug, Serialize, Deserialize)]
+enum SignMode {
+    Sign(Vec<u8>),
+    Presign,
+}
+
+impl SignMode {
+    fn presign(&self) -> bool {
+        match self {
+            SignMode::Sign(_) => false,
+            SignMode::Presign => true,
+        }
+    }
+
+    // presigning runs have their own kinds, their messages do not fit a signing run
+    fn kind(&self, round: u16) -> MessageKind {
+        MessageKind::gg18_sign_round(self.presign(), round)
+    }
+}
 
 /*
 Sign data
+
+Every message is signed with the identity key of its sender, the proofs of
+round 6 also with R as binding. The signed messages of the other signers are
+kept until the round that checks them, a failed check returns them as evidence.
 */
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct GG18SignContext1 {
     indices: Vec<u16>,
     threshold_index: usize,
-    message_hash: Vec<u8>,
+    session: SessionId,
+    identity: IdentityKey,
+    identities: Vec<Point<Secp256r1>>,
+    mode: SignMode,
     threshold: u16,
     party_id: u16,
-    party_keys: Keys,
+    party_keys: Secret<Keys>,
     vss_scheme_vec: Vec<VerifiableSS<Secp256r1, Sha256>>,
     paillier_key_vec: Vec<EncryptionKey>,
     y_sum: Point<Secp256r1>,
-    sign_keys: SignKeys,
+    sign_keys: Secret<SignKeys>,
     xi_com_vec: Vec<Point<Secp256r1>>,
     com: SignBroadcastPhase1,
     decommit: SignDecommitPhase1,
 }
 
-pub type GG18SignMsg1 = (SignBroadcastPhase1, MessageA);
-
-#[derive(Clone, Debug, Serialize, Deserialize)]
+impl_clone_secret!(GG18SignContext1 {
+    indices,
+    threshold_index,
+    session,
+    identity,
+    identities,
+    mode,
+    threshold,
+    party_id,
+    party_keys,
+    vss_scheme_vec,
+    paillier_key_vec,
+    y_sum,
+    sign_keys,
+    xi_com_vec,
+    com,
+    decommit,
+});
+
+pub type GG18SignMsg1 = SignedMsg<(SignBroadcastPhase1, MessageA)>;
+
+#[derive(Debug, Serialize, Deserialize)]
 pub struct GG18SignContext2 {
     indices: Vec<u16>,
     threshold_index: usize,
-    message_hash: Vec<u8>,
+    session: SessionId,
+    identity: IdentityKey,
+    identities: Vec<Point<Secp256r1>>,
+    signed1: BTreeMap<u16, GG18SignMsg1>,
+    mode: SignMode,
     threshold: u16,
     party_id: u16,
-    party_keys: Keys,
+    party_keys: Secret<Keys>,
     vss_scheme_vec: Vec<VerifiableSS<Secp256r1, Sha256>>,
     y_sum: Point<Secp256r1>,
-    sign_keys: SignKeys,
+    sign_keys: Secret<SignKeys>,
     xi_com_vec: Vec<Point<Secp256r1>>,
     decommit: SignDecommitPhase1,
     bc1_vec: Vec<SignBroadcastPhase1>,
-    beta_vec: Vec<Scalar<Secp256r1>>,
-    ni_vec: Vec<Scalar<Secp256r1>>,
+    beta_vec: Secret<Vec<Scalar<Secp256r1>>>,
+    ni_vec: Secret<Vec<Scalar<Secp256r1>>>,
 }
 
-pub type GG18SignMsg2 = (MessageB, MessageB);
-
-#[derive(Clone, Debug, Serialize, Deserialize)]
+impl_clone_secret!(GG18SignContext2 {
+    indices,
+    threshold_index,
+    session,
+    identity,
+    identities,
+    signed1,
+    mode,
+    threshold,
+    party_id,
+    party_keys,
+    vss_scheme_vec,
+    y_sum,
+    sign_keys,
+    xi_com_vec,
+    decommit,
+    bc1_vec,
+    beta_vec,
+    ni_vec,
+});
+
+pub type GG18SignMsg2 = SignedMsg<(MessageB, MessageB)>;
+
+#[derive(Debug, Serialize, Deserialize)]
 pub struct GG18SignContext3 {
     indices: Vec<u16>,
     threshold_index: usize,
-    message_hash: Vec<u8>,
+    session: SessionId,
+    identity: IdentityKey,
+    identities: Vec<Point<Secp256r1>>,
+    signed1: BTreeMap<u16, GG18SignMsg1>,
+    signed2: BTreeMap<u16, GG18SignMsg2>,
+    mode: SignMode,
     threshold: u16,
     party_id: u16,
     y_sum: Point<Secp256r1>,
-    sign_keys: SignKeys,
+    sign_keys: Secret<SignKeys>,
     decommit: SignDecommitPhase1,
     bc1_vec: Vec<SignBroadcastPhase1>,
     m_b_gamma_rec_vec: Vec<MessageB>,
     delta_i: Scalar<Secp256r1>,
-    sigma: Scalar<Secp256r1>,
+    sigma: Secret<Scalar<Secp256r1>>,
 }
 
-pub type GG18SignMsg3 = Scalar<Secp256r1>;
-
-#[derive(Clone, Debug, Serialize, Deserialize)]
+impl_clone_secret!(GG18SignContext3 {
+    indices,
+    threshold_index,
+    session,
+    identity,
+    identities,
+    signed1,
+    signed2,
+    mode,
+    threshold,
+    party_id,
+    y_sum,
+    sign_keys,
+    decommit,
+    bc1_vec,
+    m_b_gamma_rec_vec,
+    delta_i,
+    sigma,
+});
+
+pub type GG18SignMsg3 = SignedMsg<Scalar<Secp256r1>>;
+
+#[derive(Debug, Serialize, Deserialize)]
 pub struct GG18SignContext4 {
     indices: Vec<u16>,
     threshold_index: usize,
-    message_hash: Vec<u8>,
+    session: SessionId,
+    identity: IdentityKey,
+    identities: Vec<Point<Secp256r1>>,
+    signed1: BTreeMap<u16, GG18SignMsg1>,
+    signed2: BTreeMap<u16, GG18SignMsg2>,
+    mode: SignMode,
     threshold: u16,
     party_id: u16,
     y_sum: Point<Secp256r1>,
-    sign_keys: SignKeys,
+    sign_keys: Secret<SignKeys>,
     decommit: SignDecommitPhase1,
     bc1_vec: Vec<SignBroadcastPhase1>,
     m_b_gamma_rec_vec: Vec<MessageB>,
-    sigma: Scalar<Secp256r1>,
+    sigma: Secret<Scalar<Secp256r1>>,
     delta_inv: Scalar<Secp256r1>,
 }
 
-pub type GG18SignMsg4 = SignDecommitPhase1;
-
-#[derive(Clone, Debug, Serialize, Deserialize)]
+impl_clone_secret!(GG18SignContext4 {
+    indices,
+    threshold_index,
+    session,
+    identity,
+    identities,
+    signed1,
+    signed2,
+    mode,
+    threshold,
+    party_id,
+    y_sum,
+    sign_keys,
+    decommit,
+    bc1_vec,
+    m_b_gamma_rec_vec,
+    sigma,
+    delta_inv,
+});
+
+pub type GG18SignMsg4 = SignedMsg<SignDecommitPhase1>;
+
+#[derive(Debug, Serialize, Deserialize)]
 pub struct GG18SignContext5 {
     indices: Vec<u16>,
     threshold_index: usize,
+    session: SessionId,
+    identity: IdentityKey,
+    identities: Vec<Point<Secp256r1>>,
     threshold: u16,
     party_id: u16,
-    local_sig: LocalSignature,
+    local_sig: Secret<LocalSignature>,
     phase5_com: Phase5Com1,
     phase_5a_decom: Phase5ADecom1,
     helgamal_proof: HomoELGamalProof<Secp256r1, Sha256>,
@@ -126,15 +263,35 @@ pub struct GG18SignContext5 {
     r: Point<Secp256r1>,
 }
 
-pub type GG18SignMsg5 = Phase5Com1;
-
-#[derive(Clone, Debug, Serialize, Deserialize)]
+impl_clone_secret!(GG18SignContext5 {
+    indices,
+    threshold_index,
+    session,
+    identity,
+    identities,
+    threshold,
+    party_id,
+    local_sig,
+    phase5_com,
+    phase_5a_decom,
+    helgamal_proof,
+    dlog_proof_rho,
+    r,
+});
+
+pub type GG18SignMsg5 = SignedMsg<Phase5Com1>;
+
+#[derive(Debug, Serialize, Deserialize)]
 pub struct GG18SignContext6 {
     indices: Vec<u16>,
     threshold_index: usize,
+    session: SessionId,
+    identity: IdentityKey,
+    identities: Vec<Point<Secp256r1>>,
+    signed5: BTreeMap<u16, GG18SignMsg5>,
     threshold: u16,
     party_id: u16,
-    local_sig: LocalSignature,
+    local_sig: Secret<LocalSignature>,
     phase_5a_decom: Phase5ADecom1,
     helgamal_proof: HomoELGamalProof<Secp256r1, Sha256>,
     dlog_proof_rho: DLogProof<Secp256r1, Sha256>,
@@ -142,19 +299,39 @@ pub struct GG18SignContext6 {
     commit5a_vec: Vec<Phase5Com1>,
 }
 
-pub type GG18SignMsg6 = (
+impl_clone_secret!(GG18SignContext6 {
+    indices,
+    threshold_index,
+    session,
+    identity,
+    identities,
+    signed5,
+    threshold,
+    party_id,
+    local_sig,
+    phase_5a_decom,
+    helgamal_proof,
+    dlog_proof_rho,
+    r,
+    commit5a_vec,
+});
+
+pub type GG18SignMsg6 = SignedMsg<(
     Phase5ADecom1,
     HomoELGamalProof<Secp256r1, Sha256>,
     DLogProof<Secp256r1, Sha256>,
-);
+)>;
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct GG18SignContext7 {
     indices: Vec<u16>,
     threshold_index: usize,
+    session: SessionId,
+    identity: IdentityKey,
+    identities: Vec<Point<Secp256r1>>,
     threshold: u16,
     party_id: u16,
-    local_sig: LocalSignature,
+    local_sig: Secret<LocalSignature>,
     phase_5a_decom: Phase5ADecom1,
     decommit5a_and_elgamal_and_dlog_vec_includes_i: Vec<(
         Phase5ADecom1,
@@ -166,15 +343,35 @@ pub struct GG18SignContext7 {
     phase_5d_decom2: Phase5DDecom2,
 }
 
-pub type GG18SignMsg7 = Phase5Com2;
-
-#[derive(Clone, Debug, Serialize, Deserialize)]
+impl_clone_secret!(GG18SignContext7 {
+    indices,
+    threshold_index,
+    session,
+    identity,
+    identities,
+    threshold,
+    party_id,
+    local_sig,
+    phase_5a_decom,
+    decommit5a_and_elgamal_and_dlog_vec_includes_i,
+    phase_5a_decomm_vec,
+    phase5_com2,
+    phase_5d_decom2,
+});
+
+pub type GG18SignMsg7 = SignedMsg<Phase5Com2>;
+
+#[derive(Debug, Serialize, Deserialize)]
 pub struct GG18SignContext8 {
     indices: Vec<u16>,
     threshold_index: usize,
+    session: SessionId,
+    identity: IdentityKey,
+    identities: Vec<Point<Secp256r1>>,
+    signed7: BTreeMap<u16, GG18SignMsg7>,
     threshold: u16,
     party_id: u16,
-    local_sig: LocalSignature,
+    local_sig: Secret<LocalSignature>,
     phase_5a_decom: Phase5ADecom1,
     decommit5a_and_elgamal_and_dlog_vec_includes_i: Vec<(
         Phase5ADecom1,
@@ -186,23 +383,86 @@ pub struct GG18SignContext8 {
     commit5c_vec: Vec<Phase5Com2>,
 }
 
-pub type GG18SignMsg8 = Phase5DDecom2;
-
-#[derive(Clone, Debug, Serialize, Deserialize)]
+impl_clone_secret!(GG18SignContext8 {
+    indices,
+    threshold_index,
+    session,
+    identity,
+    identities,
+    signed7,
+    threshold,
+    party_id,
+    local_sig,
+    phase_5a_decom,
+    decommit5a_and_elgamal_and_dlog_vec_includes_i,
+    phase_5a_decomm_vec,
+    phase_5d_decom2,
+    commit5c_vec,
+});
+
+pub type GG18SignMsg8 = SignedMsg<Phase5DDecom2>;
+
+#[derive(Debug, Serialize, Deserialize)]
 pub struct GG18SignContext9 {
     threshold: u16,
-    local_sig: LocalSignature,
+    session: SessionId,
+    identities: Vec<Point<Secp256r1>>,
+    indices: Vec<u16>,
+    threshold_index: usize,
+    local_sig: Secret<LocalSignature>,
 }
 
-pub type GG18SignMsg9 = Scalar<Secp256r1>;
+impl_clone_secret!(GG18SignContext9 {
+    threshold,
+    session,
+    identities,
+    indices,
+    threshold_index,
+    local_sig,
+});
+
+pub type GG18SignMsg9 = SignedMsg<Scalar<Secp256r1>>;
 
 pub fn gg18_sign1(
     context: GG18SignContext,
     indices: Vec<u16>,
     threshold_index: usize,
     message_hash: Vec<u8>,
-) -> Result<(GG18SignMsg1, GG18SignContext1), &'static str> {
-    let private = PartyPrivate::set_private(context.party_keys.clone(), context.shared_keys);
+    session: SessionId,
+) -> Result<(GG18SignMsg1, GG18SignContext1), Error> {
+    let mode = SignMode::Sign(message_hash);
+    sign1(context, indices, threshold_index, mode, session)
+}
+
+fn sign1(
+    context: GG18SignContext,
+    indices: Vec<u16>,
+    threshold_index: usize,
+    mode: SignMode,
+    session: SessionId,
+) -> Result<(GG18SignMsg1, GG18SignContext1), Error> {
+    let parties = context.paillier_key_vec.len();
+    let mut sorted_indices = indices.clone();
+    sorted_indices.sort_unstable();
+    sorted_indices.dedup();
+    if indices.len() != context.threshold as usize
+        || sorted_indices.len() != indices.len()
+        || indices.iter().any(|&i| i as usize >= parties)
+        || context.vss_scheme_vec.len() != parties
+        || context.identities.len() != parties
+        || !check_identities(&context.identity, &context.identities, context.index)
+        || indices.get(threshold_index) != Some(&context.index)
+    {
+        return Err(Error::MalformedInput {
+            round: 1,
+            party: None,
+        });
+    }
+
+    let private = PartyPrivate::set_private(
+        (*context.party_keys).clone(),
+        (*context.shared_keys).clone(),
+    );
     let sign_keys = SignKeys::create(
         &private,
         &context.vss_scheme_vec[context.index as usize],
@@ -214,29 +474,52 @@ pub fn gg18_sign1(
     let (com, decommit) = sign_keys.phase1_broadcast();
     let (m_a_k, _) = MessageA::a(&sign_keys.k_i, &context.party_keys.ek, &[]);
 
+    let msg1 = SignedMsg::sign(
+        session,
+        (com.clone(), m_a_k),
+        &context.identity,
+        &Envelope::broadcast(mode.kind(1), context.index),
+    )?;
     let context1 = GG18SignContext1 {
         indices,
         threshold_index,
-        message_hash,
+        session,
+        identity: context.identity,
+        identities: context.identities,
+        mode,
         threshold: context.threshold,
         party_id: context.index,
         party_keys: context.party_keys,
         vss_scheme_vec: context.vss_scheme_vec,
         paillier_key_vec: context.paillier_key_vec,
         y_sum: context.pk,
-        sign_keys,
+        sign_keys: Secret::new(sign_keys),
         xi_com_vec,
         com,
         decommit,
     };
 
-    Ok(((context1.com.clone(), m_a_k), context1))
+    Ok((msg1, context1))
 }
 
+// the MtA responses are keyed by their recipient
 pub fn gg18_sign2(
-    messages: Vec<GG18SignMsg1>,
+    messages: BTreeMap<u16, GG18SignMsg1>,
     context: GG18SignContext1,
-) -> Result<(Vec<GG18SignMsg2>, GG18SignContext2), &'static str> {
+) -> Result<(BTreeMap<u16, GG18SignMsg2>, GG18SignContext2), Error> {
+    let senders = other_signers(&context.indices, context.threshold_index);
+    let signed1 = open_signed_all(
+        &context.session,
+        context.mode.kind(1),
+        messages,
+        &senders,
+        &context.identities,
+        None,
+        &[],
+    )?;
+    let messages: Vec<(SignBroadcastPhase1, MessageA)> =
+        signed1.iter().map(|m| m.body.clone()).collect();
+
     let mut j = 0;
     let mut bc1_vec: Vec<SignBroadcastPhase1> = Vec::new();
     let mut m_a_vec: Vec<MessageA> = Vec::new();
@@ -252,10 +535,9 @@ pub fn gg18_sign2(
             j += 1;
         }
     }
-    assert_eq!(context.indices.len(), bc1_vec.len());
 
     //////////////////////////////////////////////////////////////////////////////
-    let mut send_vec: Vec<(MessageB, MessageB)> = Vec::new();
+    let mut send_vec: BTreeMap<u16, (MessageB, MessageB)> = BTreeMap::new();
     let mut beta_vec: Vec<Scalar<Secp256r1>> = Vec::new();
     let mut ni_vec: Vec<Scalar<Secp256r1>> = Vec::new();
     let mut j = 0;
@@ -274,11 +556,14 @@ pub fn gg18_sign2(
                 &[],
             );
             if result1.is_err() || result2.is_err() {
-                return Err("mta message B failed");
+                return Err(Error::InvalidProof {
+                    round: 2,
+                    party: Some(context.indices[i as usize]),
+                });
             }
             let (m_b_gamma, beta_gamma, _, _) = result1.unwrap();
             let (m_b_w, beta_wi, _, _) = result2.unwrap();
-            send_vec.push((m_b_gamma, m_b_w));
+            send_vec.insert(context.indices[i as usize], (m_b_gamma, m_b_w));
             beta_vec.push(beta_gamma);
             ni_vec.push(beta_wi);
             j += 1;
@@ -288,7 +573,11 @@ pub fn gg18_sign2(
     let context2 = GG18SignContext2 {
         indices: context.indices,
         threshold_index: context.threshold_index,
-        message_hash: context.message_hash,
+        session: context.session,
+        identity: context.identity,
+        identities: context.identities,
+        signed1: senders.iter().copied().zip(signed1).collect(),
+        mode: context.mode,
         threshold: context.threshold,
         party_id: context.party_id,
         party_keys: context.party_keys,
@@ -298,17 +587,42 @@ pub fn gg18_sign2(
         xi_com_vec: context.xi_com_vec,
         decommit: context.decommit,
         bc1_vec,
-        beta_vec,
-        ni_vec,
+        beta_vec: Secret::new(beta_vec),
+        ni_vec: Secret::new(ni_vec),
     };
 
+    let send_vec = send_vec
+        .into_iter()
+        .map(|(j, m)| {
+            let envelope = Envelope {
+                kind: context2.mode.kind(2),
+                sender: context2.party_id,
+                receiver: Some(j),
+                binding: &[],
+            };
+            let msg = SignedMsg::sign(context2.session, m, &context2.identity, &envelope)?;
+            Ok((j, msg))
+        })
+        .collect::<Result<BTreeMap<u16, GG18SignMsg2>, Error>>()?;
     Ok((send_vec, context2))
 }
 
 pub fn gg18_sign3(
-    messages: Vec<GG18SignMsg2>,
+    messages: BTreeMap<u16, GG18SignMsg2>,
     context: GG18SignContext2,
-) -> Result<(GG18SignMsg3, GG18SignContext3), &'static str> {
+) -> Result<(GG18SignMsg3, GG18SignContext3), Error> {
+    let senders = other_signers(&context.indices, context.threshold_index);
+    let signed2 = open_signed_all(
+        &context.session,
+        context.mode.kind(2),
+        messages,
+        &senders,
+        &context.identities,
+        Some(context.party_id),
+        &[],
+    )?;
+    let messages: Vec<(MessageB, MessageB)> = signed2.iter().map(|m| m.body.clone()).collect();
+
     let mut m_b_gamma_rec_vec: Vec<MessageB> = Vec::new();
     let mut m_b_w_rec_vec: Vec<MessageB> = Vec::new();
     for i in 0..(context.threshold - 1) {
@@ -322,32 +636,46 @@ pub fn gg18_sign3(
     let mut j = 0;
     for i in 0..context.threshold {
         if (i as usize) != context.threshold_index {
-            let m_b = m_b_gamma_rec_vec[j].clone();
-            let result =
-                m_b.verify_proofs_get_alpha(&context.party_keys.dk, &context.sign_keys.k_i);
-            if result.is_err() {
-                return Err("wrong dlog or m_b");
-            }
-            let alpha_ij_gamma = result.unwrap();
-
-            let m_b = m_b_w_rec_vec[j].clone();
-            let result =
-                m_b.verify_proofs_get_alpha(&context.party_keys.dk, &context.sign_keys.k_i);
-            if result.is_err() {
-                return Err("wrong dlog or m_b");
-            }
-            let alpha_ij_wi = result.unwrap();
-
-            alpha_vec.push(alpha_ij_gamma.0);
-            miu_vec.push(alpha_ij_wi.0);
-
+            let party = context.indices[i as usize];
             let g_w_i = Keys::update_commitments_to_xi(
-                &context.xi_com_vec[context.indices[i as usize] as usize],
-                &context.vss_scheme_vec[context.indices[i as usize] as usize],
-                context.indices[i as usize],
+                &context.xi_com_vec[party as usize],
+                &context.vss_scheme_vec[party as usize],
+                party,
                 &context.indices,
             );
-            assert_eq!(m_b.b_proof.pk, g_w_i);
+            let alpha_ij_gamma = m_b_gamma_rec_vec[j]
+                .verify_proofs_get_alpha(&context.party_keys.dk, &context.sign_keys.k_i);
+            let alpha_ij_wi = m_b_w_rec_vec[j]
+                .verify_proofs_get_alpha(&context.party_keys.dk, &context.sign_keys.k_i);
+
+            match (alpha_ij_gamma, alpha_ij_wi) {
+                (Ok(alpha_ij_gamma), Ok(alpha_ij_wi)) if m_b_w_rec_vec[j].b_proof.pk == g_w_i => {
+                    alpha_vec.push(alpha_ij_gamma.0);
+                    miu_vec.push(alpha_ij_wi.0);
+                }
+                // the proofs and the share in them can be checked by anyone, alpha only here
+                _ if !sign_mta_proofs_valid(&m_b_gamma_rec_vec[j], &m_b_w_rec_vec[j], &g_w_i) => {
+                    return Err(misbehavior(
+                        &context.session,
+                        3,
+                        party,
+                        Claim::SignMtaProof {
+                            presign: context.mode.presign(),
+                            receiver: context.party_id,
+                            indices: context.indices.clone(),
+                            parameters: context.vss_scheme_vec[party as usize].parameters.clone(),
+                            public_share: context.xi_com_vec[party as usize].clone(),
+                            proofs: signed2[j].clone(),
+                        },
+                    ));
+                }
+                _ => {
+                    return Err(Error::InvalidProof {
+                        round: 3,
+                        party: Some(party),
+                    })
+                }
+            }
             j += 1;
         }
     }
@@ -357,10 +685,21 @@ pub fn gg18_sign3(
         .phase2_delta_i(&alpha_vec, &context.beta_vec);
     let sigma = context.sign_keys.phase2_sigma_i(&miu_vec, &context.ni_vec);
 
+    let msg3 = SignedMsg::sign(
+        context.session,
+        delta_i.clone(),
+        &context.identity,
+        &Envelope::broadcast(context.mode.kind(3), context.party_id),
+    )?;
     let context3 = GG18SignContext3 {
         indices: context.indices,
         threshold_index: context.threshold_index,
-        message_hash: context.message_hash,
+        session: context.session,
+        identity: context.identity,
+        identities: context.identities,
+        signed1: context.signed1,
+        signed2: senders.iter().copied().zip(signed2).collect(),
+        mode: context.mode,
         threshold: context.threshold,
         party_id: context.party_id,
         y_sum: context.y_sum,
@@ -368,17 +707,31 @@ pub fn gg18_sign3(
         decommit: context.decommit,
         bc1_vec: context.bc1_vec,
         m_b_gamma_rec_vec,
-        delta_i: delta_i.clone(),
-        sigma,
+        delta_i,
+        sigma: Secret::new(sigma),
     };
 
-    Ok((delta_i, context3))
+    Ok((msg3, context3))
 }
 
 pub fn gg18_sign4(
-    messages: Vec<GG18SignMsg3>,
+    messages: BTreeMap<u16, GG18SignMsg3>,
     context: GG18SignContext3,
-) -> Result<(GG18SignMsg4, GG18SignContext4), &'static str> {
+) -> Result<(GG18SignMsg4, GG18SignContext4), Error> {
+    let senders = other_signers(&context.indices, context.threshold_index);
+    let messages: Vec<Scalar<Secp256r1>> = open_signed_all(
+        &context.session,
+        context.mode.kind(3),
+        messages,
+        &senders,
+        &context.identities,
+        None,
+        &[],
+    )?
+    .into_iter()
+    .map(|m| m.body)
+    .collect();
+
     let mut delta_vec: Vec<Scalar<Secp256r1>> = Vec::new();
 
     let mut j = 0;
@@ -391,12 +744,33 @@ pub fn gg18_sign4(
         }
     }
 
+    // a zero sum would make the inversion below panic
+    let delta = delta_vec
+        .iter()
+        .fold(Scalar::<Secp256r1>::zero(), |acc, x| acc + x);
+    if delta.is_zero() {
+        return Err(Error::MalformedInput {
+            round: 4,
+            party: None,
+        });
+    }
     let delta_inv = SignKeys::phase3_reconstruct_delta(&delta_vec);
 
+    let msg4 = SignedMsg::sign(
+        context.session,
+        context.decommit.clone(),
+        &context.identity,
+        &Envelope::broadcast(context.mode.kind(4), context.party_id),
+    )?;
     let context4 = GG18SignContext4 {
         indices: context.indices,
         threshold_index: context.threshold_index,
-        message_hash: context.message_hash,
+        session: context.session,
+        identity: context.identity,
+        identities: context.identities,
+        signed1: context.signed1,
+        signed2: context.signed2,
+        mode: context.mode,
         threshold: context.threshold,
         party_id: context.party_id,
         y_sum: context.y_sum,
@@ -408,73 +782,84 @@ pub fn gg18_sign4(
         delta_inv,
     };
 
-    Ok((context4.decommit.clone(), context4))
+    Ok((msg4, context4))
 }
 
 pub fn gg18_sign5(
-    messages: Vec<GG18SignMsg4>,
+    messages: BTreeMap<u16, GG18SignMsg4>,
     context: GG18SignContext4,
-) -> Result<(GG18SignMsg5, GG18SignContext5), &'static str> {
-    let mut bc1_vec = context.bc1_vec.clone();
-    let mut decommit_vec: Vec<SignDecommitPhase1> = Vec::new();
-
-    let mut j = 0;
-    for i in 0..context.threshold {
-        if (i as usize) == context.threshold_index {
-            decommit_vec.push(context.decommit.clone());
-        } else {
-            decommit_vec.push(messages[j].clone());
-            j += 1;
+) -> Result<(GG18SignMsg5, GG18SignContext5), Error> {
+    let message_hash = match &context.mode {
+        SignMode::Sign(message_hash) => message_hash.clone(),
+        SignMode::Presign => {
+            return Err(Error::MalformedInput {
+                round: 5,
+                party: None,
+            })
         }
-    }
-
-    let decomm_i = decommit_vec.remove(context.threshold_index);
-    bc1_vec.remove(context.threshold_index);
-    let b_proof_vec = (0..context.m_b_gamma_rec_vec.len())
-        .map(|i| &context.m_b_gamma_rec_vec[i].b_proof)
-        .collect::<Vec<&DLogProof<Secp256r1, Sha256>>>();
-    let result = SignKeys::phase4(&context.delta_inv, &b_proof_vec, decommit_vec, &bc1_vec);
-
-    if result.is_err() {
-        return Err("bad gamma_i decommit");
-    }
-
-    let r = result.unwrap();
-
-    // adding local g_gamma_i
-    let r = r + decomm_i.g_gamma_i * context.delta_inv;
+    };
+    let presignature = presignature(messages, context)?;
+    sign5(presignature, &message_hash)
+}
 
-    let message_bn = BigInt::from_bytes(&context.message_hash);
+// the local signature of round 5 and the commitment to its 5A values
+fn sign5(
+    presignature: GG18Presignature,
+    message_hash: &[u8],
+) -> Result<(GG18SignMsg5, GG18SignContext5), Error> {
+    let presignature = presignature.data;
+    let message_bn = BigInt::from_bytes(message_hash);
     let local_sig = LocalSignature::phase5_local_sig(
-        &context.sign_keys.k_i,
+        &presignature.k_i,
         &message_bn,
-        &r,
-        &context.sigma,
-        &context.y_sum,
+        &presignature.r,
+        &presignature.sigma,
+        &presignature.y_sum,
     );
 
     let (phase5_com, phase_5a_decom, helgamal_proof, dlog_proof_rho) =
         local_sig.phase5a_broadcast_5b_zkproof();
 
+    let msg5 = SignedMsg::sign(
+        presignature.session,
+        phase5_com.clone(),
+        &presignature.identity,
+        &Envelope::broadcast(MessageKind::GG18_SIGN_MSG5, presignature.party_id),
+    )?;
     let context5 = GG18SignContext5 {
-        indices: context.indices,
-        threshold_index: context.threshold_index,
-        threshold: context.threshold,
-        party_id: context.party_id,
-        local_sig,
+        indices: presignature.indices,
+        threshold_index: presignature.threshold_index,
+        session: presignature.session,
+        identity: presignature.identity,
+        identities: presignature.identities,
+        threshold: presignature.threshold,
+        party_id: presignature.party_id,
+        local_sig: Secret::new(local_sig),
         phase5_com,
         phase_5a_decom,
         helgamal_proof,
         dlog_proof_rho,
-        r,
+        r: presignature.r,
     };
-    Ok((context5.phase5_com.clone(), context5))
+    Ok((msg5, context5))
 }
 
 pub fn gg18_sign6(
-    messages: Vec<GG18SignMsg5>,
+    messages: BTreeMap<u16, GG18SignMsg5>,
     context: GG18SignContext5,
-) -> Result<(GG18SignMsg6, GG18SignContext6), &'static str> {
+) -> Result<(GG18SignMsg6, GG18SignContext6), Error> {
+    let senders = other_signers(&context.indices, context.threshold_index);
+    let signed5 = open_signed_all(
+        &context.session,
+        MessageKind::GG18_SIGN_MSG5,
+        messages,
+        &senders,
+        &context.identities,
+        None,
+        &[],
+    )?;
+    let messages: Vec<Phase5Com1> = signed5.iter().map(|m| m.body.clone()).collect();
+
     let mut commit5a_vec: Vec<Phase5Com1> = Vec::new();
 
     let mut j = 0;
@@ -487,9 +872,32 @@ pub fn gg18_sign6(
         }
     }
 
+    // the proofs are for R, the receivers check that they see the same R
+    let binding = context.r.to_bytes(true);
+    let envelope = Envelope {
+        kind: MessageKind::GG18_SIGN_MSG6,
+        sender: context.party_id,
+        receiver: None,
+        binding: binding.as_ref(),
+    };
+    let msg6 = SignedMsg::sign(
+        context.session,
+        (
+            context.phase_5a_decom.clone(),
+            context.helgamal_proof.clone(),
+            context.dlog_proof_rho.clone(),
+        ),
+        &context.identity,
+        &envelope,
+    )?;
+
     let context6 = GG18SignContext6 {
         indices: context.indices,
         threshold_index: context.threshold_index,
+        session: context.session,
+        identity: context.identity,
+        identities: context.identities,
+        signed5: senders.iter().copied().zip(signed5).collect(),
         threshold: context.threshold,
         party_id: context.party_id,
         local_sig: context.local_sig,
@@ -500,20 +908,25 @@ pub fn gg18_sign6(
         commit5a_vec,
     };
 
-    Ok((
-        (
-            context6.phase_5a_decom.clone(),
-            context6.helgamal_proof.clone(),
-            context6.dlog_proof_rho.clone(),
-        ),
-        context6,
-    ))
+    Ok((msg6, context6))
 }
 
 pub fn gg18_sign7(
-    messages: Vec<GG18SignMsg6>,
+    messages: BTreeMap<u16, GG18SignMsg6>,
     context: GG18SignContext6,
-) -> Result<(GG18SignMsg7, GG18SignContext7), &'static str> {
+) -> Result<(GG18SignMsg7, GG18SignContext7), Error> {
+    let senders = other_signers(&context.indices, context.threshold_index);
+    let binding = context.r.to_bytes(true);
+    let signed6 = open_signed_all(
+        &context.session,
+        MessageKind::GG18_SIGN_MSG6,
+        messages,
+        &senders,
+        &context.identities,
+        None,
+        binding.as_ref(),
+    )?;
+
     let mut commit5a_vec = context.commit5a_vec;
     let mut decommit5a_and_elgamal_and_dlog_vec: Vec<(
         Phase5ADecom1,
@@ -530,7 +943,7 @@ pub fn gg18_sign7(
                 context.dlog_proof_rho.clone(),
             ));
         } else {
-            decommit5a_and_elgamal_and_dlog_vec.push(messages[j].clone());
+            decommit5a_and_elgamal_and_dlog_vec.push(signed6[j].body.clone());
             j += 1;
         }
     }
@@ -559,14 +972,47 @@ pub fn gg18_sign7(
     );
 
     if result.is_err() {
-        return Err("error phase5");
+        let culprit = (0..senders.len()).find(|&k| {
+            !sign_phase5a_valid(
+                &context.r,
+                &commit5a_vec[k],
+                &phase_5a_decomm_vec[k],
+                &phase_5a_elgamal_vec[k],
+                &phase_5a_dlog_vec[k],
+            )
+        });
+        return Err(match culprit {
+            Some(k) => misbehavior(
+                &context.session,
+                7,
+                senders[k],
+                Claim::SignPhase5A {
+                    r: context.r.clone(),
+                    commitment: context.signed5[&senders[k]].clone(),
+                    decommitment: signed6[k].clone(),
+                },
+            ),
+            None => Error::InvalidProof {
+                round: 7,
+                party: None,
+            },
+        });
     }
 
     let (phase5_com2, phase_5d_decom2) = result.unwrap();
 
+    let msg7 = SignedMsg::sign(
+        context.session,
+        phase5_com2.clone(),
+        &context.identity,
+        &Envelope::broadcast(MessageKind::GG18_SIGN_MSG7, context.party_id),
+    )?;
     let context7 = GG18SignContext7 {
         indices: context.indices,
         threshold_index: context.threshold_index,
+        session: context.session,
+        identity: context.identity,
+        identities: context.identities,
         threshold: context.threshold,
         party_id: context.party_id,
         local_sig: context.local_sig,
@@ -577,27 +1023,48 @@ pub fn gg18_sign7(
         phase_5d_decom2,
     };
 
-    Ok((context7.phase5_com2.clone(), context7))
+    Ok((msg7, context7))
 }
 
 pub fn gg18_sign8(
-    messages: Vec<GG18SignMsg7>,
+    messages: BTreeMap<u16, GG18SignMsg7>,
     context: GG18SignContext7,
-) -> Result<(GG18SignMsg8, GG18SignContext8), &'static str> {
+) -> Result<(GG18SignMsg8, GG18SignContext8), Error> {
+    let senders = other_signers(&context.indices, context.threshold_index);
+    let signed7 = open_signed_all(
+        &context.session,
+        MessageKind::GG18_SIGN_MSG7,
+        messages,
+        &senders,
+        &context.identities,
+        None,
+        &[],
+    )?;
+
     let mut commit5c_vec: Vec<Phase5Com2> = Vec::new();
     let mut j = 0;
     for i in 0..context.threshold {
         if (i as usize) == context.threshold_index {
             commit5c_vec.push(context.phase5_com2.clone());
         } else {
-            commit5c_vec.push(messages[j].clone());
+            commit5c_vec.push(signed7[j].body.clone());
             j += 1;
         }
     }
 
+    let msg8 = SignedMsg::sign(
+        context.session,
+        context.phase_5d_decom2.clone(),
+        &context.identity,
+        &Envelope::broadcast(MessageKind::GG18_SIGN_MSG8, context.party_id),
+    )?;
     let context8 = GG18SignContext8 {
         indices: context.indices,
         threshold_index: context.threshold_index,
+        session: context.session,
+        identity: context.identity,
+        identities: context.identities,
+        signed7: senders.iter().copied().zip(signed7).collect(),
         threshold: context.threshold,
         party_id: context.party_id,
         local_sig: context.local_sig,
@@ -609,23 +1076,36 @@ pub fn gg18_sign8(
         commit5c_vec,
     };
 
-    Ok((context8.phase_5d_decom2.clone(), context8))
+    Ok((msg8, context8))
 }
 
 pub fn gg18_sign9(
-    messages: Vec<GG18SignMsg8>,
+    messages: BTreeMap<u16, GG18SignMsg8>,
     context: GG18SignContext8,
-) -> Result<(GG18SignMsg9, GG18SignContext9), &'static str> {
-    let mut decommit5d_vec: Vec<Phase5DDecom2> = Vec::new();
-    let mut j = 0;
-    for i in 0..context.threshold {
-        if (i as usize) == context.threshold_index {
-            decommit5d_vec.push(context.phase_5d_decom2.clone());
-        } else {
-            decommit5d_vec.push(messages[j].clone());
-            j += 1;
-        }
-    }
+) -> Result<(GG18SignMsg9, GG18SignContext9), Error> {
+    let senders = other_signers(&context.indices, context.threshold_index);
+    let signed8: BTreeMap<u16, GG18SignMsg8> = senders
+        .iter()
+        .copied()
+        .zip(open_signed_all(
+            &context.session,
+            MessageKind::GG18_SIGN_MSG8,
+            messages,
+            &senders,
+            &context.identities,
+            None,
+            &[],
+        )?)
+        .collect();
+
+    let decommit5d_vec: Vec<Phase5DDecom2> = context
+        .indices
+        .iter()
+        .map(|j| match signed8.get(j) {
+            Some(m) => m.body.clone(),
+            None => context.phase_5d_decom2.clone(),
+        })
+        .collect();
 
     let phase_5a_decomm_vec_includes_i = (0..context.threshold)
         .map(|i| {
@@ -640,35 +1120,344 @@ pub fn gg18_sign9(
         &phase_5a_decomm_vec_includes_i,
     );
 
+    // a failed commitment identifies its sender, a failed sum check does not
     if s_i.is_err() {
-        return Err("bad com 5d");
+        let culprit = (0..context.threshold as usize).find(|&k| {
+            k != context.threshold_index
+                && !sign_phase5d_valid(&context.commit5c_vec[k], &decommit5d_vec[k])
+        });
+        return Err(match culprit {
+            Some(k) => {
+                let j = context.indices[k];
+                misbehavior(
+                    &context.session,
+                    9,
+                    j,
+                    Claim::SignPhase5D {
+                        commitment: context.signed7[&j].clone(),
+                        decommitment: signed8[&j].clone(),
+                    },
+                )
+            }
+            None => Error::CommitmentMismatch {
+                round: 9,
+                party: None,
+            },
+        });
     }
 
+    let msg9 = SignedMsg::sign(
+        context.session,
+        s_i.unwrap(),
+        &context.identity,
+        &Envelope::broadcast(MessageKind::GG18_SIGN_MSG9, context.party_id),
+    )?;
     let context9 = GG18SignContext9 {
         threshold: context.threshold,
+        session: context.session,
+        identities: context.identities,
+        indices: context.indices,
+        threshold_index: context.threshold_index,
         local_sig: context.local_sig,
     };
 
-    Ok((s_i.unwrap(), context9))
+    Ok((msg9, context9))
 }
 
 pub fn gg18_sign10(
-    messages: Vec<GG18SignMsg9>,
+    messages: BTreeMap<u16, GG18SignMsg9>,
     context: GG18SignContext9,
-) -> Result<Vec<u8>, &'static str> {
-    let mut s_i_vec: Vec<Scalar<Secp256r1>> = Vec::new();
-
-    for i in 0..(context.threshold - 1) {
-        s_i_vec.push(messages[i as usize].clone());
-    }
+) -> Result<Signature, Error> {
+    let senders = other_signers(&context.indices, context.threshold_index);
+    let s_i_vec: Vec<Scalar<Secp256r1>> = open_signed_all(
+        &context.session,
+        MessageKind::GG18_SIGN_MSG9,
+        messages,
+        &senders,
+        &context.identities,
+        None,
+        &[],
+    )?
+    .into_iter()
+    .map(|m| m.body)
+    .collect();
 
     let sig = context.local_sig.output_signature(&s_i_vec);
 
     if sig.is_err() {
-        return Err("verification failed");
+        return Err(Error::InvalidSignature { round: 10 });
     }
 
     let sig = sig.unwrap();
 
-    Ok([sig.r.to_bytes().as_ref(), sig.s.to_bytes().as_ref()].concat())
+    Ok(Signature::from_scalars(&sig.r, &sig.s).normalize_s())
+}
+
+/*
+Presigning.
+
+Rounds 1 to 4 and R in round 5 do not depend on the message. A presigning run
+starts with gg18_presign1, continues with gg18_presign2 to gg18_presign4 and
+ends with gg18_presign5, which returns a GG18Presignature instead of a round 5
+message. Once the hash is known, gg18_presign_sign turns the presignature into
+the round 5 message and context, and signing continues with gg18_sign6 to
+gg18_sign10. The messages of rounds 1 to 4 are signed as GG18_PRESIGN_MSG1 to
+GG18_PRESIGN_MSG4, so they are rejected by a signing run and the other way
+around.
+
+The online phase is rounds 5 to 10 of signing, five message exchanges, not one.
+GG18 has no safe one-round online phase: a share s_i sent without the checks
+of phase 5 can leak the key shares of the honest signers to one that deviated
+in the MtA rounds. The shares are only sent in round 9 after the commitments
+and proofs of 5A to 5D are verified, with the same evidence as signing.
+Signers that disagree on the message fail the check of round 9 without sending
+their shares.
+
+The presignature holds the nonce share k_i. Two signatures with the same nonce
+reveal the key, so a presignature must be used at most once: it is neither
+Clone, CloneSecret nor Serialize, and gg18_presign_sign consumes it. It can only
+be stored in a PresignPool, which seals it and hands it out once. A
+presignature whose online phase was started is spent even if signing fails.
+*/
+#[derive(Debug)]
+pub struct GG18Presignature {
+    data: PresignatureData,
+}
+
+// only the presignature pool serializes presignatures, a copy must not leak
+#[derive(Debug, Serialize, Deserialize)]
+struct PresignatureData {
+    indices: Vec<u16>,
+    threshold_index: usize,
+    session: SessionId,
+    identity: IdentityKey,
+    identities: Vec<Point<Secp256r1>>,
+    threshold: u16,
+    party_id: u16,
+    y_sum: Point<Secp256r1>,
+    r: Point<Secp256r1>,
+    k_i: Secret<Scalar<Secp256r1>>,
+    sigma: Secret<Scalar<Secp256r1>>,
+}
+
+impl Persist for PresignatureData {
+    const PROTOCOL: Protocol = Protocol::Gg18;
+    const KIND: &'static str = "presignature";
+}
+
+impl GG18Presignature {
+    // the session of the presigning run, the online phase runs in it as well
+    pub fn session(&self) -> SessionId {
+        self.data.session
+    }
+
+    pub fn indices(&self) -> &[u16] {
+        &self.data.indices
+    }
+
+    // the joint nonce point, the same for all signers of the run
+    pub fn r(&self) -> &Point<Secp256r1> {
+        &self.data.r
+    }
+
+    pub(crate) fn to_json(&self) -> Result<String, Error> {
+        self.data.to_json()
+    }
+
+    pub(crate) fn from_json(json: &str) -> Result<Self, Error> {
+        let data = PresignatureData::from_json(json)?;
+        Ok(GG18Presignature { data })
+    }
+}
+
+/// Contexts of a presigning run.
+///
+/// They hold the nonce share k_i of the presignature they end in, a copy could
+/// finish a second presignature with the same nonce. Unlike the contexts of
+/// signing they are neither Clone, CloneSecret nor Serialize, so they cannot be
+/// copied or persisted:
+///
+/// ```compile_fail
+/// use mpecdsa::gg18_sign::GG18PresignContext4;
+/// use mpecdsa::secret::CloneSecret;
+///
+/// fn copy(context: &GG18PresignContext4) -> GG18PresignContext4 {
+///     context.clone_secret()
+/// }
+/// ```
+///
+/// ```compile_fail
+/// use mpecdsa::gg18_sign::GG18PresignContext4;
+///
+/// fn persist(context: &GG18PresignContext4) -> String {
+///     serde_json::to_string(context).unwrap()
+/// }
+/// ```
+#[derive(Debug)]
+pub struct GG18PresignContext1(GG18SignContext1);
+
+#[derive(Debug)]
+pub struct GG18PresignContext2(GG18SignContext2);
+
+#[derive(Debug)]
+pub struct GG18PresignContext3(GG18SignContext3);
+
+#[derive(Debug)]
+pub struct GG18PresignContext4(GG18SignContext4);
+
+pub fn gg18_presign1(
+    context: GG18SignContext,
+    indices: Vec<u16>,
+    threshold_index: usize,
+    session: SessionId,
+) -> Result<(GG18SignMsg1, GG18PresignContext1), Error> {
+    let (msg1, context1) = sign1(
+        context,
+        indices,
+        threshold_index,
+        SignMode::Presign,
+        session,
+    )?;
+    Ok((msg1, GG18PresignContext1(context1)))
+}
+
+pub fn gg18_presign2(
+    messages: BTreeMap<u16, GG18SignMsg1>,
+    context: GG18PresignContext1,
+) -> Result<(BTreeMap<u16, GG18SignMsg2>, GG18PresignContext2), Error> {
+    let (msg2, context2) = gg18_sign2(messages, context.0)?;
+    Ok((msg2, GG18PresignContext2(context2)))
+}
+
+pub fn gg18_presign3(
+    messages: BTreeMap<u16, GG18SignMsg2>,
+    context: GG18PresignContext2,
+) -> Result<(GG18SignMsg3, GG18PresignContext3), Error> {
+    let (msg3, context3) = gg18_sign3(messages, context.0)?;
+    Ok((msg3, GG18PresignContext3(context3)))
+}
+
+pub fn gg18_presign4(
+    messages: BTreeMap<u16, GG18SignMsg3>,
+    context: GG18PresignContext3,
+) -> Result<(GG18SignMsg4, GG18PresignContext4), Error> {
+    let (msg4, context4) = gg18_sign4(messages, context.0)?;
+    Ok((msg4, GG18PresignContext4(context4)))
+}
+
+pub fn gg18_presign5(
+    messages: BTreeMap<u16, GG18SignMsg4>,
+    context: GG18PresignContext4,
+) -> Result<GG18Presignature, Error> {
+    presignature(messages, context.0)
+}
+
+pub fn gg18_presign_sign(
+    presignature: GG18Presignature,
+    message_hash: Vec<u8>,
+) -> Result<(GG18SignMsg5, GG18SignContext5), Error> {
+    sign5(presignature, &message_hash)
+}
+
+// the message independent state after round 4, R is checked against the decommitments
+fn presignature(
+    messages: BTreeMap<u16, GG18SignMsg4>,
+    context: GG18SignContext4,
+) -> Result<GG18Presignature, Error> {
+    let r = reconstruct_r(messages, &context)?;
+
+    let data = PresignatureData {
+        indices: context.indices,
+        threshold_index: context.threshold_index,
+        session: context.session,
+        identity: context.identity,
+        identities: context.identities,
+        threshold: context.threshold,
+        party_id: context.party_id,
+        y_sum: context.y_sum,
+        r,
+        k_i: Secret::new(context.sign_keys.k_i.clone()),
+        sigma: context.sigma,
+    };
+    Ok(GG18Presignature { data })
+}
+
+// R from the decommitments of round 4, the message independent part of round 5
+fn reconstruct_r(
+    messages: BTreeMap<u16, GG18SignMsg4>,
+    context: &GG18SignContext4,
+) -> Result<Point<Secp256r1>, Error> {
+    let senders = other_signers(&context.indices, context.threshold_index);
+    let signed4 = open_signed_all(
+        &context.session,
+        context.mode.kind(4),
+        messages,
+        &senders,
+        &context.identities,
+        None,
+        &[],
+    )?;
+
+    let mut bc1_vec = context.bc1_vec.clone();
+    let mut decommit_vec: Vec<SignDecommitPhase1> = Vec::new();
+
+    let mut j = 0;
+    for i in 0..context.threshold {
+        if (i as usize) == context.threshold_index {
+            decommit_vec.push(context.decommit.clone());
+        } else {
+            decommit_vec.push(signed4[j].body.clone());
+            j += 1;
+        }
+    }
+
+    let decomm_i = decommit_vec.remove(context.threshold_index);
+    bc1_vec.remove(context.threshold_index);
+    let b_proof_vec = (0..context.m_b_gamma_rec_vec.len())
+        .map(|i| &context.m_b_gamma_rec_vec[i].b_proof)
+        .collect::<Vec<&DLogProof<Secp256r1, Sha256>>>();
+    let result = SignKeys::phase4(
+        &context.delta_inv,
+        &b_proof_vec,
+        decommit_vec.clone(),
+        &bc1_vec,
+    );
+
+    if result.is_err() {
+        let culprit = (0..senders.len())
+            .find(|&k| !sign_decommitment_valid(&bc1_vec[k], &decommit_vec[k], b_proof_vec[k]));
+        return Err(match culprit {
+            Some(k) => misbehavior(
+                &context.session,
+                5,
+                senders[k],
+                Claim::SignDecommitment {
+                    presign: context.mode.presign(),
+                    receiver: context.party_id,
+                    commitment: context.signed1[&senders[k]].clone(),
+                    b_proof: context.signed2[&senders[k]].clone(),
+                    decommitment: signed4[k].clone(),
+                },
+            ),
+            None => Error::CommitmentMismatch {
+                round: 5,
+                party: None,
+            },
+        });
+    }
+
+    let r = result.unwrap();
+
+    // adding local g_gamma_i
+    Ok(r + decomm_i.g_gamma_i * &context.delta_inv)
+}
+
+// the other signers in the order of indices, every round takes one message from each
+pub(crate) fn other_signers(indices: &[u16], threshold_index: usize) -> Vec<u16> {
+    indices
+        .iter()
+        .enumerate()
+        .filter(|&(i, _)| i != threshold_index)
+        .map(|(_, &j)| j)
+        .collect()
 }
@@ -0,0 +1,330 @@
+use crate::gg18_key_gen::GG18SignContext;
+use crate::gg18_sign::*;
+use crate::persist::{Persist, Protocol};
+use crate::session::SessionId;
+use crate::signature::Signature;
+use crate::state_machine::{take_round, Msg, StateMachine};
+use crate::wire::{read_kind, write_message, MessageKind, Reader, Wire};
+use crate::Error;
+use serde::{Deserialize, Serialize};
+use std::mem;
+
+/*
+State machine for GG18 signing.
+
+Round 2 answers the MtA requests of every other signer, its messages are P2P.
+All other rounds are broadcasts.
+*/
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum Gg18SignMsg {
+    Round1(GG18SignMsg1),
+    Round2(GG18SignMsg2),
+    Round3(GG18SignMsg3),
+    Round4(GG18SignMsg4),
+    Round5(GG18SignMsg5),
+    Round6(GG18SignMsg6),
+    Round7(GG18SignMsg7),
+    Round8(GG18SignMsg8),
+    Round9(GG18SignMsg9),
+}
+
+impl Gg18SignMsg {
+    pub fn round(&self) -> u16 {
+        match self {
+            Gg18SignMsg::Round1(_) => 1,
+            Gg18SignMsg::Round2(_) => 2,
+            Gg18SignMsg::Round3(_) => 3,
+            Gg18SignMsg::Round4(_) => 4,
+            Gg18SignMsg::Round5(_) => 5,
+            Gg18SignMsg::Round6(_) => 6,
+            Gg18SignMsg::Round7(_) => 7,
+            Gg18SignMsg::Round8(_) => 8,
+            Gg18SignMsg::Round9(_) => 9,
+        }
+    }
+
+    pub fn is_p2p(&self) -> bool {
+        self.round() == 2
+    }
+}
+
+impl Wire for Gg18SignMsg {
+    fn write(&self, out: &mut Vec<u8>) -> Result<(), Error> {
+        match self {
+            Gg18SignMsg::Round1(m) => write_message(MessageKind::GG18_SIGN_MSG1, m, out),
+            Gg18SignMsg::Round2(m) => write_message(MessageKind::GG18_SIGN_MSG2, m, out),
+            Gg18SignMsg::Round3(m) => write_message(MessageKind::GG18_SIGN_MSG3, m, out),
+            Gg18SignMsg::Round4(m) => write_message(MessageKind::GG18_SIGN_MSG4, m, out),
+            Gg18SignMsg::Round5(m) => write_message(MessageKind::GG18_SIGN_MSG5, m, out),
+            Gg18SignMsg::Round6(m) => write_message(MessageKind::GG18_SIGN_MSG6, m, out),
+            Gg18SignMsg::Round7(m) => write_message(MessageKind::GG18_SIGN_MSG7, m, out),
+            Gg18SignMsg::Round8(m) => write_message(MessageKind::GG18_SIGN_MSG8, m, out),
+            Gg18SignMsg::Round9(m) => write_message(MessageKind::GG18_SIGN_MSG9, m, out),
+        }
+    }
+
+    fn read(input: &mut Reader<'_>) -> Result<Self, Error> {
+        Ok(match read_kind(input)? {
+            MessageKind::GG18_SIGN_MSG1 => Gg18SignMsg::Round1(Wire::read(input)?),
+            MessageKind::GG18_SIGN_MSG2 => Gg18SignMsg::Round2(Wire::read(input)?),
+            MessageKind::GG18_SIGN_MSG3 => Gg18SignMsg::Round3(Wire::read(input)?),
+            MessageKind::GG18_SIGN_MSG4 => Gg18SignMsg::Round4(Wire::read(input)?),
+            MessageKind::GG18_SIGN_MSG5 => Gg18SignMsg::Round5(Wire::read(input)?),
+            MessageKind::GG18_SIGN_MSG6 => Gg18SignMsg::Round6(Wire::read(input)?),
+            MessageKind::GG18_SIGN_MSG7 => Gg18SignMsg::Round7(Wire::read(input)?),
+            MessageKind::GG18_SIGN_MSG8 => Gg18SignMsg::Round8(Wire::read(input)?),
+            MessageKind::GG18_SIGN_MSG9 => Gg18SignMsg::Round9(Wire::read(input)?),
+            _ => return Err(Error::InvalidEncoding),
+        })
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+enum SignRound {
+    Round1(GG18SignContext1),
+    Round2(GG18SignContext2),
+    Round3(GG18SignContext3),
+    Round4(GG18SignContext4),
+    Round5(GG18SignContext5),
+    Round6(GG18SignContext6),
+    Round7(GG18SignContext7),
+    Round8(GG18SignContext8),
+    Round9(GG18SignContext9),
+    Finished,
+    Failed,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Gg18Signing {
+    index: u16,
+    indices: Vec<u16>,
+    round: SignRound,
+    incoming: Vec<Msg<Gg18SignMsg>>,
+    queue: Vec<Msg<Gg18SignMsg>>,
+    output: Option<Signature>,
+}
+
+impl Persist for Gg18Signing {
+    const PROTOCOL: Protocol = Protocol::Gg18;
+    const KIND: &'static str = "sign_state";
+}
+
+impl Gg18Signing {
+    // indices are the key generation indices of all signers, including ours
+    pub fn new(
+        context: GG18SignContext,
+        indices: Vec<u16>,
+        message_hash: Vec<u8>,
+        session: SessionId,
+    ) -> Result<Self, Error> {
+        let index = context.index;
+        let threshold_index =
+            indices
+                .iter()
+                .position(|&i| i == index)
+                .ok_or(Error::MalformedInput {
+                    round: 1,
+                    party: None,
+                })?;
+        let (msg1, context1) = gg18_sign1(
+            context,
+            indices.clone(),
+            threshold_index,
+            message_hash,
+            session,
+        )?;
+        let mut signing = Gg18Signing {
+            index,
+            indices,
+            round: SignRound::Round1(context1),
+            incoming: Vec::new(),
+            queue: Vec::new(),
+            output: None,
+        };
+        signing.broadcast(Gg18SignMsg::Round1(msg1));
+        Ok(signing)
+    }
+
+    fn broadcast(&mut self, body: Gg18SignMsg) {
+        self.queue.push(Msg {
+            sender: self.index,
+            receiver: None,
+            body,
+        });
+    }
+}
+
+impl StateMachine for Gg18Signing {
+    type MessageBody = Gg18SignMsg;
+    type Output = Signature;
+
+    fn handle_incoming(&mut self, msg: Msg<Gg18SignMsg>) -> Result<(), Error> {
+        // messages of round r are taken by gg18_sign{r + 1}
+        let round = msg.body.round() + 1;
+        if !self.indices.contains(&msg.sender) || msg.sender == self.index {
+            return Err(Error::UnexpectedSender {
+                round,
+                party: msg.sender,
+            });
+        }
+        let receiver = if msg.body.is_p2p() {
+            Some(self.index)
+        } else {
+            None
+        };
+        let duplicate = self
+            .incoming
+            .iter()
+            .any(|m| m.sender == msg.sender && m.body.round() == msg.body.round());
+        if msg.receiver != receiver || msg.body.round() < self.current_round() || duplicate {
+            return Err(Error::MalformedInput {
+                round,
+                party: Some(msg.sender),
+            });
+        }
+        self.incoming.push(msg);
+        Ok(())
+    }
+
+    fn message_queue(&mut self) -> &mut Vec<Msg<Gg18SignMsg>> {
+        &mut self.queue
+    }
+
+    fn wants_to_proceed(&self) -> bool {
+        let round = self.current_round();
+        match self.round {
+            SignRound::Finished | SignRound::Failed => false,
+            _ => {
+                self.incoming
+                    .iter()
+                    .filter(|m| m.body.round() == round)
+                    .count()
+                    == self.indices.len() - 1
+            }
+        }
+    }
+
+    fn proceed(&mut self) -> Result<(), Error> {
+        if !self.wants_to_proceed() {
+            return Ok(());
+        }
+        self.round = match mem::replace(&mut self.round, SignRound::Failed) {
+            SignRound::Round1(context) => {
+                let messages = take_round(&mut self.incoming, |m| match m {
+                    Gg18SignMsg::Round1(m) => Ok(m),
+                    m => Err(m),
+                });
+                let (requests, context) = gg18_sign2(messages, context)?;
+                for (j, msg) in requests {
+                    self.queue.push(Msg {
+                        sender: self.index,
+                        receiver: Some(j),
+                        body: Gg18SignMsg::Round2(msg),
+                    });
+                }
+                SignRound::Round2(context)
+            }
+            SignRound::Round2(context) => {
+                let messages = take_round(&mut self.incoming, |m| match m {
+                    Gg18SignMsg::Round2(m) => Ok(m),
+                    m => Err(m),
+                });
+                let (msg, context) = gg18_sign3(messages, context)?;
+                self.broadcast(Gg18SignMsg::Round3(msg));
+                SignRound::Round3(context)
+            }
+            SignRound::Round3(context) => {
+                let messages = take_round(&mut self.incoming, |m| match m {
+                    Gg18SignMsg::Round3(m) => Ok(m),
+                    m => Err(m),
+                });
+                let (msg, context) = gg18_sign4(messages, context)?;
+                self.broadcast(Gg18SignMsg::Round4(msg));
+                SignRound::Round4(context)
+            }
+            SignRound::Round4(context) => {
+                let messages = take_round(&mut self.incoming, |m| match m {
+                    Gg18SignMsg::Round4(m) => Ok(m),
+                    m => Err(m),
+                });
+                let (msg, context) = gg18_sign5(messages, context)?;
+                self.broadcast(Gg18SignMsg::Round5(msg));
+                SignRound::Round5(context)
+            }
+            SignRound::Round5(context) => {
+                let messages = take_round(&mut self.incoming, |m| match m {
+                    Gg18SignMsg::Round5(m) => Ok(m),
+                    m => Err(m),
+                });
+                let (msg, context) = gg18_sign6(messages, context)?;
+                self.broadcast(Gg18SignMsg::Round6(msg));
+                SignRound::Round6(context)
+            }
+            SignRound::Round6(context) => {
+                let messages = take_round(&mut self.incoming, |m| match m {
+                    Gg18SignMsg::Round6(m) => Ok(m),
+                    m => Err(m),
+                });
+                let (msg, context) = gg18_sign7(messages, context)?;
+                self.broadcast(Gg18SignMsg::Round7(msg));
+                SignRound::Round7(context)
+            }
+            SignRound::Round7(context) => {
+                let messages = take_round(&mut self.incoming, |m| match m {
+                    Gg18SignMsg::Round7(m) => Ok(m),
+                    m => Err(m),
+                });
+                let (msg, context) = gg18_sign8(messages, context)?;
+                self.broadcast(Gg18SignMsg::Round8(msg));
+                SignRound::Round8(context)
+            }
+            SignRound::Round8(context) => {
+                let messages = take_round(&mut self.incoming, |m| match m {
+                    Gg18SignMsg::Round8(m) => Ok(m),
+                    m => Err(m),
+                });
+                let (msg, context) = gg18_sign9(messages, context)?;
+                self.broadcast(Gg18SignMsg::Round9(msg));
+                SignRound::Round9(context)
+            }
+            SignRound::Round9(context) => {
+                let messages = take_round(&mut self.incoming, |m| match m {
+                    Gg18SignMsg::Round9(m) => Ok(m),
+                    m => Err(m),
+                });
+                self.output = Some(gg18_sign10(messages, context)?);
+                SignRound::Finished
+            }
+            SignRound::Finished | SignRound::Failed => unreachable!(),
+        };
+        Ok(())
+    }
+
+    fn current_round(&self) -> u16 {
+        match self.round {
+            SignRound::Round1(_) => 1,
+            SignRound::Round2(_) => 2,
+            SignRound::Round3(_) => 3,
+            SignRound::Round4(_) => 4,
+            SignRound::Round5(_) => 5,
+            SignRound::Round6(_) => 6,
+            SignRound::Round7(_) => 7,
+            SignRound::Round8(_) => 8,
+            SignRound::Round9(_) => 9,
+            SignRound::Finished | SignRound::Failed => 10,
+        }
+    }
+
+    fn is_finished(&self) -> bool {
+        matches!(self.round, SignRound::Finished)
+    }
+
+    fn pick_output(&mut self) -> Option<Signature> {
+        self.output.take()
+    }
+
+    fn party_index(&self) -> u16 {
+        self.index
+    }
+}
@@ -1,18 +1,38 @@
+use crate::gg18_batch_sign::{
+    gg18_batch_sign1, gg18_batch_sign10, gg18_batch_sign2, gg18_batch_sign3, gg18_batch_sign4,
+    gg18_batch_sign5, gg18_batch_sign6, gg18_batch_sign7, gg18_batch_sign8, gg18_batch_sign9,
+};
 use crate::gg18_key_gen::{
-    gg18_key_gen_1, gg18_key_gen_2, gg18_key_gen_3, gg18_key_gen_4, gg18_key_gen_5, gg18_key_gen_6,
+    dh_binding, gg18_key_gen_1, gg18_key_gen_2, gg18_key_gen_3, gg18_key_gen_4, gg18_key_gen_5,
+    gg18_key_gen_6, GG18KeyGenContext1, GG18KeyGenMsg1, GG18SignContext,
+};
+use crate::gg18_refresh::{gg18_refresh_1, gg18_refresh_2, gg18_refresh_3};
+use crate::gg18_reshare::{
+    deal, gg18_reshare_dealer, gg18_reshare_receiver_1, gg18_reshare_receiver_2, GG18ReshareDealers,
 };
 use crate::gg18_sign::{
+    gg18_presign1, gg18_presign2, gg18_presign3, gg18_presign4, gg18_presign5, gg18_presign_sign,
     gg18_sign1, gg18_sign10, gg18_sign2, gg18_sign3, gg18_sign4, gg18_sign5, gg18_sign6,
-    gg18_sign7, gg18_sign8, gg18_sign9,
+    gg18_sign7, gg18_sign8, gg18_sign9, GG18PresignContext4, GG18Presignature, GG18SignContext5,
+    GG18SignContext8, GG18SignMsg4, GG18SignMsg5, GG18SignMsg8,
 };
-use curv::arithmetic::traits::*;
+use crate::identity::IdentityKey;
+use crate::secret::CloneSecret;
+use crate::session::{Envelope, SessionId, SignedMsg};
+use crate::simulation::{Outcome, Simulation};
+use crate::wire::{MessageKind, Wire};
+use crate::{Error, Evidence, Signature};
+use curv::cryptographic_primitives::secret_sharing::feldman_vss::VerifiableSS;
 use curv::elliptic::curves::{p256::Secp256r1, Point, Scalar};
-use curv::BigInt;
+use multi_party_ecdsa::protocols::multi_party_ecdsa::gg_2018::party_i::Keys;
 use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
 
-pub fn check_sig(r: &Scalar<Secp256r1>, s: &Scalar<Secp256r1>, msg: &[u8], pk: &Point<Secp256r1>) {
-    use p256::ecdsa::{signature::Verifier, Signature, VerifyingKey};
-    use p256::FieldBytes;
+pub const SID: SessionId = SessionId::new([9; 32]);
+
+pub fn check_sig(sig: &Signature, msg: &[u8], pk: &Point<Secp256r1>) {
+    use p256::ecdsa::{signature::Verifier, VerifyingKey};
 
     let slice = pk.to_bytes(false);
     let mut raw_pk = Vec::new();
@@ -29,14 +49,7 @@ pub fn check_sig(r: &Scalar<Secp256r1>, s: &Scalar<Secp256r1>, msg: &[u8], pk: &
 
     let public_key: VerifyingKey = VerifyingKey::from_sec1_bytes(&raw_pk).unwrap();
 
-    let bytes_r = &r.to_bytes()[..];
-    let bytes_s = &s.to_bytes()[..];
-
-    let signature = Signature::from_scalars(
-        *FieldBytes::from_slice(bytes_r),
-        *FieldBytes::from_slice(bytes_s),
-    )
-    .unwrap();
+    let signature = p256::ecdsa::Signature::try_from(sig).unwrap();
 
     let is_correct = public_key.verify(msg, &signature).is_ok();
     assert!(is_correct);
@@ -44,407 +57,1421 @@ pub fn check_sig(r: &Scalar<Secp256r1>, s: &Scalar<Secp256r1>, msg: &[u8], pk: &
 
 #[test]
 fn t3_of_n4() {
-    // keygen1
-    let (gg18_key_gen_msg1_p0, keygen_context1_p0) = gg18_key_gen_1(4, 3, 0).unwrap();
-    let (gg18_key_gen_msg1_p1, keygen_context1_p1) = gg18_key_gen_1(4, 3, 1).unwrap();
-    let (gg18_key_gen_msg1_p2, keygen_context1_p2) = gg18_key_gen_1(4, 3, 2).unwrap();
-    let (gg18_key_gen_msg1_p3, keygen_context1_p3) = gg18_key_gen_1(4, 3, 3).unwrap();
-
-    // keygen2
-
-    let (gg18_key_gen_msg2_p0, keygen_context2_p0) = gg18_key_gen_2(
-        [
-            gg18_key_gen_msg1_p1.clone(),
-            gg18_key_gen_msg1_p2.clone(),
-            gg18_key_gen_msg1_p3.clone(),
-        ]
-        .to_vec(),
-        keygen_context1_p0,
-    )
-    .unwrap();
-    let (gg18_key_gen_msg2_p1, keygen_context2_p1) = gg18_key_gen_2(
-        [
-            gg18_key_gen_msg1_p0.clone(),
-            gg18_key_gen_msg1_p2.clone(),
-            gg18_key_gen_msg1_p3.clone(),
-        ]
-        .to_vec(),
-        keygen_context1_p1,
-    )
-    .unwrap();
-    let (gg18_key_gen_msg2_p2, keygen_context2_p2) = gg18_key_gen_2(
-        [
-            gg18_key_gen_msg1_p0.clone(),
-            gg18_key_gen_msg1_p1.clone(),
-            gg18_key_gen_msg1_p3.clone(),
-        ]
-        .to_vec(),
-        keygen_context1_p2,
-    )
-    .unwrap();
-    let (gg18_key_gen_msg2_p3, keygen_context2_p3) = gg18_key_gen_2(
-        [
-            gg18_key_gen_msg1_p0.clone(),
-            gg18_key_gen_msg1_p1.clone(),
-            gg18_key_gen_msg1_p2.clone(),
-        ]
-        .to_vec(),
-        keygen_context1_p3,
-    )
-    .unwrap();
+    let contexts = keygen(4, 3);
+    let pk = contexts[0].pk.clone();
+    assert!(contexts.iter().all(|c| c.pk == pk));
 
-    // keygen3
+    // signers in any order, here 2, 3 and 0
+    let message = b"random message";
+    let hash = Sha256::digest(message);
+    let signers = [2, 3, 0]
+        .iter()
+        .map(|&i| contexts[i].clone_secret())
+        .collect();
+    let outcomes = Simulation::new(SID).gg18_sign(signers, &hash).unwrap();
+    assert_eq!(outcomes.len(), 3);
+    for outcome in outcomes {
+        check_sig(&outcome.output().unwrap(), message, &pk);
+    }
+}
 
-    let (gg18_key_gen_msg3_p0, keygen_context3_p0) = gg18_key_gen_3(
-        [
-            gg18_key_gen_msg2_p1.clone(),
-            gg18_key_gen_msg2_p2.clone(),
-            gg18_key_gen_msg2_p3.clone(),
-        ]
-        .to_vec(),
-        keygen_context2_p0,
-    )
-    .unwrap();
-    let (gg18_key_gen_msg3_p1, keygen_context3_p1) = gg18_key_gen_3(
-        [
-            gg18_key_gen_msg2_p0.clone(),
-            gg18_key_gen_msg2_p2.clone(),
-            gg18_key_gen_msg2_p3.clone(),
-        ]
-        .to_vec(),
-        keygen_context2_p1,
-    )
-    .unwrap();
-    let (gg18_key_gen_msg3_p2, keygen_context3_p2) = gg18_key_gen_3(
-        [
-            gg18_key_gen_msg2_p0.clone(),
-            gg18_key_gen_msg2_p1.clone(),
-            gg18_key_gen_msg2_p3.clone(),
-        ]
-        .to_vec(),
-        keygen_context2_p2,
-    )
-    .unwrap();
-    let (gg18_key_gen_msg3_p3, keygen_context3_p3) = gg18_key_gen_3(
-        [
-            gg18_key_gen_msg2_p0.clone(),
-            gg18_key_gen_msg2_p1.clone(),
-            gg18_key_gen_msg2_p2.clone(),
-        ]
-        .to_vec(),
-        keygen_context2_p3,
-    )
-    .unwrap();
+// the broadcasts of every party but i, keyed by sender
+fn others<T: Clone>(messages: &[T], i: usize) -> BTreeMap<u16, T> {
+    messages
+        .iter()
+        .enumerate()
+        .filter(|(j, _)| *j != i)
+        .map(|(j, m)| (j as u16, m.clone()))
+        .collect()
+}
 
-    // keygen4
+// the p2p messages addressed to party i, keyed by sender
+fn p2p<T: Clone>(outputs: &[BTreeMap<u16, T>], i: usize) -> BTreeMap<u16, T> {
+    outputs
+        .iter()
+        .enumerate()
+        .filter(|(j, _)| *j != i)
+        .map(|(j, out)| (j as u16, out[&(i as u16)].clone()))
+        .collect()
+}
 
-    let (gg18_key_gen_msg4_p0, keygen_context4_p0) = gg18_key_gen_4(
-        [
-            gg18_key_gen_msg3_p1[0].clone(),
-            gg18_key_gen_msg3_p2[0].clone(),
-            gg18_key_gen_msg3_p3[0].clone(),
-        ]
-        .to_vec(),
-        keygen_context3_p0,
-    )
-    .unwrap();
-    let (gg18_key_gen_msg4_p1, keygen_context4_p1) = gg18_key_gen_4(
-        [
-            gg18_key_gen_msg3_p0[0].clone(),
-            gg18_key_gen_msg3_p2[1].clone(),
-            gg18_key_gen_msg3_p3[1].clone(),
-        ]
-        .to_vec(),
-        keygen_context3_p1,
-    )
-    .unwrap();
-    let (gg18_key_gen_msg4_p2, keygen_context4_p2) = gg18_key_gen_4(
-        [
-            gg18_key_gen_msg3_p0[1].clone(),
-            gg18_key_gen_msg3_p1[1].clone(),
-            gg18_key_gen_msg3_p3[2].clone(),
-        ]
-        .to_vec(),
-        keygen_context3_p2,
-    )
-    .unwrap();
-    let (gg18_key_gen_msg4_p3, keygen_context4_p3) = gg18_key_gen_4(
-        [
-            gg18_key_gen_msg3_p0[2].clone(),
-            gg18_key_gen_msg3_p1[2].clone(),
-            gg18_key_gen_msg3_p2[2].clone(),
-        ]
-        .to_vec(),
-        keygen_context3_p3,
-    )
-    .unwrap();
+pub fn one<T>(sender: u16, message: T) -> BTreeMap<u16, T> {
+    let mut messages = BTreeMap::new();
+    messages.insert(sender, message);
+    messages
+}
 
-    // keygen5
+fn relabel<T>(messages: &mut BTreeMap<u16, T>, from: u16, to: u16) {
+    let message = messages.remove(&from).unwrap();
+    messages.insert(to, message);
+}
 
-    let (gg18_key_gen_msg5_p0, keygen_context5_p0) = gg18_key_gen_5(
-        [
-            gg18_key_gen_msg4_p1.clone(),
-            gg18_key_gen_msg4_p2.clone(),
-            gg18_key_gen_msg4_p3.clone(),
-        ]
-        .to_vec(),
-        keygen_context4_p0,
-    )
-    .unwrap();
-    let (gg18_key_gen_msg5_p1, keygen_context5_p1) = gg18_key_gen_5(
-        [
-            gg18_key_gen_msg4_p0.clone(),
-            gg18_key_gen_msg4_p2.clone(),
-            gg18_key_gen_msg4_p3.clone(),
-        ]
-        .to_vec(),
-        keygen_context4_p1,
-    )
-    .unwrap();
-    let (gg18_key_gen_msg5_p2, keygen_context5_p2) = gg18_key_gen_5(
-        [
-            gg18_key_gen_msg4_p0.clone(),
-            gg18_key_gen_msg4_p1.clone(),
-            gg18_key_gen_msg4_p3.clone(),
-        ]
-        .to_vec(),
-        keygen_context4_p2,
-    )
-    .unwrap();
-    let (gg18_key_gen_msg5_p3, keygen_context5_p3) = gg18_key_gen_5(
-        [
-            gg18_key_gen_msg4_p0.clone(),
-            gg18_key_gen_msg4_p1.clone(),
-            gg18_key_gen_msg4_p2.clone(),
-        ]
-        .to_vec(),
-        keygen_context4_p3,
+fn swap<T>(messages: &mut BTreeMap<u16, T>, a: u16, b: u16) {
+    let message_a = messages.remove(&a).unwrap();
+    let message_b = messages.insert(b, message_a).unwrap();
+    messages.insert(a, message_b);
+}
+
+// fresh identity keys of parties 0 to parties - 1 and their public keys
+pub fn identities(parties: u16) -> (Vec<IdentityKey>, Vec<Point<Secp256r1>>) {
+    let keys: Vec<IdentityKey> = (0..parties).map(|_| IdentityKey::random()).collect();
+    let publics = keys.iter().map(|k| k.public()).collect();
+    (keys, publics)
+}
+
+// round 1 of key generation at every party, with copies of the identity keys
+fn key_gen_start(
+    parties: u16,
+    threshold: u16,
+    session: SessionId,
+) -> (
+    Vec<GG18KeyGenMsg1>,
+    Vec<GG18KeyGenContext1>,
+    Vec<IdentityKey>,
+) {
+    let (keys, ids) = identities(parties);
+    let (msg1, ctx1) = keys
+        .iter()
+        .enumerate()
+        .map(|(i, k)| {
+            gg18_key_gen_1(threshold, i as u16, session, k.clone_secret(), ids.clone()).unwrap()
+        })
+        .unzip();
+    (msg1, ctx1, keys)
+}
+
+// a message as a misbehaving sender signs it, whatever its body
+fn signed<T: Wire>(body: T, key: &IdentityKey, envelope: Envelope) -> SignedMsg<T> {
+    SignedMsg::sign(SID, body, key, &envelope).unwrap()
+}
+
+pub fn keygen(parties: u16, threshold: u16) -> Vec<GG18SignContext> {
+    Simulation::new(SID)
+        .gg18_key_gen(parties, threshold)
+        .unwrap()
+        .into_iter()
+        .map(|o| o.output().unwrap())
+        .collect()
+}
+
+#[test]
+fn key_gen_rejects_bad_parameters() {
+    let (keys, ids) = identities(3);
+    let key_gen_1 = |threshold, index: u16, ids: Vec<_>| {
+        gg18_key_gen_1(threshold, index, SID, keys[0].clone_secret(), ids)
+    };
+    assert!(key_gen_1(0, 0, ids.clone()).is_err());
+    assert!(key_gen_1(4, 0, ids.clone()).is_err());
+    assert!(key_gen_1(2, 3, ids.clone()).is_err());
+    assert!(key_gen_1(0, 0, Vec::new()).is_err());
+
+    // our identity key is not the one at our index
+    assert!(key_gen_1(2, 1, ids.clone()).is_err());
+    let mut zero = ids.clone();
+    zero[2] = Point::zero();
+    assert!(key_gen_1(2, 0, zero).is_err());
+    assert!(key_gen_1(2, 0, ids).is_ok());
+}
+
+#[test]
+fn key_gen_rejects_malformed_messages() {
+    let (msg1, ctx1, _) = key_gen_start(3, 2, SID);
+
+    // truncated round 1 broadcast
+    let mut truncated = others(&msg1, 0);
+    truncated.remove(&2);
+    assert_eq!(
+        gg18_key_gen_2(truncated, ctx1[0].clone_secret()).unwrap_err(),
+        Error::WrongMessageCount {
+            round: 2,
+            expected: 2,
+            received: 1
+        }
+    );
+
+    // a message keyed by a party outside the key generation, or by ourselves
+    let mut relabelled = others(&msg1, 0);
+    relabel(&mut relabelled, 2, 5);
+    assert_eq!(
+        gg18_key_gen_2(relabelled, ctx1[0].clone_secret()).unwrap_err(),
+        Error::UnexpectedSender { round: 2, party: 5 }
+    );
+    let mut relabelled = others(&msg1, 0);
+    relabel(&mut relabelled, 2, 0);
+    assert_eq!(
+        gg18_key_gen_2(relabelled, ctx1[0].clone_secret()).unwrap_err(),
+        Error::UnexpectedSender { round: 2, party: 0 }
+    );
+
+    let (msg2, ctx2): (Vec<_>, Vec<_>) = ctx1
+        .into_iter()
+        .enumerate()
+        .map(|(i, c)| gg18_key_gen_2(others(&msg1, i), c).unwrap())
+        .unzip();
+
+    // swapped decommitments no longer match the commitments
+    let mut swapped = others(&msg2, 0);
+    swap(&mut swapped, 1, 2);
+    assert!(gg18_key_gen_3(swapped, ctx2[0].clone_secret()).is_err());
+    assert!(gg18_key_gen_3(BTreeMap::new(), ctx2[0].clone_secret()).is_err());
+
+    let (msg3, ctx3): (Vec<_>, Vec<_>) = ctx2
+        .into_iter()
+        .enumerate()
+        .map(|(i, c)| gg18_key_gen_3(others(&msg2, i), c).unwrap())
+        .unzip();
+
+    assert!(gg18_key_gen_4(one(1, p2p(&msg3, 0)[&1].clone()), ctx3[0].clone_secret()).is_err());
+
+    let (msg4, ctx4): (Vec<_>, Vec<_>) = ctx3
+        .into_iter()
+        .enumerate()
+        .map(|(i, c)| gg18_key_gen_4(p2p(&msg3, i), c).unwrap())
+        .unzip();
+
+    // swapped vss schemes do not verify the received shares
+    let mut swapped = others(&msg4, 0);
+    swap(&mut swapped, 1, 2);
+    assert!(gg18_key_gen_5(swapped, ctx4[0].clone_secret()).is_err());
+    assert!(gg18_key_gen_5(one(1, others(&msg4, 0)[&1].clone()), ctx4[0].clone_secret()).is_err());
+
+    let (_msg5, ctx5): (Vec<_>, Vec<_>) = ctx4
+        .into_iter()
+        .enumerate()
+        .map(|(i, c)| gg18_key_gen_5(others(&msg4, i), c).unwrap())
+        .unzip();
+
+    assert!(gg18_key_gen_6(BTreeMap::new(), ctx5[0].clone_secret()).is_err());
+}
+
+#[test]
+fn key_gen_rejects_tampered_shares() {
+    let (msg1, ctx1, keys) = key_gen_start(3, 2, SID);
+
+    // an ephemeral key at infinity
+    let mut zero_key = others(&msg1, 0);
+    let body = (msg1[1].body.0.clone(), Point::zero());
+    let envelope = Envelope::broadcast(MessageKind::GG18_KEY_GEN_MSG1, 1);
+    zero_key.insert(1, signed(body, &keys[1], envelope));
+    assert_eq!(
+        gg18_key_gen_2(zero_key, ctx1[0].clone_secret()).unwrap_err(),
+        Error::MalformedInput {
+            round: 2,
+            party: Some(1)
+        }
+    );
+
+    let (msg2, ctx2): (Vec<_>, Vec<_>) = ctx1
+        .into_iter()
+        .enumerate()
+        .map(|(i, c)| gg18_key_gen_2(others(&msg1, i), c).unwrap())
+        .unzip();
+    let (msg3, ctx3): (Vec<_>, Vec<_>) = ctx2
+        .into_iter()
+        .enumerate()
+        .map(|(i, c)| gg18_key_gen_3(others(&msg2, i), c).unwrap())
+        .unzip();
+
+    // a 32 byte share and the tag
+    assert_eq!(msg3[1][&0].body.len(), 32 + 16);
+
+    // a flipped bit in the sealed share, signed by its sender
+    let mut flipped = p2p(&msg3, 0);
+    let mut body = msg3[1][&0].body.clone();
+    body[0] ^= 1;
+    let binding = msg1[0].body.1.to_bytes(true);
+    let envelope = Envelope {
+        kind: MessageKind::GG18_KEY_GEN_MSG3,
+        sender: 1,
+        receiver: Some(0),
+        binding: binding.as_ref(),
+    };
+    flipped.insert(1, signed(body, &keys[1], envelope));
+    assert_blamed(
+        gg18_key_gen_4(flipped, ctx3[0].clone_secret()),
+        4,
+        1,
+        &keys[1].public(),
+    );
+
+    // the share of party 2 delivered to party 0 was not signed for party 0
+    let mut misdelivered = p2p(&msg3, 0);
+    misdelivered.insert(1, msg3[1][&2].clone());
+    assert_eq!(
+        gg18_key_gen_4(misdelivered, ctx3[0].clone_secret()).unwrap_err(),
+        Error::InvalidIdentitySignature {
+            round: 4,
+            party: Some(1)
+        }
+    );
+
+    // a flipped bit without a new signature
+    let mut unsigned = p2p(&msg3, 0);
+    unsigned.get_mut(&1).unwrap().body[0] ^= 1;
+    assert_eq!(
+        gg18_key_gen_4(unsigned, ctx3[0].clone_secret()).unwrap_err(),
+        Error::InvalidIdentitySignature {
+            round: 4,
+            party: Some(1)
+        }
+    );
+
+    assert!(gg18_key_gen_4(p2p(&msg3, 0), ctx3[0].clone_secret()).is_ok());
+}
+
+// identity is the identity public key of party
+fn assert_blamed<T>(result: Result<T, Error>, round: u16, party: u16, identity: &Point<Secp256r1>) {
+    let evidence = match result {
+        Err(Error::Misbehavior { evidence }) => evidence,
+        _ => panic!("expected misbehavior"),
+    };
+    assert_eq!((evidence.round, evidence.party), (round, party));
+    assert!(evidence.verify(identity));
+
+    // the messages were not signed by anyone else
+    assert!(!evidence.verify(&IdentityKey::random().public()));
+
+    // the evidence survives serialization and cannot be moved to another party
+    let json = serde_json::to_string(&evidence).unwrap();
+    let mut evidence: Evidence = serde_json::from_str(&json).unwrap();
+    assert!(evidence.verify(identity));
+    evidence.round += 1;
+    assert!(!evidence.verify(identity));
+    evidence.round -= 1;
+    evidence.party = (party + 1) % 3;
+    assert!(!evidence.verify(identity));
+    evidence.party = party;
+    evidence.session = SessionId::derive(&[b"other session"]);
+    assert!(!evidence.verify(identity));
+}
+
+#[test]
+fn key_gen_blames_misbehaving_parties() {
+    let (msg1, ctx1, keys) = key_gen_start(3, 2, SID);
+    let (msg2, ctx2): (Vec<_>, Vec<_>) = ctx1
+        .into_iter()
+        .enumerate()
+        .map(|(i, c)| gg18_key_gen_2(others(&msg1, i), c).unwrap())
+        .unzip();
+
+    // party 2 decommits to the value of party 1
+    let mut copied = others(&msg2, 0);
+    let envelope = Envelope::broadcast(MessageKind::GG18_KEY_GEN_MSG2, 2);
+    copied.insert(2, signed(msg2[1].body.clone(), &keys[2], envelope));
+    assert_blamed(
+        gg18_key_gen_3(copied, ctx2[0].clone_secret()),
+        3,
+        2,
+        &keys[2].public(),
+    );
+
+    // the decommitment of party 1 passed off as one of party 2
+    let mut replayed = others(&msg2, 0);
+    replayed.insert(2, msg2[1].clone());
+    assert_eq!(
+        gg18_key_gen_3(replayed, ctx2[0].clone_secret()).unwrap_err(),
+        Error::InvalidIdentitySignature {
+            round: 3,
+            party: Some(2)
+        }
+    );
+
+    let (msg3, ctx3): (Vec<_>, Vec<_>) = ctx2
+        .into_iter()
+        .enumerate()
+        .map(|(i, c)| gg18_key_gen_3(others(&msg2, i), c).unwrap())
+        .unzip();
+    let (msg4, ctx4): (Vec<_>, Vec<_>) = ctx3
+        .into_iter()
+        .enumerate()
+        .map(|(i, c)| gg18_key_gen_4(p2p(&msg3, i), c).unwrap())
+        .unzip();
+
+    // party 1 publishes a vss scheme that does not match its share
+    let mut copied = others(&msg4, 0);
+    let envelope = Envelope::broadcast(MessageKind::GG18_KEY_GEN_MSG4, 1);
+    copied.insert(1, signed(msg4[2].body.clone(), &keys[1], envelope));
+    assert_blamed(
+        gg18_key_gen_5(copied, ctx4[0].clone_secret()),
+        5,
+        1,
+        &keys[1].public(),
+    );
+
+    let (msg5, ctx5): (Vec<_>, Vec<_>) = ctx4
+        .into_iter()
+        .enumerate()
+        .map(|(i, c)| gg18_key_gen_5(others(&msg4, i), c).unwrap())
+        .unzip();
+
+    let mut forged = others(&msg5, 0);
+    let mut proof = msg5[2].body.clone();
+    proof.pk = proof.pk + Point::generator();
+    let envelope = Envelope::broadcast(MessageKind::GG18_KEY_GEN_MSG5, 2);
+    forged.insert(2, signed(proof, &keys[2], envelope));
+    assert_blamed(
+        gg18_key_gen_6(forged, ctx5[0].clone_secret()),
+        6,
+        2,
+        &keys[2].public(),
+    );
+}
+
+#[test]
+fn sign_blames_misbehaving_parties() {
+    let contexts = keygen(3, 2);
+    let hash = Sha256::digest(b"random message").to_vec();
+    let indices = [0u16, 2].to_vec();
+
+    let (msg1_a, ctx1_a) = gg18_sign1(
+        contexts[0].clone_secret(),
+        indices.clone(),
+        0,
+        hash.clone(),
+        SID,
     )
     .unwrap();
+    let (msg1_b, ctx1_b) = gg18_sign1(contexts[2].clone_secret(), indices, 1, hash, SID).unwrap();
+    let (msg2_a, ctx2_a) = gg18_sign2(one(2, msg1_b), ctx1_a).unwrap();
+    let (msg2_b, ctx2_b) = gg18_sign2(one(0, msg1_a), ctx1_b).unwrap();
+    let key = &contexts[2].identity;
+    let identity = key.public();
 
-    // keygen6
+    // party 2 answers the MtA of party 0 for w_i with its proof for gamma_i
+    let envelope = || Envelope {
+        kind: MessageKind::GG18_SIGN_MSG2,
+        sender: 2,
+        receiver: Some(0),
+        binding: &[],
+    };
+    let (m_b_gamma, m_b_w) = msg2_b[&0].body.clone();
+    let result = gg18_sign3(
+        one(
+            2,
+            signed((m_b_gamma.clone(), m_b_gamma.clone()), key, envelope()),
+        ),
+        ctx2_a.clone_secret(),
+    );
+    // the evidence is checked against the share of party 2 weighted for the signers
+    let public_shares = Keys::get_commitments_to_xi(&contexts[0].vss_scheme_vec);
+    match &result {
+        Err(Error::Misbehavior { evidence }) => assert_eq!(
+            evidence.public_data(),
+            Some((vec![0, 2], public_shares[2].clone()))
+        ),
+        _ => panic!("expected misbehavior"),
+    }
+    assert_blamed(result, 3, 2, &identity);
+
+    // a proof that does not verify
+    let mut broken = m_b_w;
+    broken.beta_tag_proof.pk = Point::generator().to_point();
+    assert_blamed(
+        gg18_sign3(
+            one(2, signed((m_b_gamma, broken), key, envelope())),
+            ctx2_a.clone_secret(),
+        ),
+        3,
+        2,
+        &identity,
+    );
+
+    let (msg3_a, ctx3_a) = gg18_sign3(msg2_b, ctx2_a).unwrap();
+    let (msg3_b, ctx3_b) = gg18_sign3(msg2_a, ctx2_b).unwrap();
+    // R from the public delta shares and decommitments, party 2 signs its proofs for it
+    let delta = msg3_a.body.clone() + msg3_b.body.clone();
+    let (msg4_a, ctx4_a) = gg18_sign4(one(2, msg3_b), ctx3_a).unwrap();
+    let (msg4_b, ctx4_b) = gg18_sign4(one(0, msg3_a), ctx3_b).unwrap();
+    let r =
+        (msg4_a.body.g_gamma_i.clone() + msg4_b.body.g_gamma_i.clone()) * delta.invert().unwrap();
+
+    // party 2 replays the messages of party 0 in every round, signed as its own
+    let envelope = Envelope::broadcast(MessageKind::GG18_SIGN_MSG4, 2);
+    assert_blamed(
+        gg18_sign5(
+            one(2, signed(msg4_a.body.clone(), key, envelope)),
+            ctx4_a.clone_secret(),
+        ),
+        5,
+        2,
+        &identity,
+    );
+
+    let (msg5_a, ctx5_a) = gg18_sign5(one(2, msg4_b), ctx4_a).unwrap();
+    let (msg5_b, ctx5_b) = gg18_sign5(one(0, msg4_a), ctx4_b).unwrap();
+    let (msg6_a, ctx6_a) = gg18_sign6(one(2, msg5_b), ctx5_a).unwrap();
+    let (msg6_b, ctx6_b) = gg18_sign6(one(0, msg5_a), ctx5_b).unwrap();
+
+    let binding = r.to_bytes(true);
+    let envelope = Envelope {
+        kind: MessageKind::GG18_SIGN_MSG6,
+        sender: 2,
+        receiver: None,
+        binding: binding.as_ref(),
+    };
+    assert_blamed(
+        gg18_sign7(
+            one(2, signed(msg6_a.body.clone(), key, envelope)),
+            ctx6_a.clone_secret(),
+        ),
+        7,
+        2,
+        &identity,
+    );
+
+    // the proofs of party 2 signed for another R
+    let envelope = Envelope {
+        kind: MessageKind::GG18_SIGN_MSG6,
+        sender: 2,
+        receiver: None,
+        binding: &[],
+    };
+    assert_eq!(
+        gg18_sign7(
+            one(2, signed(msg6_b.body.clone(), key, envelope)),
+            ctx6_a.clone_secret()
+        )
+        .unwrap_err(),
+        Error::InvalidIdentitySignature {
+            round: 7,
+            party: Some(2)
+        }
+    );
+
+    let (msg7_a, ctx7_a) = gg18_sign7(one(2, msg6_b), ctx6_a).unwrap();
+    let (msg7_b, ctx7_b) = gg18_sign7(one(0, msg6_a), ctx6_b).unwrap();
+    let (msg8_a, ctx8_a) = gg18_sign8(one(2, msg7_b), ctx7_a).unwrap();
+    let (msg8_b, ctx8_b) = gg18_sign8(one(0, msg7_a), ctx7_b).unwrap();
 
-    let gg18_sign_context_p0 = gg18_key_gen_6(
-        [
-            gg18_key_gen_msg5_p1.clone(),
-            gg18_key_gen_msg5_p2.clone(),
-            gg18_key_gen_msg5_p3.clone(),
-        ]
-        .to_vec(),
-        keygen_context5_p0,
+    let envelope = Envelope::broadcast(MessageKind::GG18_SIGN_MSG8, 2);
+    assert_blamed(
+        gg18_sign9(
+            one(2, signed(msg8_a.body.clone(), key, envelope)),
+            ctx8_a.clone_secret(),
+        ),
+        9,
+        2,
+        &identity,
+    );
+
+    assert!(gg18_sign9(one(2, msg8_b), ctx8_a).is_ok());
+    assert!(gg18_sign9(one(0, msg8_a), ctx8_b).is_ok());
+}
+
+#[test]
+fn sign_rejects_malformed_messages() {
+    let contexts = keygen(3, 2);
+    let hash = Sha256::digest(b"random message").to_vec();
+    let indices = [0u16, 2].to_vec();
+
+    // own index not at the given position, index out of range, duplicates
+    assert!(gg18_sign1(
+        contexts[0].clone_secret(),
+        indices.clone(),
+        1,
+        hash.clone(),
+        SID
     )
-    .unwrap();
-    let gg18_sign_context_p1 = gg18_key_gen_6(
-        [
-            gg18_key_gen_msg5_p0.clone(),
-            gg18_key_gen_msg5_p2.clone(),
-            gg18_key_gen_msg5_p3.clone(),
-        ]
-        .to_vec(),
-        keygen_context5_p1,
+    .is_err());
+    assert!(gg18_sign1(
+        contexts[0].clone_secret(),
+        [0, 3].to_vec(),
+        0,
+        hash.clone(),
+        SID
     )
-    .unwrap();
-    let gg18_sign_context_p2 = gg18_key_gen_6(
-        [
-            gg18_key_gen_msg5_p0.clone(),
-            gg18_key_gen_msg5_p1.clone(),
-            gg18_key_gen_msg5_p3.clone(),
-        ]
-        .to_vec(),
-        keygen_context5_p2,
+    .is_err());
+    assert!(gg18_sign1(
+        contexts[0].clone_secret(),
+        [0, 0].to_vec(),
+        0,
+        hash.clone(),
+        SID
     )
-    .unwrap();
-    let gg18_sign_context_p3 = gg18_key_gen_6(
-        [
-            gg18_key_gen_msg5_p0.clone(),
-            gg18_key_gen_msg5_p1.clone(),
-            gg18_key_gen_msg5_p2.clone(),
-        ]
-        .to_vec(),
-        keygen_context5_p3,
+    .is_err());
+    assert!(gg18_sign1(
+        contexts[0].clone_secret(),
+        [0].to_vec(),
+        0,
+        hash.clone(),
+        SID
     )
-    .unwrap();
+    .is_err());
 
-    let pk_p0 = gg18_sign_context_p0.pk.clone();
-    let _pk_p1 = gg18_sign_context_p1.pk.clone();
-    let pk_p2 = gg18_sign_context_p2.pk.clone();
-    let _pk_p3 = gg18_sign_context_p3.pk.clone();
-
-    // hash msg
-    let mut hasher = Sha256::new();
-    hasher.update(b"random message");
-    let hash = hasher.finalize().to_vec();
-
-    // gg18_sign1
-    let (gg18_sign_msg1_p0, gg18_sign_context1_p0) =
-        gg18_sign1(gg18_sign_context_p0, [2, 3, 0].to_vec(), 2, hash.clone()).unwrap();
-    let (gg18_sign_msg1_p2, gg18_sign_context1_p2) =
-        gg18_sign1(gg18_sign_context_p2, [2, 3, 0].to_vec(), 0, hash.clone()).unwrap();
-    let (gg18_sign_msg1_p3, gg18_sign_context1_p3) =
-        gg18_sign1(gg18_sign_context_p3, [2, 3, 0].to_vec(), 1, hash.clone()).unwrap();
-
-    // gg18_sign2
-    let (gg18_sign_msg2_p0, gg18_sign_context2_p0) = gg18_sign2(
-        [gg18_sign_msg1_p2.clone(), gg18_sign_msg1_p3.clone()].to_vec(),
-        gg18_sign_context1_p0,
-    )
-    .unwrap();
-    let (gg18_sign_msg2_p2, gg18_sign_context2_p2) = gg18_sign2(
-        [gg18_sign_msg1_p3.clone(), gg18_sign_msg1_p0.clone()].to_vec(),
-        gg18_sign_context1_p2,
+    let (msg1_a, ctx1_a) = gg18_sign1(
+        contexts[0].clone_secret(),
+        indices.clone(),
+        0,
+        hash.clone(),
+        SID,
     )
     .unwrap();
-    let (gg18_sign_msg2_p3, gg18_sign_context2_p3) = gg18_sign2(
-        [gg18_sign_msg1_p2.clone(), gg18_sign_msg1_p0.clone()].to_vec(),
-        gg18_sign_context1_p3,
+    let (msg1_b, ctx1_b) = gg18_sign1(
+        contexts[2].clone_secret(),
+        indices.clone(),
+        1,
+        hash.clone(),
+        SID,
     )
     .unwrap();
 
-    // gg18_sign3
-    let (gg18_sign_msg3_p0, gg18_sign_context3_p0) = gg18_sign3(
-        [gg18_sign_msg2_p2[1].clone(), gg18_sign_msg2_p3[1].clone()].to_vec(),
-        gg18_sign_context2_p0,
-    )
-    .unwrap();
-    let (gg18_sign_msg3_p2, gg18_sign_context3_p2) = gg18_sign3(
-        [gg18_sign_msg2_p3[0].clone(), gg18_sign_msg2_p0[0].clone()].to_vec(),
-        gg18_sign_context2_p2,
-    )
-    .unwrap();
-    let (gg18_sign_msg3_p3, gg18_sign_context3_p3) = gg18_sign3(
-        [gg18_sign_msg2_p2[0].clone(), gg18_sign_msg2_p0[1].clone()].to_vec(),
-        gg18_sign_context2_p3,
-    )
-    .unwrap();
+    assert!(gg18_sign2(BTreeMap::new(), ctx1_a.clone_secret()).is_err());
+    // party 1 is not part of the signing set
+    let mut outsider = one(2, msg1_b.clone());
+    outsider.insert(1, msg1_b.clone());
+    assert_eq!(
+        gg18_sign2(outsider, ctx1_a.clone_secret()).unwrap_err(),
+        Error::WrongMessageCount {
+            round: 2,
+            expected: 1,
+            received: 2
+        }
+    );
+    let mut outsider = one(2, msg1_b.clone());
+    relabel(&mut outsider, 2, 1);
+    assert_eq!(
+        gg18_sign2(outsider, ctx1_a.clone_secret()).unwrap_err(),
+        Error::UnexpectedSender { round: 2, party: 1 }
+    );
 
-    // gg18_sign4
-    let (gg18_sign_msg4_p0, gg18_sign_context4_p0) = gg18_sign4(
-        [gg18_sign_msg3_p2.clone(), gg18_sign_msg3_p3.clone()].to_vec(),
-        gg18_sign_context3_p0,
-    )
-    .unwrap();
-    let (gg18_sign_msg4_p2, gg18_sign_context4_p2) = gg18_sign4(
-        [gg18_sign_msg3_p3.clone(), gg18_sign_msg3_p0.clone()].to_vec(),
-        gg18_sign_context3_p2,
-    )
-    .unwrap();
-    let (gg18_sign_msg4_p3, gg18_sign_context4_p3) = gg18_sign4(
-        [gg18_sign_msg3_p2.clone(), gg18_sign_msg3_p0.clone()].to_vec(),
-        gg18_sign_context3_p3,
-    )
-    .unwrap();
+    let (msg2_a, ctx2_a) = gg18_sign2(one(2, msg1_b), ctx1_a).unwrap();
+    let (msg2_b, ctx2_b) = gg18_sign2(one(0, msg1_a), ctx1_b).unwrap();
 
-    // gg18_sign5
-    let (gg18_sign_msg5_p0, gg18_sign_context5_p0) = gg18_sign5(
-        [gg18_sign_msg4_p2.clone(), gg18_sign_msg4_p3.clone()].to_vec(),
-        gg18_sign_context4_p0,
-    )
-    .unwrap();
-    let (gg18_sign_msg5_p2, gg18_sign_context5_p2) = gg18_sign5(
-        [gg18_sign_msg4_p3.clone(), gg18_sign_msg4_p0.clone()].to_vec(),
-        gg18_sign_context4_p2,
-    )
-    .unwrap();
-    let (gg18_sign_msg5_p3, gg18_sign_context5_p3) = gg18_sign5(
-        [gg18_sign_msg4_p2.clone(), gg18_sign_msg4_p0.clone()].to_vec(),
-        gg18_sign_context4_p3,
-    )
-    .unwrap();
+    // MtA responses with the gamma and w parts swapped fail the b proof check
+    let (m_b_gamma, m_b_w) = msg2_b[&0].body.clone();
+    let envelope = Envelope {
+        kind: MessageKind::GG18_SIGN_MSG2,
+        sender: 2,
+        receiver: Some(0),
+        binding: &[],
+    };
+    let swapped = signed((m_b_w, m_b_gamma), &contexts[2].identity, envelope);
+    assert_eq!(
+        gg18_sign3(one(2, swapped), ctx2_a.clone_secret()).unwrap_err(),
+        Error::InvalidProof {
+            round: 3,
+            party: Some(2)
+        }
+    );
+    // a response signed for another receiver
+    let envelope = Envelope {
+        kind: MessageKind::GG18_SIGN_MSG2,
+        sender: 2,
+        receiver: Some(1),
+        binding: &[],
+    };
+    let misdelivered = signed(msg2_b[&0].body.clone(), &contexts[2].identity, envelope);
+    assert_eq!(
+        gg18_sign3(one(2, misdelivered), ctx2_a.clone_secret()).unwrap_err(),
+        Error::InvalidIdentitySignature {
+            round: 3,
+            party: Some(2)
+        }
+    );
+    assert!(gg18_sign3(BTreeMap::new(), ctx2_a.clone_secret()).is_err());
 
-    // gg18_sign6
-    let (gg18_sign_msg6_p0, gg18_sign_context6_p0) = gg18_sign6(
-        [gg18_sign_msg5_p2.clone(), gg18_sign_msg5_p3.clone()].to_vec(),
-        gg18_sign_context5_p0,
-    )
-    .unwrap();
-    let (gg18_sign_msg6_p2, gg18_sign_context6_p2) = gg18_sign6(
-        [gg18_sign_msg5_p3.clone(), gg18_sign_msg5_p0.clone()].to_vec(),
-        gg18_sign_context5_p2,
-    )
-    .unwrap();
-    let (gg18_sign_msg6_p3, gg18_sign_context6_p3) = gg18_sign6(
-        [gg18_sign_msg5_p2.clone(), gg18_sign_msg5_p0.clone()].to_vec(),
-        gg18_sign_context5_p3,
-    )
-    .unwrap();
+    let (msg3_a, ctx3_a) = gg18_sign3(msg2_b, ctx2_a).unwrap();
+    let (msg3_b, ctx3_b) = gg18_sign3(msg2_a, ctx2_b).unwrap();
 
-    // gg18_sign7
-    let (gg18_sign_msg7_p0, gg18_sign_context7_p0) = gg18_sign7(
-        [gg18_sign_msg6_p2.clone(), gg18_sign_msg6_p3.clone()].to_vec(),
-        gg18_sign_context6_p0,
-    )
-    .unwrap();
-    let (gg18_sign_msg7_p2, gg18_sign_context7_p2) = gg18_sign7(
-        [gg18_sign_msg6_p3.clone(), gg18_sign_msg6_p0.clone()].to_vec(),
-        gg18_sign_context6_p2,
-    )
-    .unwrap();
-    let (gg18_sign_msg7_p3, gg18_sign_context7_p3) = gg18_sign7(
-        [gg18_sign_msg6_p2.clone(), gg18_sign_msg6_p0.clone()].to_vec(),
-        gg18_sign_context6_p3,
-    )
-    .unwrap();
+    // a delta share cancelling ours would make the sum non-invertible
+    let envelope = Envelope::broadcast(MessageKind::GG18_SIGN_MSG3, 2);
+    let cancelling = signed(-msg3_a.body.clone(), &contexts[2].identity, envelope);
+    assert_eq!(
+        gg18_sign4(one(2, cancelling), ctx3_a.clone_secret()).unwrap_err(),
+        Error::MalformedInput {
+            round: 4,
+            party: None
+        }
+    );
+    assert!(gg18_sign4(BTreeMap::new(), ctx3_a.clone_secret()).is_err());
 
-    // gg18_sign8
-    let (gg18_sign_msg8_p0, gg18_sign_context8_p0) = gg18_sign8(
-        [gg18_sign_msg7_p2.clone(), gg18_sign_msg7_p3.clone()].to_vec(),
-        gg18_sign_context7_p0,
-    )
-    .unwrap();
-    let (gg18_sign_msg8_p2, gg18_sign_context8_p2) = gg18_sign8(
-        [gg18_sign_msg7_p3.clone(), gg18_sign_msg7_p0.clone()].to_vec(),
-        gg18_sign_context7_p2,
-    )
-    .unwrap();
-    let (gg18_sign_msg8_p3, gg18_sign_context8_p3) = gg18_sign8(
-        [gg18_sign_msg7_p2.clone(), gg18_sign_msg7_p0.clone()].to_vec(),
-        gg18_sign_context7_p3,
-    )
-    .unwrap();
+    let (msg4_a, ctx4_a) = gg18_sign4(one(2, msg3_b), ctx3_a).unwrap();
+    let (msg4_b, ctx4_b) = gg18_sign4(one(0, msg3_a), ctx3_b).unwrap();
+
+    // party 0's own decommitment does not open party 2's commitment
+    assert!(gg18_sign5(one(0, msg4_a.clone()), ctx4_a.clone_secret()).is_err());
+    assert!(gg18_sign5(BTreeMap::new(), ctx4_a.clone_secret()).is_err());
+
+    let (msg5_a, ctx5_a) = gg18_sign5(one(2, msg4_b), ctx4_a).unwrap();
+    let (msg5_b, ctx5_b) = gg18_sign5(one(0, msg4_a), ctx4_b).unwrap();
+
+    assert!(gg18_sign6(BTreeMap::new(), ctx5_a.clone_secret()).is_err());
+
+    let (msg6_a, ctx6_a) = gg18_sign6(one(2, msg5_b), ctx5_a).unwrap();
+    let (msg6_b, ctx6_b) = gg18_sign6(one(0, msg5_a), ctx5_b).unwrap();
+
+    assert!(gg18_sign7(one(0, msg6_a.clone()), ctx6_a.clone_secret()).is_err());
+    assert!(gg18_sign7(BTreeMap::new(), ctx6_a.clone_secret()).is_err());
+
+    let (msg7_a, ctx7_a) = gg18_sign7(one(2, msg6_b), ctx6_a).unwrap();
+    let (msg7_b, ctx7_b) = gg18_sign7(one(0, msg6_a), ctx6_b).unwrap();
+
+    assert!(gg18_sign8(BTreeMap::new(), ctx7_a.clone_secret()).is_err());
 
-    // gg18_sign9
-    let (gg18_sign_msg9_p0, gg18_sign_context9_p0) = gg18_sign9(
-        [gg18_sign_msg8_p2.clone(), gg18_sign_msg8_p3.clone()].to_vec(),
-        gg18_sign_context8_p0,
+    let (msg8_a, ctx8_a) = gg18_sign8(one(2, msg7_b), ctx7_a).unwrap();
+    let (msg8_b, ctx8_b) = gg18_sign8(one(0, msg7_a), ctx7_b).unwrap();
+
+    assert!(gg18_sign9(one(0, msg8_a.clone()), ctx8_a.clone_secret()).is_err());
+    assert!(gg18_sign9(BTreeMap::new(), ctx8_a.clone_secret()).is_err());
+
+    let (msg9_a, ctx9_a) = gg18_sign9(one(2, msg8_b), ctx8_a).unwrap();
+    let (_msg9_b, _ctx9_b) = gg18_sign9(one(0, msg8_a), ctx8_b).unwrap();
+
+    assert!(gg18_sign10(one(0, msg9_a.clone()), ctx9_a.clone_secret()).is_err());
+    assert!(gg18_sign10(BTreeMap::new(), ctx9_a).is_err());
+}
+
+#[test]
+fn rejects_messages_from_other_sessions() {
+    let other = SessionId::derive(&[b"other session"]);
+
+    let (msg1_a, _, _) = key_gen_start(2, 2, other);
+    let (_, mut ctx1, _) = key_gen_start(2, 2, SID);
+    let ctx1_b = ctx1.remove(1);
+    assert_eq!(
+        gg18_key_gen_2(one(0, msg1_a[0].clone()), ctx1_b).unwrap_err(),
+        Error::SessionMismatch {
+            round: 2,
+            party: Some(0)
+        }
+    );
+
+    let contexts = keygen(3, 2);
+    let hash = Sha256::digest(b"random message").to_vec();
+    let indices = [0u16, 2].to_vec();
+    let (_, ctx1_a) = gg18_sign1(
+        contexts[0].clone_secret(),
+        indices.clone(),
+        0,
+        hash.clone(),
+        SID,
     )
     .unwrap();
-    let (gg18_sign_msg9_p2, gg18_sign_context9_p2) = gg18_sign9(
-        [gg18_sign_msg8_p3.clone(), gg18_sign_msg8_p0.clone()].to_vec(),
-        gg18_sign_context8_p2,
+    let (msg1_b, _) = gg18_sign1(contexts[2].clone_secret(), indices, 1, hash, other).unwrap();
+    assert_eq!(
+        gg18_sign2(one(2, msg1_b), ctx1_a).unwrap_err(),
+        Error::SessionMismatch {
+            round: 2,
+            party: Some(2)
+        }
+    );
+}
+
+// the broadcasts of every signer but the one at position t, keyed by index
+pub fn from_signers<T: Clone>(indices: &[u16], messages: &[T], t: usize) -> BTreeMap<u16, T> {
+    indices
+        .iter()
+        .zip(messages)
+        .enumerate()
+        .filter(|(k, _)| *k != t)
+        .map(|(_, (&j, m))| (j, m.clone()))
+        .collect()
+}
+
+// the round 2 messages addressed to signer t, keyed by their senders
+fn addressed_to<M: Clone>(
+    indices: &[u16],
+    messages: &[BTreeMap<u16, M>],
+    t: usize,
+) -> BTreeMap<u16, M> {
+    let addressed: Vec<_> = messages.iter().map(|out| out.get(&indices[t])).collect();
+    from_signers(indices, &addressed, t)
+        .into_iter()
+        .map(|(j, m)| (j, m.unwrap().clone()))
+        .collect()
+}
+
+// rounds 1 to 4 of presigning
+fn presign_to_round4(
+    contexts: Vec<GG18SignContext>,
+    session: SessionId,
+) -> (Vec<GG18SignMsg4>, Vec<GG18PresignContext4>) {
+    let indices: Vec<u16> = contexts.iter().map(|c| c.index).collect();
+    let (msg1, ctx1): (Vec<_>, Vec<_>) = contexts
+        .into_iter()
+        .enumerate()
+        .map(|(t, c)| gg18_presign1(c, indices.clone(), t, session).unwrap())
+        .unzip();
+    let (msg2, ctx2): (Vec<_>, Vec<_>) = batch_round(&indices, &msg1, ctx1, gg18_presign2)
+        .into_iter()
+        .unzip();
+    let (msg3, ctx3): (Vec<_>, Vec<_>) = ctx2
+        .into_iter()
+        .enumerate()
+        .map(|(t, c)| gg18_presign3(addressed_to(&indices, &msg2, t), c).unwrap())
+        .unzip();
+    batch_round(&indices, &msg3, ctx3, gg18_presign4)
+        .into_iter()
+        .unzip()
+}
+
+pub fn presign(contexts: Vec<GG18SignContext>, session: SessionId) -> Vec<GG18Presignature> {
+    let indices: Vec<u16> = contexts.iter().map(|c| c.index).collect();
+    let (msg4, ctx4) = presign_to_round4(contexts, session);
+    batch_round(&indices, &msg4, ctx4, gg18_presign5)
+}
+
+// rounds 6 to 8 of signing, what is left before the shares are sent
+fn sign_to_round8(
+    indices: &[u16],
+    msg5: &[GG18SignMsg5],
+    ctx5: Vec<GG18SignContext5>,
+) -> (Vec<GG18SignMsg8>, Vec<GG18SignContext8>) {
+    let (msg6, ctx6): (Vec<_>, Vec<_>) = batch_round(indices, msg5, ctx5, gg18_sign6)
+        .into_iter()
+        .unzip();
+    let (msg7, ctx7): (Vec<_>, Vec<_>) = batch_round(indices, &msg6, ctx6, gg18_sign7)
+        .into_iter()
+        .unzip();
+    batch_round(indices, &msg7, ctx7, gg18_sign8)
+        .into_iter()
+        .unzip()
+}
+
+// rounds 6 to 10 of signing, from round 5 of a signing or presigning run
+pub fn sign_from_round5(
+    indices: &[u16],
+    msg5: &[GG18SignMsg5],
+    ctx5: Vec<GG18SignContext5>,
+) -> Vec<Signature> {
+    let (msg8, ctx8) = sign_to_round8(indices, msg5, ctx5);
+    let (msg9, ctx9): (Vec<_>, Vec<_>) = batch_round(indices, &msg8, ctx8, gg18_sign9)
+        .into_iter()
+        .unzip();
+    batch_round(indices, &msg9, ctx9, gg18_sign10)
+}
+
+#[test]
+fn presign_signs_in_online_phase() {
+    let contexts = keygen(3, 2);
+    let pk = contexts[0].pk.clone();
+    let session = SessionId::derive(&[b"presign"]);
+    let indices = [0u16, 2];
+    let signers = || vec![contexts[0].clone_secret(), contexts[2].clone_secret()];
+
+    let presignatures = presign(signers(), session);
+    assert_eq!(presignatures[0].r(), presignatures[1].r());
+    assert_eq!(presignatures[0].indices(), &indices);
+
+    let message = b"presigned";
+    let hash = Sha256::digest(message).to_vec();
+    let (msg5, ctx5): (Vec<_>, Vec<_>) = presignatures
+        .into_iter()
+        .map(|p| gg18_presign_sign(p, hash.clone()).unwrap())
+        .unzip();
+    for sig in sign_from_round5(&indices, &msg5, ctx5) {
+        check_sig(&sig, message, &pk);
+    }
+
+    // signers that disagree on the message fail phase 5 before sending their shares
+    let presignatures = presign(signers(), session);
+    let hashes = [hash, Sha256::digest(b"other message").to_vec()];
+    let (msg5, ctx5): (Vec<_>, Vec<_>) = presignatures
+        .into_iter()
+        .zip(hashes.iter())
+        .map(|(p, h)| gg18_presign_sign(p, h.clone()).unwrap())
+        .unzip();
+    let (msg8, mut ctx8) = sign_to_round8(&indices, &msg5, ctx5);
+    assert_eq!(
+        gg18_sign9(from_signers(&indices, &msg8, 0), ctx8.remove(0)).unwrap_err(),
+        Error::CommitmentMismatch {
+            round: 9,
+            party: None
+        }
+    );
+}
+
+#[test]
+fn presign_blames_misbehaving_signers() {
+    let contexts = keygen(3, 2);
+    let indices = [0u16, 2];
+    let signers = vec![contexts[0].clone_secret(), contexts[2].clone_secret()];
+    let hash = Sha256::digest(b"presigned").to_vec();
+    let key = &contexts[2].identity;
+
+    let presignatures = presign(signers, SID);
+    let binding = presignatures[0].r().to_bytes(true);
+    let (msg5, ctx5): (Vec<_>, Vec<_>) = presignatures
+        .into_iter()
+        .map(|p| gg18_presign_sign(p, hash.clone()).unwrap())
+        .unzip();
+    let (msg6, mut ctx6): (Vec<_>, Vec<_>) = batch_round(&indices, &msg5, ctx5, gg18_sign6)
+        .into_iter()
+        .unzip();
+
+    // party 2 replays the 5A proofs of party 0 in the online phase, signed as its own
+    let envelope = Envelope {
+        kind: MessageKind::GG18_SIGN_MSG6,
+        sender: 2,
+        receiver: None,
+        binding: binding.as_ref(),
+    };
+    assert_blamed(
+        gg18_sign7(
+            one(2, signed(msg6[0].body.clone(), key, envelope)),
+            ctx6.remove(0),
+        ),
+        7,
+        2,
+        &key.public(),
+    );
+}
+
+#[test]
+fn presign_and_sign_runs_do_not_mix() {
+    let contexts = keygen(3, 2);
+    let hash = Sha256::digest(b"random message").to_vec();
+
+    // the messages of presigning are signed as their own kind
+    let (_, ctx1) =
+        gg18_sign1(contexts[0].clone_secret(), vec![0, 2], 0, hash.clone(), SID).unwrap();
+    let (msg1, _) = gg18_presign1(contexts[2].clone_secret(), vec![0, 2], 1, SID).unwrap();
+    assert_eq!(
+        gg18_sign2(one(2, msg1), ctx1).unwrap_err(),
+        Error::InvalidIdentitySignature {
+            round: 2,
+            party: Some(2)
+        }
+    );
+
+    // and the other way around
+    let (msg1, _) = gg18_sign1(contexts[0].clone_secret(), vec![0, 2], 0, hash, SID).unwrap();
+    let (_, ctx1) = gg18_presign1(contexts[2].clone_secret(), vec![0, 2], 1, SID).unwrap();
+    assert_eq!(
+        gg18_presign2(one(0, msg1), ctx1).unwrap_err(),
+        Error::InvalidIdentitySignature {
+            round: 2,
+            party: Some(0)
+        }
+    );
+}
+
+fn refresh(contexts: Vec<GG18SignContext>, session: SessionId) -> Vec<GG18SignContext> {
+    let (msg1, ctx1): (Vec<_>, Vec<_>) = contexts
+        .into_iter()
+        .map(|c| gg18_refresh_1(c, session).unwrap())
+        .unzip();
+    let (msg2, ctx2): (Vec<_>, Vec<_>) = ctx1
+        .into_iter()
+        .enumerate()
+        .map(|(i, c)| gg18_refresh_2(others(&msg1, i), c).unwrap())
+        .unzip();
+    ctx2.into_iter()
+        .enumerate()
+        .map(|(i, c)| gg18_refresh_3(others(&msg2, i), c).unwrap())
+        .collect()
+}
+
+#[test]
+fn refresh_keeps_public_key() {
+    let contexts = keygen(3, 2);
+    let pk = contexts[0].pk.clone();
+    let old: Vec<_> = contexts.iter().map(|c| c.clone_secret()).collect();
+    let refreshed = refresh(contexts, SessionId::derive(&[b"refresh"]));
+
+    for (old, new) in old.iter().zip(refreshed.iter()) {
+        assert_eq!(new.pk, pk);
+        assert_ne!(new.shared_keys.x_i, old.shared_keys.x_i);
+        assert_ne!(new.paillier_key_vec, old.paillier_key_vec);
+    }
+
+    let message = b"refreshed";
+    let hash = Sha256::digest(message);
+    let signers = vec![refreshed[0].clone_secret(), refreshed[2].clone_secret()];
+    let outcomes = Simulation::new(SID).gg18_sign(signers, &hash).unwrap();
+    for outcome in outcomes {
+        check_sig(&outcome.output().unwrap(), message, &pk);
+    }
+
+    // an old share does not combine with a new one
+    let mixed = vec![old[0].clone_secret(), refreshed[1].clone_secret()];
+    let outcomes = Simulation::new(SID).gg18_sign(mixed, &hash).unwrap();
+    assert!(outcomes
+        .into_iter()
+        .all(|o| !matches!(o, Outcome::Output(_))));
+}
+
+#[test]
+fn refresh_rejects_wrong_reshares() {
+    let contexts = keygen(3, 2);
+    let keys: Vec<IdentityKey> = contexts.iter().map(|c| c.identity.clone_secret()).collect();
+    let session = SessionId::derive(&[b"refresh"]);
+    let (msg1, ctx1): (Vec<_>, Vec<_>) = contexts
+        .into_iter()
+        .map(|c| gg18_refresh_1(c, session).unwrap())
+        .unzip();
+
+    // a Paillier key with the proof of another party
+    let mut body = msg1[1].body.clone();
+    body.1 = msg1[2].body.1.clone();
+    let mut forged = others(&msg1, 0);
+    let envelope = Envelope::broadcast(MessageKind::GG18_REFRESH_MSG1, 1);
+    forged.insert(
+        1,
+        SignedMsg::sign(session, body, &keys[1], &envelope).unwrap(),
+    );
+    assert_eq!(
+        gg18_refresh_2(forged, ctx1[0].clone_secret()).unwrap_err(),
+        Error::InvalidPaillierKey {
+            round: 2,
+            party: Some(1)
+        }
+    );
+
+    let (msg2, ctx2): (Vec<_>, Vec<_>) = ctx1
+        .into_iter()
+        .enumerate()
+        .map(|(i, c)| gg18_refresh_2(others(&msg1, i), c).unwrap())
+        .unzip();
+    let dh_public_vec: Vec<_> = msg1.iter().map(|m| m.body.2.clone()).collect();
+    let binding = dh_binding(&dh_public_vec);
+    let envelope = Envelope {
+        kind: MessageKind::GG18_REFRESH_MSG2,
+        sender: 1,
+        receiver: None,
+        binding: &binding,
+    };
+
+    // party 1 reshares the weighted share of party 2
+    let mut body = msg2[1].body.clone();
+    body.0 = msg2[2].body.0.clone();
+    let mut copied = others(&msg2, 0);
+    copied.insert(
+        1,
+        SignedMsg::sign(session, body, &keys[1], &envelope).unwrap(),
+    );
+    assert_eq!(
+        gg18_refresh_3(copied, ctx2[0].clone_secret()).unwrap_err(),
+        Error::InvalidVss {
+            round: 3,
+            party: Some(1)
+        }
+    );
+
+    // no share sealed for party 0
+    let mut body = msg2[1].body.clone();
+    body.1.remove(&0);
+    let mut missing = others(&msg2, 0);
+    missing.insert(
+        1,
+        SignedMsg::sign(session, body, &keys[1], &envelope).unwrap(),
+    );
+    assert_eq!(
+        gg18_refresh_3(missing, ctx2[0].clone_secret()).unwrap_err(),
+        Error::MalformedInput {
+            round: 3,
+            party: Some(1)
+        }
+    );
+
+    assert!(gg18_refresh_3(others(&msg2, 0), ctx2[0].clone_secret()).is_ok());
+}
+
+#[test]
+fn refresh_rejects_substituted_dh_keys() {
+    let contexts = keygen(3, 2);
+    let keys: Vec<IdentityKey> = contexts.iter().map(|c| c.identity.clone_secret()).collect();
+    let session = SessionId::derive(&[b"refresh"]);
+    let (msg1, ctx1): (Vec<_>, Vec<_>) = contexts
+        .into_iter()
+        .map(|c| gg18_refresh_1(c, session).unwrap())
+        .unzip();
+
+    // an eavesdropper swaps in its own ECDH key for the one of party 1
+    let mut substituted = others(&msg1, 0);
+    substituted.get_mut(&1).unwrap().body.2 = Point::generator() * Scalar::<Secp256r1>::random();
+    assert_eq!(
+        gg18_refresh_2(substituted, ctx1[0].clone_secret()).unwrap_err(),
+        Error::InvalidIdentitySignature {
+            round: 2,
+            party: Some(1)
+        }
+    );
+
+    // or re-signs the round 1 message of party 1 with a key of its own
+    let mut forged = others(&msg1, 0);
+    let mut body = msg1[1].body.clone();
+    body.2 = Point::generator() * Scalar::<Secp256r1>::random();
+    let envelope = Envelope::broadcast(MessageKind::GG18_REFRESH_MSG1, 1);
+    let key = IdentityKey::random();
+    forged.insert(1, SignedMsg::sign(session, body, &key, &envelope).unwrap());
+    assert_eq!(
+        gg18_refresh_2(forged, ctx1[0].clone_secret()).unwrap_err(),
+        Error::InvalidIdentitySignature {
+            round: 2,
+            party: Some(1)
+        }
+    );
+
+    // party 2 was shown another ECDH key of party 0 and sealed its share for it
+    let (msg2, ctx2): (Vec<_>, Vec<_>) = ctx1
+        .into_iter()
+        .enumerate()
+        .map(|(i, c)| gg18_refresh_2(others(&msg1, i), c).unwrap())
+        .unzip();
+    let mut dh_public_vec: Vec<_> = msg1.iter().map(|m| m.body.2.clone()).collect();
+    dh_public_vec[0] = Point::generator() * Scalar::<Secp256r1>::random();
+    let binding = dh_binding(&dh_public_vec);
+    let envelope = Envelope {
+        kind: MessageKind::GG18_REFRESH_MSG2,
+        sender: 2,
+        receiver: None,
+        binding: &binding,
+    };
+    let mut resealed = others(&msg2, 0);
+    let body = msg2[2].body.clone();
+    resealed.insert(
+        2,
+        SignedMsg::sign(session, body, &keys[2], &envelope).unwrap(),
+    );
+    assert_eq!(
+        gg18_refresh_3(resealed, ctx2[0].clone_secret()).unwrap_err(),
+        Error::InvalidIdentitySignature {
+            round: 3,
+            party: Some(2)
+        }
+    );
+}
+
+#[test]
+fn reshare_to_new_committee() {
+    let contexts = keygen(3, 2);
+    let pk = contexts[0].pk.clone();
+    let session = SessionId::derive(&[b"reshare"]);
+
+    // parties 0 and 2 move the key from 2-of-3 to 3-of-5
+    let dealers = vec![0u16, 2];
+    let vss_scheme_vec = &contexts[0].vss_scheme_vec;
+    let old_ids = &contexts[0].identities;
+    assert!(GG18ReshareDealers::from_key(vss_scheme_vec, old_ids, &[0, 1, 2]).is_err());
+    assert!(GG18ReshareDealers::from_key(vss_scheme_vec, old_ids, &[0, 3]).is_err());
+    assert!(GG18ReshareDealers::from_key(vss_scheme_vec, &old_ids[1..], &dealers).is_err());
+    let quorum = GG18ReshareDealers::from_key(vss_scheme_vec, old_ids, &dealers).unwrap();
+    let (keys, ids) = identities(5);
+    let (msg1, ctx1): (Vec<_>, Vec<_>) = keys
+        .into_iter()
+        .enumerate()
+        .map(|(i, k)| {
+            let quorum = quorum.clone();
+            gg18_reshare_receiver_1(3, i as u16, quorum, session, k, ids.clone()).unwrap()
+        })
+        .unzip();
+    let all: BTreeMap<u16, _> = msg1
+        .iter()
+        .cloned()
+        .enumerate()
+        .map(|(i, m)| (i as u16, m))
+        .collect();
+    let msg2: BTreeMap<u16, _> = dealers
+        .iter()
+        .map(|&d| {
+            let msg = gg18_reshare_dealer(
+                all.clone(),
+                contexts[d as usize].clone_secret(),
+                dealers.clone(),
+                &ids,
+                3,
+                session,
+            )
+            .unwrap();
+            (d, msg)
+        })
+        .collect();
+
+    // a dealer outside the quorum and a quorum of the wrong size
+    assert!(gg18_reshare_dealer(
+        all.clone(),
+        contexts[1].clone_secret(),
+        dealers.clone(),
+        &ids,
+        3,
+        session
     )
-    .unwrap();
-    let (gg18_sign_msg9_p3, gg18_sign_context9_p3) = gg18_sign9(
-        [gg18_sign_msg8_p2.clone(), gg18_sign_msg8_p0.clone()].to_vec(),
-        gg18_sign_context8_p3,
+    .is_err());
+    assert!(gg18_reshare_dealer(
+        all.clone(),
+        contexts[0].clone_secret(),
+        vec![0, 1, 2],
+        &ids,
+        3,
+        session
     )
-    .unwrap();
+    .is_err());
 
-    // gg18_sign10
-    let gg18_signature_p0 = gg18_sign10(
-        [gg18_sign_msg9_p2.clone(), gg18_sign_msg9_p3.clone()].to_vec(),
-        gg18_sign_context9_p0,
-    )
-    .unwrap();
-    let gg18_signature_p2 = gg18_sign10(
-        [gg18_sign_msg9_p3.clone(), gg18_sign_msg9_p0.clone()].to_vec(),
-        gg18_sign_context9_p2,
+    // no share sealed for receiver 4
+    let dh_public_vec: Vec<_> = msg1.iter().map(|m| m.body.2.clone()).collect();
+    let binding = dh_binding(&dh_public_vec);
+    let envelope = Envelope {
+        kind: MessageKind::GG18_RESHARE_MSG2,
+        sender: 2,
+        receiver: None,
+        binding: &binding,
+    };
+    let mut body = msg2[&2].body.clone();
+    body.2.remove(&4);
+    let mut missing = msg2.clone();
+    let key = &contexts[2].identity;
+    missing.insert(2, SignedMsg::sign(session, body, key, &envelope).unwrap());
+    assert_eq!(
+        gg18_reshare_receiver_2(others(&msg1, 4), missing, ctx1[4].clone_secret()).unwrap_err(),
+        Error::MalformedInput {
+            round: 3,
+            party: Some(2)
+        }
+    );
+
+    // the dealers shift part of their shares, the deals still add up to the key
+    let shift = Scalar::<Secp256r1>::random();
+    let shifted: BTreeMap<u16, _> = dealers
+        .iter()
+        .map(|&d| {
+            let context = &contexts[d as usize];
+            let lambda = VerifiableSS::<Secp256r1, Sha256>::map_share_to_new_params(
+                &context.vss_scheme_vec[d as usize].parameters,
+                d,
+                &dealers,
+            );
+            let mut share = lambda * &context.shared_keys.x_i;
+            share = if d == 0 {
+                share + &shift
+            } else {
+                share - &shift
+            };
+            let dealt = deal(all.clone(), d, &share, &context.identity, &ids, 3, session);
+            (d, dealt.unwrap())
+        })
+        .collect();
+    assert_eq!(
+        gg18_reshare_receiver_2(others(&msg1, 1), shifted, ctx1[1].clone_secret()).unwrap_err(),
+        Error::InvalidVss {
+            round: 3,
+            party: Some(0)
+        }
+    );
+
+    let reshared: Vec<_> = ctx1
+        .into_iter()
+        .enumerate()
+        .map(|(i, c)| gg18_reshare_receiver_2(others(&msg1, i), msg2.clone(), c).unwrap())
+        .collect();
+    assert!(reshared.iter().all(|c| c.pk == pk && c.threshold == 3));
+
+    // the evenly split schemes of a reshared key give its public shares
+    let quorum = GG18ReshareDealers::from_key(
+        &reshared[0].vss_scheme_vec,
+        &reshared[0].identities,
+        &[1, 3, 4],
     )
     .unwrap();
-    let gg18_signature_p3 = gg18_sign10(
-        [gg18_sign_msg9_p2.clone(), gg18_sign_msg9_p0.clone()].to_vec(),
-        gg18_sign_context9_p3,
+    assert_eq!(quorum.pk, pk);
+
+    let message = b"reshared";
+    let hash = Sha256::digest(message);
+    let signers = vec![
+        reshared[1].clone_secret(),
+        reshared[3].clone_secret(),
+        reshared[4].clone_secret(),
+    ];
+    let outcomes = Simulation::new(SID).gg18_sign(signers, &hash).unwrap();
+    for outcome in outcomes {
+        check_sig(&outcome.output().unwrap(), message, &pk);
+    }
+}
+
+#[test]
+fn reshare_rejects_forged_keys() {
+    let contexts = keygen(3, 2);
+    let session = SessionId::derive(&[b"reshare"]);
+    let dealers = vec![0u16, 1];
+    let quorum = GG18ReshareDealers::from_key(
+        &contexts[0].vss_scheme_vec,
+        &contexts[0].identities,
+        &dealers,
     )
     .unwrap();
+    let (keys, ids) = identities(3);
+    let (msg1, ctx1): (Vec<_>, Vec<_>) = keys
+        .into_iter()
+        .enumerate()
+        .map(|(i, k)| {
+            let quorum = quorum.clone();
+            gg18_reshare_receiver_1(2, i as u16, quorum, session, k, ids.clone()).unwrap()
+        })
+        .unzip();
+    let all: BTreeMap<u16, _> = msg1
+        .iter()
+        .cloned()
+        .enumerate()
+        .map(|(i, m)| (i as u16, m))
+        .collect();
 
-    let r = Scalar::<Secp256r1>::from(&BigInt::from_bytes(&gg18_signature_p0[..32]));
-    let s = Scalar::<Secp256r1>::from(&BigInt::from_bytes(&gg18_signature_p0[32..]));
-    check_sig(&r, &s, "random message".as_bytes(), &pk_p0);
+    // an eavesdropper posts its own ECDH key as receiver 2
+    let mut body = msg1[2].body.clone();
+    body.2 = Point::generator() * Scalar::<Secp256r1>::random();
+    let envelope = Envelope::broadcast(MessageKind::GG18_RESHARE_MSG1, 2);
+    let mut forged = all.clone();
+    forged.insert(
+        2,
+        SignedMsg::sign(session, body, &IdentityKey::random(), &envelope).unwrap(),
+    );
+    let dealer = contexts[0].clone_secret();
+    assert_eq!(
+        gg18_reshare_dealer(forged.clone(), dealer, dealers.clone(), &ids, 2, session).unwrap_err(),
+        Error::InvalidIdentitySignature {
+            round: 2,
+            party: Some(2)
+        }
+    );
+    let mut keys = forged;
+    keys.remove(&0);
+    let msg2: BTreeMap<u16, _> = dealers
+        .iter()
+        .map(|&d| {
+            let dealer = contexts[d as usize].clone_secret();
+            let msg = gg18_reshare_dealer(all.clone(), dealer, dealers.clone(), &ids, 2, session);
+            (d, msg.unwrap())
+        })
+        .collect();
+    assert_eq!(
+        gg18_reshare_receiver_2(keys, msg2.clone(), ctx1[0].clone_secret()).unwrap_err(),
+        Error::InvalidIdentitySignature {
+            round: 3,
+            party: Some(2)
+        }
+    );
+
+    // or deals as dealer 1 with a key outside the old committee
+    let body = msg2[&1].body.clone();
+    let dh_public_vec: Vec<_> = msg1.iter().map(|m| m.body.2.clone()).collect();
+    let binding = dh_binding(&dh_public_vec);
+    let envelope = Envelope {
+        kind: MessageKind::GG18_RESHARE_MSG2,
+        sender: 1,
+        receiver: None,
+        binding: &binding,
+    };
+    let mut deals = msg2.clone();
+    deals.insert(
+        1,
+        SignedMsg::sign(session, body, &IdentityKey::random(), &envelope).unwrap(),
+    );
+    assert_eq!(
+        gg18_reshare_receiver_2(others(&msg1, 0), deals, ctx1[0].clone_secret()).unwrap_err(),
+        Error::InvalidIdentitySignature {
+            round: 3,
+            party: Some(1)
+        }
+    );
+
+    assert!(gg18_reshare_receiver_2(others(&msg1, 0), msg2, ctx1[0].clone_secret()).is_ok());
+}
+
+// one round at every signer, with the broadcasts of the others
+fn batch_round<M: Clone, C, O>(
+    indices: &[u16],
+    messages: &[M],
+    contexts: Vec<C>,
+    round: fn(BTreeMap<u16, M>, C) -> Result<O, Error>,
+) -> Vec<O> {
+    contexts
+        .into_iter()
+        .enumerate()
+        .map(|(t, c)| round(from_signers(indices, messages, t), c).unwrap())
+        .collect()
+}
+
+// the signatures of every signer, one per hash
+fn batch_sign(
+    signers: &[&GG18SignContext],
+    hashes: &[[u8; 32]],
+    session: SessionId,
+) -> Vec<Vec<Signature>> {
+    let indices: Vec<u16> = signers.iter().map(|c| c.index).collect();
+    let (msg1, ctx1): (Vec<_>, Vec<_>) = signers
+        .iter()
+        .enumerate()
+        .map(|(t, c)| gg18_batch_sign1(c, indices.clone(), t, hashes, session).unwrap())
+        .unzip();
+    let (msg2, ctx2): (Vec<_>, Vec<_>) = batch_round(&indices, &msg1, ctx1, gg18_batch_sign2)
+        .into_iter()
+        .unzip();
+    let (msg3, ctx3): (Vec<_>, Vec<_>) = ctx2
+        .into_iter()
+        .enumerate()
+        .map(|(t, c)| {
+            let addressed: Vec<_> = msg2.iter().map(|out| out.get(&indices[t])).collect();
+            let incoming = from_signers(&indices, &addressed, t)
+                .into_iter()
+                .map(|(j, m)| (j, m.unwrap().clone()))
+                .collect();
+            gg18_batch_sign3(incoming, c).unwrap()
+        })
+        .unzip();
+    let (msg4, ctx4): (Vec<_>, Vec<_>) = batch_round(&indices, &msg3, ctx3, gg18_batch_sign4)
+        .into_iter()
+        .unzip();
+    let (msg5, ctx5): (Vec<_>, Vec<_>) = batch_round(&indices, &msg4, ctx4, gg18_batch_sign5)
+        .into_iter()
+        .unzip();
+    let (msg6, ctx6): (Vec<_>, Vec<_>) = batch_round(&indices, &msg5, ctx5, gg18_batch_sign6)
+        .into_iter()
+        .unzip();
+    let (msg7, ctx7): (Vec<_>, Vec<_>) = batch_round(&indices, &msg6, ctx6, gg18_batch_sign7)
+        .into_iter()
+        .unzip();
+    let (msg8, ctx8): (Vec<_>, Vec<_>) = batch_round(&indices, &msg7, ctx7, gg18_batch_sign8)
+        .into_iter()
+        .unzip();
+    let (msg9, ctx9): (Vec<_>, Vec<_>) = batch_round(&indices, &msg8, ctx8, gg18_batch_sign9)
+        .into_iter()
+        .unzip();
+    batch_round(&indices, &msg9, ctx9, gg18_batch_sign10)
+}
+
+#[test]
+fn batch_sign_many_hashes() {
+    let contexts = keygen(3, 2);
+    let pk = contexts[0].pk.clone();
+    let signers = [&contexts[0], &contexts[2]];
+    let messages: [&[u8]; 3] = [b"first", b"second", b"third"];
+    let hashes: Vec<[u8; 32]> = messages.iter().map(|m| Sha256::digest(m).into()).collect();
+
+    for signatures in batch_sign(&signers, &hashes, SID) {
+        assert_eq!(signatures.len(), messages.len());
+        for (sig, message) in signatures.iter().zip(messages.iter()) {
+            check_sig(sig, message, &pk);
+        }
+    }
+
+    // the key contexts are only borrowed, they sign the next batch
+    let session = SessionId::derive(&[b"next batch"]);
+    let signatures = batch_sign(&signers, &hashes[2..], session);
+    check_sig(&signatures[1][0], messages[2], &pk);
+}
+
+#[test]
+fn batch_sign_rejects_wrong_number_of_entries() {
+    let contexts = keygen(3, 2);
+    let hashes = [[1u8; 32], [2u8; 32]];
+    let indices = vec![0u16, 2];
 
-    let r = Scalar::<Secp256r1>::from(&BigInt::from_bytes(&gg18_signature_p2[..32]));
-    let s = Scalar::<Secp256r1>::from(&BigInt::from_bytes(&gg18_signature_p2[32..]));
-    check_sig(&r, &s, "random message".as_bytes(), &pk_p2);
+    assert_eq!(
+        gg18_batch_sign1(&contexts[0], indices.clone(), 0, &[], SID).unwrap_err(),
+        Error::MalformedInput {
+            round: 1,
+            party: None
+        }
+    );
 
-    let r = Scalar::<Secp256r1>::from(&BigInt::from_bytes(&gg18_signature_p3[..32]));
-    let s = Scalar::<Secp256r1>::from(&BigInt::from_bytes(&gg18_signature_p3[32..]));
-    check_sig(&r, &s, "random message".as_bytes(), &pk_p0);
+    // signer 2 leaves out the instance of the second hash
+    let (_, ctx1) = gg18_batch_sign1(&contexts[0], indices.clone(), 0, &hashes, SID).unwrap();
+    let (mut msg1, _) = gg18_batch_sign1(&contexts[2], indices, 1, &hashes, SID).unwrap();
+    msg1.body.pop();
+    assert_eq!(
+        gg18_batch_sign2(one(2, msg1), ctx1).unwrap_err(),
+        Error::MalformedInput {
+            round: 2,
+            party: Some(2)
+        }
+    );
 }
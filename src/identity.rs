@@ -0,0 +1,80 @@
+use crate::secret::Secret;
+use crate::signature::Signature;
+use crate::Error;
+use curv::arithmetic::traits::*;
+use curv::elliptic::curves::{p256::Secp256r1, Point, Scalar};
+use p256::ecdsa::signature::{Signer, Verifier};
+use p256::ecdsa::{self, SigningKey, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+use zeroize::Zeroizing;
+
+/*
+Long-term identity keys of GG18 parties.
+
+Every GG18 party signs its key generation and signing messages with an ECDSA
+key of its own, see SignedMsg. The identity public keys of all parties are
+agreed on out of band and passed to key generation, which keeps them in the
+sign context next to the key share. The signatures are what makes Evidence
+checkable by anyone who knows the identity key of the accused party.
+*/
+#[derive(Debug, Serialize, Deserialize)]
+pub struct IdentityKey {
+    secret: Secret<Scalar<Secp256r1>>,
+}
+
+impl_clone_secret!(IdentityKey { secret });
+
+impl IdentityKey {
+    pub fn random() -> Self {
+        IdentityKey {
+            secret: Secret::new(Scalar::random()),
+        }
+    }
+
+    pub fn from_secret(secret: Secret<Scalar<Secp256r1>>) -> Result<Self, Error> {
+        if secret.is_zero() {
+            return Err(Error::InvalidEncoding);
+        }
+        Ok(IdentityKey { secret })
+    }
+
+    pub fn public(&self) -> Point<Secp256r1> {
+        Point::generator() * &*self.secret
+    }
+
+    pub(crate) fn sign(&self, bytes: &[u8]) -> Result<Signature, Error> {
+        let scalar = Zeroizing::new(self.secret.to_bigint().to_bytes());
+        let mut fixed = Zeroizing::new([0u8; 32]);
+        fixed[32 - scalar.len()..].copy_from_slice(&scalar);
+        let key = SigningKey::from_bytes(&fixed[..]).map_err(|_| Error::InvalidEncoding)?;
+        let signature: ecdsa::Signature = key.sign(bytes);
+        Ok(Signature::from(signature))
+    }
+}
+
+pub(crate) fn verify_identity(
+    public: &Point<Secp256r1>,
+    bytes: &[u8],
+    signature: &Signature,
+) -> bool {
+    let key = match VerifyingKey::from_sec1_bytes(public.to_bytes(true).as_ref()) {
+        Ok(key) => key,
+        Err(_) => return false,
+    };
+    match ecdsa::Signature::try_from(signature) {
+        Ok(signature) => key.verify(bytes, &signature).is_ok(),
+        Err(_) => false,
+    }
+}
+
+// the identities of a run: one public key per party, ours at our index
+pub(crate) fn check_identities(
+    identity: &IdentityKey,
+    identities: &[Point<Secp256r1>],
+    index: u16,
+) -> bool {
+    identities.len() <= u16::MAX as usize
+        && identities.iter().all(|p| !p.is_zero())
+        && identities.get(index as usize) == Some(&identity.public())
+}
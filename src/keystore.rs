@@ -0,0 +1,243 @@
+use crate::gg18_key_gen::GG18SignContext;
+use crate::li17_key_gen::{Li17SignP1Context, Li17SignP2Context};
+use crate::persist::{Persist, Protocol, FORMAT_VERSION};
+use crate::session::SessionId;
+use crate::Error;
+use argon2::{Algorithm, Argon2, Params, Version};
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, KeyInit, OsRng, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use curv::elliptic::curves::{p256::Secp256r1, Point};
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroizing;
+
+/*
+Encrypted storage of long-term key shares.
+
+A share is persisted in its versioned envelope and sealed with ChaCha20-Poly1305,
+either under a raw 256-bit key or under a key derived from a password with
+Argon2id. The header is stored in clear, so the public key and party index can
+be read without the password, but it is authenticated as associated data:
+any change to it or to the ciphertext makes unsealing fail.
+
+The Argon2id parameters are read from the header before it can be
+authenticated, so unsealing rejects parameters outside fixed bounds instead of
+letting a tampered file pick the memory and time spent.
+
+The presignature pool seals its entries with the same cipher under a raw key
+(seal_entry), with the id of the entry as associated data.
+*/
+
+// OWASP recommended Argon2id parameters
+const ARGON2_M_COST: u32 = 19456;
+const ARGON2_T_COST: u32 = 2;
+const ARGON2_P_COST: u32 = 1;
+
+// bounds on the parameters of sealed files, m_cost is in KiB
+const ARGON2_MAX_M_COST: u32 = 1 << 18;
+const ARGON2_MAX_T_COST: u32 = 16;
+const ARGON2_MAX_P_COST: u32 = 8;
+
+pub trait KeyShare: Persist {
+    fn public_key(&self) -> &Point<Secp256r1>;
+    fn party_index(&self) -> u16;
+}
+
+impl KeyShare for GG18SignContext {
+    fn public_key(&self) -> &Point<Secp256r1> {
+        &self.pk
+    }
+
+    fn party_index(&self) -> u16 {
+        self.index
+    }
+}
+
+impl KeyShare for Li17SignP1Context {
+    fn public_key(&self) -> &Point<Secp256r1> {
+        &self.public
+    }
+
+    fn party_index(&self) -> u16 {
+        1
+    }
+}
+
+impl KeyShare for Li17SignP2Context {
+    fn public_key(&self) -> &Point<Secp256r1> {
+        &self.public
+    }
+
+    fn party_index(&self) -> u16 {
+        2
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Kdf {
+    RawKey,
+    Argon2id {
+        m_cost: u32,
+        t_cost: u32,
+        p_cost: u32,
+        salt: [u8; 16],
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct KeystoreHeader {
+    pub version: u16,
+    pub protocol: Protocol,
+    pub kind: String,
+    pub public_key: Point<Secp256r1>,
+    pub party_index: u16,
+    pub kdf: Kdf,
+    pub nonce: [u8; 12],
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SealedKeyShare {
+    pub header: KeystoreHeader,
+    pub ciphertext: Vec<u8>,
+}
+
+pub fn seal_with_key<T: KeyShare>(share: &T, key: &[u8; 32]) -> Result<SealedKeyShare, Error> {
+    seal(share, key, Kdf::RawKey)
+}
+
+pub fn seal_with_password<T: KeyShare>(
+    share: &T,
+    password: &[u8],
+) -> Result<SealedKeyShare, Error> {
+    let mut salt = [0u8; 16];
+    OsRng.fill_bytes(&mut salt);
+    let kdf = Kdf::Argon2id {
+        m_cost: ARGON2_M_COST,
+        t_cost: ARGON2_T_COST,
+        p_cost: ARGON2_P_COST,
+        salt,
+    };
+    let key = derive_key(&kdf, password)?;
+    seal(share, &key, kdf)
+}
+
+pub fn unseal_with_key<T: KeyShare>(sealed: &SealedKeyShare, key: &[u8; 32]) -> Result<T, Error> {
+    if sealed.header.kdf != Kdf::RawKey {
+        return Err(Error::DecryptionFailed);
+    }
+    unseal(sealed, key)
+}
+
+pub fn unseal_with_password<T: KeyShare>(
+    sealed: &SealedKeyShare,
+    password: &[u8],
+) -> Result<T, Error> {
+    if sealed.header.kdf == Kdf::RawKey {
+        return Err(Error::DecryptionFailed);
+    }
+    let key = derive_key(&sealed.header.kdf, password)?;
+    unseal(sealed, &key)
+}
+
+fn seal<T: KeyShare>(share: &T, key: &[u8; 32], kdf: Kdf) -> Result<SealedKeyShare, Error> {
+    let mut nonce = [0u8; 12];
+    OsRng.fill_bytes(&mut nonce);
+    let header = KeystoreHeader {
+        version: FORMAT_VERSION,
+        protocol: T::PROTOCOL,
+        kind: T::KIND.to_string(),
+        public_key: share.public_key().clone(),
+        party_index: share.party_index(),
+        kdf,
+        nonce,
+    };
+    let aad = serde_json::to_vec(&header).map_err(|_| Error::InvalidEncoding)?;
+    let plaintext = Zeroizing::new(share.to_json()?);
+
+    let ciphertext = encrypt(key, &nonce, &aad, plaintext.as_bytes())?;
+    Ok(SealedKeyShare { header, ciphertext })
+}
+
+fn unseal<T: KeyShare>(sealed: &SealedKeyShare, key: &[u8; 32]) -> Result<T, Error> {
+    let header = &sealed.header;
+    if header.protocol != T::PROTOCOL || header.kind != T::KIND {
+        return Err(Error::InvalidEncoding);
+    }
+    let aad = serde_json::to_vec(header).map_err(|_| Error::InvalidEncoding)?;
+
+    let plaintext = decrypt(key, &header.nonce, &aad, &sealed.ciphertext)?;
+    let plaintext = std::str::from_utf8(&plaintext).map_err(|_| Error::InvalidEncoding)?;
+    let share = T::from_json(plaintext)?;
+
+    // the header was written by whoever holds the key, but check it anyway
+    if share.public_key() != &header.public_key || share.party_index() != header.party_index {
+        return Err(Error::InvalidEncoding);
+    }
+    Ok(share)
+}
+
+// a presignature entry of the pool, sealed under the raw key of the pool
+#[derive(Serialize, Deserialize)]
+struct SealedEntry {
+    nonce: [u8; 12],
+    ciphertext: Vec<u8>,
+}
+
+// the id of the entry is the associated data, an entry cannot be moved to another id
+pub(crate) fn seal_entry(key: &[u8; 32], id: &SessionId, plaintext: &str) -> Result<String, Error> {
+    let mut nonce = [0u8; 12];
+    OsRng.fill_bytes(&mut nonce);
+    let ciphertext = encrypt(key, &nonce, id.as_bytes(), plaintext.as_bytes())?;
+    serde_json::to_string(&SealedEntry { nonce, ciphertext }).map_err(|_| Error::InvalidEncoding)
+}
+
+pub(crate) fn unseal_entry(
+    key: &[u8; 32],
+    id: &SessionId,
+    entry: &str,
+) -> Result<Zeroizing<String>, Error> {
+    let sealed: SealedEntry = serde_json::from_str(entry).map_err(|_| Error::InvalidEncoding)?;
+    let plaintext = decrypt(key, &sealed.nonce, id.as_bytes(), &sealed.ciphertext)?;
+    let plaintext = std::str::from_utf8(&plaintext).map_err(|_| Error::InvalidEncoding)?;
+    Ok(Zeroizing::new(plaintext.to_string()))
+}
+
+fn encrypt(key: &[u8; 32], nonce: &[u8; 12], aad: &[u8], msg: &[u8]) -> Result<Vec<u8>, Error> {
+    ChaCha20Poly1305::new(Key::from_slice(key))
+        .encrypt(Nonce::from_slice(nonce), Payload { msg, aad })
+        .map_err(|_| Error::InvalidEncoding)
+}
+
+fn decrypt(
+    key: &[u8; 32],
+    nonce: &[u8; 12],
+    aad: &[u8],
+    msg: &[u8],
+) -> Result<Zeroizing<Vec<u8>>, Error> {
+    ChaCha20Poly1305::new(Key::from_slice(key))
+        .decrypt(Nonce::from_slice(nonce), Payload { msg, aad })
+        .map(Zeroizing::new)
+        .map_err(|_| Error::DecryptionFailed)
+}
+
+fn derive_key(kdf: &Kdf, password: &[u8]) -> Result<Zeroizing<[u8; 32]>, Error> {
+    let (m_cost, t_cost, p_cost, salt) = match *kdf {
+        Kdf::Argon2id {
+            m_cost,
+            t_cost,
+            p_cost,
+            salt,
+        } => (m_cost, t_cost, p_cost, salt),
+        Kdf::RawKey => return Err(Error::DecryptionFailed),
+    };
+    if m_cost > ARGON2_MAX_M_COST || t_cost > ARGON2_MAX_T_COST || p_cost > ARGON2_MAX_P_COST {
+        return Err(Error::InvalidEncoding);
+    }
+    let params =
+        Params::new(m_cost, t_cost, p_cost, Some(32)).map_err(|_| Error::InvalidEncoding)?;
+    let mut key = Zeroizing::new([0u8; 32]);
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(password, &salt, &mut *key)
+        .map_err(|_| Error::InvalidEncoding)?;
+    Ok(key)
+}
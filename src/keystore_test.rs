@@ -0,0 +1,126 @@
+use crate::gg18_key_gen::GG18SignContext;
+use crate::gg18_test::keygen;
+use crate::keystore::{
+    seal_with_key, seal_with_password, unseal_with_key, unseal_with_password, Kdf,
+};
+use crate::li17_key_gen::{Li17SignP1Context, Li17SignP2Context};
+use crate::li17_test::run_keygen;
+use crate::Error;
+use curv::elliptic::curves::{p256::Secp256r1, Point};
+
+#[test]
+fn seal_and_unseal_li17() {
+    let (sign_context_p1, sign_context_p2) = run_keygen();
+
+    let sealed = seal_with_password(&sign_context_p1, b"correct horse").unwrap();
+    assert_eq!(sealed.header.public_key, sign_context_p1.public);
+    assert_eq!(sealed.header.party_index, 1);
+    let unsealed: Li17SignP1Context = unseal_with_password(&sealed, b"correct horse").unwrap();
+    assert_eq!(unsealed.public, sign_context_p1.public);
+    assert_eq!(unsealed.public_p1, sign_context_p1.public_p1);
+
+    let key = [7u8; 32];
+    let sealed = seal_with_key(&sign_context_p2, &key).unwrap();
+    assert_eq!(sealed.header.kdf, Kdf::RawKey);
+    let unsealed: Li17SignP2Context = unseal_with_key(&sealed, &key).unwrap();
+    assert_eq!(unsealed.public_p2, sign_context_p2.public_p2);
+
+    // a share of the other party is not accepted
+    assert_eq!(
+        unseal_with_key::<Li17SignP1Context>(&sealed, &key).err(),
+        Some(Error::InvalidEncoding)
+    );
+}
+
+#[test]
+fn seal_and_unseal_gg18() {
+    let contexts = keygen(3, 2);
+    let key = [1u8; 32];
+
+    for context in contexts.iter() {
+        let sealed = seal_with_key(context, &key).unwrap();
+        assert_eq!(sealed.header.party_index, context.index);
+        let unsealed: GG18SignContext = unseal_with_key(&sealed, &key).unwrap();
+        assert_eq!(unsealed.pk, context.pk);
+        assert_eq!(unsealed.shared_keys.x_i, context.shared_keys.x_i);
+    }
+}
+
+#[test]
+fn wrong_password_or_key_is_detected() {
+    let (sign_context_p1, _) = run_keygen();
+
+    let sealed = seal_with_password(&sign_context_p1, b"correct horse").unwrap();
+    assert_eq!(
+        unseal_with_password::<Li17SignP1Context>(&sealed, b"battery staple").err(),
+        Some(Error::DecryptionFailed)
+    );
+    assert_eq!(
+        unseal_with_key::<Li17SignP1Context>(&sealed, &[0u8; 32]).err(),
+        Some(Error::DecryptionFailed)
+    );
+
+    let sealed = seal_with_key(&sign_context_p1, &[1u8; 32]).unwrap();
+    assert_eq!(
+        unseal_with_key::<Li17SignP1Context>(&sealed, &[2u8; 32]).err(),
+        Some(Error::DecryptionFailed)
+    );
+}
+
+#[test]
+fn tampering_is_detected() {
+    let (sign_context_p1, _) = run_keygen();
+    let key = [3u8; 32];
+    let sealed = seal_with_key(&sign_context_p1, &key).unwrap();
+
+    let mut tampered = sealed.clone();
+    tampered.ciphertext[10] ^= 1;
+    assert_eq!(
+        unseal_with_key::<Li17SignP1Context>(&tampered, &key).err(),
+        Some(Error::DecryptionFailed)
+    );
+
+    let mut tampered = sealed.clone();
+    tampered.ciphertext.pop();
+    assert_eq!(
+        unseal_with_key::<Li17SignP1Context>(&tampered, &key).err(),
+        Some(Error::DecryptionFailed)
+    );
+
+    let mut tampered = sealed.clone();
+    tampered.header.public_key = Point::<Secp256r1>::generator().to_point();
+    assert_eq!(
+        unseal_with_key::<Li17SignP1Context>(&tampered, &key).err(),
+        Some(Error::DecryptionFailed)
+    );
+
+    let mut tampered = sealed.clone();
+    tampered.header.party_index = 2;
+    assert_eq!(
+        unseal_with_key::<Li17SignP1Context>(&tampered, &key).err(),
+        Some(Error::DecryptionFailed)
+    );
+
+    let mut tampered = sealed;
+    tampered.header.nonce[0] ^= 1;
+    assert_eq!(
+        unseal_with_key::<Li17SignP1Context>(&tampered, &key).err(),
+        Some(Error::DecryptionFailed)
+    );
+}
+
+#[test]
+fn oversized_kdf_parameters_are_rejected() {
+    let (sign_context_p1, _) = run_keygen();
+    let sealed = seal_with_password(&sign_context_p1, b"correct horse").unwrap();
+
+    // rejected before any memory is allocated for Argon2
+    let mut tampered = sealed;
+    if let Kdf::Argon2id { m_cost, .. } = &mut tampered.header.kdf {
+        *m_cost = u32::MAX;
+    }
+    assert_eq!(
+        unseal_with_password::<Li17SignP1Context>(&tampered, b"correct horse").err(),
+        Some(Error::InvalidEncoding)
+    );
+}
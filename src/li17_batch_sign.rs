@@ -0,0 +1,217 @@
+use crate::li17_key_gen::{Li17SignP1Context, Li17SignP2Context};
+use crate::persist::{Persist, Protocol};
+use crate::secret::Secret;
+use crate::session::{SessionId, SessionMsg};
+use crate::signature::Signature;
+use crate::Error;
+use curv::arithmetic::traits::*;
+use curv::elliptic::curves::Scalar;
+use curv::BigInt;
+use multi_party_ecdsa::protocols::two_party_ecdsa::lindell_2017::*;
+use serde::{Deserialize, Serialize};
+
+/*
+Li17 signing of many hashes in one session.
+
+The messages of li17_sign carry one entry per hash, every hash gets its own
+ephemeral keys, the flow stays at three messages:
+
+    P2: li17_p2_batch_sign1 -> P1: li17_p1_batch_sign1 -> P2: li17_p2_batch_sign2
+    -> P1: li17_p1_batch_sign2
+
+The key contexts are borrowed, so they sign batch after batch. Both parties
+must pass the same hashes in the same order. Errors that concern a single hash
+are reported in its place of the result, the other hashes are still signed;
+errors that concern the whole message, e.g. a wrong session or number of
+entries, fail the whole batch. A hash that P2 skips is reported by P1 as
+MalformedInput from party 2.
+*/
+
+#[derive(Serialize, Deserialize)]
+pub struct Li17BatchSignP2Context1 {
+    session: SessionId,
+    hashes: Vec<BigInt>,
+    p2_eph_comm_witnesses: Vec<party_two::EphCommWitness>,
+    p2_eph_ec_key_pairs: Secret<Vec<party_two::EphEcKeyPair>>,
+}
+
+pub type Li17BatchSignP2Msg1 = SessionMsg<Vec<party_two::EphKeyGenFirstMsg>>;
+
+// None in place of a hash whose ephemeral key of P1 was rejected
+pub type Li17BatchSignP2Msg2 =
+    SessionMsg<Vec<Option<(party_two::PartialSig, party_two::EphKeyGenSecondMsg)>>>;
+
+#[derive(Serialize, Deserialize)]
+pub struct Li17BatchSignP1Context1 {
+    session: SessionId,
+    hashes: Vec<BigInt>,
+    p1_eph_ec_key_pairs: Secret<Vec<party_one::EphEcKeyPair>>,
+    p1_msg1_from_p2: Vec<party_two::EphKeyGenFirstMsg>,
+}
+
+pub type Li17BatchSignP1Msg1 = SessionMsg<Vec<party_one::EphKeyGenFirstMsg>>;
+
+impl Persist for Li17BatchSignP1Context1 {
+    const PROTOCOL: Protocol = Protocol::Li17;
+    const KIND: &'static str = "batch_sign_p1_context1";
+}
+
+impl Persist for Li17BatchSignP2Context1 {
+    const PROTOCOL: Protocol = Protocol::Li17;
+    const KIND: &'static str = "batch_sign_p2_context1";
+}
+
+pub fn li17_p2_batch_sign1(
+    hashes: &[[u8; 32]],
+    session: SessionId,
+) -> Result<(Li17BatchSignP2Msg1, Li17BatchSignP2Context1), Error> {
+    if hashes.is_empty() {
+        return Err(Error::MalformedInput {
+            round: 1,
+            party: None,
+        });
+    }
+
+    let mut first_messages = Vec::with_capacity(hashes.len());
+    let mut comm_witnesses = Vec::with_capacity(hashes.len());
+    let mut eph_ec_key_pairs = Vec::with_capacity(hashes.len());
+    for _ in hashes {
+        let (eph_party_two_first_message, eph_comm_witness, eph_ec_key_pair_party2) =
+            party_two::EphKeyGenFirstMsg::create_commitments();
+        first_messages.push(eph_party_two_first_message);
+        comm_witnesses.push(eph_comm_witness);
+        eph_ec_key_pairs.push(eph_ec_key_pair_party2);
+    }
+
+    let context1 = Li17BatchSignP2Context1 {
+        session,
+        hashes: hashes.iter().map(|h| BigInt::from_bytes(h)).collect(),
+        p2_eph_comm_witnesses: comm_witnesses,
+        p2_eph_ec_key_pairs: Secret::new(eph_ec_key_pairs),
+    };
+    Ok((SessionMsg::new(session, first_messages), context1))
+}
+
+pub fn li17_p1_batch_sign1(
+    msg: Li17BatchSignP2Msg1,
+    hashes: &[[u8; 32]],
+    session: SessionId,
+) -> Result<(Li17BatchSignP1Msg1, Li17BatchSignP1Context1), Error> {
+    let msg = msg.open(&session, 1, Some(2))?;
+    if hashes.is_empty() || msg.len() != hashes.len() {
+        return Err(Error::MalformedInput {
+            round: 1,
+            party: Some(2),
+        });
+    }
+
+    let (first_messages, eph_ec_key_pairs): (Vec<_>, Vec<_>) = hashes
+        .iter()
+        .map(|_| party_one::EphKeyGenFirstMsg::create())
+        .unzip();
+    let context1 = Li17BatchSignP1Context1 {
+        session,
+        hashes: hashes.iter().map(|h| BigInt::from_bytes(h)).collect(),
+        p1_eph_ec_key_pairs: Secret::new(eph_ec_key_pairs),
+        p1_msg1_from_p2: msg,
+    };
+    Ok((SessionMsg::new(session, first_messages), context1))
+}
+
+// the results say which hashes got a partial signature
+pub fn li17_p2_batch_sign2(
+    msg: Li17BatchSignP1Msg1,
+    context1: Li17BatchSignP2Context1,
+    context: &Li17SignP2Context,
+) -> Result<(Li17BatchSignP2Msg2, Vec<Result<(), Error>>), Error> {
+    let msg = msg.open(&context1.session, 2, Some(1))?;
+    if msg.len() != context1.hashes.len() {
+        return Err(Error::MalformedInput {
+            round: 2,
+            party: Some(1),
+        });
+    }
+
+    let mut partial_sigs = Vec::with_capacity(msg.len());
+    let mut results = Vec::with_capacity(msg.len());
+    let items = msg
+        .iter()
+        .zip(context1.p2_eph_comm_witnesses)
+        .zip(context1.p2_eph_ec_key_pairs.iter())
+        .zip(context1.hashes.iter());
+    for (((p1_msg, comm_witness), eph_ec_key_pair), hash) in items {
+        match party_two::EphKeyGenSecondMsg::verify_and_decommit(comm_witness, p1_msg) {
+            Ok(eph_party_two_second_message) => {
+                let partial_sig = party_two::PartialSig::compute(
+                    &context.p2_paillier_public.ek,
+                    &context.p2_paillier_public.encrypted_secret_share,
+                    &context.p2_private,
+                    eph_ec_key_pair,
+                    &p1_msg.public_share,
+                    hash,
+                );
+                partial_sigs.push(Some((partial_sig, eph_party_two_second_message)));
+                results.push(Ok(()));
+            }
+            Err(_) => {
+                partial_sigs.push(None);
+                results.push(Err(Error::InvalidProof {
+                    round: 2,
+                    party: Some(1),
+                }));
+            }
+        }
+    }
+    Ok((SessionMsg::new(context1.session, partial_sigs), results))
+}
+
+pub fn li17_p1_batch_sign2(
+    msg: Li17BatchSignP2Msg2,
+    context1: Li17BatchSignP1Context1,
+    context: &Li17SignP1Context,
+) -> Result<Vec<Result<Signature, Error>>, Error> {
+    let msg = msg.open(&context1.session, 2, Some(2))?;
+    if msg.len() != context1.hashes.len() {
+        return Err(Error::MalformedInput {
+            round: 2,
+            party: Some(2),
+        });
+    }
+
+    let items = msg
+        .into_iter()
+        .zip(context1.p1_msg1_from_p2.iter())
+        .zip(context1.p1_eph_ec_key_pairs.iter())
+        .zip(context1.hashes.iter());
+    Ok(items
+        .map(|(((item, p2_msg1), eph_ec_key_pair), hash)| {
+            let (partial_sig, eph_party_two_second_message) =
+                item.ok_or(Error::MalformedInput {
+                    round: 2,
+                    party: Some(2),
+                })?;
+            if party_one::EphKeyGenSecondMsg::verify_commitments_and_dlog_proof(
+                p2_msg1,
+                &eph_party_two_second_message,
+            )
+            .is_err()
+            {
+                return Err(Error::InvalidEphemeralKey {
+                    round: 2,
+                    party: Some(2),
+                });
+            }
+
+            let sig = party_one::Signature::compute(
+                &context.p1_private,
+                &partial_sig.c3,
+                eph_ec_key_pair,
+                &eph_party_two_second_message.comm_witness.public_share,
+            );
+            if party_one::verify(&sig, &context.public, hash).is_err() {
+                return Err(Error::InvalidSignature { round: 2 });
+            }
+            Ok(Signature::from_scalars(&Scalar::from(&sig.r), &Scalar::from(&sig.s)).normalize_s())
+        })
+        .collect())
+}
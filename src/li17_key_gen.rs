@@ -1,4 +1,9 @@
-use curv::elliptic::curves::{p256::Secp256r1, Point};
+use crate::persist::{Persist, Protocol};
+use crate::secret::Secret;
+use crate::session::{SessionId, SessionMsg};
+use crate::Error;
+use curv::arithmetic::traits::*;
+use curv::elliptic::curves::{p256::Secp256r1, Point, Scalar};
 use curv::BigInt;
 use multi_party_ecdsa::protocols::two_party_ecdsa::lindell_2017::*;
 use multi_party_ecdsa::utilities::zk_pdl_with_slack::{PDLwSlackProof, PDLwSlackStatement};
@@ -6,23 +11,41 @@ use paillier::EncryptionKey;
 use serde::{Deserialize, Serialize};
 use zk_paillier::zkproofs::{CompositeDLogProof, NiCorrectKeyProof};
 
+/*
+The upstream key types keep the shares x1 and x2 in private fields that cannot
+be wiped from here. The shares are generated here and kept in crate-owned
+fields instead: the key generation contexts hold only those, the upstream key
+pairs are rebuilt from them when a round needs them. The sign contexts still
+hold Party1Private and Party2Private next to the share, they are wiped by an
+overwrite through serde, see the secret module.
+*/
+
 // party one structures
-#[derive(Clone, Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Li17KeyGenP1Context1 {
-    p1_ec_key_pair: party_one::EcKeyPair,
+    session: SessionId,
+    x1: Secret<Scalar<Secp256r1>>,
     p1_comm_witness: party_one::CommWitness,
 }
 
-pub type Li17KeyGenP1Msg1 = party_one::KeyGenFirstMsg;
+impl_clone_secret!(Li17KeyGenP1Context1 {
+    session,
+    x1,
+    p1_comm_witness,
+});
+
+pub type Li17KeyGenP1Msg1 = SessionMsg<party_one::KeyGenFirstMsg>;
 
+#[derive(Serialize, Deserialize)]
 pub struct Li17SignP1Context {
     pub public: Point<Secp256r1>,
     pub public_p1: Point<Secp256r1>,
     pub public_p2: Point<Secp256r1>,
-    pub p1_private: party_one::Party1Private,
+    pub x1: Secret<Scalar<Secp256r1>>,
+    pub p1_private: Secret<party_one::Party1Private>,
 }
 
-pub type Li17KeyGenP1Msg2 = (
+pub type Li17KeyGenP1Msg2 = SessionMsg<(
     party_one::KeyGenSecondMsg,
     NiCorrectKeyProof,
     PDLwSlackStatement,
@@ -30,55 +53,110 @@ pub type Li17KeyGenP1Msg2 = (
     CompositeDLogProof,
     EncryptionKey,
     BigInt,
-);
+)>;
 
 //party two structures
-#[derive(Clone, Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Li17KeyGenP2Context1 {
+    session: SessionId,
     p2_msg1_from_p1: party_one::KeyGenFirstMsg,
-    p2_ec_key_pair: party_two::EcKeyPair,
+    x2: Secret<Scalar<Secp256r1>>,
 }
 
-pub type Li17KeyGenP2Msg1 = party_two::KeyGenFirstMsg;
+impl_clone_secret!(Li17KeyGenP2Context1 {
+    session,
+    p2_msg1_from_p1,
+    x2,
+});
+
+pub type Li17KeyGenP2Msg1 = SessionMsg<party_two::KeyGenFirstMsg>;
 
 #[derive(Serialize, Deserialize)]
 pub struct Li17SignP2Context {
     pub public: Point<Secp256r1>,
     pub public_p1: Point<Secp256r1>,
     pub public_p2: Point<Secp256r1>,
-    pub p2_private: party_two::Party2Private,
+    pub x2: Secret<Scalar<Secp256r1>>,
+    pub p2_private: Secret<party_two::Party2Private>,
     pub p2_paillier_public: party_two::PaillierPublic,
 }
 
-pub type Li17KeyGenP2Msg2 = Point<Secp256r1>;
+pub type Li17KeyGenP2Msg2 = SessionMsg<Point<Secp256r1>>;
+
+impl Persist for Li17KeyGenP1Context1 {
+    const PROTOCOL: Protocol = Protocol::Li17;
+    const KIND: &'static str = "key_gen_p1_context1";
+}
+
+impl Persist for Li17SignP1Context {
+    const PROTOCOL: Protocol = Protocol::Li17;
+    const KIND: &'static str = "sign_p1_context";
+}
+
+impl Persist for Li17KeyGenP2Context1 {
+    const PROTOCOL: Protocol = Protocol::Li17;
+    const KIND: &'static str = "key_gen_p2_context1";
+}
+
+impl Persist for Li17SignP2Context {
+    const PROTOCOL: Protocol = Protocol::Li17;
+    const KIND: &'static str = "sign_p2_context";
+}
 
 // party one functions
-pub fn li17_p1_key_gen1() -> Result<(Li17KeyGenP1Msg1, Li17KeyGenP1Context1), &'static str> {
-    let (party1_first_message, p1_comm_witness, p1_ec_key_pair) =
-        party_one::KeyGenFirstMsg::create_commitments();
+pub fn li17_p1_key_gen1(
+    session: SessionId,
+) -> Result<(Li17KeyGenP1Msg1, Li17KeyGenP1Context1), Error> {
+    // x1 < q/3 as in upstream create_commitments, the range proof of the original Li17
+    // needs it but the PDL proof used here does not: shares from a refresh or a
+    // conversion of GG18 keys cover the whole range
+    let x1 = Scalar::<Secp256r1>::random()
+        .to_bigint()
+        .div_floor(&BigInt::from(3u32));
+    let (party1_first_message, context1) =
+        li17_p1_key_gen1_with_share(session, Secret::new(Scalar::from(&x1)));
+    Ok((SessionMsg::new(session, party1_first_message), context1))
+}
+
+// key generation with a given share, used to convert GG18 keys
+pub(crate) fn li17_p1_key_gen1_with_share(
+    session: SessionId,
+    x1: Secret<Scalar<Secp256r1>>,
+) -> (party_one::KeyGenFirstMsg, Li17KeyGenP1Context1) {
+    let (party1_first_message, p1_comm_witness, _) =
+        party_one::KeyGenFirstMsg::create_commitments_with_fixed_secret_share((*x1).clone());
     let context1 = Li17KeyGenP1Context1 {
-        p1_ec_key_pair,
+        session,
+        x1,
         p1_comm_witness,
     };
-    Ok((party1_first_message, context1))
+    (party1_first_message, context1)
 }
 
 pub fn li17_p1_key_gen2(
     msg: Li17KeyGenP2Msg1,
     context: Li17KeyGenP1Context1,
-) -> Result<(Li17KeyGenP1Msg2, Li17SignP1Context), &'static str> {
+) -> Result<(Li17KeyGenP1Msg2, Li17SignP1Context), Error> {
+    let msg = msg.open(&context.session, 2, Some(2))?;
     let p1_second_message =
         party_one::KeyGenSecondMsg::verify_and_decommit(context.p1_comm_witness, &msg.d_log_proof);
 
     if p1_second_message.is_err() {
-        return Err("failed to verify and decommit");
+        return Err(Error::InvalidProof {
+            round: 2,
+            party: Some(2),
+        });
     }
     let p1_second_message = p1_second_message.unwrap();
 
+    let (_, _, p1_ec_key_pair) =
+        party_one::KeyGenFirstMsg::create_commitments_with_fixed_secret_share(
+            (*context.x1).clone(),
+        );
     let paillier_key_pair =
-        party_one::PaillierKeyPair::generate_keypair_and_encrypted_share(&context.p1_ec_key_pair);
+        party_one::PaillierKeyPair::generate_keypair_and_encrypted_share(&p1_ec_key_pair);
     let party_one_private =
-        party_one::Party1Private::set_private_key(&context.p1_ec_key_pair, &paillier_key_pair);
+        party_one::Party1Private::set_private_key(&p1_ec_key_pair, &paillier_key_pair);
 
     let correct_key_proof =
         party_one::PaillierKeyPair::generate_ni_proof_correct_key(&paillier_key_pair);
@@ -89,24 +167,28 @@ pub fn li17_p1_key_gen2(
     let encrypted_share = paillier_key_pair.encrypted_share.clone();
 
     let party_one_private =
-        party_one::Party1Private::set_private_key(&context.p1_ec_key_pair, &paillier_key_pair);
+        party_one::Party1Private::set_private_key(&p1_ec_key_pair, &paillier_key_pair);
     let public_key = party_one::compute_pubkey(&party_one_private, &msg.public_share);
     let sign_context = Li17SignP1Context {
         public: public_key,
-        public_p1: context.p1_ec_key_pair.public_share,
+        public_p1: p1_ec_key_pair.public_share.clone(),
         public_p2: msg.public_share,
-        p1_private: party_one_private,
+        x1: context.x1,
+        p1_private: Secret::new(party_one_private),
     };
 
     Ok((
-        (
-            p1_second_message,
-            correct_key_proof,
-            pdl_statement,
-            pdl_proof,
-            composite_dlog_proof,
-            ek,
-            encrypted_share,
+        SessionMsg::new(
+            context.session,
+            (
+                p1_second_message,
+                correct_key_proof,
+                pdl_statement,
+                pdl_proof,
+                composite_dlog_proof,
+                ek,
+                encrypted_share,
+            ),
         ),
         sign_context,
     ))
@@ -115,19 +197,35 @@ pub fn li17_p1_key_gen2(
 // party two functions
 pub fn li17_p2_key_gen1(
     msg: Li17KeyGenP1Msg1,
-) -> Result<(Li17KeyGenP2Msg1, Li17KeyGenP2Context1), &'static str> {
-    let (p2_first_message, p2_ec_key_pair) = party_two::KeyGenFirstMsg::create();
+    session: SessionId,
+) -> Result<(Li17KeyGenP2Msg1, Li17KeyGenP2Context1), Error> {
+    let msg = msg.open(&session, 1, Some(1))?;
+    Ok(li17_p2_key_gen1_with_share(
+        msg,
+        session,
+        Secret::new(Scalar::random()),
+    ))
+}
+
+pub(crate) fn li17_p2_key_gen1_with_share(
+    msg: party_one::KeyGenFirstMsg,
+    session: SessionId,
+    x2: Secret<Scalar<Secp256r1>>,
+) -> (Li17KeyGenP2Msg1, Li17KeyGenP2Context1) {
+    let (p2_first_message, _) =
+        party_two::KeyGenFirstMsg::create_with_fixed_secret_share((*x2).clone());
     let context2 = Li17KeyGenP2Context1 {
+        session,
         p2_msg1_from_p1: msg,
-        p2_ec_key_pair,
+        x2,
     };
-    Ok((p2_first_message, context2))
+    (SessionMsg::new(session, p2_first_message), context2)
 }
 
 pub fn li17_p2_key_gen2(
     msg: Li17KeyGenP1Msg2,
     context: Li17KeyGenP2Context1,
-) -> Result<(Li17KeyGenP2Msg2, Li17SignP2Context), &'static str> {
+) -> Result<(Li17KeyGenP2Msg2, Li17SignP2Context), Error> {
     let (
         party_one_second_message,
         correct_key_proof,
@@ -136,7 +234,7 @@ pub fn li17_p2_key_gen2(
         composite_dlog_proof,
         paillier_ek,
         paillier_encrypted_share,
-    ) = msg;
+    ) = msg.open(&context.session, 2, Some(1))?;
 
     let r = party_two::KeyGenSecondMsg::verify_commitments_and_dlog_proof(
         &context.p2_msg1_from_p1,
@@ -144,7 +242,10 @@ pub fn li17_p2_key_gen2(
     );
 
     if r.is_err() {
-        return Err("failed to verify commitments and DLog proof");
+        return Err(Error::CommitmentMismatch {
+            round: 2,
+            party: Some(1),
+        });
     }
 
     let party_two_paillier = party_two::PaillierPublic {
@@ -152,34 +253,49 @@ pub fn li17_p2_key_gen2(
         encrypted_secret_share: paillier_encrypted_share,
     };
 
-    party_two::PaillierPublic::verify_ni_proof_correct_key(
+    if party_two::PaillierPublic::verify_ni_proof_correct_key(
         correct_key_proof,
         &party_two_paillier.ek,
     )
-    .expect("bad paillier key");
+    .is_err()
+    {
+        return Err(Error::InvalidPaillierKey {
+            round: 2,
+            party: Some(1),
+        });
+    }
 
-    party_two::PaillierPublic::pdl_verify(
+    if party_two::PaillierPublic::pdl_verify(
         &composite_dlog_proof,
         &pdl_statement,
         &pdl_proof,
         &party_two_paillier,
         &party_one_second_message.comm_witness.public_share,
     )
-    .expect("PDL error");
+    .is_err()
+    {
+        return Err(Error::InvalidProof {
+            round: 2,
+            party: Some(1),
+        });
+    }
 
-    let party_two_private = party_two::Party2Private::set_private_key(&context.p2_ec_key_pair);
+    let (_, p2_ec_key_pair) =
+        party_two::KeyGenFirstMsg::create_with_fixed_secret_share((*context.x2).clone());
+    let party_two_private = party_two::Party2Private::set_private_key(&p2_ec_key_pair);
     let public_key = party_two::compute_pubkey(
-        &context.p2_ec_key_pair,
+        &p2_ec_key_pair,
         &party_one_second_message.comm_witness.public_share,
     );
 
     let sign_context = Li17SignP2Context {
         public: public_key.clone(),
         public_p1: party_one_second_message.comm_witness.public_share,
-        public_p2: context.p2_ec_key_pair.public_share,
-        p2_private: party_two_private,
+        public_p2: p2_ec_key_pair.public_share.clone(),
+        x2: context.x2,
+        p2_private: Secret::new(party_two_private),
         p2_paillier_public: party_two_paillier,
     };
 
-    Ok((public_key, sign_context))
+    Ok((SessionMsg::new(context.session, public_key), sign_context))
 }
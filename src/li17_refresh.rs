@@ -1,25 +1,36 @@
 use crate::li17_key_gen::{Li17SignP1Context, Li17SignP2Context};
+use crate::persist::{Persist, Protocol};
+use crate::secret::Secret;
+use crate::session::{SessionId, SessionMsg};
+use crate::Error;
+use curv::cryptographic_primitives::proofs::sigma_valid_pedersen::PedersenProof;
+use curv::cryptographic_primitives::proofs::sigma_valid_pedersen_blind::PedersenBlindingProof;
 use curv::cryptographic_primitives::twoparty::coin_flip_optimal_rounds;
 use curv::elliptic::curves::{p256::Secp256r1, Point, Scalar};
 use curv::BigInt;
 use multi_party_ecdsa::protocols::two_party_ecdsa::lindell_2017::*;
 use multi_party_ecdsa::utilities::zk_pdl_with_slack::{PDLwSlackProof, PDLwSlackStatement};
 use paillier::EncryptionKey;
+use serde::{Deserialize, Serialize};
 use sha2::Sha256;
 use zk_paillier::zkproofs::{CompositeDLogProof, NiCorrectKeyProof, SALT_STRING};
 
+#[derive(Serialize, Deserialize)]
 pub struct Li17RefreshP1Context1 {
+    session: SessionId,
     public: Point<Secp256r1>,
     public_p1: Point<Secp256r1>,
     public_p2: Point<Secp256r1>,
-    p1_private: party_one::Party1Private,
-    p1_m1: Scalar<Secp256r1>,
-    p1_r1: Scalar<Secp256r1>,
+    x1: Secret<Scalar<Secp256r1>>,
+    p1_private: Secret<party_one::Party1Private>,
+    p1_m1: Secret<Scalar<Secp256r1>>,
+    p1_r1: Secret<Scalar<Secp256r1>>,
 }
 
-pub type Li17RefreshP1Msg1 = coin_flip_optimal_rounds::Party1FirstMessage<Secp256r1, Sha256>;
+pub type Li17RefreshP1Msg1 =
+    SessionMsg<coin_flip_optimal_rounds::Party1FirstMessage<Secp256r1, Sha256>>;
 
-pub type Li17RefreshP1Msg2 = (
+pub type Li17RefreshP1Msg2 = SessionMsg<(
     coin_flip_optimal_rounds::Party1SecondMessage<Secp256r1, Sha256>,
     NiCorrectKeyProof,
     PDLwSlackStatement,
@@ -27,40 +38,60 @@ pub type Li17RefreshP1Msg2 = (
     CompositeDLogProof,
     EncryptionKey,
     BigInt,
-);
+)>;
 
+#[derive(Serialize, Deserialize)]
 pub struct Li17RefreshP2Context1 {
+    session: SessionId,
     public: Point<Secp256r1>,
     public_p1: Point<Secp256r1>,
     public_p2: Point<Secp256r1>,
-    p2_private: party_two::Party2Private,
-    p2_coin_flip_first_message: Li17RefreshP2Msg1,
+    x2: Secret<Scalar<Secp256r1>>,
+    p2_private: Secret<party_two::Party2Private>,
+    p2_coin_flip_first_message: coin_flip_optimal_rounds::Party2FirstMessage<Secp256r1>,
     p2_msg1_from_p1: coin_flip_optimal_rounds::Party1FirstMessage<Secp256r1, Sha256>,
 }
 
-pub type Li17RefreshP2Msg1 = coin_flip_optimal_rounds::Party2FirstMessage<Secp256r1>;
+pub type Li17RefreshP2Msg1 = SessionMsg<coin_flip_optimal_rounds::Party2FirstMessage<Secp256r1>>;
+
+impl Persist for Li17RefreshP1Context1 {
+    const PROTOCOL: Protocol = Protocol::Li17;
+    const KIND: &'static str = "refresh_p1_context1";
+}
+
+impl Persist for Li17RefreshP2Context1 {
+    const PROTOCOL: Protocol = Protocol::Li17;
+    const KIND: &'static str = "refresh_p2_context1";
+}
 
 pub fn li17_p1_refresh1(
     context: Li17SignP1Context,
-) -> Result<(Li17RefreshP1Msg1, Li17RefreshP1Context1), &'static str> {
+    session: SessionId,
+) -> Result<(Li17RefreshP1Msg1, Li17RefreshP1Context1), Error> {
     let (p1_coin_flip_first_message, m1, r1) =
         coin_flip_optimal_rounds::Party1FirstMessage::<Secp256r1, Sha256>::commit();
 
     let context1 = Li17RefreshP1Context1 {
+        session,
         public: context.public,
         public_p1: context.public_p1,
         public_p2: context.public_p2,
+        x1: context.x1,
         p1_private: context.p1_private,
-        p1_m1: m1,
-        p1_r1: r1,
+        p1_m1: Secret::new(m1),
+        p1_r1: Secret::new(r1),
     };
-    Ok((p1_coin_flip_first_message, context1))
+    Ok((
+        SessionMsg::new(session, p1_coin_flip_first_message),
+        context1,
+    ))
 }
 
 pub fn li17_p1_refresh2(
     msg: Li17RefreshP2Msg1,
     context: Li17RefreshP1Context1,
-) -> Result<(Li17RefreshP1Msg2, Li17SignP1Context), &'static str> {
+) -> Result<(Li17RefreshP1Msg2, Li17SignP1Context), Error> {
+    let msg = msg.open(&context.session, 2, Some(2))?;
     let (p1_second_message, res) =
         coin_flip_optimal_rounds::Party1SecondMessage::<Secp256r1, Sha256>::reveal(
             &msg.seed,
@@ -68,6 +99,11 @@ pub fn li17_p1_refresh2(
             &context.p1_r1,
         );
 
+    let res_inv = res.invert().ok_or(Error::MalformedInput {
+        round: 2,
+        party: Some(2),
+    })?;
+
     let (
         ek_new,
         c_key_new,
@@ -81,19 +117,23 @@ pub fn li17_p1_refresh2(
     let sign_context = Li17SignP1Context {
         public: context.public,
         public_p1: context.public_p1 * &res,
-        public_p2: context.public_p2 * &res.invert().unwrap(),
-        p1_private: new_private,
+        public_p2: context.public_p2 * &res_inv,
+        x1: Secret::new(&*context.x1 * &res),
+        p1_private: Secret::new(new_private),
     };
 
     Ok((
-        (
-            p1_second_message,
-            correct_key_proof,
-            pdl_statement,
-            pdl_proof,
-            composite_dlog_proof,
-            ek_new,
-            c_key_new,
+        SessionMsg::new(
+            context.session,
+            (
+                p1_second_message,
+                correct_key_proof,
+                pdl_statement,
+                pdl_proof,
+                composite_dlog_proof,
+                ek_new,
+                c_key_new,
+            ),
         ),
         sign_context,
     ))
@@ -102,29 +142,63 @@ pub fn li17_p1_refresh2(
 pub fn li17_p2_refresh1(
     msg: Li17RefreshP1Msg1,
     context: Li17SignP2Context,
-) -> Result<(Li17RefreshP2Msg1, Li17RefreshP2Context1), &'static str> {
+    session: SessionId,
+) -> Result<(Li17RefreshP2Msg1, Li17RefreshP2Context1), Error> {
+    let msg = msg.open(&session, 1, Some(1))?;
+    if PedersenProof::verify(&msg.proof).is_err() {
+        return Err(Error::InvalidProof {
+            round: 1,
+            party: Some(1),
+        });
+    }
+
     let p2_coin_flip_first_message =
         coin_flip_optimal_rounds::Party2FirstMessage::share(&msg.proof);
     let context2 = Li17RefreshP2Context1 {
+        session,
         public: context.public,
         public_p1: context.public_p1,
         public_p2: context.public_p2,
+        x2: context.x2,
         p2_private: context.p2_private,
         p2_coin_flip_first_message: p2_coin_flip_first_message.clone(),
         p2_msg1_from_p1: msg,
     };
-    Ok((p2_coin_flip_first_message, context2))
+    Ok((
+        SessionMsg::new(session, p2_coin_flip_first_message),
+        context2,
+    ))
 }
 
 pub fn li17_p2_refresh2(
     msg: Li17RefreshP1Msg2,
     context: Li17RefreshP2Context1,
-) -> Result<Li17SignP2Context, &'static str> {
+) -> Result<Li17SignP2Context, Error> {
+    let msg = msg.open(&context.session, 2, Some(1))?;
+
+    // finalize panics on a bad proof or commitment, check both beforehand
+    if PedersenBlindingProof::verify(&msg.0.proof).is_err() {
+        return Err(Error::InvalidProof {
+            round: 2,
+            party: Some(1),
+        });
+    }
+    if msg.0.proof.com != context.p2_msg1_from_p1.proof.com {
+        return Err(Error::CommitmentMismatch {
+            round: 2,
+            party: Some(1),
+        });
+    }
+
     let res = coin_flip_optimal_rounds::finalize(
         &msg.0.proof,
         &context.p2_coin_flip_first_message.seed,
         &context.p2_msg1_from_p1.proof.com,
     );
+    let res_inv = res.invert().ok_or(Error::MalformedInput {
+        round: 2,
+        party: Some(1),
+    })?;
     let party_two_paillier = party_two::PaillierPublic {
         ek: msg.5.clone(),
         encrypted_secret_share: msg.6.clone(),
@@ -139,21 +213,28 @@ pub fn li17_p2_refresh2(
     )
     .is_err()
     {
-        return Err("proof failed");
+        return Err(Error::InvalidProof {
+            round: 2,
+            party: Some(1),
+        });
     }
 
     if msg.1.verify(&party_two_paillier.ek, SALT_STRING).is_err() {
-        return Err("proof failed");
+        return Err(Error::InvalidPaillierKey {
+            round: 2,
+            party: Some(1),
+        });
     }
 
     let sign_context = Li17SignP2Context {
         public: context.public,
         public_p1: context.public_p1 * &res,
-        public_p2: context.public_p2 * &res.invert().unwrap(),
-        p2_private: party_two::Party2Private::update_private_key(
+        public_p2: context.public_p2 * &res_inv,
+        x2: Secret::new(&*context.x2 * &res_inv),
+        p2_private: Secret::new(party_two::Party2Private::update_private_key(
             &context.p2_private,
-            &res.invert().unwrap().to_bigint(),
-        ),
+            &res_inv.to_bigint(),
+        )),
         p2_paillier_public: party_two_paillier,
     };
     Ok(sign_context)
@@ -1,36 +1,57 @@
 use crate::li17_key_gen::{Li17SignP1Context, Li17SignP2Context};
+use crate::persist::{Persist, Protocol};
+use crate::secret::Secret;
+use crate::session::{SessionId, SessionMsg};
+use crate::signature::Signature;
+use crate::Error;
 use curv::arithmetic::traits::*;
-use curv::elliptic::curves::{p256::Secp256r1, Point};
+use curv::elliptic::curves::{p256::Secp256r1, Point, Scalar};
 use curv::BigInt;
 use multi_party_ecdsa::protocols::two_party_ecdsa::lindell_2017::*;
+use serde::{Deserialize, Serialize};
 
+#[derive(Serialize, Deserialize)]
 pub struct Li17SignP2Context1 {
     pub public: Point<Secp256r1>,
-    pub p2_private: party_two::Party2Private,
+    pub p2_private: Secret<party_two::Party2Private>,
     pub p2_paillier_public: party_two::PaillierPublic,
     hash: BigInt,
+    session: SessionId,
     p2_eph_comm_witness: party_two::EphCommWitness,
-    p2_eph_ec_key_pair: party_two::EphEcKeyPair,
+    p2_eph_ec_key_pair: Secret<party_two::EphEcKeyPair>,
 }
 
-pub type Li17SignP2Msg1 = party_two::EphKeyGenFirstMsg;
+pub type Li17SignP2Msg1 = SessionMsg<party_two::EphKeyGenFirstMsg>;
 
-pub type Li17SignP2Msg2 = (party_two::PartialSig, party_two::EphKeyGenSecondMsg);
+pub type Li17SignP2Msg2 = SessionMsg<(party_two::PartialSig, party_two::EphKeyGenSecondMsg)>;
 
+#[derive(Serialize, Deserialize)]
 pub struct Li17SignP1Context1 {
     pub public: Point<Secp256r1>,
-    pub p1_private: party_one::Party1Private,
+    pub p1_private: Secret<party_one::Party1Private>,
     hash: BigInt,
-    p1_eph_ec_key_pair: party_one::EphEcKeyPair,
-    p1_msg1_from_p2: Li17SignP2Msg1,
+    session: SessionId,
+    p1_eph_ec_key_pair: Secret<party_one::EphEcKeyPair>,
+    p1_msg1_from_p2: party_two::EphKeyGenFirstMsg,
 }
 
-pub type Li17SignP1Msg1 = party_one::EphKeyGenFirstMsg;
+pub type Li17SignP1Msg1 = SessionMsg<party_one::EphKeyGenFirstMsg>;
+
+impl Persist for Li17SignP1Context1 {
+    const PROTOCOL: Protocol = Protocol::Li17;
+    const KIND: &'static str = "sign_p1_context1";
+}
+
+impl Persist for Li17SignP2Context1 {
+    const PROTOCOL: Protocol = Protocol::Li17;
+    const KIND: &'static str = "sign_p2_context1";
+}
 
 pub fn li17_p2_sign1(
     context: Li17SignP2Context,
     message_hash: &[u8],
-) -> Result<(Li17SignP2Msg1, Li17SignP2Context1), &'static str> {
+    session: SessionId,
+) -> Result<(Li17SignP2Msg1, Li17SignP2Context1), Error> {
     let (eph_party_two_first_message, eph_comm_witness, eph_ec_key_pair_party2) =
         party_two::EphKeyGenFirstMsg::create_commitments();
 
@@ -39,21 +60,29 @@ pub fn li17_p2_sign1(
         p2_private: context.p2_private,
         p2_paillier_public: context.p2_paillier_public,
         hash: BigInt::from_bytes(message_hash),
+        session,
         p2_eph_comm_witness: eph_comm_witness,
-        p2_eph_ec_key_pair: eph_ec_key_pair_party2,
+        p2_eph_ec_key_pair: Secret::new(eph_ec_key_pair_party2),
     };
-    Ok((eph_party_two_first_message, context1))
+    Ok((
+        SessionMsg::new(session, eph_party_two_first_message),
+        context1,
+    ))
 }
 
 pub fn li17_p2_sign2(
     msg: Li17SignP1Msg1,
     context: Li17SignP2Context1,
-) -> Result<Li17SignP2Msg2, &'static str> {
+) -> Result<Li17SignP2Msg2, Error> {
+    let msg = msg.open(&context.session, 2, Some(1))?;
     let eph_party_two_second_message =
         party_two::EphKeyGenSecondMsg::verify_and_decommit(context.p2_eph_comm_witness, &msg);
 
     if eph_party_two_second_message.is_err() {
-        return Err("party1 DLog proof failed");
+        return Err(Error::InvalidProof {
+            round: 2,
+            party: Some(1),
+        });
     }
 
     let partial_sig = party_two::PartialSig::compute(
@@ -64,37 +93,49 @@ pub fn li17_p2_sign2(
         &msg.public_share,
         &context.hash,
     );
-    Ok((partial_sig, eph_party_two_second_message.unwrap()))
+    Ok(SessionMsg::new(
+        context.session,
+        (partial_sig, eph_party_two_second_message.unwrap()),
+    ))
 }
 
 pub fn li17_p1_sign1(
     msg: Li17SignP2Msg1,
     context: Li17SignP1Context,
     message_hash: &[u8],
-) -> Result<(Li17SignP1Msg1, Li17SignP1Context1), &'static str> {
+    session: SessionId,
+) -> Result<(Li17SignP1Msg1, Li17SignP1Context1), Error> {
+    let msg = msg.open(&session, 1, Some(2))?;
     let (eph_party_one_first_message, eph_ec_key_pair_party1) =
         party_one::EphKeyGenFirstMsg::create();
     let context2 = Li17SignP1Context1 {
         public: context.public,
         p1_private: context.p1_private,
         hash: BigInt::from_bytes(message_hash),
-        p1_eph_ec_key_pair: eph_ec_key_pair_party1,
+        session,
+        p1_eph_ec_key_pair: Secret::new(eph_ec_key_pair_party1),
         p1_msg1_from_p2: msg,
     };
-    Ok((eph_party_one_first_message, context2))
+    Ok((
+        SessionMsg::new(session, eph_party_one_first_message),
+        context2,
+    ))
 }
 
-pub fn li17_p1_sign2(
-    msg: Li17SignP2Msg2,
-    context: Li17SignP1Context1,
-) -> Result<Vec<u8>, &'static str> {
-    let (partial_sig, eph_party_two_second_message) = msg;
+pub fn li17_p1_sign2(msg: Li17SignP2Msg2, context: Li17SignP1Context1) -> Result<Signature, Error> {
+    let (partial_sig, eph_party_two_second_message) = msg.open(&context.session, 2, Some(2))?;
 
-    let _eph_party_one_second_message =
-        party_one::EphKeyGenSecondMsg::verify_commitments_and_dlog_proof(
-            &context.p1_msg1_from_p2,
-            &eph_party_two_second_message,
-        );
+    if party_one::EphKeyGenSecondMsg::verify_commitments_and_dlog_proof(
+        &context.p1_msg1_from_p2,
+        &eph_party_two_second_message,
+    )
+    .is_err()
+    {
+        return Err(Error::InvalidEphemeralKey {
+            round: 2,
+            party: Some(2),
+        });
+    }
 
     let sig = party_one::Signature::compute(
         &context.p1_private,
@@ -104,7 +145,251 @@ pub fn li17_p1_sign2(
     );
 
     if party_one::verify(&sig, &context.public, &context.hash).is_err() {
-        return Err("invalid signature");
+        return Err(Error::InvalidSignature { round: 2 });
+    }
+    Ok(Signature::from_scalars(&Scalar::from(&sig.r), &Scalar::from(&sig.s)).normalize_s())
+}
+
+/*
+Presigning.
+
+The ephemeral key exchange does not depend on the message, it can run ahead
+of time and leave both parties with a presignature for a one-time joint R:
+
+    P2: li17_p2_presign1 -> P1: li17_p1_presign1 -> P2: li17_p2_presign2
+    -> P1: li17_p1_presign2
+
+Once the hash is known, P2 sends its partial signature (li17_p2_presign_sign)
+and P1 computes the signature (li17_p1_presign_sign). As in li17_batch_sign the
+key contexts are borrowed, the contexts and presignatures only hold the
+ephemeral data of one nonce. A presignature holds an ephemeral secret that must
+not sign twice: the presignatures are neither cloneable nor serializable and
+signing consumes them. They can only be stored in a PresignPool, which hands
+each out once. The contexts of presigning hold the same ephemeral secrets, a
+copy could finish two presignatures with one nonce, so they are neither
+cloneable nor serializable either and the run has to finish in one process.
+*/
+/// Context of P2 between its two presigning rounds, it cannot be persisted:
+///
+/// ```compile_fail
+/// use mpecdsa::li17_sign::Li17PresignP2Context1;
+/// use mpecdsa::Persist;
+///
+/// fn persist(context: &Li17PresignP2Context1) -> String {
+///     context.to_json().unwrap()
+/// }
+/// ```
+pub struct Li17PresignP2Context1 {
+    session: SessionId,
+    p2_eph_comm_witness: party_two::EphCommWitness,
+    p2_eph_ec_key_pair: Secret<party_two::EphEcKeyPair>,
+}
+
+pub type Li17PresignP2Msg1 = SessionMsg<party_two::EphKeyGenFirstMsg>;
+
+pub type Li17PresignP2Msg2 = SessionMsg<party_two::EphKeyGenSecondMsg>;
+
+pub type Li17PresignP2Msg3 = SessionMsg<party_two::PartialSig>;
+
+pub struct Li17P2Presignature {
+    data: P2PresignatureData,
+}
+
+// only the presignature pool serializes presignatures, a copy must not leak
+#[derive(Serialize, Deserialize)]
+struct P2PresignatureData {
+    session: SessionId,
+    p2_eph_ec_key_pair: Secret<party_two::EphEcKeyPair>,
+    p1_eph_public_share: Point<Secp256r1>,
+}
+
+/// Context of P1 between its two presigning rounds, it cannot be persisted:
+///
+/// ```compile_fail
+/// use mpecdsa::li17_sign::Li17PresignP1Context1;
+/// use mpecdsa::Persist;
+///
+/// fn persist(context: &Li17PresignP1Context1) -> String {
+///     context.to_json().unwrap()
+/// }
+/// ```
+pub struct Li17PresignP1Context1 {
+    session: SessionId,
+    p1_eph_ec_key_pair: Secret<party_one::EphEcKeyPair>,
+    p1_msg1_from_p2: party_two::EphKeyGenFirstMsg,
+}
+
+pub type Li17PresignP1Msg1 = SessionMsg<party_one::EphKeyGenFirstMsg>;
+
+pub struct Li17P1Presignature {
+    data: P1PresignatureData,
+}
+
+#[derive(Serialize, Deserialize)]
+struct P1PresignatureData {
+    session: SessionId,
+    p1_eph_ec_key_pair: Secret<party_one::EphEcKeyPair>,
+    p2_eph_public_share: Point<Secp256r1>,
+}
+
+impl Persist for P1PresignatureData {
+    const PROTOCOL: Protocol = Protocol::Li17;
+    const KIND: &'static str = "p1_presignature";
+}
+
+impl Persist for P2PresignatureData {
+    const PROTOCOL: Protocol = Protocol::Li17;
+    const KIND: &'static str = "p2_presignature";
+}
+
+impl Li17P1Presignature {
+    pub fn session(&self) -> SessionId {
+        self.data.session
+    }
+
+    pub(crate) fn to_json(&self) -> Result<String, Error> {
+        self.data.to_json()
+    }
+
+    pub(crate) fn from_json(json: &str) -> Result<Self, Error> {
+        let data = P1PresignatureData::from_json(json)?;
+        Ok(Li17P1Presignature { data })
+    }
+}
+
+impl Li17P2Presignature {
+    pub fn session(&self) -> SessionId {
+        self.data.session
+    }
+
+    pub(crate) fn to_json(&self) -> Result<String, Error> {
+        self.data.to_json()
+    }
+
+    pub(crate) fn from_json(json: &str) -> Result<Self, Error> {
+        let data = P2PresignatureData::from_json(json)?;
+        Ok(Li17P2Presignature { data })
+    }
+}
+
+pub fn li17_p2_presign1(
+    session: SessionId,
+) -> Result<(Li17PresignP2Msg1, Li17PresignP2Context1), Error> {
+    let (eph_party_two_first_message, eph_comm_witness, eph_ec_key_pair_party2) =
+        party_two::EphKeyGenFirstMsg::create_commitments();
+
+    let context1 = Li17PresignP2Context1 {
+        session,
+        p2_eph_comm_witness: eph_comm_witness,
+        p2_eph_ec_key_pair: Secret::new(eph_ec_key_pair_party2),
+    };
+    Ok((
+        SessionMsg::new(session, eph_party_two_first_message),
+        context1,
+    ))
+}
+
+pub fn li17_p1_presign1(
+    msg: Li17PresignP2Msg1,
+    session: SessionId,
+) -> Result<(Li17PresignP1Msg1, Li17PresignP1Context1), Error> {
+    let msg = msg.open(&session, 1, Some(2))?;
+    let (eph_party_one_first_message, eph_ec_key_pair_party1) =
+        party_one::EphKeyGenFirstMsg::create();
+    let context1 = Li17PresignP1Context1 {
+        session,
+        p1_eph_ec_key_pair: Secret::new(eph_ec_key_pair_party1),
+        p1_msg1_from_p2: msg,
+    };
+    Ok((
+        SessionMsg::new(session, eph_party_one_first_message),
+        context1,
+    ))
+}
+
+pub fn li17_p2_presign2(
+    msg: Li17PresignP1Msg1,
+    context: Li17PresignP2Context1,
+) -> Result<(Li17PresignP2Msg2, Li17P2Presignature), Error> {
+    let msg = msg.open(&context.session, 2, Some(1))?;
+    let eph_party_two_second_message =
+        party_two::EphKeyGenSecondMsg::verify_and_decommit(context.p2_eph_comm_witness, &msg)
+            .map_err(|_| Error::InvalidProof {
+                round: 2,
+                party: Some(1),
+            })?;
+
+    let data = P2PresignatureData {
+        session: context.session,
+        p2_eph_ec_key_pair: context.p2_eph_ec_key_pair,
+        p1_eph_public_share: msg.public_share,
+    };
+    Ok((
+        SessionMsg::new(data.session, eph_party_two_second_message),
+        Li17P2Presignature { data },
+    ))
+}
+
+pub fn li17_p1_presign2(
+    msg: Li17PresignP2Msg2,
+    context: Li17PresignP1Context1,
+) -> Result<Li17P1Presignature, Error> {
+    let eph_party_two_second_message = msg.open(&context.session, 2, Some(2))?;
+    if party_one::EphKeyGenSecondMsg::verify_commitments_and_dlog_proof(
+        &context.p1_msg1_from_p2,
+        &eph_party_two_second_message,
+    )
+    .is_err()
+    {
+        return Err(Error::InvalidEphemeralKey {
+            round: 2,
+            party: Some(2),
+        });
+    }
+
+    let data = P1PresignatureData {
+        session: context.session,
+        p1_eph_ec_key_pair: context.p1_eph_ec_key_pair,
+        p2_eph_public_share: eph_party_two_second_message.comm_witness.public_share,
+    };
+    Ok(Li17P1Presignature { data })
+}
+
+pub fn li17_p2_presign_sign(
+    presignature: Li17P2Presignature,
+    context: &Li17SignP2Context,
+    message_hash: &[u8],
+) -> Result<Li17PresignP2Msg3, Error> {
+    let presignature = presignature.data;
+    let partial_sig = party_two::PartialSig::compute(
+        &context.p2_paillier_public.ek,
+        &context.p2_paillier_public.encrypted_secret_share,
+        &context.p2_private,
+        &presignature.p2_eph_ec_key_pair,
+        &presignature.p1_eph_public_share,
+        &BigInt::from_bytes(message_hash),
+    );
+    Ok(SessionMsg::new(presignature.session, partial_sig))
+}
+
+pub fn li17_p1_presign_sign(
+    msg: Li17PresignP2Msg3,
+    presignature: Li17P1Presignature,
+    context: &Li17SignP1Context,
+    message_hash: &[u8],
+) -> Result<Signature, Error> {
+    let presignature = presignature.data;
+    let partial_sig = msg.open(&presignature.session, 3, Some(2))?;
+
+    let sig = party_one::Signature::compute(
+        &context.p1_private,
+        &partial_sig.c3,
+        &presignature.p1_eph_ec_key_pair,
+        &presignature.p2_eph_public_share,
+    );
+
+    if party_one::verify(&sig, &context.public, &BigInt::from_bytes(message_hash)).is_err() {
+        return Err(Error::InvalidSignature { round: 3 });
     }
-    Ok([BigInt::to_bytes(&sig.r), BigInt::to_bytes(&sig.s)].concat())
+    Ok(Signature::from_scalars(&Scalar::from(&sig.r), &Scalar::from(&sig.s)).normalize_s())
 }
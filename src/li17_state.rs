@@ -0,0 +1,457 @@
+use crate::li17_key_gen::*;
+use crate::li17_refresh::*;
+use crate::li17_sign::*;
+use crate::persist::{Persist, Protocol};
+use crate::session::SessionId;
+use crate::signature::Signature;
+use crate::state_machine::{Msg, StateMachine};
+use crate::wire::{read_kind, write_message, MessageKind, Reader, Wire};
+use crate::Error;
+use serde::{Deserialize, Serialize};
+use std::mem;
+
+/*
+State machines for the two-party Li17 protocols.
+
+Li17Party1 and Li17Party2 run key generation, signing or refresh, depending on
+the constructor. Party one has index 1 and party two index 2, every message is
+P2P to the other party. The two parties take turns, so at most one message is
+awaited at a time. Key generation and refresh are started by party one, signing
+by party two.
+*/
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum Li17Msg {
+    KeyGenP1Msg1(Li17KeyGenP1Msg1),
+    KeyGenP2Msg1(Li17KeyGenP2Msg1),
+    KeyGenP1Msg2(Li17KeyGenP1Msg2),
+    SignP2Msg1(Li17SignP2Msg1),
+    SignP1Msg1(Li17SignP1Msg1),
+    SignP2Msg2(Li17SignP2Msg2),
+    RefreshP1Msg1(Li17RefreshP1Msg1),
+    RefreshP2Msg1(Li17RefreshP2Msg1),
+    RefreshP1Msg2(Li17RefreshP1Msg2),
+}
+
+impl Li17Msg {
+    pub fn round(&self) -> u16 {
+        match self {
+            Li17Msg::KeyGenP1Msg1(_)
+            | Li17Msg::KeyGenP2Msg1(_)
+            | Li17Msg::SignP2Msg1(_)
+            | Li17Msg::SignP1Msg1(_)
+            | Li17Msg::RefreshP1Msg1(_)
+            | Li17Msg::RefreshP2Msg1(_) => 1,
+            Li17Msg::KeyGenP1Msg2(_) | Li17Msg::SignP2Msg2(_) | Li17Msg::RefreshP1Msg2(_) => 2,
+        }
+    }
+}
+
+impl Wire for Li17Msg {
+    fn write(&self, out: &mut Vec<u8>) -> Result<(), Error> {
+        match self {
+            Li17Msg::KeyGenP1Msg1(m) => write_message(MessageKind::LI17_KEY_GEN_P1_MSG1, m, out),
+            Li17Msg::KeyGenP2Msg1(m) => write_message(MessageKind::LI17_KEY_GEN_P2_MSG1, m, out),
+            Li17Msg::KeyGenP1Msg2(m) => write_message(MessageKind::LI17_KEY_GEN_P1_MSG2, m, out),
+            Li17Msg::SignP2Msg1(m) => write_message(MessageKind::LI17_SIGN_P2_MSG1, m, out),
+            Li17Msg::SignP1Msg1(m) => write_message(MessageKind::LI17_SIGN_P1_MSG1, m, out),
+            Li17Msg::SignP2Msg2(m) => write_message(MessageKind::LI17_SIGN_P2_MSG2, m, out),
+            Li17Msg::RefreshP1Msg1(m) => write_message(MessageKind::LI17_REFRESH_P1_MSG1, m, out),
+            Li17Msg::RefreshP2Msg1(m) => write_message(MessageKind::LI17_REFRESH_P2_MSG1, m, out),
+            Li17Msg::RefreshP1Msg2(m) => write_message(MessageKind::LI17_REFRESH_P1_MSG2, m, out),
+        }
+    }
+
+    fn read(input: &mut Reader<'_>) -> Result<Self, Error> {
+        Ok(match read_kind(input)? {
+            MessageKind::LI17_KEY_GEN_P1_MSG1 => Li17Msg::KeyGenP1Msg1(Wire::read(input)?),
+            MessageKind::LI17_KEY_GEN_P2_MSG1 => Li17Msg::KeyGenP2Msg1(Wire::read(input)?),
+            MessageKind::LI17_KEY_GEN_P1_MSG2 => Li17Msg::KeyGenP1Msg2(Wire::read(input)?),
+            MessageKind::LI17_SIGN_P2_MSG1 => Li17Msg::SignP2Msg1(Wire::read(input)?),
+            MessageKind::LI17_SIGN_P1_MSG1 => Li17Msg::SignP1Msg1(Wire::read(input)?),
+            MessageKind::LI17_SIGN_P2_MSG2 => Li17Msg::SignP2Msg2(Wire::read(input)?),
+            MessageKind::LI17_REFRESH_P1_MSG1 => Li17Msg::RefreshP1Msg1(Wire::read(input)?),
+            MessageKind::LI17_REFRESH_P2_MSG1 => Li17Msg::RefreshP2Msg1(Wire::read(input)?),
+            MessageKind::LI17_REFRESH_P1_MSG2 => Li17Msg::RefreshP1Msg2(Wire::read(input)?),
+            _ => return Err(Error::InvalidEncoding),
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub enum Li17P1Output {
+    KeyShare(Li17SignP1Context),
+    Signature(Signature),
+}
+
+#[derive(Serialize, Deserialize)]
+pub enum Li17P2Output {
+    KeyShare(Li17SignP2Context),
+    // party two only contributes its partial signature, party one computes the signature
+    Signed,
+}
+
+#[derive(Serialize, Deserialize)]
+enum P1Round {
+    KeyGen1(Li17KeyGenP1Context1),
+    Sign0 {
+        context: Li17SignP1Context,
+        message_hash: Vec<u8>,
+        session: SessionId,
+    },
+    Sign1(Li17SignP1Context1),
+    Refresh1(Li17RefreshP1Context1),
+    Finished,
+    Failed,
+}
+
+#[derive(Serialize, Deserialize)]
+enum P2Round {
+    KeyGen0 {
+        session: SessionId,
+    },
+    KeyGen1(Li17KeyGenP2Context1),
+    Sign1(Li17SignP2Context1),
+    Refresh0 {
+        context: Li17SignP2Context,
+        session: SessionId,
+    },
+    Refresh1(Li17RefreshP2Context1),
+    Finished,
+    Failed,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Li17Party1 {
+    round: P1Round,
+    incoming: Option<Li17Msg>,
+    queue: Vec<Msg<Li17Msg>>,
+    output: Option<Li17P1Output>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Li17Party2 {
+    round: P2Round,
+    incoming: Option<Li17Msg>,
+    queue: Vec<Msg<Li17Msg>>,
+    output: Option<Li17P2Output>,
+}
+
+impl Persist for Li17Party1 {
+    const PROTOCOL: Protocol = Protocol::Li17;
+    const KIND: &'static str = "p1_state";
+}
+
+impl Persist for Li17Party2 {
+    const PROTOCOL: Protocol = Protocol::Li17;
+    const KIND: &'static str = "p2_state";
+}
+
+impl Li17Party1 {
+    pub fn key_gen(session: SessionId) -> Result<Self, Error> {
+        let (msg1, context1) = li17_p1_key_gen1(session)?;
+        let mut party = Li17Party1::with_round(P1Round::KeyGen1(context1));
+        party.send(Li17Msg::KeyGenP1Msg1(msg1));
+        Ok(party)
+    }
+
+    // waits for the first message of party two
+    pub fn sign(
+        context: Li17SignP1Context,
+        message_hash: &[u8],
+        session: SessionId,
+    ) -> Result<Self, Error> {
+        Ok(Li17Party1::with_round(P1Round::Sign0 {
+            context,
+            message_hash: message_hash.to_vec(),
+            session,
+        }))
+    }
+
+    pub fn refresh(context: Li17SignP1Context, session: SessionId) -> Result<Self, Error> {
+        let (msg1, context1) = li17_p1_refresh1(context, session)?;
+        let mut party = Li17Party1::with_round(P1Round::Refresh1(context1));
+        party.send(Li17Msg::RefreshP1Msg1(msg1));
+        Ok(party)
+    }
+
+    fn with_round(round: P1Round) -> Self {
+        Li17Party1 {
+            round,
+            incoming: None,
+            queue: Vec::new(),
+            output: None,
+        }
+    }
+
+    fn send(&mut self, body: Li17Msg) {
+        self.queue.push(Msg {
+            sender: 1,
+            receiver: Some(2),
+            body,
+        });
+    }
+
+    fn expects(&self, body: &Li17Msg) -> bool {
+        matches!(
+            (&self.round, body),
+            (P1Round::KeyGen1(_), Li17Msg::KeyGenP2Msg1(_))
+                | (P1Round::Sign0 { .. }, Li17Msg::SignP2Msg1(_))
+                | (P1Round::Sign1(_), Li17Msg::SignP2Msg2(_))
+                | (P1Round::Refresh1(_), Li17Msg::RefreshP2Msg1(_))
+        )
+    }
+}
+
+impl Li17Party2 {
+    // waits for the first message of party one
+    pub fn key_gen(session: SessionId) -> Result<Self, Error> {
+        Ok(Li17Party2::with_round(P2Round::KeyGen0 { session }))
+    }
+
+    pub fn sign(
+        context: Li17SignP2Context,
+        message_hash: &[u8],
+        session: SessionId,
+    ) -> Result<Self, Error> {
+        let (msg1, context1) = li17_p2_sign1(context, message_hash, session)?;
+        let mut party = Li17Party2::with_round(P2Round::Sign1(context1));
+        party.send(Li17Msg::SignP2Msg1(msg1));
+        Ok(party)
+    }
+
+    // waits for the first message of party one
+    pub fn refresh(context: Li17SignP2Context, session: SessionId) -> Result<Self, Error> {
+        Ok(Li17Party2::with_round(P2Round::Refresh0 {
+            context,
+            session,
+        }))
+    }
+
+    fn with_round(round: P2Round) -> Self {
+        Li17Party2 {
+            round,
+            incoming: None,
+            queue: Vec::new(),
+            output: None,
+        }
+    }
+
+    fn send(&mut self, body: Li17Msg) {
+        self.queue.push(Msg {
+            sender: 2,
+            receiver: Some(1),
+            body,
+        });
+    }
+
+    fn expects(&self, body: &Li17Msg) -> bool {
+        matches!(
+            (&self.round, body),
+            (P2Round::KeyGen0 { .. }, Li17Msg::KeyGenP1Msg1(_))
+                | (P2Round::KeyGen1(_), Li17Msg::KeyGenP1Msg2(_))
+                | (P2Round::Sign1(_), Li17Msg::SignP1Msg1(_))
+                | (P2Round::Refresh0 { .. }, Li17Msg::RefreshP1Msg1(_))
+                | (P2Round::Refresh1(_), Li17Msg::RefreshP1Msg2(_))
+        )
+    }
+}
+
+// checks the addressing of a message for the party with the given index,
+// the other party is the only valid sender
+fn check_msg(
+    msg: &Msg<Li17Msg>,
+    index: u16,
+    round: u16,
+    expected: bool,
+    buffered: bool,
+) -> Result<(), Error> {
+    let other = 3 - index;
+    if msg.sender != other {
+        return Err(Error::UnexpectedSender {
+            round,
+            party: msg.sender,
+        });
+    }
+    if msg.receiver != Some(index) || !expected || buffered {
+        return Err(malformed(round, other));
+    }
+    Ok(())
+}
+
+fn malformed(round: u16, party: u16) -> Error {
+    Error::MalformedInput {
+        round,
+        party: Some(party),
+    }
+}
+
+impl StateMachine for Li17Party1 {
+    type MessageBody = Li17Msg;
+    type Output = Li17P1Output;
+
+    fn handle_incoming(&mut self, msg: Msg<Li17Msg>) -> Result<(), Error> {
+        check_msg(
+            &msg,
+            1,
+            self.current_round(),
+            self.expects(&msg.body),
+            self.incoming.is_some(),
+        )?;
+        self.incoming = Some(msg.body);
+        Ok(())
+    }
+
+    fn message_queue(&mut self) -> &mut Vec<Msg<Li17Msg>> {
+        &mut self.queue
+    }
+
+    fn wants_to_proceed(&self) -> bool {
+        self.incoming.is_some()
+    }
+
+    fn proceed(&mut self) -> Result<(), Error> {
+        let msg = match self.incoming.take() {
+            Some(msg) => msg,
+            None => return Ok(()),
+        };
+        let round = self.current_round();
+        self.round = match (mem::replace(&mut self.round, P1Round::Failed), msg) {
+            (P1Round::KeyGen1(context), Li17Msg::KeyGenP2Msg1(msg)) => {
+                let (msg2, context) = li17_p1_key_gen2(msg, context)?;
+                self.send(Li17Msg::KeyGenP1Msg2(msg2));
+                self.output = Some(Li17P1Output::KeyShare(context));
+                P1Round::Finished
+            }
+            (
+                P1Round::Sign0 {
+                    context,
+                    message_hash,
+                    session,
+                },
+                Li17Msg::SignP2Msg1(msg),
+            ) => {
+                let (msg1, context) = li17_p1_sign1(msg, context, &message_hash, session)?;
+                self.send(Li17Msg::SignP1Msg1(msg1));
+                P1Round::Sign1(context)
+            }
+            (P1Round::Sign1(context), Li17Msg::SignP2Msg2(msg)) => {
+                self.output = Some(Li17P1Output::Signature(li17_p1_sign2(msg, context)?));
+                P1Round::Finished
+            }
+            (P1Round::Refresh1(context), Li17Msg::RefreshP2Msg1(msg)) => {
+                let (msg2, context) = li17_p1_refresh2(msg, context)?;
+                self.send(Li17Msg::RefreshP1Msg2(msg2));
+                self.output = Some(Li17P1Output::KeyShare(context));
+                P1Round::Finished
+            }
+            _ => return Err(malformed(round, 2)),
+        };
+        Ok(())
+    }
+
+    // the round function of party one that runs next
+    fn current_round(&self) -> u16 {
+        match self.round {
+            P1Round::Sign0 { .. } => 1,
+            P1Round::KeyGen1(_) | P1Round::Sign1(_) | P1Round::Refresh1(_) => 2,
+            P1Round::Finished | P1Round::Failed => 3,
+        }
+    }
+
+    fn is_finished(&self) -> bool {
+        matches!(self.round, P1Round::Finished)
+    }
+
+    fn pick_output(&mut self) -> Option<Li17P1Output> {
+        self.output.take()
+    }
+
+    fn party_index(&self) -> u16 {
+        1
+    }
+}
+
+impl StateMachine for Li17Party2 {
+    type MessageBody = Li17Msg;
+    type Output = Li17P2Output;
+
+    fn handle_incoming(&mut self, msg: Msg<Li17Msg>) -> Result<(), Error> {
+        check_msg(
+            &msg,
+            2,
+            self.current_round(),
+            self.expects(&msg.body),
+            self.incoming.is_some(),
+        )?;
+        self.incoming = Some(msg.body);
+        Ok(())
+    }
+
+    fn message_queue(&mut self) -> &mut Vec<Msg<Li17Msg>> {
+        &mut self.queue
+    }
+
+    fn wants_to_proceed(&self) -> bool {
+        self.incoming.is_some()
+    }
+
+    fn proceed(&mut self) -> Result<(), Error> {
+        let msg = match self.incoming.take() {
+            Some(msg) => msg,
+            None => return Ok(()),
+        };
+        let round = self.current_round();
+        self.round = match (mem::replace(&mut self.round, P2Round::Failed), msg) {
+            (P2Round::KeyGen0 { session }, Li17Msg::KeyGenP1Msg1(msg)) => {
+                let (msg1, context) = li17_p2_key_gen1(msg, session)?;
+                self.send(Li17Msg::KeyGenP2Msg1(msg1));
+                P2Round::KeyGen1(context)
+            }
+            (P2Round::KeyGen1(context), Li17Msg::KeyGenP1Msg2(msg)) => {
+                // party one already knows the public key, it is not sent back
+                let (_, context) = li17_p2_key_gen2(msg, context)?;
+                self.output = Some(Li17P2Output::KeyShare(context));
+                P2Round::Finished
+            }
+            (P2Round::Sign1(context), Li17Msg::SignP1Msg1(msg)) => {
+                let msg2 = li17_p2_sign2(msg, context)?;
+                self.send(Li17Msg::SignP2Msg2(msg2));
+                self.output = Some(Li17P2Output::Signed);
+                P2Round::Finished
+            }
+            (P2Round::Refresh0 { context, session }, Li17Msg::RefreshP1Msg1(msg)) => {
+                let (msg1, context) = li17_p2_refresh1(msg, context, session)?;
+                self.send(Li17Msg::RefreshP2Msg1(msg1));
+                P2Round::Refresh1(context)
+            }
+            (P2Round::Refresh1(context), Li17Msg::RefreshP1Msg2(msg)) => {
+                self.output = Some(Li17P2Output::KeyShare(li17_p2_refresh2(msg, context)?));
+                P2Round::Finished
+            }
+            _ => return Err(malformed(round, 1)),
+        };
+        Ok(())
+    }
+
+    // the round function of party two that runs next
+    fn current_round(&self) -> u16 {
+        match self.round {
+            P2Round::KeyGen0 { .. } | P2Round::Refresh0 { .. } => 1,
+            P2Round::KeyGen1(_) | P2Round::Sign1(_) | P2Round::Refresh1(_) => 2,
+            P2Round::Finished | P2Round::Failed => 3,
+        }
+    }
+
+    fn is_finished(&self) -> bool {
+        matches!(self.round, P2Round::Finished)
+    }
+
+    fn pick_output(&mut self) -> Option<Li17P2Output> {
+        self.output.take()
+    }
+
+    fn party_index(&self) -> u16 {
+        2
+    }
+}
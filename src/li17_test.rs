@@ -1,14 +1,30 @@
-use crate::li17_key_gen::{li17_p1_key_gen1, li17_p1_key_gen2, li17_p2_key_gen1, li17_p2_key_gen2};
+use crate::li17_batch_sign::{
+    li17_p1_batch_sign1, li17_p1_batch_sign2, li17_p2_batch_sign1, li17_p2_batch_sign2,
+};
+use crate::li17_key_gen::{
+    li17_p1_key_gen1, li17_p1_key_gen2, li17_p2_key_gen1, li17_p2_key_gen2, Li17KeyGenP1Msg2,
+    Li17KeyGenP2Context1, Li17SignP1Context, Li17SignP2Context,
+};
 use crate::li17_refresh::{li17_p1_refresh1, li17_p1_refresh2, li17_p2_refresh1, li17_p2_refresh2};
-use crate::li17_sign::{li17_p1_sign1, li17_p1_sign2, li17_p2_sign1, li17_p2_sign2};
-use curv::arithmetic::traits::*;
+use crate::li17_sign::{
+    li17_p1_presign1, li17_p1_presign2, li17_p1_presign_sign, li17_p1_sign1, li17_p1_sign2,
+    li17_p2_presign1, li17_p2_presign2, li17_p2_presign_sign, li17_p2_sign1, li17_p2_sign2,
+    Li17P1Presignature, Li17P2Presignature,
+};
+use crate::li17_state::{Li17P1Output, Li17P2Output};
+use crate::persist::{Envelope, Persist, FORMAT_VERSION};
+use crate::presign_pool::{MemoryStore, PresignPool};
+use crate::session::{SessionId, SessionMsg};
+use crate::simulation::{Outcome, Simulation};
+use crate::{Error, Signature};
 use curv::elliptic::curves::{p256::Secp256r1, Point, Scalar};
-use curv::BigInt;
 use sha2::{Digest, Sha256};
+use std::convert::TryFrom;
 
-pub fn check_sig(r: &Scalar<Secp256r1>, s: &Scalar<Secp256r1>, msg: &[u8], pk: &Point<Secp256r1>) {
-    use p256::ecdsa::{signature::Verifier, Signature, VerifyingKey};
-    use p256::FieldBytes;
+pub const SID: SessionId = SessionId::new([7; 32]);
+
+pub fn check_sig(sig: &Signature, msg: &[u8], pk: &Point<Secp256r1>) {
+    use p256::ecdsa::{signature::Verifier, VerifyingKey};
 
     let slice = pk.to_bytes(false);
     let mut raw_pk = Vec::new();
@@ -24,147 +40,514 @@ pub fn check_sig(r: &Scalar<Secp256r1>, s: &Scalar<Secp256r1>, msg: &[u8], pk: &
 
     let public_key: VerifyingKey = VerifyingKey::from_sec1_bytes(&raw_pk).unwrap();
 
-    let bytes_r = &r.to_bytes()[..];
-    let bytes_s = &s.to_bytes()[..];
-
-    let signature = Signature::from_scalars(
-        *FieldBytes::from_slice(bytes_r),
-        *FieldBytes::from_slice(bytes_s),
-    )
-    .unwrap();
+    let signature = p256::ecdsa::Signature::try_from(sig).unwrap();
 
     let is_correct = public_key.verify(msg, &signature).is_ok();
     assert!(is_correct);
 }
 
-#[test]
-fn keygen() {
-    // keygen
-    let (msg1p1, context1p1) = li17_p1_key_gen1().unwrap();
-
-    let (msg1p2, context1p2) = li17_p2_key_gen1(msg1p1).unwrap();
-
-    let (msg2p1, _sign_context_p1) = li17_p1_key_gen2(msg1p2, context1p1).unwrap();
-
-    let (_pk, _sign_context_p2) = li17_p2_key_gen2(msg2p1, context1p2).unwrap();
+// the key shares of a key generation or refresh run
+fn key_shares(
+    p1: Outcome<Li17P1Output>,
+    p2: Outcome<Li17P2Output>,
+) -> (Li17SignP1Context, Li17SignP2Context) {
+    match (p1.output(), p2.output()) {
+        (Some(Li17P1Output::KeyShare(p1)), Some(Li17P2Output::KeyShare(p2))) => (p1, p2),
+        _ => panic!("expected key shares"),
+    }
 }
 
-#[test]
-fn sign() {
-    // keygen
-    let (msg1p1, context1p1) = li17_p1_key_gen1().unwrap();
-
-    let (msg1p2, context1p2) = li17_p2_key_gen1(msg1p1).unwrap();
-
-    let (msg2p1, sign_context_p1) = li17_p1_key_gen2(msg1p2, context1p1).unwrap();
-
-    let (pk, sign_context_p2) = li17_p2_key_gen2(msg2p1, context1p2).unwrap();
-
-    // sign
-
-    let mut hasher = Sha256::new();
-    hasher.update(b"random message");
-    let hash = hasher.finalize().to_vec();
+pub fn run_keygen() -> (Li17SignP1Context, Li17SignP2Context) {
+    let (p1, p2) = Simulation::new(SID).li17_key_gen().unwrap();
+    key_shares(p1, p2)
+}
 
-    let (smsg1p2, context1p2) = li17_p2_sign1(sign_context_p2, &hash).unwrap();
+fn run_refresh(
+    p1: Li17SignP1Context,
+    p2: Li17SignP2Context,
+) -> (Li17SignP1Context, Li17SignP2Context) {
+    let (p1, p2) = Simulation::new(SID).li17_refresh(p1, p2).unwrap();
+    key_shares(p1, p2)
+}
 
-    let (smsg1p1, context1p1) = li17_p1_sign1(smsg1p2, sign_context_p1, &hash).unwrap();
+fn run_sign(p1: Li17SignP1Context, p2: Li17SignP2Context, message: &[u8]) -> Signature {
+    let hash = Sha256::digest(message);
+    let (p1, p2) = Simulation::new(SID).li17_sign(p1, p2, &hash).unwrap();
+    assert!(matches!(p2.output(), Some(Li17P2Output::Signed)));
+    match p1.output() {
+        Some(Li17P1Output::Signature(sig)) => sig,
+        _ => panic!("expected a signature"),
+    }
+}
 
-    let smsg2p2 = li17_p2_sign2(smsg1p1, context1p2).unwrap();
+// the crate-owned shares are those behind the public shares
+fn check_shares(p1: &Li17SignP1Context, p2: &Li17SignP2Context) {
+    let g = Point::<Secp256r1>::generator();
+    assert_eq!(g * &*p1.x1, p1.public_p1);
+    assert_eq!(g * &*p2.x2, p2.public_p2);
+    assert_eq!(p1.public, p2.public);
+}
 
-    let sig = li17_p1_sign2(smsg2p2, context1p1).unwrap();
+#[test]
+fn keygen() {
+    let (p1, p2) = run_keygen();
+    check_shares(&p1, &p2);
+}
 
-    let r = Scalar::<Secp256r1>::from(&BigInt::from_bytes(&sig[..32]));
-    let s = Scalar::<Secp256r1>::from(&BigInt::from_bytes(&sig[32..]));
-    check_sig(&r, &s, "random message".as_bytes(), &pk);
+#[test]
+fn sign() {
+    let (p1, p2) = run_keygen();
+    let pk = p1.public.clone();
+    let sig = run_sign(p1, p2, b"random message");
+    check_sig(&sig, b"random message", &pk);
 }
 
 #[test]
 fn refresh_and_sign() {
-    // keygen
-    let (msg1p1, context1p1) = li17_p1_key_gen1().unwrap();
-
-    let (msg1p2, context1p2) = li17_p2_key_gen1(msg1p1).unwrap();
-
-    let (msg2p1, sign_context_p1) = li17_p1_key_gen2(msg1p2, context1p1).unwrap();
-
-    let (pk, sign_context_p2) = li17_p2_key_gen2(msg2p1, context1p2).unwrap();
-
-    // refresh
-
-    let (msg1p1, context1p1) = li17_p1_refresh1(sign_context_p1).unwrap();
-
-    let (msg1p2, context1p2) = li17_p2_refresh1(msg1p1, sign_context_p2).unwrap();
-
-    let (msg2p1, sign_context_p1) = li17_p1_refresh2(msg1p2, context1p1).unwrap();
-
-    let sign_context_p2 = li17_p2_refresh2(msg2p1, context1p2).unwrap();
-
-    // sign
-
-    let mut hasher = Sha256::new();
-    hasher.update(b"random message");
-    let hash = hasher.finalize().to_vec();
-
-    let (smsg1p2, context1p2) = li17_p2_sign1(sign_context_p2, &hash).unwrap();
+    let (p1, p2) = run_keygen();
+    let pk = p1.public.clone();
+    let x1 = (*p1.x1).clone();
 
-    let (smsg1p1, context1p1) = li17_p1_sign1(smsg1p2, sign_context_p1, &hash).unwrap();
+    let (p1, p2) = run_refresh(p1, p2);
+    check_shares(&p1, &p2);
+    assert_eq!(p1.public, pk);
+    assert_ne!(*p1.x1, x1);
 
-    let smsg2p2 = li17_p2_sign2(smsg1p1, context1p2).unwrap();
-
-    let sig = li17_p1_sign2(smsg2p2, context1p1).unwrap();
-
-    let r = Scalar::<Secp256r1>::from(&BigInt::from_bytes(&sig[..32]));
-    let s = Scalar::<Secp256r1>::from(&BigInt::from_bytes(&sig[32..]));
-    check_sig(&r, &s, "random message".as_bytes(), &pk);
+    let sig = run_sign(p1, p2, b"random message");
+    check_sig(&sig, b"random message", &pk);
 }
 
 #[test]
 fn twice_refresh_and_sign() {
-    // keygen
-    let (msg1p1, context1p1) = li17_p1_key_gen1().unwrap();
-
-    let (msg1p2, context1p2) = li17_p2_key_gen1(msg1p1).unwrap();
-
-    let (msg2p1, sign_context_p1) = li17_p1_key_gen2(msg1p2, context1p1).unwrap();
+    let (p1, p2) = run_keygen();
+    let pk = p1.public.clone();
 
-    let (pk, sign_context_p2) = li17_p2_key_gen2(msg2p1, context1p2).unwrap();
+    let (p1, p2) = run_refresh(p1, p2);
+    let (p1, p2) = run_refresh(p1, p2);
+    check_shares(&p1, &p2);
 
-    // refresh
+    let sig = run_sign(p1, p2, b"random message");
+    check_sig(&sig, b"random message", &pk);
+}
 
-    let (msg1p1, context1p1) = li17_p1_refresh1(sign_context_p1).unwrap();
+fn run_keygen_until_p1_msg2() -> (Li17KeyGenP1Msg2, Li17KeyGenP2Context1) {
+    let (msg1p1, context1p1) = li17_p1_key_gen1(SID).unwrap();
+    let (msg1p2, context1p2) = li17_p2_key_gen1(msg1p1, SID).unwrap();
+    let (msg2p1, _) = li17_p1_key_gen2(msg1p2, context1p1).unwrap();
+    (msg2p1, context1p2)
+}
 
-    let (msg1p2, context1p2) = li17_p2_refresh1(msg1p1, sign_context_p2).unwrap();
+#[test]
+fn keygen_rejects_foreign_proofs() {
+    // correct key proof for another Paillier key
+    let (msg2p1, context1p2) = run_keygen_until_p1_msg2();
+    let (second, _, pdl_statement, pdl_proof, dlog_proof, ek, share) = msg2p1.body;
+    let (_, key_proof_other, _, _, _, _, _) = run_keygen_until_p1_msg2().0.body;
+    let result = li17_p2_key_gen2(
+        SessionMsg::new(
+            SID,
+            (
+                second,
+                key_proof_other,
+                pdl_statement,
+                pdl_proof,
+                dlog_proof,
+                ek,
+                share,
+            ),
+        ),
+        context1p2,
+    );
+    assert_eq!(
+        result.err(),
+        Some(Error::InvalidPaillierKey {
+            round: 2,
+            party: Some(1)
+        })
+    );
+
+    // PDL proof for another encrypted share
+    let (msg2p1, context1p2) = run_keygen_until_p1_msg2();
+    let (second, key_proof, _, _, _, ek, share) = msg2p1.body;
+    let (_, _, pdl_statement_other, pdl_proof_other, dlog_proof_other, _, _) =
+        run_keygen_until_p1_msg2().0.body;
+    let result = li17_p2_key_gen2(
+        SessionMsg::new(
+            SID,
+            (
+                second,
+                key_proof,
+                pdl_statement_other,
+                pdl_proof_other,
+                dlog_proof_other,
+                ek,
+                share,
+            ),
+        ),
+        context1p2,
+    );
+    assert_eq!(
+        result.err(),
+        Some(Error::InvalidProof {
+            round: 2,
+            party: Some(1)
+        })
+    );
+
+    // whole message from another run with the same session id
+    let (_, context1p2) = run_keygen_until_p1_msg2();
+    let (msg2p1_other, _) = run_keygen_until_p1_msg2();
+    let result = li17_p2_key_gen2(msg2p1_other, context1p2);
+    assert_eq!(
+        result.err(),
+        Some(Error::CommitmentMismatch {
+            round: 2,
+            party: Some(1)
+        })
+    );
+}
 
-    let (msg2p1, sign_context_p1) = li17_p1_refresh2(msg1p2, context1p1).unwrap();
+#[test]
+fn refresh_rejects_foreign_messages() {
+    let (sign_context_p1_a, sign_context_p2_a) = run_keygen();
+    let (sign_context_p1_b, sign_context_p2_b) = run_keygen();
+
+    let (msg1p1_a, _context1p1_a) = li17_p1_refresh1(sign_context_p1_a, SID).unwrap();
+    let (_msg1p2_a, context1p2_a) = li17_p2_refresh1(msg1p1_a, sign_context_p2_a, SID).unwrap();
+
+    let (msg1p1_b, context1p1_b) = li17_p1_refresh1(sign_context_p1_b, SID).unwrap();
+    let (msg1p2_b, _context1p2_b) = li17_p2_refresh1(msg1p1_b, sign_context_p2_b, SID).unwrap();
+    let (msg2p1_b, _) = li17_p1_refresh2(msg1p2_b, context1p1_b).unwrap();
+
+    // the coin flip decommitment does not match party one's first message
+    assert_eq!(
+        li17_p2_refresh2(msg2p1_b, context1p2_a).err(),
+        Some(Error::CommitmentMismatch {
+            round: 2,
+            party: Some(1)
+        })
+    );
+}
 
-    let sign_context_p2 = li17_p2_refresh2(msg2p1, context1p2).unwrap();
+#[test]
+fn sign_rejects_tampered_ephemeral_key() {
+    let hash = Sha256::digest(b"random message").to_vec();
+
+    // public share replaced after committing to it
+    let (sign_context_p1, sign_context_p2) = run_keygen();
+    let (smsg1p2, context1p2) = li17_p2_sign1(sign_context_p2, &hash, SID).unwrap();
+    let (smsg1p1, context1p1) = li17_p1_sign1(smsg1p2, sign_context_p1, &hash, SID).unwrap();
+    let (partial_sig, mut eph_second_message) = li17_p2_sign2(smsg1p1, context1p2).unwrap().body;
+    eph_second_message.comm_witness.public_share =
+        Point::<Secp256r1>::generator() * Scalar::<Secp256r1>::random();
+    assert_eq!(
+        li17_p1_sign2(
+            SessionMsg::new(SID, (partial_sig, eph_second_message)),
+            context1p1
+        )
+        .err(),
+        Some(Error::InvalidEphemeralKey {
+            round: 2,
+            party: Some(2)
+        })
+    );
+
+    // party one holds the ephemeral commitment of another session
+    let (sign_context_p1, sign_context_p2) = run_keygen();
+    let (_, other_context_p2) = run_keygen();
+    let (_smsg1p2, context1p2) = li17_p2_sign1(sign_context_p2, &hash, SID).unwrap();
+    let (other_smsg1p2, _) = li17_p2_sign1(other_context_p2, &hash, SID).unwrap();
+    let (smsg1p1, context1p1) = li17_p1_sign1(other_smsg1p2, sign_context_p1, &hash, SID).unwrap();
+    let smsg2p2 = li17_p2_sign2(smsg1p1, context1p2).unwrap();
+    assert_eq!(
+        li17_p1_sign2(smsg2p2, context1p1).err(),
+        Some(Error::InvalidEphemeralKey {
+            round: 2,
+            party: Some(2)
+        })
+    );
+}
 
-    // refresh 2
+pub fn run_presign(session: SessionId) -> (Li17P1Presignature, Li17P2Presignature) {
+    let (pmsg1p2, context1p2) = li17_p2_presign1(session).unwrap();
+    let (pmsg1p1, context1p1) = li17_p1_presign1(pmsg1p2, session).unwrap();
+    let (pmsg2p2, presignature_p2) = li17_p2_presign2(pmsg1p1, context1p2).unwrap();
+    let presignature_p1 = li17_p1_presign2(pmsg2p2, context1p1).unwrap();
+    (presignature_p1, presignature_p2)
+}
 
-    let (msg1p1, context1p1) = li17_p1_refresh1(sign_context_p1).unwrap();
+#[test]
+fn presign_and_sign() {
+    let (sign_context_p1, sign_context_p2) = run_keygen();
+    let pk = sign_context_p1.public.clone();
+
+    // presignatures are stored in a pool until the message arrives
+    let pool_p1 = PresignPool::new(MemoryStore::default(), &[1; 32], 2);
+    let pool_p2 = PresignPool::new(MemoryStore::default(), &[2; 32], 2);
+    let session = SessionId::derive(&[b"second presignature"]);
+    for id in [SID, session].iter() {
+        let (presignature_p1, presignature_p2) = run_presign(*id);
+        pool_p1.add(presignature_p1).unwrap();
+        pool_p2.add(presignature_p2).unwrap();
+    }
 
-    let (msg1p2, context1p2) = li17_p2_refresh1(msg1p1, sign_context_p2).unwrap();
+    // the key contexts are only borrowed, they sign with presignature after presignature
+    for (id, message) in [(SID, "random message"), (session, "next message")].iter() {
+        let hash = Sha256::digest(message.as_bytes()).to_vec();
+        let presignature_p1 = pool_p1.take(id).unwrap();
+        let presignature_p2 = pool_p2.take(id).unwrap();
+        let pmsg3p2 = li17_p2_presign_sign(presignature_p2, &sign_context_p2, &hash).unwrap();
+        let sig = li17_p1_presign_sign(pmsg3p2, presignature_p1, &sign_context_p1, &hash).unwrap();
+        check_sig(&sig, message.as_bytes(), &pk);
+    }
+}
 
-    let (msg2p1, sign_context_p1) = li17_p1_refresh2(msg1p2, context1p1).unwrap();
+#[test]
+fn presign_rejects_tampered_messages() {
+    // the parties sign different messages
+    let (sign_context_p1, sign_context_p2) = run_keygen();
+    let (presignature_p1, presignature_p2) = run_presign(SID);
+    let hash = Sha256::digest(b"one message");
+    let pmsg3p2 = li17_p2_presign_sign(presignature_p2, &sign_context_p2, &hash).unwrap();
+    let other = Sha256::digest(b"another");
+    assert_eq!(
+        li17_p1_presign_sign(pmsg3p2, presignature_p1, &sign_context_p1, &other).err(),
+        Some(Error::InvalidSignature { round: 3 })
+    );
+
+    // public share replaced after committing to it
+    let (pmsg1p2, context1p2) = li17_p2_presign1(SID).unwrap();
+    let (pmsg1p1, context1p1) = li17_p1_presign1(pmsg1p2, SID).unwrap();
+    let (mut pmsg2p2, _) = li17_p2_presign2(pmsg1p1, context1p2).unwrap();
+    pmsg2p2.body.comm_witness.public_share =
+        Point::<Secp256r1>::generator() * Scalar::<Secp256r1>::random();
+    assert_eq!(
+        li17_p1_presign2(pmsg2p2, context1p1).err(),
+        Some(Error::InvalidEphemeralKey {
+            round: 2,
+            party: Some(2)
+        })
+    );
+}
 
-    let sign_context_p2 = li17_p2_refresh2(msg2p1, context1p2).unwrap();
+fn reload<T: Persist>(context: &T) -> T {
+    T::from_json(&context.to_json().unwrap()).unwrap()
+}
 
-    // sign
+#[test]
+fn repeated_signatures_have_fixed_width() {
+    let (sign_context_p1, sign_context_p2) = run_keygen();
+    let pk = sign_context_p1.public.clone();
+
+    for i in 0..2000u32 {
+        let message = i.to_be_bytes();
+        let hash = Sha256::digest(&message).to_vec();
+        let (p1, p2) = (reload(&sign_context_p1), reload(&sign_context_p2));
+
+        let (smsg1p2, context1p2) = li17_p2_sign1(p2, &hash, SID).unwrap();
+        let (smsg1p1, context1p1) = li17_p1_sign1(smsg1p2, p1, &hash, SID).unwrap();
+        let smsg2p2 = li17_p2_sign2(smsg1p1, context1p2).unwrap();
+        let sig = li17_p1_sign2(smsg2p2, context1p1).unwrap();
+
+        assert!(sig.is_low_s());
+        assert_eq!(Signature::from_bytes(&sig.to_bytes()).unwrap(), sig);
+        assert_eq!(Signature::from_der(&sig.to_der()).unwrap(), sig);
+        check_sig(&sig, &message, &pk);
+    }
+}
 
-    let mut hasher = Sha256::new();
-    hasher.update(b"random message");
-    let hash = hasher.finalize().to_vec();
+#[test]
+fn contexts_survive_persistence() {
+    let (msg1p1, context1p1) = li17_p1_key_gen1(SID).unwrap();
+    let (msg1p2, context1p2) = li17_p2_key_gen1(msg1p1, SID).unwrap();
+    let (msg2p1, sign_context_p1) = li17_p1_key_gen2(msg1p2, reload(&context1p1)).unwrap();
+    let (pk, sign_context_p2) = li17_p2_key_gen2(msg2p1, reload(&context1p2)).unwrap();
+
+    let (rmsg1p1, rcontext1p1) = li17_p1_refresh1(reload(&sign_context_p1), SID).unwrap();
+    let (rmsg1p2, rcontext1p2) = li17_p2_refresh1(rmsg1p1, reload(&sign_context_p2), SID).unwrap();
+    let (rmsg2p1, sign_context_p1) = li17_p1_refresh2(rmsg1p2, reload(&rcontext1p1)).unwrap();
+    let sign_context_p2 = li17_p2_refresh2(rmsg2p1, reload(&rcontext1p2)).unwrap();
+
+    let message = b"persisted";
+    let hash = Sha256::digest(message).to_vec();
+    let (smsg1p2, context1p2) = li17_p2_sign1(reload(&sign_context_p2), &hash, SID).unwrap();
+    let (smsg1p1, context1p1) =
+        li17_p1_sign1(smsg1p2, reload(&sign_context_p1), &hash, SID).unwrap();
+    let smsg2p2 = li17_p2_sign2(smsg1p1, reload(&context1p2)).unwrap();
+    let sig = li17_p1_sign2(smsg2p2, reload(&context1p1)).unwrap();
+    check_sig(&sig, message, &pk);
+}
 
-    let (smsg1p2, context1p2) = li17_p2_sign1(sign_context_p2, &hash).unwrap();
+#[test]
+fn persistence_rejects_foreign_envelopes() {
+    let (_, sign_context_p2) = run_keygen();
+    let envelope = sign_context_p2.to_envelope().unwrap();
+    assert_eq!(envelope.version, FORMAT_VERSION);
+
+    let json = serde_json::to_string(&envelope).unwrap();
+    assert_eq!(
+        Li17SignP1Context::from_json(&json).err(),
+        Some(Error::InvalidEncoding)
+    );
+
+    let mut other_curve = envelope.clone();
+    other_curve.curve = "secp256k1".to_string();
+    assert_eq!(
+        Li17SignP2Context::from_envelope(other_curve).err(),
+        Some(Error::InvalidEncoding)
+    );
+
+    let future = Envelope {
+        version: FORMAT_VERSION + 1,
+        ..envelope
+    };
+    assert_eq!(
+        Li17SignP2Context::from_envelope(future).err(),
+        Some(Error::UnsupportedVersion {
+            version: FORMAT_VERSION + 1
+        })
+    );
+
+    assert_eq!(
+        Li17SignP2Context::from_json("{}").err(),
+        Some(Error::InvalidEncoding)
+    );
+}
 
-    let (smsg1p1, context1p1) = li17_p1_sign1(smsg1p2, sign_context_p1, &hash).unwrap();
+fn hash32(message: &[u8]) -> [u8; 32] {
+    let mut hash = [0u8; 32];
+    hash.copy_from_slice(&Sha256::digest(message));
+    hash
+}
 
-    let smsg2p2 = li17_p2_sign2(smsg1p1, context1p2).unwrap();
+#[test]
+fn batch_sign() {
+    let (sign_context_p1, sign_context_p2) = run_keygen();
+    let messages: Vec<Vec<u8>> = (0..4u8).map(|i| vec![b'm', i]).collect();
+    let hashes: Vec<[u8; 32]> = messages.iter().map(|m| hash32(m)).collect();
+
+    // the key contexts are borrowed and sign a second batch
+    for session in [SID, SessionId::derive(&[b"second batch"])].iter() {
+        let (bmsg1p2, bcontext1p2) = li17_p2_batch_sign1(&hashes, *session).unwrap();
+        let (bmsg1p1, bcontext1p1) = li17_p1_batch_sign1(bmsg1p2, &hashes, *session).unwrap();
+        let (bmsg2p2, results) =
+            li17_p2_batch_sign2(bmsg1p1, bcontext1p2, &sign_context_p2).unwrap();
+        assert!(results.iter().all(Result::is_ok));
+        let sigs = li17_p1_batch_sign2(bmsg2p2, bcontext1p1, &sign_context_p1).unwrap();
+        assert_eq!(sigs.len(), messages.len());
+        for (sig, message) in sigs.iter().zip(messages.iter()) {
+            check_sig(sig.as_ref().unwrap(), message, &sign_context_p1.public);
+        }
+    }
+}
 
-    let sig = li17_p1_sign2(smsg2p2, context1p1).unwrap();
+#[test]
+fn batch_sign_reports_failures_per_hash() {
+    let (sign_context_p1, sign_context_p2) = run_keygen();
+    let hashes: Vec<[u8; 32]> = (0..3u8).map(|i| hash32(&[i])).collect();
+    let tampered = || Point::<Secp256r1>::generator() * Scalar::<Secp256r1>::random();
+
+    let (bmsg1p2, bcontext1p2) = li17_p2_batch_sign1(&hashes, SID).unwrap();
+    assert_eq!(
+        li17_p1_batch_sign1(bmsg1p2.clone(), &hashes[..2], SID).err(),
+        Some(Error::MalformedInput {
+            round: 1,
+            party: Some(2)
+        })
+    );
+
+    // the ephemeral key of P1 for the first hash and of P2 for the second are replaced
+    let (mut bmsg1p1, bcontext1p1) = li17_p1_batch_sign1(bmsg1p2, &hashes, SID).unwrap();
+    bmsg1p1.body[0].public_share = tampered();
+    let (mut bmsg2p2, results) =
+        li17_p2_batch_sign2(bmsg1p1, bcontext1p2, &sign_context_p2).unwrap();
+    assert_eq!(
+        results,
+        vec![
+            Err(Error::InvalidProof {
+                round: 2,
+                party: Some(1)
+            }),
+            Ok(()),
+            Ok(())
+        ]
+    );
+    bmsg2p2.body[1]
+        .as_mut()
+        .unwrap()
+        .1
+        .comm_witness
+        .public_share = tampered();
+
+    let sigs = li17_p1_batch_sign2(bmsg2p2, bcontext1p1, &sign_context_p1).unwrap();
+    assert_eq!(
+        sigs[0].as_ref().err(),
+        Some(&Error::MalformedInput {
+            round: 2,
+            party: Some(2)
+        })
+    );
+    assert_eq!(
+        sigs[1].as_ref().err(),
+        Some(&Error::InvalidEphemeralKey {
+            round: 2,
+            party: Some(2)
+        })
+    );
+    check_sig(sigs[2].as_ref().unwrap(), &[2], &sign_context_p1.public);
+}
 
-    let r = Scalar::<Secp256r1>::from(&BigInt::from_bytes(&sig[..32]));
-    let s = Scalar::<Secp256r1>::from(&BigInt::from_bytes(&sig[32..]));
-    check_sig(&r, &s, "random message".as_bytes(), &pk);
+#[test]
+fn rejects_messages_from_other_sessions() {
+    let other = SessionId::derive(&[b"other session"]);
+
+    let (msg1p1, _) = li17_p1_key_gen1(other).unwrap();
+    assert_eq!(
+        li17_p2_key_gen1(msg1p1, SID).err(),
+        Some(Error::SessionMismatch {
+            round: 1,
+            party: Some(1)
+        })
+    );
+
+    let (msg1p1, context1p1) = li17_p1_key_gen1(SID).unwrap();
+    let (msg1p2, _) = li17_p2_key_gen1(msg1p1, other).unwrap();
+    assert_eq!(
+        li17_p1_key_gen2(msg1p2, context1p1).err(),
+        Some(Error::SessionMismatch {
+            round: 2,
+            party: Some(2)
+        })
+    );
+
+    let hash = Sha256::digest(b"random message").to_vec();
+    let (sign_context_p1, sign_context_p2) = run_keygen();
+    let (smsg1p2, _) = li17_p2_sign1(reload(&sign_context_p2), &hash, other).unwrap();
+    assert_eq!(
+        li17_p1_sign1(smsg1p2, reload(&sign_context_p1), &hash, SID).err(),
+        Some(Error::SessionMismatch {
+            round: 1,
+            party: Some(2)
+        })
+    );
+
+    let (smsg1p2, _) = li17_p2_sign1(reload(&sign_context_p2), &hash, SID).unwrap();
+    let (smsg1p1, _) = li17_p1_sign1(smsg1p2, reload(&sign_context_p1), &hash, SID).unwrap();
+    let (_, other_context1p2) = li17_p2_sign1(reload(&sign_context_p2), &hash, other).unwrap();
+    assert_eq!(
+        li17_p2_sign2(smsg1p1, other_context1p2).err(),
+        Some(Error::SessionMismatch {
+            round: 2,
+            party: Some(1)
+        })
+    );
+
+    let (rmsg1p1, _) = li17_p1_refresh1(sign_context_p1, other).unwrap();
+    assert_eq!(
+        li17_p2_refresh1(rmsg1p1, sign_context_p2, SID).err(),
+        Some(Error::SessionMismatch {
+            round: 1,
+            party: Some(1)
+        })
+    );
 }
@@ -15,13 +15,64 @@ You should have received a copy of the GNU General Public License
 along with this program.  If not, see <https://www.gnu.org/licenses/>.
 */
 
+#[macro_use]
+pub mod secret;
+
+pub mod conversion;
+pub mod error;
+pub mod evidence;
+pub mod gg18_batch_sign;
 pub mod gg18_key_gen;
+pub mod gg18_key_gen_state;
+pub mod gg18_refresh;
+pub mod gg18_reshare;
 pub mod gg18_sign;
+pub mod gg18_sign_state;
+pub mod identity;
+pub mod keystore;
+pub mod li17_batch_sign;
 pub mod li17_key_gen;
 pub mod li17_refresh;
 pub mod li17_sign;
+pub mod li17_state;
+pub mod persist;
+pub mod presign_pool;
+pub mod session;
+pub mod signature;
+#[cfg(any(test, feature = "simulation"))]
+pub mod simulation;
+pub mod state_machine;
+#[cfg(any(test, feature = "transport"))]
+pub mod transport;
+pub mod wire;
+
+pub use error::Error;
+pub use evidence::Evidence;
+pub use identity::IdentityKey;
+pub use persist::Persist;
+pub use session::SessionId;
+pub use signature::Signature;
+pub use state_machine::{Msg, StateMachine};
 
+#[cfg(test)]
+mod conversion_test;
 #[cfg(test)]
 mod gg18_test;
 #[cfg(test)]
+mod keystore_test;
+#[cfg(test)]
 mod li17_test;
+#[cfg(test)]
+mod presign_pool_test;
+#[cfg(test)]
+mod secret_test;
+#[cfg(test)]
+mod signature_test;
+#[cfg(test)]
+mod simulation_test;
+#[cfg(test)]
+mod state_machine_test;
+#[cfg(test)]
+mod transport_test;
+#[cfg(test)]
+mod wire_test;
@@ -0,0 +1,75 @@
+use crate::Error;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/*
+Versioned envelope for persisting protocol contexts.
+
+Every stored context records the protocol, the curve, the kind of context and
+the format version it was written with. The payload is kept as a JSON value,
+so that contexts written by an older version of the crate can be migrated
+before they are deserialized into the current structures.
+*/
+
+pub const FORMAT_VERSION: u16 = 1;
+
+pub const CURVE: &str = "secp256r1";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Protocol {
+    Li17,
+    Gg18,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Envelope {
+    pub protocol: Protocol,
+    pub curve: String,
+    pub version: u16,
+    pub kind: String,
+    pub payload: Value,
+}
+
+pub trait Persist: Serialize + DeserializeOwned {
+    const PROTOCOL: Protocol;
+    const KIND: &'static str;
+
+    // upgrades a payload written with an older format version
+    fn migrate(version: u16, payload: Value) -> Result<Value, Error> {
+        if version != FORMAT_VERSION {
+            return Err(Error::UnsupportedVersion { version });
+        }
+        Ok(payload)
+    }
+
+    fn to_envelope(&self) -> Result<Envelope, Error> {
+        Ok(Envelope {
+            protocol: Self::PROTOCOL,
+            curve: CURVE.to_string(),
+            version: FORMAT_VERSION,
+            kind: Self::KIND.to_string(),
+            payload: serde_json::to_value(self).map_err(|_| Error::InvalidEncoding)?,
+        })
+    }
+
+    fn from_envelope(envelope: Envelope) -> Result<Self, Error> {
+        if envelope.protocol != Self::PROTOCOL
+            || envelope.curve != CURVE
+            || envelope.kind != Self::KIND
+        {
+            return Err(Error::InvalidEncoding);
+        }
+        let payload = Self::migrate(envelope.version, envelope.payload)?;
+        serde_json::from_value(payload).map_err(|_| Error::InvalidEncoding)
+    }
+
+    fn to_json(&self) -> Result<String, Error> {
+        serde_json::to_string(&self.to_envelope()?).map_err(|_| Error::InvalidEncoding)
+    }
+
+    fn from_json(json: &str) -> Result<Self, Error> {
+        let envelope: Envelope = serde_json::from_str(json).map_err(|_| Error::InvalidEncoding)?;
+        Self::from_envelope(envelope)
+    }
+}
@@ -0,0 +1,347 @@
+use crate::gg18_sign::GG18Presignature;
+use crate::keystore::{seal_entry, unseal_entry};
+use crate::li17_sign::{Li17P1Presignature, Li17P2Presignature};
+use crate::presign_pool::sealed::Stored;
+use crate::session::SessionId;
+use crate::Error;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::convert::TryFrom;
+use std::fs::{self, File, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::marker::PhantomData;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread::{self, JoinHandle};
+use zeroize::Zeroizing;
+
+/*
+Pool of presignatures.
+
+A presignature holds a nonce share, and a second signature with the same nonce
+reveals the key. PresignPool keeps a stock of presignatures of one party in a
+PresignStore and only hands one out after the store has marked it consumed.
+The mark is made atomically and durably before the presignature is decoded, so
+a crash after taking loses the presignature but never offers it again.
+
+Presignatures are identified by the session of their presigning run, which is
+the same for all signers: the coordinator picks an id from the available ones
+and every signer takes the presignature of that id from its own pool.
+
+The presignatures are generated by the caller, e.g. a presigning run with the
+other signers over the transport module. refill and spawn_refill call it until
+the pool holds its target depth.
+
+Presignatures are not serializable outside the crate, the pool is the only way
+to store one. It seals every entry with the keystore cipher under the key of
+the pool before the store sees it, so the store only holds ciphertexts bound
+to their id.
+*/
+
+pub trait Presignature: Stored + Send + 'static {
+    fn session(&self) -> SessionId;
+}
+
+pub(crate) mod sealed {
+    use crate::Error;
+
+    // the encoding of a presignature, not reachable from outside the crate
+    pub trait Stored: Sized {
+        fn to_entry(&self) -> Result<String, Error>;
+        fn from_entry(entry: &str) -> Result<Self, Error>;
+    }
+}
+
+macro_rules! impl_presignature {
+    ($name:ident) => {
+        impl Presignature for $name {
+            fn session(&self) -> SessionId {
+                $name::session(self)
+            }
+        }
+
+        impl Stored for $name {
+            fn to_entry(&self) -> Result<String, Error> {
+                self.to_json()
+            }
+
+            fn from_entry(entry: &str) -> Result<Self, Error> {
+                $name::from_json(entry)
+            }
+        }
+    };
+}
+
+impl_presignature!(GG18Presignature);
+impl_presignature!(Li17P1Presignature);
+impl_presignature!(Li17P2Presignature);
+
+// entries are sealed presignatures, keyed by session
+pub trait PresignStore: Send {
+    // fails if an entry with the id was ever stored, used or not
+    fn insert(&mut self, id: SessionId, entry: String) -> Result<(), Error>;
+
+    fn unused(&self) -> Result<Vec<SessionId>, Error>;
+
+    /*
+    Marks an unused entry as consumed and returns it, None if there is no unused
+    entry with the id. The mark must be atomic and durable before the entry is
+    returned: a consumed entry must never be returned again, even after a crash.
+    */
+    fn consume(&mut self, id: &SessionId) -> Result<Option<String>, Error>;
+}
+
+// keeps the entries in memory, they do not survive a restart
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    entries: BTreeMap<SessionId, String>,
+    consumed: BTreeSet<SessionId>,
+}
+
+impl PresignStore for MemoryStore {
+    fn insert(&mut self, id: SessionId, entry: String) -> Result<(), Error> {
+        if self.entries.contains_key(&id) || self.consumed.contains(&id) {
+            return Err(Error::Storage);
+        }
+        self.entries.insert(id, entry);
+        Ok(())
+    }
+
+    fn unused(&self) -> Result<Vec<SessionId>, Error> {
+        Ok(self.entries.keys().copied().collect())
+    }
+
+    fn consume(&mut self, id: &SessionId) -> Result<Option<String>, Error> {
+        let entry = self.entries.remove(id);
+        if entry.is_some() {
+            self.consumed.insert(*id);
+        }
+        Ok(entry)
+    }
+}
+
+/*
+Keeps every entry in its own file <session id>.unused of a directory.
+Consuming renames the file to <session id>.consumed and syncs the directory
+before the entry is read; the rename is atomic, so of two concurrent consumers
+only one succeeds. The consumed file is emptied afterwards and kept, so that a
+stale copy of the entry cannot be inserted again. A crash between the rename
+and emptying leaves the entry in the consumed file, where it is never read.
+
+Emptying the consumed file does not securely erase the entry: the filesystem,
+its journal or the disk may keep the old blocks. The entries are sealed by the
+pool, such a leftover is only readable with the key of the pool.
+*/
+#[derive(Debug)]
+pub struct FileStore {
+    dir: PathBuf,
+}
+
+const UNUSED: &str = "unused";
+const CONSUMED: &str = "consumed";
+const PARTIAL: &str = "partial";
+
+impl FileStore {
+    pub fn open<P: Into<PathBuf>>(dir: P) -> Result<Self, Error> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir).map_err(|_| Error::Storage)?;
+        Ok(FileStore { dir })
+    }
+
+    fn path(&self, id: &SessionId, state: &str) -> PathBuf {
+        self.dir
+            .join(format!("{}.{}", hex::encode(id.as_bytes()), state))
+    }
+
+    fn sync_dir(&self) -> Result<(), Error> {
+        File::open(&self.dir)
+            .and_then(|dir| dir.sync_all())
+            .map_err(|_| Error::Storage)
+    }
+}
+
+impl PresignStore for FileStore {
+    fn insert(&mut self, id: SessionId, entry: String) -> Result<(), Error> {
+        let unused = self.path(&id, UNUSED);
+        if self.path(&id, CONSUMED).exists() {
+            return Err(Error::Storage);
+        }
+
+        /*
+        The entry is written and synced under a partial name, then linked to its
+        final name, so it only appears there once it is complete. Creating either
+        name fails if it exists: of two concurrent inserts of an id only one
+        succeeds, and an entry is never overwritten.
+        */
+        let partial = self.path(&id, PARTIAL);
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&partial)
+            .map_err(|_| Error::Storage)?;
+        let linked = file
+            .write_all(entry.as_bytes())
+            .and_then(|_| file.sync_all())
+            .and_then(|_| fs::hard_link(&partial, &unused));
+        let removed = fs::remove_file(&partial);
+        linked.and(removed).map_err(|_| Error::Storage)?;
+
+        // the id was consumed between the check and the link
+        if self.path(&id, CONSUMED).exists() {
+            let _ = fs::remove_file(&unused);
+            return Err(Error::Storage);
+        }
+        self.sync_dir()
+    }
+
+    fn unused(&self) -> Result<Vec<SessionId>, Error> {
+        let mut ids = Vec::new();
+        for file in fs::read_dir(&self.dir).map_err(|_| Error::Storage)? {
+            let path = file.map_err(|_| Error::Storage)?.path();
+            if path.extension().and_then(|e| e.to_str()) != Some(UNUSED) {
+                continue;
+            }
+            let id = path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .and_then(|stem| hex::decode(stem).ok())
+                .and_then(|bytes| <[u8; 32]>::try_from(bytes.as_slice()).ok())
+                .ok_or(Error::Storage)?;
+            ids.push(SessionId::new(id));
+        }
+        ids.sort_unstable();
+        Ok(ids)
+    }
+
+    fn consume(&mut self, id: &SessionId) -> Result<Option<String>, Error> {
+        let consumed = self.path(id, CONSUMED);
+        match fs::rename(self.path(id, UNUSED), &consumed) {
+            Ok(()) => {}
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(_) => return Err(Error::Storage),
+        }
+        self.sync_dir()?;
+
+        let entry = fs::read_to_string(&consumed).map_err(|_| Error::Storage)?;
+        File::create(&consumed)
+            .and_then(|file| file.sync_all())
+            .map_err(|_| Error::Storage)?;
+        Ok(Some(entry))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PresignMetrics {
+    pub available: usize,
+    pub target_depth: usize,
+    // counted since the pool was created
+    pub generated: u64,
+    pub consumed: u64,
+    pub failed: u64,
+}
+
+pub struct PresignPool<T, S> {
+    store: Mutex<S>,
+    key: Zeroizing<[u8; 32]>,
+    target_depth: usize,
+    generated: AtomicU64,
+    consumed: AtomicU64,
+    failed: AtomicU64,
+    presignature: PhantomData<fn() -> T>,
+}
+
+impl<T: Presignature, S: PresignStore> PresignPool<T, S> {
+    // key seals the entries in the store, it must be the same when the store is reopened
+    pub fn new(store: S, key: &[u8; 32], target_depth: usize) -> Self {
+        PresignPool {
+            store: Mutex::new(store),
+            key: Zeroizing::new(*key),
+            target_depth,
+            generated: AtomicU64::new(0),
+            consumed: AtomicU64::new(0),
+            failed: AtomicU64::new(0),
+            presignature: PhantomData,
+        }
+    }
+
+    // the ids of the presignatures that can be taken
+    pub fn available(&self) -> Result<Vec<SessionId>, Error> {
+        self.store()?.unused()
+    }
+
+    pub fn add(&self, presignature: T) -> Result<(), Error> {
+        let id = presignature.session();
+        let entry = seal_entry(&self.key, &id, &Zeroizing::new(presignature.to_entry()?))?;
+        self.store()?.insert(id, entry)?;
+        self.generated.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /*
+    Takes the presignature with the given id out of the pool. It is consumed
+    even if this returns an error afterwards or the signing run fails.
+    */
+    pub fn take(&self, id: &SessionId) -> Result<T, Error> {
+        let entry = self
+            .store()?
+            .consume(id)?
+            .ok_or(Error::PresignatureUnavailable)?;
+        self.consumed.fetch_add(1, Ordering::Relaxed);
+
+        let presignature = T::from_entry(&unseal_entry(&self.key, id, &entry)?)?;
+        if &presignature.session() != id {
+            return Err(Error::InvalidEncoding);
+        }
+        Ok(presignature)
+    }
+
+    /*
+    Calls presign until the pool holds target_depth presignatures, returns how
+    many were added. Stops at the first error. The store is not locked while
+    presign runs, concurrent refills may overshoot the depth.
+    */
+    pub fn refill<F>(&self, mut presign: F) -> Result<usize, Error>
+    where
+        F: FnMut() -> Result<T, Error>,
+    {
+        let mut added = 0;
+        while self.available()?.len() < self.target_depth {
+            match presign() {
+                Ok(presignature) => self.add(presignature)?,
+                Err(e) => {
+                    self.failed.fetch_add(1, Ordering::Relaxed);
+                    return Err(e);
+                }
+            }
+            added += 1;
+        }
+        Ok(added)
+    }
+
+    pub fn metrics(&self) -> Result<PresignMetrics, Error> {
+        Ok(PresignMetrics {
+            available: self.available()?.len(),
+            target_depth: self.target_depth,
+            generated: self.generated.load(Ordering::Relaxed),
+            consumed: self.consumed.load(Ordering::Relaxed),
+            failed: self.failed.load(Ordering::Relaxed),
+        })
+    }
+
+    // a panic while the store was locked leaves it in an unknown state
+    fn store(&self) -> Result<MutexGuard<'_, S>, Error> {
+        self.store.lock().map_err(|_| Error::Storage)
+    }
+}
+
+impl<T: Presignature, S: PresignStore + 'static> PresignPool<T, S> {
+    // runs refill on a background thread
+    pub fn spawn_refill<F>(self: &Arc<Self>, presign: F) -> JoinHandle<Result<usize, Error>>
+    where
+        F: FnMut() -> Result<T, Error> + Send + 'static,
+    {
+        let pool = Arc::clone(self);
+        thread::spawn(move || pool.refill(presign))
+    }
+}
@@ -0,0 +1,196 @@
+use crate::gg18_sign::{gg18_presign_sign, GG18Presignature};
+use crate::gg18_test::{check_sig, keygen, presign, sign_from_round5};
+use crate::persist::{Persist, Protocol};
+use crate::presign_pool::sealed::Stored;
+use crate::presign_pool::{
+    FileStore, MemoryStore, PresignMetrics, PresignPool, PresignStore, Presignature,
+};
+use crate::secret::CloneSecret;
+use crate::session::SessionId;
+use crate::Error;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+use std::thread;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Nonce {
+    session: SessionId,
+}
+
+impl Persist for Nonce {
+    const PROTOCOL: Protocol = Protocol::Gg18;
+    const KIND: &'static str = "test_nonce";
+}
+
+impl Presignature for Nonce {
+    fn session(&self) -> SessionId {
+        self.session
+    }
+}
+
+impl Stored for Nonce {
+    fn to_entry(&self) -> Result<String, Error> {
+        self.to_json()
+    }
+
+    fn from_entry(entry: &str) -> Result<Self, Error> {
+        Nonce::from_json(entry)
+    }
+}
+
+const KEY: [u8; 32] = [7; 32];
+
+fn nonces() -> impl FnMut() -> Result<Nonce, Error> {
+    let mut counter = 0u64;
+    move || {
+        counter += 1;
+        Ok(Nonce {
+            session: SessionId::derive(&[b"nonce", &counter.to_be_bytes()]),
+        })
+    }
+}
+
+#[test]
+fn pool_signs_with_gg18_presignatures() {
+    let contexts = keygen(3, 2);
+    let pk = contexts[0].pk.clone();
+    let indices = [0u16, 2];
+    let pools: Vec<PresignPool<GG18Presignature, MemoryStore>> = (0..2)
+        .map(|_| PresignPool::new(MemoryStore::default(), &KEY, 2))
+        .collect();
+
+    for run in 0..2u8 {
+        let session = SessionId::derive(&[b"pool", &[run]]);
+        let signers = vec![contexts[0].clone_secret(), contexts[2].clone_secret()];
+        for (pool, presignature) in pools.iter().zip(presign(signers, session)) {
+            pool.add(presignature).unwrap();
+        }
+    }
+
+    let id = pools[0].available().unwrap()[0];
+    let message = b"pooled";
+    let hash = Sha256::digest(message).to_vec();
+    let (msg5, ctx5): (Vec<_>, Vec<_>) = pools
+        .iter()
+        .map(|pool| gg18_presign_sign(pool.take(&id).unwrap(), hash.clone()).unwrap())
+        .unzip();
+    for sig in sign_from_round5(&indices, &msg5, ctx5) {
+        check_sig(&sig, message, &pk);
+    }
+
+    assert_eq!(
+        pools[0].take(&id).err(),
+        Some(Error::PresignatureUnavailable)
+    );
+    assert_eq!(pools[1].available().unwrap().len(), 1);
+}
+
+#[test]
+fn pool_refills_to_target_depth() {
+    let pool: Arc<PresignPool<Nonce, MemoryStore>> =
+        Arc::new(PresignPool::new(MemoryStore::default(), &KEY, 3));
+    assert_eq!(pool.spawn_refill(nonces()).join().unwrap(), Ok(3));
+    assert_eq!(pool.refill(nonces()), Ok(0));
+
+    let id = pool.available().unwrap()[1];
+    assert_eq!(pool.take(&id).unwrap().session, id);
+    assert_eq!(pool.take(&id).err(), Some(Error::PresignatureUnavailable));
+
+    // a presigning run that fails is counted and stops the refill
+    assert_eq!(pool.refill(|| Err(Error::Transport)), Err(Error::Transport));
+    assert_eq!(
+        pool.metrics().unwrap(),
+        PresignMetrics {
+            available: 2,
+            target_depth: 3,
+            generated: 3,
+            consumed: 1,
+            failed: 1,
+        }
+    );
+}
+
+#[test]
+fn file_store_never_returns_an_entry_twice() {
+    let dir = std::env::temp_dir().join(format!("mpecdsa-presign-{}", std::process::id()));
+    let id = SessionId::derive(&[b"file store"]);
+    let other = SessionId::derive(&[b"other entry"]);
+
+    let mut store = FileStore::open(&dir).unwrap();
+    store.insert(id, "entry".to_string()).unwrap();
+    store.insert(other, "other".to_string()).unwrap();
+    assert_eq!(store.insert(id, "copy".to_string()), Err(Error::Storage));
+    assert_eq!(store.consume(&id), Ok(Some("entry".to_string())));
+    assert_eq!(store.consume(&id), Ok(None));
+
+    // a restarted store keeps the consumed mark
+    let mut store = FileStore::open(&dir).unwrap();
+    assert_eq!(store.unused(), Ok(vec![other]));
+    assert_eq!(store.consume(&id), Ok(None));
+    assert_eq!(store.insert(id, "entry".to_string()), Err(Error::Storage));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn file_store_inserts_an_id_once() {
+    let dir = std::env::temp_dir().join(format!("mpecdsa-insert-{}", std::process::id()));
+    let id = SessionId::derive(&[b"concurrent insert"]);
+    FileStore::open(&dir).unwrap();
+
+    let inserts: Vec<_> = (0..8)
+        .map(|i| {
+            let dir = dir.clone();
+            thread::spawn(move || {
+                let mut store = FileStore::open(&dir).unwrap();
+                store.insert(id, format!("entry {}", i)).map(|_| i)
+            })
+        })
+        .collect();
+    let inserted: Vec<_> = inserts
+        .into_iter()
+        .filter_map(|insert| insert.join().unwrap().ok())
+        .collect();
+    assert_eq!(inserted.len(), 1);
+
+    let mut store = FileStore::open(&dir).unwrap();
+    assert_eq!(store.unused(), Ok(vec![id]));
+    assert_eq!(
+        store.consume(&id),
+        Ok(Some(format!("entry {}", inserted[0])))
+    );
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn pool_seals_its_entries() {
+    let dir = std::env::temp_dir().join(format!("mpecdsa-sealed-{}", std::process::id()));
+    let nonce = Nonce {
+        session: SessionId::derive(&[b"sealed"]),
+    };
+    let id = nonce.session;
+    let pool = PresignPool::new(FileStore::open(&dir).unwrap(), &KEY, 1);
+    pool.add(nonce).unwrap();
+
+    // the store only sees the ciphertext
+    let file = dir.join(format!("{}.unused", hex::encode(id.as_bytes())));
+    let entry = std::fs::read_to_string(&file).unwrap();
+    assert!(!entry.contains("test_nonce"));
+
+    // another key cannot open it, and the entry is spent anyway
+    let other: PresignPool<Nonce, _> =
+        PresignPool::new(FileStore::open(&dir).unwrap(), &[8; 32], 1);
+    assert_eq!(other.take(&id).err(), Some(Error::DecryptionFailed));
+    assert_eq!(pool.take(&id).err(), Some(Error::PresignatureUnavailable));
+
+    // nor can an entry be moved to another id
+    let mut store = MemoryStore::default();
+    let moved = SessionId::derive(&[b"moved"]);
+    store.insert(moved, entry).unwrap();
+    let pool: PresignPool<Nonce, _> = PresignPool::new(store, &KEY, 1);
+    assert_eq!(pool.take(&moved).err(), Some(Error::DecryptionFailed));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
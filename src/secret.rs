@@ -0,0 +1,255 @@
+use curv::arithmetic::traits::*;
+use curv::elliptic::curves::{p256::Secp256r1, Scalar};
+use curv::BigInt;
+use multi_party_ecdsa::protocols::multi_party_ecdsa::gg_2018::party_i::{
+    Keys, LocalSignature, SharedKeys, SignKeys,
+};
+use multi_party_ecdsa::protocols::two_party_ecdsa::lindell_2017::{party_one, party_two};
+use paillier::DecryptionKey;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::Value;
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+use zeroize::Zeroize;
+
+/*
+Secret values held in protocol contexts.
+
+The wrapped value is wiped with Wipe when it is dropped and is redacted in Debug
+output. Secret does not implement Clone, contexts holding secrets can only be
+copied with an explicit clone_secret call.
+
+Not every value can be wiped in place: the upstream Li17 key types keep their
+secrets in private fields and are wiped through serde, see their Wipe impls
+below. Li17 contexts keep the shares x1 and x2 in crate-owned fields to keep
+such values out of them where possible.
+*/
+pub struct Secret<T: Wipe>(T);
+
+impl<T: Wipe> Secret<T> {
+    pub fn new(value: T) -> Self {
+        Secret(value)
+    }
+}
+
+impl<T: Wipe> Deref for Secret<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T: Wipe> DerefMut for Secret<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+}
+
+impl<T: Wipe> Drop for Secret<T> {
+    fn drop(&mut self) {
+        self.0.wipe();
+    }
+}
+
+impl<T: Wipe> fmt::Debug for Secret<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("[REDACTED]")
+    }
+}
+
+impl<T: Wipe + Serialize> Serialize for Secret<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.0.serialize(serializer)
+    }
+}
+
+impl<'de, T: Wipe + Deserialize<'de>> Deserialize<'de> for Secret<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        T::deserialize(deserializer).map(Secret)
+    }
+}
+
+pub trait CloneSecret {
+    fn clone_secret(&self) -> Self;
+}
+
+impl<T: Clone> CloneSecret for T {
+    fn clone_secret(&self) -> Self {
+        self.clone()
+    }
+}
+
+impl<T: Wipe + Clone> CloneSecret for Secret<T> {
+    fn clone_secret(&self) -> Self {
+        Secret(self.0.clone())
+    }
+}
+
+// implements CloneSecret for a context by cloning it field by field
+macro_rules! impl_clone_secret {
+    ($name:ident { $($field:ident),* $(,)? }) => {
+        impl crate::secret::CloneSecret for $name {
+            fn clone_secret(&self) -> Self {
+                $name {
+                    $($field: crate::secret::CloneSecret::clone_secret(&self.$field),)*
+                }
+            }
+        }
+    };
+}
+
+pub trait Wipe {
+    fn wipe(&mut self);
+}
+
+impl Wipe for Scalar<Secp256r1> {
+    fn wipe(&mut self) {
+        *self = Scalar::zero();
+    }
+}
+
+impl Wipe for BigInt {
+    fn wipe(&mut self) {
+        self.zeroize();
+    }
+}
+
+impl<T: Wipe> Wipe for Vec<T> {
+    fn wipe(&mut self) {
+        for x in self.iter_mut() {
+            x.wipe();
+        }
+    }
+}
+
+impl Wipe for Keys {
+    fn wipe(&mut self) {
+        self.u_i.wipe();
+        self.dk.p.wipe();
+        self.dk.q.wipe();
+    }
+}
+
+impl Wipe for DecryptionKey {
+    fn wipe(&mut self) {
+        self.p.wipe();
+        self.q.wipe();
+    }
+}
+
+impl Wipe for SharedKeys {
+    fn wipe(&mut self) {
+        self.x_i.wipe();
+    }
+}
+
+impl Wipe for SignKeys {
+    fn wipe(&mut self) {
+        self.w_i.wipe();
+        self.k_i.wipe();
+        self.gamma_i.wipe();
+    }
+}
+
+impl Wipe for LocalSignature {
+    fn wipe(&mut self) {
+        self.l_i.wipe();
+        self.rho_i.wipe();
+        self.s_i.wipe();
+    }
+}
+
+/*
+The upstream Li17 key types keep their secrets in private fields and offer no
+way to clear them. They are wiped by overwriting the value with a copy whose
+secret fields are zero, built through serde: the old value is dropped and its
+scalars are zeroized on drop, as in Wipe for Scalar. The serialized copies are
+zeroized as well.
+
+The BigInt fields of Party1Private, the Paillier key and c_key_randomness, are
+not zeroized on drop. They are replaced by zero but the dropped old values are
+not wiped, this remains a known gap.
+*/
+fn overwrite<T: Serialize + DeserializeOwned>(value: &mut T, zeros: Vec<(&str, Value)>) {
+    let mut encoded = serde_json::to_value(&*value).unwrap_or(Value::Null);
+    if let Value::Object(fields) = &mut encoded {
+        for (name, zero) in zeros {
+            if let Some(mut old) = fields.insert(name.to_string(), zero) {
+                zeroize_json(&mut old);
+            }
+        }
+    }
+    if let Ok(zeroed) = serde_json::from_value(encoded) {
+        *value = zeroed;
+    }
+}
+
+fn zeroize_json(value: &mut Value) {
+    match value {
+        Value::String(s) => s.zeroize(),
+        Value::Number(n) => *n = 0.into(),
+        Value::Array(items) => items.iter_mut().for_each(zeroize_json),
+        Value::Object(fields) => fields.values_mut().for_each(zeroize_json),
+        _ => {}
+    }
+}
+
+fn zero_scalar() -> Value {
+    serde_json::to_value(Scalar::<Secp256r1>::zero()).unwrap_or(Value::Null)
+}
+
+fn zero_bigint() -> Value {
+    serde_json::to_value(BigInt::zero()).unwrap_or(Value::Null)
+}
+
+impl Wipe for party_one::EcKeyPair {
+    fn wipe(&mut self) {
+        overwrite(self, vec![("secret_share", zero_scalar())]);
+    }
+}
+
+impl Wipe for party_one::EphEcKeyPair {
+    fn wipe(&mut self) {
+        overwrite(self, vec![("secret_share", zero_scalar())]);
+    }
+}
+
+impl Wipe for party_one::Party1Private {
+    fn wipe(&mut self) {
+        let zero_dk = DecryptionKey {
+            p: BigInt::zero(),
+            q: BigInt::zero(),
+        };
+        overwrite(
+            self,
+            vec![
+                ("x1", zero_scalar()),
+                (
+                    "paillier_priv",
+                    serde_json::to_value(zero_dk).unwrap_or(Value::Null),
+                ),
+                ("c_key_randomness", zero_bigint()),
+            ],
+        );
+    }
+}
+
+impl Wipe for party_two::EcKeyPair {
+    fn wipe(&mut self) {
+        overwrite(self, vec![("secret_share", zero_scalar())]);
+    }
+}
+
+impl Wipe for party_two::EphEcKeyPair {
+    fn wipe(&mut self) {
+        overwrite(self, vec![("secret_share", zero_scalar())]);
+    }
+}
+
+impl Wipe for party_two::Party2Private {
+    fn wipe(&mut self) {
+        overwrite(self, vec![("x2", zero_scalar())]);
+    }
+}
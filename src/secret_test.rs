@@ -0,0 +1,114 @@
+use crate::gg18_test::keygen;
+use crate::li17_test::run_keygen;
+use crate::secret::{CloneSecret, Secret, Wipe};
+use curv::arithmetic::traits::*;
+use curv::elliptic::curves::{p256::Secp256r1, Scalar};
+use curv::BigInt;
+use multi_party_ecdsa::protocols::two_party_ecdsa::lindell_2017::{party_one, party_two};
+use std::cell::Cell;
+use std::rc::Rc;
+
+#[test]
+fn secrets_are_redacted() {
+    let scalar = Scalar::<Secp256r1>::random();
+    let secret = Secret::new(scalar.clone());
+    assert_eq!(format!("{:?}", secret), "[REDACTED]");
+
+    let contexts = keygen(2, 2);
+    let debug = format!("{:?}", contexts[0]);
+    let x_i = contexts[0].shared_keys.x_i.to_bigint().to_hex();
+    let u_i = contexts[0].party_keys.u_i.to_bigint().to_hex();
+    assert!(!debug.contains(&x_i));
+    assert!(!debug.contains(&u_i));
+    assert!(debug.contains("[REDACTED]"));
+}
+
+#[test]
+fn secrets_are_wiped() {
+    let mut scalar = Scalar::<Secp256r1>::random();
+    scalar.wipe();
+    assert!(scalar.is_zero());
+
+    let mut shares = vec![Scalar::<Secp256r1>::random(), Scalar::random()];
+    shares.wipe();
+    assert!(shares.iter().all(|s| s.is_zero()));
+
+    let mut n = BigInt::from(12345u32);
+    n.wipe();
+    assert_eq!(n, BigInt::zero());
+
+    let contexts = keygen(2, 2);
+    let mut party_keys = contexts[0].party_keys.clone_secret();
+    party_keys.wipe();
+    assert!(party_keys.u_i.is_zero());
+    assert_eq!(party_keys.dk.p, BigInt::zero());
+    assert_eq!(party_keys.dk.q, BigInt::zero());
+}
+
+#[test]
+fn li17_secrets_are_wiped() {
+    let zero = serde_json::to_value(Scalar::<Secp256r1>::zero()).unwrap();
+    let zero_bigint = serde_json::to_value(BigInt::zero()).unwrap();
+    let (sign_context_p1, sign_context_p2) = run_keygen();
+
+    let mut p1_private = sign_context_p1.p1_private.clone_secret();
+    p1_private.wipe();
+    let json = serde_json::to_value(&*p1_private).unwrap();
+    assert_eq!(json["x1"], zero);
+    assert_eq!(json["paillier_priv"]["p"], zero_bigint);
+    assert_eq!(json["paillier_priv"]["q"], zero_bigint);
+    assert_eq!(json["c_key_randomness"], zero_bigint);
+
+    let mut p2_private = sign_context_p2.p2_private.clone_secret();
+    p2_private.wipe();
+    assert_eq!(serde_json::to_value(&*p2_private).unwrap()["x2"], zero);
+
+    let (_, _, mut p2_eph_ec_key_pair) = party_two::EphKeyGenFirstMsg::create_commitments();
+    let public_share = p2_eph_ec_key_pair.public_share.clone();
+    p2_eph_ec_key_pair.wipe();
+    assert_eq!(p2_eph_ec_key_pair.public_share, public_share);
+    let json = serde_json::to_value(&p2_eph_ec_key_pair).unwrap();
+    assert_eq!(json["secret_share"], zero);
+
+    let (_, mut p1_eph_ec_key_pair) = party_one::EphKeyGenFirstMsg::create();
+    p1_eph_ec_key_pair.wipe();
+    let json = serde_json::to_value(&p1_eph_ec_key_pair).unwrap();
+    assert_eq!(json["secret_share"], zero);
+}
+
+#[test]
+fn clones_are_explicit() {
+    let contexts = keygen(2, 2);
+    let copy = contexts[1].clone_secret();
+    assert_eq!(copy.shared_keys.x_i, contexts[1].shared_keys.x_i);
+    assert_eq!(copy.party_keys.u_i, contexts[1].party_keys.u_i);
+    assert_eq!(copy.pk, contexts[1].pk);
+
+    // persisted Li17 shares keep their secrets
+    let (sign_context_p1, _) = run_keygen();
+    let json = serde_json::to_string(&sign_context_p1).unwrap();
+    assert!(!json.contains("REDACTED"));
+}
+
+// records whether it was wiped
+struct Probe(Rc<Cell<bool>>);
+
+impl Wipe for Probe {
+    fn wipe(&mut self) {
+        self.0.set(true);
+    }
+}
+
+#[test]
+fn secrets_are_wiped_on_drop() {
+    let wiped = Rc::new(Cell::new(false));
+    let secret = Secret::new(Probe(Rc::clone(&wiped)));
+    assert!(!wiped.get());
+    drop(secret);
+    assert!(wiped.get());
+
+    // also when the secret is dropped as part of a context
+    let wiped = Rc::new(Cell::new(false));
+    drop(vec![Secret::new(Probe(Rc::clone(&wiped)))]);
+    assert!(wiped.get());
+}
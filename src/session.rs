@@ -0,0 +1,266 @@
+use crate::error::check_senders;
+use crate::identity::{verify_identity, IdentityKey};
+use crate::signature::Signature;
+use crate::wire::{self, MessageKind, Reader, Wire};
+use crate::Error;
+use curv::elliptic::curves::{p256::Secp256r1, Point};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fmt;
+
+/*
+Session identifiers.
+
+Every key generation, refresh and signing run is started with a session id
+that all participants agree on, e.g. chosen by the orchestrator or derived
+from the run parameters with SessionId::derive. Every round message carries
+the id of the run that produced it and every round rejects messages from
+other runs with Error::SessionMismatch.
+
+The upstream commitments and zero-knowledge proofs do not take a transcript
+prefix, so the id is bound to the messages rather than mixed into the proofs.
+This makes cross-session mixups fail loudly, it is not a defence against a
+participant who rewraps a replayed message.
+*/
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct SessionId([u8; 32]);
+
+impl SessionId {
+    pub const fn new(bytes: [u8; 32]) -> Self {
+        SessionId(bytes)
+    }
+
+    // hashes length-prefixed inputs, so that ["ab", "c"] and ["a", "bc"] differ
+    pub fn derive(inputs: &[&[u8]]) -> Self {
+        let mut hasher = Sha256::new();
+        hasher.update(b"mpecdsa session id");
+        for input in inputs {
+            hasher.update(&(input.len() as u64).to_be_bytes());
+            hasher.update(input);
+        }
+        let mut bytes = [0u8; 32];
+        bytes.copy_from_slice(&hasher.finalize());
+        SessionId(bytes)
+    }
+
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+}
+
+impl Wire for SessionId {
+    fn write(&self, out: &mut Vec<u8>) -> Result<(), Error> {
+        self.0.write(out)
+    }
+
+    fn read(input: &mut Reader<'_>) -> Result<Self, Error> {
+        Ok(SessionId(Wire::read(input)?))
+    }
+}
+
+impl fmt::Debug for SessionId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SessionId({})", hex::encode(&self.0))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SessionMsg<T> {
+    pub session: SessionId,
+    pub body: T,
+}
+
+impl<T> SessionMsg<T> {
+    pub fn new(session: SessionId, body: T) -> Self {
+        SessionMsg { session, body }
+    }
+
+    pub(crate) fn open(
+        self,
+        session: &SessionId,
+        round: u16,
+        party: Option<u16>,
+    ) -> Result<T, Error> {
+        if &self.session != session {
+            return Err(Error::SessionMismatch { round, party });
+        }
+        Ok(self.body)
+    }
+}
+
+impl<T: Wire> Wire for SessionMsg<T> {
+    fn write(&self, out: &mut Vec<u8>) -> Result<(), Error> {
+        self.session.write(out)?;
+        self.body.write(out)
+    }
+
+    fn read(input: &mut Reader<'_>) -> Result<Self, Error> {
+        Ok(SessionMsg {
+            session: Wire::read(input)?,
+            body: Wire::read(input)?,
+        })
+    }
+}
+
+// opens the messages of exactly the given senders and returns them in that order
+pub(crate) fn open_all<T>(
+    session: &SessionId,
+    round: u16,
+    mut messages: BTreeMap<u16, SessionMsg<T>>,
+    senders: &[u16],
+) -> Result<Vec<T>, Error> {
+    check_senders(round, &messages, senders)?;
+    let mut opened = Vec::with_capacity(senders.len());
+    for &j in senders {
+        if let Some(m) = messages.remove(&j) {
+            opened.push(m.open(session, round, Some(j))?);
+        }
+    }
+    Ok(opened)
+}
+
+/*
+GG18 round messages, signed with the identity key of their sender.
+
+The signature covers the session, the kind of the message, its sender and, for
+P2P messages, its receiver, the wire encoding of the body and a binding: a
+value the sender derived from earlier rounds that the receiver derives as well,
+e.g. R for the proofs of signing round 6. A receiver that derived a different
+value sees an invalid signature. Signed messages are what Evidence records.
+*/
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SignedMsg<T> {
+    pub session: SessionId,
+    pub body: T,
+    pub signature: Signature,
+}
+
+impl<T: Wire> Wire for SignedMsg<T> {
+    fn write(&self, out: &mut Vec<u8>) -> Result<(), Error> {
+        self.session.write(out)?;
+        self.body.write(out)?;
+        self.signature.write(out)
+    }
+
+    fn read(input: &mut Reader<'_>) -> Result<Self, Error> {
+        Ok(SignedMsg {
+            session: Wire::read(input)?,
+            body: Wire::read(input)?,
+            signature: Wire::read(input)?,
+        })
+    }
+}
+
+// what a signature covers besides the session and the body
+pub(crate) struct Envelope<'a> {
+    pub kind: MessageKind,
+    pub sender: u16,
+    pub receiver: Option<u16>,
+    pub binding: &'a [u8],
+}
+
+impl Envelope<'static> {
+    pub(crate) fn broadcast(kind: MessageKind, sender: u16) -> Self {
+        Envelope {
+            kind,
+            sender,
+            receiver: None,
+            binding: &[],
+        }
+    }
+}
+
+impl<T: Wire> SignedMsg<T> {
+    pub(crate) fn sign(
+        session: SessionId,
+        body: T,
+        identity: &IdentityKey,
+        envelope: &Envelope,
+    ) -> Result<Self, Error> {
+        let signature = identity.sign(&signed_bytes(&session, &body, envelope)?)?;
+        Ok(SignedMsg {
+            session,
+            body,
+            signature,
+        })
+    }
+
+    pub(crate) fn verify(&self, identity: &Point<Secp256r1>, envelope: &Envelope) -> bool {
+        match signed_bytes(&self.session, &self.body, envelope) {
+            Ok(bytes) => verify_identity(identity, &bytes, &self.signature),
+            Err(_) => false,
+        }
+    }
+
+    // checks the session and the signature of a message from envelope.sender
+    pub(crate) fn check(
+        &self,
+        session: &SessionId,
+        identity: Option<&Point<Secp256r1>>,
+        envelope: &Envelope,
+        round: u16,
+    ) -> Result<(), Error> {
+        let party = Some(envelope.sender);
+        if &self.session != session {
+            return Err(Error::SessionMismatch { round, party });
+        }
+        if !identity.map_or(false, |identity| self.verify(identity, envelope)) {
+            return Err(Error::InvalidIdentitySignature { round, party });
+        }
+        Ok(())
+    }
+}
+
+fn signed_bytes<T: Wire>(
+    session: &SessionId,
+    body: &T,
+    envelope: &Envelope,
+) -> Result<Vec<u8>, Error> {
+    let mut bytes = b"mpecdsa gg18 message".to_vec();
+    bytes.extend_from_slice(session.as_bytes());
+    bytes.extend_from_slice(&envelope.sender.to_be_bytes());
+    match envelope.receiver {
+        Some(receiver) => {
+            bytes.push(1);
+            bytes.extend_from_slice(&receiver.to_be_bytes());
+        }
+        None => bytes.push(0),
+    }
+    bytes.extend_from_slice(&(envelope.binding.len() as u64).to_be_bytes());
+    bytes.extend_from_slice(envelope.binding);
+    bytes.extend(wire::encode(envelope.kind, body)?);
+    Ok(bytes)
+}
+
+/*
+Opens the signed messages of exactly the given senders and returns them in that
+order. identities are the identity keys of all parties by index. Messages of
+kind round k are taken by round k + 1, which is the round of the errors.
+*/
+pub(crate) fn open_signed_all<T: Wire>(
+    session: &SessionId,
+    kind: MessageKind,
+    mut messages: BTreeMap<u16, SignedMsg<T>>,
+    senders: &[u16],
+    identities: &[Point<Secp256r1>],
+    receiver: Option<u16>,
+    binding: &[u8],
+) -> Result<Vec<SignedMsg<T>>, Error> {
+    let round = kind.round + 1;
+    check_senders(round, &messages, senders)?;
+    let mut opened = Vec::with_capacity(senders.len());
+    for &j in senders {
+        if let Some(m) = messages.remove(&j) {
+            let envelope = Envelope {
+                kind,
+                sender: j,
+                receiver,
+                binding,
+            };
+            m.check(session, identities.get(j as usize), &envelope, round)?;
+            opened.push(m);
+        }
+    }
+    Ok(opened)
+}
@@ -0,0 +1,196 @@
+use crate::wire::{Reader, Wire};
+use crate::Error;
+use curv::arithmetic::traits::*;
+use curv::elliptic::curves::{p256::Secp256r1, Scalar};
+use curv::BigInt;
+use p256::{ecdsa, FieldBytes};
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+
+/*
+ECDSA signature produced by both Li17 and GG18 signing.
+
+r and s are kept as 32 byte big-endian integers, so to_bytes is always 64 bytes
+long regardless of leading zeros. Deserializing checks r and s like from_bytes.
+*/
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "RawSignature")]
+pub struct Signature {
+    r: [u8; 32],
+    s: [u8; 32],
+}
+
+// the serialized form of Signature, before the range checks
+#[derive(Deserialize)]
+struct RawSignature {
+    r: [u8; 32],
+    s: [u8; 32],
+}
+
+impl TryFrom<RawSignature> for Signature {
+    type Error = Error;
+
+    fn try_from(raw: RawSignature) -> Result<Self, Error> {
+        Signature::from_parts(raw.r, raw.s)
+    }
+}
+
+impl Signature {
+    pub fn from_scalars(r: &Scalar<Secp256r1>, s: &Scalar<Secp256r1>) -> Self {
+        Signature {
+            r: to_fixed_bytes(&r.to_bigint()),
+            s: to_fixed_bytes(&s.to_bigint()),
+        }
+    }
+
+    fn from_parts(r: [u8; 32], s: [u8; 32]) -> Result<Self, Error> {
+        let q = Scalar::<Secp256r1>::group_order();
+        for x in [&r, &s].iter() {
+            let x = BigInt::from_bytes(&x[..]);
+            if x == BigInt::zero() || &x >= q {
+                return Err(Error::InvalidEncoding);
+            }
+        }
+        Ok(Signature { r, s })
+    }
+
+    pub fn r(&self) -> &[u8; 32] {
+        &self.r
+    }
+
+    pub fn s(&self) -> &[u8; 32] {
+        &self.s
+    }
+
+    pub fn to_bytes(&self) -> [u8; 64] {
+        let mut bytes = [0u8; 64];
+        bytes[..32].copy_from_slice(&self.r);
+        bytes[32..].copy_from_slice(&self.s);
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        if bytes.len() != 64 {
+            return Err(Error::InvalidEncoding);
+        }
+        let mut r = [0u8; 32];
+        let mut s = [0u8; 32];
+        r.copy_from_slice(&bytes[..32]);
+        s.copy_from_slice(&bytes[32..]);
+        Signature::from_parts(r, s)
+    }
+
+    pub fn to_der(&self) -> Vec<u8> {
+        let r = der_integer(&self.r);
+        let s = der_integer(&self.s);
+        let mut der = vec![0x30, (r.len() + s.len()) as u8];
+        der.extend(r);
+        der.extend(s);
+        der
+    }
+
+    // only the minimal DER encoding is accepted
+    pub fn from_der(der: &[u8]) -> Result<Self, Error> {
+        if der.len() < 2 || der[0] != 0x30 || der[1] >= 0x80 || der[1] as usize != der.len() - 2 {
+            return Err(Error::InvalidEncoding);
+        }
+        let (r, rest) = parse_der_integer(&der[2..])?;
+        let (s, rest) = parse_der_integer(rest)?;
+        if !rest.is_empty() {
+            return Err(Error::InvalidEncoding);
+        }
+        Signature::from_parts(r, s)
+    }
+
+    pub fn is_low_s(&self) -> bool {
+        let s = BigInt::from_bytes(&self.s);
+        &(s * BigInt::from(2u32)) < Scalar::<Secp256r1>::group_order()
+    }
+
+    // (r, q - s) is an equally valid signature, this picks the one with s <= q / 2
+    pub fn normalize_s(&self) -> Self {
+        if self.is_low_s() {
+            return *self;
+        }
+        let q = Scalar::<Secp256r1>::group_order();
+        let s = BigInt::mod_sub(q, &BigInt::from_bytes(&self.s), q);
+        Signature {
+            r: self.r,
+            s: to_fixed_bytes(&s),
+        }
+    }
+}
+
+impl Wire for Signature {
+    fn write(&self, out: &mut Vec<u8>) -> Result<(), Error> {
+        out.extend_from_slice(&self.to_bytes());
+        Ok(())
+    }
+
+    fn read(input: &mut Reader<'_>) -> Result<Self, Error> {
+        Signature::from_bytes(input.take(64)?)
+    }
+}
+
+impl TryFrom<&Signature> for ecdsa::Signature {
+    type Error = Error;
+
+    fn try_from(signature: &Signature) -> Result<Self, Error> {
+        ecdsa::Signature::from_scalars(
+            *FieldBytes::from_slice(&signature.r),
+            *FieldBytes::from_slice(&signature.s),
+        )
+        .map_err(|_| Error::InvalidEncoding)
+    }
+}
+
+impl From<ecdsa::Signature> for Signature {
+    fn from(signature: ecdsa::Signature) -> Self {
+        let bytes: &[u8] = signature.as_ref();
+        let mut r = [0u8; 32];
+        let mut s = [0u8; 32];
+        r.copy_from_slice(&bytes[..32]);
+        s.copy_from_slice(&bytes[32..]);
+        Signature { r, s }
+    }
+}
+
+pub(crate) fn to_fixed_bytes(x: &BigInt) -> [u8; 32] {
+    let bytes = x.to_bytes();
+    let mut fixed = [0u8; 32];
+    fixed[32 - bytes.len()..].copy_from_slice(&bytes);
+    fixed
+}
+
+fn der_integer(x: &[u8; 32]) -> Vec<u8> {
+    let start = x.iter().position(|&b| b != 0).unwrap_or(31);
+    let mut value = x[start..].to_vec();
+    if value[0] & 0x80 != 0 {
+        value.insert(0, 0);
+    }
+    let mut der = vec![0x02, value.len() as u8];
+    der.extend(value);
+    der
+}
+
+fn parse_der_integer(der: &[u8]) -> Result<([u8; 32], &[u8]), Error> {
+    if der.len() < 2 || der[0] != 0x02 {
+        return Err(Error::InvalidEncoding);
+    }
+    let len = der[1] as usize;
+    if len == 0 || len > 33 || der.len() < 2 + len {
+        return Err(Error::InvalidEncoding);
+    }
+    let value = &der[2..2 + len];
+    // negative or padded with a superfluous zero byte
+    if value[0] & 0x80 != 0 || (len > 1 && value[0] == 0 && value[1] & 0x80 == 0) {
+        return Err(Error::InvalidEncoding);
+    }
+    let value = if value[0] == 0 { &value[1..] } else { value };
+    if value.len() > 32 {
+        return Err(Error::InvalidEncoding);
+    }
+    let mut fixed = [0u8; 32];
+    fixed[32 - value.len()..].copy_from_slice(value);
+    Ok((fixed, &der[2 + len..]))
+}
@@ -0,0 +1,100 @@
+use crate::{Error, Signature};
+use curv::arithmetic::traits::*;
+use curv::elliptic::curves::{p256::Secp256r1, Scalar};
+use curv::BigInt;
+use std::convert::TryFrom;
+
+fn scalar(x: u32) -> Scalar<Secp256r1> {
+    Scalar::from(&BigInt::from(x))
+}
+
+#[test]
+fn fixed_width_encoding() {
+    let sig = Signature::from_scalars(&scalar(1), &scalar(2));
+    let bytes = sig.to_bytes();
+    assert_eq!(bytes[..31], [0u8; 31]);
+    assert_eq!(bytes[31], 1);
+    assert_eq!(bytes[32..63], [0u8; 31]);
+    assert_eq!(bytes[63], 2);
+    assert_eq!(Signature::from_bytes(&bytes).unwrap(), sig);
+
+    assert_eq!(
+        Signature::from_bytes(&bytes[..63]),
+        Err(Error::InvalidEncoding)
+    );
+    assert_eq!(
+        Signature::from_bytes(&[0u8; 64]),
+        Err(Error::InvalidEncoding)
+    );
+    let mut too_big = bytes;
+    too_big[..32].copy_from_slice(&[0xffu8; 32]);
+    assert_eq!(Signature::from_bytes(&too_big), Err(Error::InvalidEncoding));
+}
+
+#[test]
+fn der_encoding() {
+    let mut bytes = [0u8; 64];
+    bytes[31] = 1;
+    bytes[32] = 0x80;
+    let sig = Signature::from_bytes(&bytes).unwrap();
+
+    let mut expected = vec![0x30, 0x26, 0x02, 0x01, 0x01, 0x02, 0x21, 0x00, 0x80];
+    expected.extend_from_slice(&[0u8; 31]);
+    assert_eq!(sig.to_der(), expected);
+    assert_eq!(Signature::from_der(&expected).unwrap(), sig);
+
+    // superfluous zero padding of r
+    let padded = [&[0x30, 0x27, 0x02, 0x02, 0x00, 0x01][..], &expected[5..]].concat();
+    assert_eq!(Signature::from_der(&padded), Err(Error::InvalidEncoding));
+
+    // trailing data
+    let mut trailing = expected.clone();
+    trailing[1] += 1;
+    trailing.push(0);
+    assert_eq!(Signature::from_der(&trailing), Err(Error::InvalidEncoding));
+
+    // negative s
+    let mut negative = vec![0x30, 0x25, 0x02, 0x01, 0x01, 0x02, 0x20, 0x80];
+    negative.extend_from_slice(&[0u8; 31]);
+    assert_eq!(Signature::from_der(&negative), Err(Error::InvalidEncoding));
+}
+
+#[test]
+fn low_s_normalisation() {
+    let q = Scalar::<Secp256r1>::group_order();
+    let high = Signature::from_scalars(&scalar(1), &Scalar::from(&(q - BigInt::from(1u32))));
+    assert!(!high.is_low_s());
+
+    let low = high.normalize_s();
+    assert!(low.is_low_s());
+    assert_eq!(low, Signature::from_scalars(&scalar(1), &scalar(1)));
+    assert_eq!(low.normalize_s(), low);
+}
+
+#[test]
+fn random_round_trips() {
+    for _ in 0..10000 {
+        let sig = Signature::from_scalars(
+            &Scalar::<Secp256r1>::random(),
+            &Scalar::<Secp256r1>::random(),
+        );
+        assert_eq!(Signature::from_bytes(&sig.to_bytes()).unwrap(), sig);
+        assert_eq!(Signature::from_der(&sig.to_der()).unwrap(), sig);
+
+        let p256_sig = p256::ecdsa::Signature::try_from(&sig).unwrap();
+        assert_eq!(Signature::from(p256_sig), sig);
+    }
+}
+
+#[test]
+fn deserialize_checks_range() {
+    let json = |r: [u8; 32], s: [u8; 32]| serde_json::json!({ "r": r, "s": s }).to_string();
+    let sig = Signature::from_scalars(&scalar(1), &scalar(2));
+    assert_eq!(
+        serde_json::from_str::<Signature>(&json(*sig.r(), *sig.s())).unwrap(),
+        sig
+    );
+
+    assert!(serde_json::from_str::<Signature>(&json(*sig.r(), [0u8; 32])).is_err());
+    assert!(serde_json::from_str::<Signature>(&json([0xffu8; 32], *sig.s())).is_err());
+}
@@ -0,0 +1,438 @@
+use crate::gg18_key_gen::GG18SignContext;
+use crate::gg18_key_gen_state::{Gg18KeyGen, Gg18KeyGenMsg};
+use crate::gg18_sign_state::{Gg18SignMsg, Gg18Signing};
+use crate::identity::IdentityKey;
+use crate::li17_key_gen::{Li17SignP1Context, Li17SignP2Context};
+use crate::li17_state::{Li17Msg, Li17P1Output, Li17P2Output, Li17Party1, Li17Party2};
+use crate::session::SessionId;
+use crate::signature::Signature;
+use crate::state_machine::{Msg, StateMachine};
+use crate::Error;
+use std::collections::{BTreeMap, BTreeSet};
+use std::mem;
+use std::panic;
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/*
+In-process simulation of a protocol run.
+
+Every party runs its state machine in its own thread, all messages pass an
+in-memory router that can drop, delay, reorder or corrupt chosen messages.
+Faults apply to single deliveries, a broadcast is delivered to every receiver
+separately. A run ends when all parties are finished or failed, or when no
+party can make progress anymore, the waiting parties are then reported as
+stalled.
+*/
+
+pub trait RoundMsg {
+    fn round(&self) -> u16;
+}
+
+impl RoundMsg for Gg18KeyGenMsg {
+    fn round(&self) -> u16 {
+        Gg18KeyGenMsg::round(self)
+    }
+}
+
+impl RoundMsg for Gg18SignMsg {
+    fn round(&self) -> u16 {
+        Gg18SignMsg::round(self)
+    }
+}
+
+impl RoundMsg for Li17Msg {
+    fn round(&self) -> u16 {
+        Li17Msg::round(self)
+    }
+}
+
+// selects the deliveries of a round from sender, to receiver or to anyone
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Selector {
+    pub round: u16,
+    pub sender: u16,
+    pub receiver: Option<u16>,
+}
+
+impl Selector {
+    fn matches<B: RoundMsg>(&self, msg: &Msg<B>, receiver: u16) -> bool {
+        self.round == msg.body.round()
+            && self.sender == msg.sender
+            && self.receiver.map_or(true, |r| r == receiver)
+    }
+}
+
+pub enum Fault<B> {
+    Drop(Selector),
+    Delay(Selector, Duration),
+    // delivered after the next message to the same receiver
+    Reorder(Selector),
+    Corrupt(Selector, Box<dyn Fn(&mut B)>),
+}
+
+impl<B> Fault<B> {
+    fn selector(&self) -> &Selector {
+        match self {
+            Fault::Drop(s) | Fault::Delay(s, _) | Fault::Reorder(s) | Fault::Corrupt(s, _) => s,
+        }
+    }
+}
+
+pub enum Outcome<O> {
+    Output(O),
+    Failed(Error),
+    Stalled { round: u16 },
+}
+
+impl<O> Outcome<O> {
+    pub fn output(self) -> Option<O> {
+        match self {
+            Outcome::Output(output) => Some(output),
+            _ => None,
+        }
+    }
+}
+
+pub struct Simulation<B> {
+    session: SessionId,
+    faults: Vec<Fault<B>>,
+}
+
+impl<B: RoundMsg + Clone + Send + 'static> Simulation<B> {
+    pub fn new(session: SessionId) -> Self {
+        Simulation {
+            session,
+            faults: Vec::new(),
+        }
+    }
+
+    // the first matching fault applies to a delivery
+    pub fn with_fault(mut self, fault: Fault<B>) -> Self {
+        self.faults.push(fault);
+        self
+    }
+
+    pub fn run<M>(self, machines: Vec<M>) -> Vec<Outcome<M::Output>>
+    where
+        M: StateMachine<MessageBody = B> + Send + 'static,
+        M::Output: Send + 'static,
+    {
+        let (events, router_events) = channel();
+        let mut router = Router::new(self.faults, router_events);
+        let handles: Vec<_> = machines
+            .into_iter()
+            .map(|m| router.spawn(m, events.clone()))
+            .collect();
+        drop(events);
+        router.route();
+        handles.into_iter().map(join).collect()
+    }
+
+    // run for two parties whose machines have different types
+    pub fn run_pair<M1, M2>(self, a: M1, b: M2) -> (Outcome<M1::Output>, Outcome<M2::Output>)
+    where
+        M1: StateMachine<MessageBody = B> + Send + 'static,
+        M1::Output: Send + 'static,
+        M2: StateMachine<MessageBody = B> + Send + 'static,
+        M2::Output: Send + 'static,
+    {
+        let (events, router_events) = channel();
+        let mut router = Router::new(self.faults, router_events);
+        let a = router.spawn(a, events.clone());
+        let b = router.spawn(b, events);
+        router.route();
+        (join(a), join(b))
+    }
+}
+
+impl Simulation<Gg18KeyGenMsg> {
+    pub fn gg18_key_gen(
+        self,
+        parties: u16,
+        threshold: u16,
+    ) -> Result<Vec<Outcome<GG18SignContext>>, Error> {
+        // every party gets a fresh identity key
+        let keys: Vec<IdentityKey> = (0..parties).map(|_| IdentityKey::random()).collect();
+        let identities: Vec<_> = keys.iter().map(|k| k.public()).collect();
+        let machines = keys
+            .into_iter()
+            .enumerate()
+            .map(|(i, k)| Gg18KeyGen::new(threshold, i as u16, self.session, k, identities.clone()))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(self.run(machines))
+    }
+}
+
+impl Simulation<Gg18SignMsg> {
+    // all given key shares sign, in the order of their indices
+    pub fn gg18_sign(
+        self,
+        contexts: Vec<GG18SignContext>,
+        message_hash: &[u8],
+    ) -> Result<Vec<Outcome<Signature>>, Error> {
+        let indices: Vec<u16> = contexts.iter().map(|c| c.index).collect();
+        let machines = contexts
+            .into_iter()
+            .map(|c| Gg18Signing::new(c, indices.clone(), message_hash.to_vec(), self.session))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(self.run(machines))
+    }
+}
+
+impl Simulation<Li17Msg> {
+    pub fn li17_key_gen(self) -> Result<(Outcome<Li17P1Output>, Outcome<Li17P2Output>), Error> {
+        let p1 = Li17Party1::key_gen(self.session)?;
+        let p2 = Li17Party2::key_gen(self.session)?;
+        Ok(self.run_pair(p1, p2))
+    }
+
+    pub fn li17_sign(
+        self,
+        p1: Li17SignP1Context,
+        p2: Li17SignP2Context,
+        message_hash: &[u8],
+    ) -> Result<(Outcome<Li17P1Output>, Outcome<Li17P2Output>), Error> {
+        let p1 = Li17Party1::sign(p1, message_hash, self.session)?;
+        let p2 = Li17Party2::sign(p2, message_hash, self.session)?;
+        Ok(self.run_pair(p1, p2))
+    }
+
+    pub fn li17_refresh(
+        self,
+        p1: Li17SignP1Context,
+        p2: Li17SignP2Context,
+    ) -> Result<(Outcome<Li17P1Output>, Outcome<Li17P2Output>), Error> {
+        let p1 = Li17Party1::refresh(p1, self.session)?;
+        let p2 = Li17Party2::refresh(p2, self.session)?;
+        Ok(self.run_pair(p1, p2))
+    }
+}
+
+enum Event<B> {
+    Outgoing(Msg<B>),
+    // the party blocks after having received that many messages
+    Waiting { party: u16, received: usize },
+    Done(u16),
+}
+
+struct Router<B> {
+    faults: Vec<Fault<B>>,
+    events: Receiver<Event<B>>,
+    parties: BTreeMap<u16, Sender<Msg<B>>>,
+    delivered: BTreeMap<u16, usize>,
+    waiting: BTreeMap<u16, usize>,
+    done: BTreeSet<u16>,
+    delayed: Vec<(Instant, u16, Msg<B>)>,
+    held: Vec<(u16, Msg<B>)>,
+}
+
+impl<B: RoundMsg + Clone + Send + 'static> Router<B> {
+    fn new(faults: Vec<Fault<B>>, events: Receiver<Event<B>>) -> Self {
+        Router {
+            faults,
+            events,
+            parties: BTreeMap::new(),
+            delivered: BTreeMap::new(),
+            waiting: BTreeMap::new(),
+            done: BTreeSet::new(),
+            delayed: Vec::new(),
+            held: Vec::new(),
+        }
+    }
+
+    fn spawn<M>(&mut self, machine: M, events: Sender<Event<B>>) -> JoinHandle<Outcome<M::Output>>
+    where
+        M: StateMachine<MessageBody = B> + Send + 'static,
+        M::Output: Send + 'static,
+    {
+        let (sender, incoming) = channel();
+        let party = machine.party_index();
+        self.parties.insert(party, sender);
+        self.delivered.insert(party, 0);
+        thread::spawn(move || drive(machine, incoming, events))
+    }
+
+    // returns once all parties are done or stalled, dropping the router
+    // disconnects the stalled ones
+    fn route(mut self) {
+        while self.done.len() < self.parties.len() {
+            if self.is_idle() {
+                if self.held.is_empty() {
+                    return;
+                }
+                for (to, msg) in mem::take(&mut self.held) {
+                    self.deliver(to, msg);
+                }
+                continue;
+            }
+            let event = match self.delayed.iter().map(|d| d.0).min() {
+                Some(due) => {
+                    match self
+                        .events
+                        .recv_timeout(due.saturating_duration_since(Instant::now()))
+                    {
+                        Ok(event) => event,
+                        Err(RecvTimeoutError::Timeout) => {
+                            self.deliver_due();
+                            continue;
+                        }
+                        Err(RecvTimeoutError::Disconnected) => return,
+                    }
+                }
+                None => match self.events.recv() {
+                    Ok(event) => event,
+                    Err(_) => return,
+                },
+            };
+            match event {
+                Event::Outgoing(msg) => self.dispatch(msg),
+                Event::Waiting { party, received } => {
+                    self.waiting.insert(party, received);
+                }
+                Event::Done(party) => {
+                    self.done.insert(party);
+                }
+            }
+        }
+    }
+
+    // every party still running waits for a message that is not on its way
+    fn is_idle(&self) -> bool {
+        self.delayed.is_empty()
+            && self
+                .parties
+                .keys()
+                .filter(|p| !self.done.contains(p))
+                .all(|p| self.waiting.get(p) == self.delivered.get(p))
+    }
+
+    fn dispatch(&mut self, msg: Msg<B>) {
+        let receivers: Vec<u16> = match msg.receiver {
+            Some(receiver) => vec![receiver],
+            None => self
+                .parties
+                .keys()
+                .copied()
+                .filter(|&p| p != msg.sender)
+                .collect(),
+        };
+        for to in receivers {
+            let mut copy = msg.clone();
+            match self.faults.iter().find(|f| f.selector().matches(&copy, to)) {
+                Some(Fault::Drop(_)) => {}
+                Some(Fault::Delay(_, delay)) => {
+                    self.delayed.push((Instant::now() + *delay, to, copy));
+                }
+                Some(Fault::Reorder(_)) => self.held.push((to, copy)),
+                Some(Fault::Corrupt(_, corrupt)) => {
+                    corrupt(&mut copy.body);
+                    self.deliver(to, copy);
+                    self.release(to);
+                }
+                None => {
+                    self.deliver(to, copy);
+                    self.release(to);
+                }
+            }
+        }
+    }
+
+    fn deliver_due(&mut self) {
+        let now = Instant::now();
+        let (due, later): (Vec<_>, Vec<_>) = mem::take(&mut self.delayed)
+            .into_iter()
+            .partition(|d| d.0 <= now);
+        self.delayed = later;
+        for (_, to, msg) in due {
+            self.deliver(to, msg);
+            self.release(to);
+        }
+    }
+
+    // held messages follow the next message to their receiver
+    fn release(&mut self, to: u16) {
+        let (released, held): (Vec<_>, Vec<_>) = mem::take(&mut self.held)
+            .into_iter()
+            .partition(|h| h.0 == to);
+        self.held = held;
+        for (_, msg) in released {
+            self.deliver(to, msg);
+        }
+    }
+
+    fn deliver(&mut self, to: u16, msg: Msg<B>) {
+        if let Some(party) = self.parties.get(&to) {
+            if party.send(msg).is_ok() {
+                *self.delivered.get_mut(&to).unwrap() += 1;
+            }
+        }
+    }
+}
+
+// reports the party as done also when its thread panics
+struct DoneGuard<B> {
+    party: u16,
+    events: Sender<Event<B>>,
+}
+
+impl<B> Drop for DoneGuard<B> {
+    fn drop(&mut self) {
+        let _ = self.events.send(Event::Done(self.party));
+    }
+}
+
+fn drive<M: StateMachine>(
+    mut machine: M,
+    incoming: Receiver<Msg<M::MessageBody>>,
+    events: Sender<Event<M::MessageBody>>,
+) -> Outcome<M::Output> {
+    let party = machine.party_index();
+    let guard = DoneGuard {
+        party,
+        events: events.clone(),
+    };
+    let mut received = 0;
+    let outcome = loop {
+        if let Err(e) = machine.proceed() {
+            break Outcome::Failed(e);
+        }
+        for msg in machine.message_queue().drain(..) {
+            let _ = events.send(Event::Outgoing(msg));
+        }
+        if machine.is_finished() {
+            match machine.pick_output() {
+                Some(output) => break Outcome::Output(output),
+                None => {
+                    break Outcome::Stalled {
+                        round: machine.current_round(),
+                    }
+                }
+            }
+        }
+        if machine.wants_to_proceed() {
+            continue;
+        }
+        let _ = events.send(Event::Waiting { party, received });
+        match incoming.recv() {
+            Ok(msg) => {
+                received += 1;
+                if let Err(e) = machine.handle_incoming(msg) {
+                    break Outcome::Failed(e);
+                }
+            }
+            Err(_) => {
+                break Outcome::Stalled {
+                    round: machine.current_round(),
+                }
+            }
+        }
+    };
+    drop(guard);
+    outcome
+}
+
+fn join<O>(handle: JoinHandle<Outcome<O>>) -> Outcome<O> {
+    handle.join().unwrap_or_else(|e| panic::resume_unwind(e))
+}
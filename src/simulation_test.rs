@@ -0,0 +1,145 @@
+use crate::gg18_key_gen_state::Gg18KeyGenMsg;
+use crate::gg18_test::{check_sig, keygen, SID};
+use crate::li17_state::{Li17P1Output, Li17P2Output};
+use crate::session::SessionId;
+use crate::simulation::{Fault, Outcome, Selector, Simulation};
+use crate::Error;
+use sha2::{Digest, Sha256};
+use std::time::Duration;
+
+#[test]
+fn gg18_configurations() {
+    for &(parties, threshold) in &[(2, 2), (3, 2), (4, 3)] {
+        let contexts = keygen(parties, threshold);
+        let pk = contexts[0].pk.clone();
+        assert!(contexts.iter().all(|c| c.pk == pk));
+
+        // the last threshold parties sign
+        let message = b"simulation";
+        let hash = Sha256::digest(message);
+        let signers = contexts
+            .into_iter()
+            .skip((parties - threshold) as usize)
+            .collect();
+        let signatures: Vec<_> = Simulation::new(SID)
+            .gg18_sign(signers, &hash)
+            .unwrap()
+            .into_iter()
+            .map(|o| o.output().unwrap())
+            .collect();
+        assert!(signatures.iter().all(|s| s == &signatures[0]));
+        check_sig(&signatures[0], message, &pk);
+    }
+}
+
+#[test]
+fn gg18_key_gen_tolerates_delay_and_reordering() {
+    let outcomes = Simulation::new(SID)
+        .with_fault(Fault::Delay(
+            Selector {
+                round: 1,
+                sender: 0,
+                receiver: None,
+            },
+            Duration::from_millis(50),
+        ))
+        .with_fault(Fault::Reorder(Selector {
+            round: 3,
+            sender: 2,
+            receiver: Some(1),
+        }))
+        .gg18_key_gen(3, 2)
+        .unwrap();
+    assert!(outcomes.into_iter().all(|o| o.output().is_some()));
+}
+
+#[test]
+fn gg18_key_gen_stalls_on_dropped_share() {
+    let outcomes = Simulation::new(SID)
+        .with_fault(Fault::Drop(Selector {
+            round: 3,
+            sender: 0,
+            receiver: Some(2),
+        }))
+        .gg18_key_gen(3, 2)
+        .unwrap();
+    let rounds: Vec<u16> = outcomes
+        .into_iter()
+        .map(|o| match o {
+            Outcome::Stalled { round } => round,
+            _ => panic!("expected a stalled party"),
+        })
+        .collect();
+    assert_eq!(rounds, vec![4, 4, 3]);
+}
+
+#[test]
+fn gg18_key_gen_reports_corrupted_message() {
+    let outcomes = Simulation::new(SID)
+        .with_fault(Fault::Corrupt(
+            Selector {
+                round: 1,
+                sender: 1,
+                receiver: Some(0),
+            },
+            Box::new(|m| {
+                if let Gg18KeyGenMsg::Round1(m) = m {
+                    m.session = SessionId::new([0; 32]);
+                }
+            }),
+        ))
+        .gg18_key_gen(3, 2)
+        .unwrap();
+    let mut outcomes = outcomes.into_iter();
+    match outcomes.next().unwrap() {
+        Outcome::Failed(e) => assert_eq!(
+            e,
+            Error::SessionMismatch {
+                round: 2,
+                party: Some(1)
+            }
+        ),
+        _ => panic!("expected a failed party"),
+    }
+    assert!(outcomes.all(|o| matches!(o, Outcome::Stalled { round: 2 })));
+}
+
+#[test]
+fn li17_flows() {
+    let (p1, p2) = Simulation::new(SID).li17_key_gen().unwrap();
+    let (p1, p2) = match (p1.output(), p2.output()) {
+        (Some(Li17P1Output::KeyShare(p1)), Some(Li17P2Output::KeyShare(p2))) => (p1, p2),
+        _ => panic!("expected key shares"),
+    };
+    let pk = p1.public.clone();
+
+    let (p1, p2) = Simulation::new(SID).li17_refresh(p1, p2).unwrap();
+    let (p1, p2) = match (p1.output(), p2.output()) {
+        (Some(Li17P1Output::KeyShare(p1)), Some(Li17P2Output::KeyShare(p2))) => (p1, p2),
+        _ => panic!("expected key shares"),
+    };
+
+    let message = b"simulation";
+    let hash = Sha256::digest(message);
+    let (sig, _) = Simulation::new(SID).li17_sign(p1, p2, &hash).unwrap();
+    match sig.output() {
+        Some(Li17P1Output::Signature(sig)) => check_sig(&sig, message, &pk),
+        _ => panic!("expected a signature"),
+    }
+}
+
+#[test]
+fn li17_sign_stalls_on_dropped_message() {
+    let (p1, p2) = crate::li17_test::run_keygen();
+    let hash = Sha256::digest(b"simulation");
+    let (p1, p2) = Simulation::new(SID)
+        .with_fault(Fault::Drop(Selector {
+            round: 1,
+            sender: 1,
+            receiver: None,
+        }))
+        .li17_sign(p1, p2, &hash)
+        .unwrap();
+    assert!(matches!(p1, Outcome::Stalled { round: 2 }));
+    assert!(matches!(p2, Outcome::Stalled { round: 2 }));
+}
@@ -0,0 +1,93 @@
+use crate::wire::{Reader, Wire};
+use crate::Error;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::mem;
+
+/*
+Round-driven state machines.
+
+A state machine wraps the round functions of one protocol run. Incoming
+messages are passed to handle_incoming in any order, also ahead of their round,
+and proceed runs the next round function as soon as all messages it needs have
+arrived. Outgoing messages are collected in message_queue, the caller drains
+the queue and delivers every message either to all other parties (receiver is
+None) or to the given receiver only.
+*/
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Msg<B> {
+    pub sender: u16,
+    pub receiver: Option<u16>,
+    pub body: B,
+}
+
+impl<B> Msg<B> {
+    pub fn is_broadcast(&self) -> bool {
+        self.receiver.is_none()
+    }
+}
+
+// the bodies of the protocols encode as complete wire messages, header included
+impl<B: Wire> Wire for Msg<B> {
+    fn write(&self, out: &mut Vec<u8>) -> Result<(), Error> {
+        self.sender.write(out)?;
+        self.receiver.write(out)?;
+        self.body.write(out)
+    }
+
+    fn read(input: &mut Reader<'_>) -> Result<Self, Error> {
+        Ok(Msg {
+            sender: Wire::read(input)?,
+            receiver: Wire::read(input)?,
+            body: Wire::read(input)?,
+        })
+    }
+}
+
+pub trait StateMachine {
+    type MessageBody;
+    type Output;
+
+    fn handle_incoming(&mut self, msg: Msg<Self::MessageBody>) -> Result<(), Error>;
+
+    fn message_queue(&mut self) -> &mut Vec<Msg<Self::MessageBody>>;
+
+    // all messages of the current round have arrived
+    fn wants_to_proceed(&self) -> bool;
+
+    // runs the next round if wants_to_proceed, does nothing otherwise
+    fn proceed(&mut self) -> Result<(), Error>;
+
+    // the round whose messages are awaited
+    fn current_round(&self) -> u16;
+
+    fn is_finished(&self) -> bool;
+
+    // takes the output once the protocol is finished
+    fn pick_output(&mut self) -> Option<Self::Output>;
+
+    fn party_index(&self) -> u16;
+}
+
+// removes the messages accepted by open from the buffer and keys them by sender,
+// open hands back the bodies of other rounds
+pub(crate) fn take_round<B, T>(
+    incoming: &mut Vec<Msg<B>>,
+    open: impl Fn(B) -> Result<T, B>,
+) -> BTreeMap<u16, T> {
+    let mut taken = BTreeMap::new();
+    for msg in mem::take(incoming) {
+        match open(msg.body) {
+            Ok(body) => {
+                taken.insert(msg.sender, body);
+            }
+            Err(body) => incoming.push(Msg {
+                sender: msg.sender,
+                receiver: msg.receiver,
+                body,
+            }),
+        }
+    }
+    taken
+}
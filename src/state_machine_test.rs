@@ -0,0 +1,319 @@
+use crate::gg18_key_gen_state::Gg18KeyGen;
+use crate::gg18_sign_state::Gg18Signing;
+use crate::gg18_test::check_sig;
+use crate::gg18_test::{identities, SID};
+use crate::li17_state::{Li17P1Output, Li17P2Output, Li17Party1, Li17Party2};
+use crate::persist::Persist;
+use crate::state_machine::{Msg, StateMachine};
+use crate::Error;
+use sha2::{Digest, Sha256};
+
+// delivers messages between the machines until all of them are finished,
+// step is applied to every machine after each proceed
+pub fn run<M, F>(mut machines: Vec<M>, mut step: F) -> Vec<M::Output>
+where
+    M: StateMachine,
+    M::MessageBody: Clone,
+    F: FnMut(M) -> M,
+{
+    while !machines.iter().all(|m| m.is_finished()) {
+        machines = machines
+            .into_iter()
+            .map(|mut m| {
+                m.proceed().unwrap();
+                step(m)
+            })
+            .collect();
+        let outgoing: Vec<Msg<M::MessageBody>> = machines
+            .iter_mut()
+            .flat_map(|m| m.message_queue().drain(..).collect::<Vec<_>>())
+            .collect();
+        assert!(
+            !outgoing.is_empty()
+                || machines
+                    .iter()
+                    .any(|m| m.wants_to_proceed() || m.is_finished()),
+            "stuck in round {}",
+            machines[0].current_round()
+        );
+        for msg in outgoing {
+            for m in machines.iter_mut() {
+                let index = m.party_index();
+                if index != msg.sender && msg.receiver.map_or(true, |r| r == index) {
+                    m.handle_incoming(msg.clone()).unwrap();
+                }
+            }
+        }
+    }
+    machines
+        .iter_mut()
+        .map(|m| m.pick_output().unwrap())
+        .collect()
+}
+
+// run for the two Li17 roles, whose machines have different types
+fn run_pair<A, B>(mut a: A, mut b: B) -> (A::Output, B::Output)
+where
+    A: StateMachine + Persist,
+    B: StateMachine<MessageBody = A::MessageBody> + Persist,
+{
+    while !(a.is_finished() && b.is_finished()) {
+        a.proceed().unwrap();
+        b.proceed().unwrap();
+        a = reload(a);
+        b = reload(b);
+        let from_a: Vec<_> = a.message_queue().drain(..).collect();
+        let from_b: Vec<_> = b.message_queue().drain(..).collect();
+        assert!(
+            !from_a.is_empty() || !from_b.is_empty() || a.is_finished() && b.is_finished(),
+            "stuck"
+        );
+        for msg in from_a {
+            b.handle_incoming(msg).unwrap();
+        }
+        for msg in from_b {
+            a.handle_incoming(msg).unwrap();
+        }
+    }
+    (a.pick_output().unwrap(), b.pick_output().unwrap())
+}
+
+fn reload<T: Persist>(t: T) -> T {
+    T::from_json(&t.to_json().unwrap()).unwrap()
+}
+
+fn key_gen_machines(parties: u16, threshold: u16) -> Vec<Gg18KeyGen> {
+    let (keys, ids) = identities(parties);
+    keys.into_iter()
+        .enumerate()
+        .map(|(i, k)| Gg18KeyGen::new(threshold, i as u16, SID, k, ids.clone()).unwrap())
+        .collect()
+}
+
+#[test]
+fn gg18_key_gen() {
+    let machines = key_gen_machines(4, 3);
+    let contexts = run(machines, |m| m);
+    assert!(contexts.iter().all(|c| c.pk == contexts[0].pk));
+    assert!(contexts
+        .iter()
+        .enumerate()
+        .all(|(i, c)| c.index == i as u16));
+}
+
+#[test]
+fn gg18_key_gen_resumes_from_storage() {
+    let machines = key_gen_machines(3, 2);
+    let contexts = run(machines, reload);
+    assert!(contexts.iter().all(|c| c.pk == contexts[0].pk));
+}
+
+#[test]
+fn gg18_key_gen_tags_messages() {
+    let mut machines: Vec<Gg18KeyGen> = key_gen_machines(3, 2);
+    for round in 1..=5 {
+        let outgoing: Vec<_> = machines
+            .iter_mut()
+            .flat_map(|m| m.message_queue().drain(..).collect::<Vec<_>>())
+            .collect();
+        for msg in outgoing.iter() {
+            assert_eq!(msg.body.round(), round);
+            assert_eq!(msg.body.is_p2p(), !msg.is_broadcast());
+            assert_eq!(msg.is_broadcast(), round != 3);
+        }
+        // deliver in reverse, the machines must not depend on the order
+        for msg in outgoing.into_iter().rev() {
+            for m in machines.iter_mut() {
+                let index = m.party_index();
+                if index != msg.sender && msg.receiver.map_or(true, |r| r == index) {
+                    m.handle_incoming(msg.clone()).unwrap();
+                }
+            }
+        }
+        for m in machines.iter_mut() {
+            assert!(m.wants_to_proceed());
+            m.proceed().unwrap();
+        }
+    }
+    assert!(machines.iter().all(|m| m.is_finished()));
+}
+
+#[test]
+fn gg18_key_gen_rejects_unexpected_messages() {
+    let mut machines = key_gen_machines(3, 2);
+    let mut b = machines.remove(1);
+    let mut a = machines.remove(0);
+    let msg_a = a.message_queue().pop().unwrap();
+    let msg_b = b.message_queue().pop().unwrap();
+
+    // our own message
+    assert_eq!(
+        a.handle_incoming(msg_a).unwrap_err(),
+        Error::UnexpectedSender { round: 2, party: 0 }
+    );
+
+    // a broadcast sent as P2P
+    let mut p2p = msg_b.clone();
+    p2p.receiver = Some(0);
+    assert_eq!(
+        a.handle_incoming(p2p).unwrap_err(),
+        Error::MalformedInput {
+            round: 2,
+            party: Some(1)
+        }
+    );
+
+    // the same message twice
+    a.handle_incoming(msg_b.clone()).unwrap();
+    assert_eq!(
+        a.handle_incoming(msg_b.clone()).unwrap_err(),
+        Error::MalformedInput {
+            round: 2,
+            party: Some(1)
+        }
+    );
+    assert!(!a.wants_to_proceed());
+    a.proceed().unwrap();
+    assert_eq!(a.current_round(), 1);
+
+    // a party outside the key generation
+    let mut outsider = msg_b;
+    outsider.sender = 3;
+    assert_eq!(
+        a.handle_incoming(outsider).unwrap_err(),
+        Error::UnexpectedSender { round: 2, party: 3 }
+    );
+}
+
+#[test]
+fn gg18_signing() {
+    let machines = key_gen_machines(4, 3);
+    let mut contexts = run(machines, |m| m);
+    let pk = contexts[0].pk.clone();
+
+    let message = b"state machine";
+    let hash = Sha256::digest(message).to_vec();
+    let indices = vec![2u16, 3, 0];
+    let machines = indices
+        .iter()
+        .map(|&i| {
+            let context = contexts.remove(contexts.iter().position(|c| c.index == i).unwrap());
+            Gg18Signing::new(context, indices.clone(), hash.clone(), SID).unwrap()
+        })
+        .collect();
+    let signatures = run(machines, reload);
+    assert!(signatures.iter().all(|s| s == &signatures[0]));
+    check_sig(&signatures[0], message, &pk);
+}
+
+#[test]
+fn gg18_signing_waits_for_all_messages() {
+    let contexts = crate::gg18_test::keygen(3, 2);
+    let hash = Sha256::digest(b"state machine").to_vec();
+    let indices = vec![0u16, 2];
+    let mut machines: Vec<Gg18Signing> = contexts
+        .into_iter()
+        .filter(|c| indices.contains(&c.index))
+        .map(|c| Gg18Signing::new(c, indices.clone(), hash.clone(), SID).unwrap())
+        .collect();
+
+    // a party outside the signing set
+    let msg = machines[1].message_queue().pop().unwrap();
+    let mut outsider = msg.clone();
+    outsider.sender = 1;
+    assert_eq!(
+        machines[0].handle_incoming(outsider).unwrap_err(),
+        Error::UnexpectedSender { round: 2, party: 1 }
+    );
+
+    // nothing happens until the message of party 2 arrives
+    assert!(!machines[0].wants_to_proceed());
+    machines[0].proceed().unwrap();
+    assert_eq!(machines[0].current_round(), 1);
+    assert!(machines[0].message_queue().len() == 1);
+
+    machines[0].handle_incoming(msg.clone()).unwrap();
+    assert_eq!(
+        machines[0].handle_incoming(msg).unwrap_err(),
+        Error::MalformedInput {
+            round: 2,
+            party: Some(2)
+        }
+    );
+    machines[0].proceed().unwrap();
+    assert_eq!(machines[0].current_round(), 2);
+    let queue = machines[0].message_queue();
+    assert_eq!(queue.len(), 2);
+    assert_eq!(queue[1].receiver, Some(2));
+}
+
+#[test]
+fn li17_key_gen_refresh_and_sign() {
+    let (p1, p2) = run_pair(
+        Li17Party1::key_gen(SID).unwrap(),
+        Li17Party2::key_gen(SID).unwrap(),
+    );
+    let (p1, p2) = match (p1, p2) {
+        (Li17P1Output::KeyShare(p1), Li17P2Output::KeyShare(p2)) => (p1, p2),
+        _ => panic!("expected key shares"),
+    };
+    let pk = p1.public.clone();
+    assert_eq!(pk, p2.public);
+
+    let (p1, p2) = run_pair(
+        Li17Party1::refresh(p1, SID).unwrap(),
+        Li17Party2::refresh(p2, SID).unwrap(),
+    );
+    let (p1, p2) = match (p1, p2) {
+        (Li17P1Output::KeyShare(p1), Li17P2Output::KeyShare(p2)) => (p1, p2),
+        _ => panic!("expected key shares"),
+    };
+    assert_eq!(p1.public, pk);
+    assert_eq!(p2.public, pk);
+
+    let message = b"state machine";
+    let hash = Sha256::digest(message);
+    let (sig, signed) = run_pair(
+        Li17Party1::sign(p1, &hash, SID).unwrap(),
+        Li17Party2::sign(p2, &hash, SID).unwrap(),
+    );
+    assert!(matches!(signed, Li17P2Output::Signed));
+    match sig {
+        Li17P1Output::Signature(sig) => crate::li17_test::check_sig(&sig, message, &pk),
+        _ => panic!("expected a signature"),
+    }
+}
+
+#[test]
+fn li17_rejects_unexpected_messages() {
+    let mut p1 = Li17Party1::key_gen(SID).unwrap();
+    let mut p2 = Li17Party2::key_gen(SID).unwrap();
+    let msg = p1.message_queue().pop().unwrap();
+
+    // our own message
+    assert_eq!(
+        p1.handle_incoming(msg.clone()).unwrap_err(),
+        Error::UnexpectedSender { round: 2, party: 1 }
+    );
+
+    p2.handle_incoming(msg.clone()).unwrap();
+    assert_eq!(
+        p2.handle_incoming(msg.clone()).unwrap_err(),
+        Error::MalformedInput {
+            round: 1,
+            party: Some(1)
+        }
+    );
+    p2.proceed().unwrap();
+    assert_eq!(p2.current_round(), 2);
+    assert_eq!(p2.message_queue()[0].receiver, Some(1));
+
+    // the first message again, the second one is awaited now
+    assert_eq!(
+        p2.handle_incoming(msg).unwrap_err(),
+        Error::MalformedInput {
+            round: 2,
+            party: Some(1)
+        }
+    );
+}
@@ -0,0 +1,414 @@
+use crate::gg18_key_gen::GG18SignContext;
+use crate::gg18_key_gen_state::{Gg18KeyGen, Gg18KeyGenMsg};
+use crate::gg18_sign_state::{Gg18SignMsg, Gg18Signing};
+use crate::identity::IdentityKey;
+use crate::li17_key_gen::{Li17SignP1Context, Li17SignP2Context};
+use crate::li17_state::{Li17Msg, Li17P1Output, Li17P2Output, Li17Party1, Li17Party2};
+use crate::session::SessionId;
+use crate::signature::Signature;
+use crate::state_machine::{Msg, StateMachine};
+use crate::wire::{self, Wire, MAX_MESSAGE_LEN};
+use crate::Error;
+use curv::elliptic::curves::{p256::Secp256r1, Point};
+use futures::{Sink, SinkExt, Stream, StreamExt};
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+
+/*
+Async message delivery between parties.
+
+A Delivery splits into a stream of incoming and a sink of outgoing messages of
+one party. The sink sends broadcasts (receiver is None) to every other party
+and P2P messages to their receiver only. Channels connects parties of the same
+process, Tcp connects parties over TCP. TCP frames are a u32 big-endian length
+followed by sender: u16 | receiver: Option<u16> | message in the encoding of
+the wire module, the message with its header. The TCP connection itself is not
+encrypted, the secret shares of GG18 key generation are sealed for their
+receiver by the protocol.
+
+Tcp does not authenticate peers: a connecting peer announces its index and is
+taken at its word, any host that can reach the listener can pose as a party.
+Tcp must only be used on a network where that is acceptable, or under a
+channel that authenticates the hosts, e.g. TLS with client certificates or a
+VPN. GG18 messages are signed with the identity key of their sender, such a
+host cannot forge them but can still disrupt the run. Li17 messages are not
+signed.
+
+The run functions execute a whole protocol run over any Delivery, the rounds
+run on the blocking thread pool of tokio.
+*/
+
+pub trait Delivery<B> {
+    type Incoming: Stream<Item = Result<Msg<B>, Error>> + Unpin;
+    type Outgoing: Sink<Msg<B>, Error = Error> + Unpin;
+
+    fn split(self) -> (Self::Incoming, Self::Outgoing);
+}
+
+pub struct Incoming<B>(UnboundedReceiver<Result<Msg<B>, Error>>);
+
+impl<B> Stream for Incoming<B> {
+    type Item = Result<Msg<B>, Error>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.get_mut().0.poll_recv(cx)
+    }
+}
+
+// the receivers of the TCP writers never get an error
+pub struct Outgoing<B> {
+    index: u16,
+    peers: BTreeMap<u16, UnboundedSender<Result<Msg<B>, Error>>>,
+}
+
+impl<B: Clone> Outgoing<B> {
+    fn route(&self, msg: Msg<B>) -> Result<(), Error> {
+        let receivers: Vec<u16> = match msg.receiver {
+            Some(receiver) => vec![receiver],
+            None => self.peers.keys().copied().collect(),
+        };
+        for to in receivers {
+            self.peers
+                .get(&to)
+                .ok_or(Error::Transport)?
+                .send(Ok(msg.clone()))
+                .map_err(|_| Error::Transport)?;
+        }
+        Ok(())
+    }
+}
+
+impl<B: Clone> Sink<Msg<B>> for Outgoing<B> {
+    type Error = Error;
+
+    fn poll_ready(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Result<(), Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn start_send(self: Pin<&mut Self>, msg: Msg<B>) -> Result<(), Error> {
+        if msg.sender != self.index {
+            return Err(Error::Transport);
+        }
+        self.route(msg)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Result<(), Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Result<(), Error>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+pub struct Channels<B> {
+    incoming: Incoming<B>,
+    outgoing: Outgoing<B>,
+}
+
+impl<B> Channels<B> {
+    // connects all given parties with each other, in the order of parties
+    pub fn connect(parties: &[u16]) -> Vec<Self> {
+        let (senders, receivers): (Vec<_>, Vec<_>) =
+            parties.iter().map(|_| unbounded_channel()).unzip();
+        parties
+            .iter()
+            .zip(receivers)
+            .map(|(&index, receiver)| {
+                let peers = parties
+                    .iter()
+                    .zip(senders.iter())
+                    .filter(|(&j, _)| j != index)
+                    .map(|(&j, sender)| (j, sender.clone()))
+                    .collect();
+                Channels {
+                    incoming: Incoming(receiver),
+                    outgoing: Outgoing { index, peers },
+                }
+            })
+            .collect()
+    }
+}
+
+impl<B: Clone> Delivery<B> for Channels<B> {
+    type Incoming = Incoming<B>;
+    type Outgoing = Outgoing<B>;
+
+    fn split(self) -> (Incoming<B>, Outgoing<B>) {
+        (self.incoming, self.outgoing)
+    }
+}
+
+pub struct Tcp<B> {
+    incoming: Incoming<B>,
+    outgoing: Outgoing<B>,
+}
+
+impl<B> Tcp<B>
+where
+    B: Wire + Send + 'static,
+{
+    /*
+    Connects to the parties with a lower index and accepts the ones with a
+    higher index, the listeners of all parties must be bound beforehand.
+    Accepted connections that do not announce the index of an expected party
+    are dropped, the first connection that announces a party is kept.
+    */
+    pub async fn connect(
+        index: u16,
+        listener: TcpListener,
+        peers: &BTreeMap<u16, SocketAddr>,
+    ) -> Result<Self, Error> {
+        let mut streams = BTreeMap::new();
+        for (&j, addr) in peers.range(..index) {
+            let mut stream = TcpStream::connect(addr)
+                .await
+                .map_err(|_| Error::Transport)?;
+            stream
+                .write_all(&index.to_be_bytes())
+                .await
+                .map_err(|_| Error::Transport)?;
+            streams.insert(j, stream);
+        }
+        // every accepted connection announces its index on its own task, so
+        // that a silent connection does not hold up the others
+        let (announced, mut announcements) = unbounded_channel();
+        let accept = tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let announced = announced.clone();
+                tokio::spawn(async move {
+                    let mut j = [0u8; 2];
+                    if stream.read_exact(&mut j).await.is_ok() {
+                        let _ = announced.send((u16::from_be_bytes(j), stream));
+                    }
+                });
+            }
+        });
+        while streams.len() < peers.len() {
+            let (j, stream) = match announcements.recv().await {
+                Some(announcement) => announcement,
+                None => break,
+            };
+            if j > index && peers.contains_key(&j) && !streams.contains_key(&j) {
+                streams.insert(j, stream);
+            }
+        }
+        accept.abort();
+        if streams.len() < peers.len() {
+            return Err(Error::Transport);
+        }
+
+        let (incoming, receiver) = unbounded_channel();
+        let mut outgoing = BTreeMap::new();
+        for (j, stream) in streams {
+            let (reader, writer) = stream.into_split();
+            let (sender, messages) = unbounded_channel();
+            tokio::spawn(read_frames(reader, j, incoming.clone()));
+            tokio::spawn(write_frames(writer, messages));
+            outgoing.insert(j, sender);
+        }
+        Ok(Tcp {
+            incoming: Incoming(receiver),
+            outgoing: Outgoing {
+                index,
+                peers: outgoing,
+            },
+        })
+    }
+}
+
+impl<B: Clone> Delivery<B> for Tcp<B> {
+    type Incoming = Incoming<B>;
+    type Outgoing = Outgoing<B>;
+
+    fn split(self) -> (Incoming<B>, Outgoing<B>) {
+        (self.incoming, self.outgoing)
+    }
+}
+
+// a connection that is closed in the middle of a frame or carries a message
+// from another sender ends with an error
+async fn read_frames<B: Wire>(
+    mut reader: OwnedReadHalf,
+    peer: u16,
+    incoming: UnboundedSender<Result<Msg<B>, Error>>,
+) {
+    loop {
+        let mut len = [0u8; 4];
+        if reader.read_exact(&mut len).await.is_err() {
+            return;
+        }
+        let len = u32::from_be_bytes(len) as u64;
+        let result = if len > MAX_MESSAGE_LEN {
+            Err(Error::InvalidEncoding)
+        } else {
+            let mut frame = vec![0u8; len as usize];
+            match reader.read_exact(&mut frame).await {
+                Ok(_) => wire::from_bytes::<Msg<B>>(&frame).and_then(|msg| {
+                    if msg.sender == peer {
+                        Ok(msg)
+                    } else {
+                        Err(Error::Transport)
+                    }
+                }),
+                Err(_) => Err(Error::Transport),
+            }
+        };
+        let failed = result.is_err();
+        if incoming.send(result).is_err() || failed {
+            return;
+        }
+    }
+}
+
+async fn write_frames<B: Wire>(
+    mut writer: OwnedWriteHalf,
+    mut messages: UnboundedReceiver<Result<Msg<B>, Error>>,
+) {
+    while let Some(Ok(msg)) = messages.recv().await {
+        let payload = match wire::to_bytes(&msg) {
+            Ok(payload) => payload,
+            Err(_) => return,
+        };
+        let mut frame = (payload.len() as u32).to_be_bytes().to_vec();
+        frame.extend(payload);
+        if writer.write_all(&frame).await.is_err() {
+            return;
+        }
+    }
+}
+
+// drives a state machine until it is finished
+pub async fn run<M, D>(mut machine: M, delivery: D) -> Result<M::Output, Error>
+where
+    M: StateMachine + Send + 'static,
+    D: Delivery<M::MessageBody>,
+{
+    let (mut incoming, mut outgoing) = delivery.split();
+    loop {
+        // the rounds are CPU-heavy, e.g. Paillier key generation
+        let (proceeded, result) = tokio::task::spawn_blocking(move || {
+            let result = machine.proceed();
+            (machine, result)
+        })
+        .await
+        .map_err(|_| Error::Transport)?;
+        machine = proceeded;
+        result?;
+
+        let queue: Vec<_> = machine.message_queue().drain(..).collect();
+        for msg in queue {
+            outgoing.send(msg).await?;
+        }
+        if machine.is_finished() {
+            return machine.pick_output().ok_or(Error::UnexpectedOutput);
+        }
+        if machine.wants_to_proceed() {
+            continue;
+        }
+        match incoming.next().await {
+            Some(msg) => machine.handle_incoming(msg?)?,
+            None => return Err(Error::Transport),
+        }
+    }
+}
+
+pub async fn run_gg18_keygen<D: Delivery<Gg18KeyGenMsg>>(
+    threshold: u16,
+    index: u16,
+    session: SessionId,
+    identity: IdentityKey,
+    identities: Vec<Point<Secp256r1>>,
+    delivery: D,
+) -> Result<GG18SignContext, Error> {
+    run(
+        Gg18KeyGen::new(threshold, index, session, identity, identities)?,
+        delivery,
+    )
+    .await
+}
+
+pub async fn run_gg18_sign<D: Delivery<Gg18SignMsg>>(
+    context: GG18SignContext,
+    indices: Vec<u16>,
+    message_hash: Vec<u8>,
+    session: SessionId,
+    delivery: D,
+) -> Result<Signature, Error> {
+    run(
+        Gg18Signing::new(context, indices, message_hash, session)?,
+        delivery,
+    )
+    .await
+}
+
+pub async fn run_li17_p1_keygen<D: Delivery<Li17Msg>>(
+    session: SessionId,
+    delivery: D,
+) -> Result<Li17SignP1Context, Error> {
+    match run(Li17Party1::key_gen(session)?, delivery).await? {
+        Li17P1Output::KeyShare(context) => Ok(context),
+        Li17P1Output::Signature(_) => Err(Error::UnexpectedOutput),
+    }
+}
+
+pub async fn run_li17_p2_keygen<D: Delivery<Li17Msg>>(
+    session: SessionId,
+    delivery: D,
+) -> Result<Li17SignP2Context, Error> {
+    match run(Li17Party2::key_gen(session)?, delivery).await? {
+        Li17P2Output::KeyShare(context) => Ok(context),
+        Li17P2Output::Signed => Err(Error::UnexpectedOutput),
+    }
+}
+
+pub async fn run_li17_p1_sign<D: Delivery<Li17Msg>>(
+    context: Li17SignP1Context,
+    message_hash: &[u8],
+    session: SessionId,
+    delivery: D,
+) -> Result<Signature, Error> {
+    match run(Li17Party1::sign(context, message_hash, session)?, delivery).await? {
+        Li17P1Output::Signature(signature) => Ok(signature),
+        Li17P1Output::KeyShare(_) => Err(Error::UnexpectedOutput),
+    }
+}
+
+// party two does not learn the signature
+pub async fn run_li17_p2_sign<D: Delivery<Li17Msg>>(
+    context: Li17SignP2Context,
+    message_hash: &[u8],
+    session: SessionId,
+    delivery: D,
+) -> Result<(), Error> {
+    run(Li17Party2::sign(context, message_hash, session)?, delivery).await?;
+    Ok(())
+}
+
+pub async fn run_li17_p1_refresh<D: Delivery<Li17Msg>>(
+    context: Li17SignP1Context,
+    session: SessionId,
+    delivery: D,
+) -> Result<Li17SignP1Context, Error> {
+    match run(Li17Party1::refresh(context, session)?, delivery).await? {
+        Li17P1Output::KeyShare(context) => Ok(context),
+        Li17P1Output::Signature(_) => Err(Error::UnexpectedOutput),
+    }
+}
+
+pub async fn run_li17_p2_refresh<D: Delivery<Li17Msg>>(
+    context: Li17SignP2Context,
+    session: SessionId,
+    delivery: D,
+) -> Result<Li17SignP2Context, Error> {
+    match run(Li17Party2::refresh(context, session)?, delivery).await? {
+        Li17P2Output::KeyShare(context) => Ok(context),
+        Li17P2Output::Signed => Err(Error::UnexpectedOutput),
+    }
+}
@@ -0,0 +1,142 @@
+use crate::gg18_key_gen_state::Gg18KeyGenMsg;
+use crate::gg18_test::{check_sig, identities, SID};
+use crate::li17_state::Li17Msg;
+use crate::state_machine::Msg;
+use crate::transport::*;
+use crate::Error;
+use futures::future::try_join_all;
+use futures::SinkExt;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use tokio::io::AsyncWriteExt;
+use tokio::net::{TcpListener, TcpStream};
+
+#[tokio::test(flavor = "multi_thread")]
+async fn gg18_over_channels() {
+    let (keys, ids) = identities(3);
+    let runs = Channels::connect(&[0, 1, 2])
+        .into_iter()
+        .zip(keys)
+        .enumerate()
+        .map(|(i, (delivery, key))| {
+            tokio::spawn(run_gg18_keygen(
+                2,
+                i as u16,
+                SID,
+                key,
+                ids.clone(),
+                delivery,
+            ))
+        });
+    let contexts: Vec<_> = try_join_all(runs)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|c| c.unwrap())
+        .collect();
+    let pk = contexts[0].pk.clone();
+    assert!(contexts.iter().all(|c| c.pk == pk));
+
+    let message = b"transport";
+    let hash = Sha256::digest(message).to_vec();
+    let indices = vec![2u16, 0];
+    let runs = contexts
+        .into_iter()
+        .filter(|c| indices.contains(&c.index))
+        .zip(Channels::connect(&[0, 2]))
+        .map(|(c, delivery)| {
+            tokio::spawn(run_gg18_sign(
+                c,
+                indices.clone(),
+                hash.clone(),
+                SID,
+                delivery,
+            ))
+        });
+    let signatures: Vec<_> = try_join_all(runs)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|s| s.unwrap())
+        .collect();
+    assert_eq!(signatures[0], signatures[1]);
+    check_sig(&signatures[0], message, &pk);
+}
+
+async fn tcp_pair() -> (Tcp<Li17Msg>, Tcp<Li17Msg>) {
+    let l1 = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let l2 = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let peers1: BTreeMap<_, _> = vec![(2, l2.local_addr().unwrap())].into_iter().collect();
+    let peers2: BTreeMap<_, _> = vec![(1, l1.local_addr().unwrap())].into_iter().collect();
+    let p1 = tokio::spawn(async move { Tcp::connect(1, l1, &peers1).await });
+    let p2 = tokio::spawn(async move { Tcp::connect(2, l2, &peers2).await });
+    (p1.await.unwrap().unwrap(), p2.await.unwrap().unwrap())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn li17_over_tcp() {
+    let (t1, t2) = tcp_pair().await;
+    let p1 = tokio::spawn(run_li17_p1_keygen(SID, t1));
+    let p2 = tokio::spawn(run_li17_p2_keygen(SID, t2));
+    let (p1, p2) = (p1.await.unwrap().unwrap(), p2.await.unwrap().unwrap());
+    let pk = p1.public.clone();
+
+    let (t1, t2) = tcp_pair().await;
+    let p1 = tokio::spawn(run_li17_p1_refresh(p1, SID, t1));
+    let p2 = tokio::spawn(run_li17_p2_refresh(p2, SID, t2));
+    let (p1, p2) = (p1.await.unwrap().unwrap(), p2.await.unwrap().unwrap());
+    assert_eq!(p1.public, pk);
+
+    let message = b"transport";
+    let hash = Sha256::digest(message).to_vec();
+    let (t1, t2) = tcp_pair().await;
+    let hash2 = hash.clone();
+    let p1 = tokio::spawn(async move { run_li17_p1_sign(p1, &hash, SID, t1).await });
+    let p2 = tokio::spawn(async move { run_li17_p2_sign(p2, &hash2, SID, t2).await });
+    p2.await.unwrap().unwrap();
+    check_sig(&p1.await.unwrap().unwrap(), message, &pk);
+}
+
+#[tokio::test]
+async fn rejects_messages_of_other_senders() {
+    let (_, mut outgoing) = Channels::<Gg18KeyGenMsg>::connect(&[0, 1])
+        .remove(0)
+        .split();
+    let (mut keys, ids) = identities(2);
+    let msg = crate::gg18_key_gen::gg18_key_gen_1(2, 1, SID, keys.remove(1), ids)
+        .unwrap()
+        .0;
+    let result = outgoing
+        .send(Msg {
+            sender: 1,
+            receiver: None,
+            body: Gg18KeyGenMsg::Round1(msg),
+        })
+        .await;
+    assert_eq!(result.unwrap_err(), Error::Transport);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn tcp_drops_stray_connections() {
+    let l1 = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let l2 = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr1 = l1.local_addr().unwrap();
+
+    // a connection that stays silent and one that announces an unknown party
+    let _silent = TcpStream::connect(addr1).await.unwrap();
+    let mut stray = TcpStream::connect(addr1).await.unwrap();
+    stray.write_all(&9u16.to_be_bytes()).await.unwrap();
+
+    let peers1: BTreeMap<_, _> = vec![(2, l2.local_addr().unwrap())].into_iter().collect();
+    let peers2: BTreeMap<_, _> = vec![(1, addr1)].into_iter().collect();
+    let p1 = tokio::spawn(async move { Tcp::<Li17Msg>::connect(1, l1, &peers1).await });
+    let p2 = tokio::spawn(async move { Tcp::<Li17Msg>::connect(2, l2, &peers2).await });
+    let (t1, t2) = (p1.await.unwrap().unwrap(), p2.await.unwrap().unwrap());
+
+    let p1 = tokio::spawn(run_li17_p1_keygen(SID, t1));
+    let p2 = tokio::spawn(run_li17_p2_keygen(SID, t2));
+    assert_eq!(
+        p1.await.unwrap().unwrap().public,
+        p2.await.unwrap().unwrap().public
+    );
+}
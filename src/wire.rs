@@ -0,0 +1,854 @@
+use crate::persist::Protocol;
+use crate::signature::to_fixed_bytes;
+use crate::Error;
+use curv::arithmetic::traits::*;
+use curv::cryptographic_primitives::proofs::sigma_correct_homomorphic_elgamal_enc::HomoELGamalProof;
+use curv::cryptographic_primitives::proofs::sigma_dlog::DLogProof;
+use curv::cryptographic_primitives::secret_sharing::feldman_vss::VerifiableSS;
+use curv::cryptographic_primitives::twoparty::coin_flip_optimal_rounds;
+use curv::elliptic::curves::{p256::Secp256r1, Point, Scalar};
+use curv::BigInt;
+use multi_party_ecdsa::protocols::multi_party_ecdsa::gg_2018::party_i::{
+    KeyGenBroadcastMessage1, KeyGenDecommitMessage1, Phase5ADecom1, Phase5Com1, Phase5Com2,
+    Phase5DDecom2, SignBroadcastPhase1, SignDecommitPhase1,
+};
+use multi_party_ecdsa::protocols::two_party_ecdsa::lindell_2017::{party_one, party_two};
+use multi_party_ecdsa::utilities::mta::{MessageA, MessageB};
+use multi_party_ecdsa::utilities::zk_pdl_with_slack::{PDLwSlackProof, PDLwSlackStatement};
+use paillier::EncryptionKey;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use zk_paillier::zkproofs::{CompositeDLogProof, NiCorrectKeyProof};
+
+/*
+Canonical binary encoding of round messages.
+
+Every message starts with a 7 byte header:
+
+    version: u16 | protocol: u8 | phase: u8 | sender: u8 | round: u16
+
+followed by the fields of the message in order. Structs and tuples are the
+concatenation of their fields, the fields are encoded as:
+
+    u8, u16, u32, u64   big-endian, fixed width
+    bool                1 byte, 0 or 1
+    Option<T>           0 for None, 1 followed by T for Some
+    Vec<T>              count: u32 | elements, so Vec<u8> is len: u32 | bytes
+    BTreeMap<u16, T>    count: u32 | key: u16 | T | ... in increasing key order
+    SessionId           32 bytes
+    Point               33 bytes SEC1 compressed, the identity as 33 zero bytes
+    Scalar              32 bytes big-endian, less than the group order
+    BigInt              negative: bool | len: u32 | magnitude, big-endian
+                        without leading zeros, zero is 0 | 0
+    EncryptionKey       n as BigInt, n² is not sent
+    Signature           r | s, 32 bytes each
+
+The fields of the upstream messages and proofs are listed below in the order
+they are encoded in. They are taken out of the upstream types by name, the
+bytes do not depend on how upstream serializes its types. Multi-byte header
+fields are big-endian as well. Li17 messages carry the sending party (1 or 2)
+in the header, GG18 messages carry 0 as any party may send them.
+
+Decoding is strict: the header must match the expected message, every field
+must be in its canonical form and trailing bytes are rejected, so a message has
+exactly one encoding. wire_test pins the encoding of every message kind with a
+golden vector.
+*/
+
+pub const WIRE_VERSION: u16 = 1;
+
+pub const HEADER_LEN: usize = 7;
+
+// upper bound on the size of a single message, guards against huge length prefixes
+pub(crate) const MAX_MESSAGE_LEN: u64 = 1 << 24;
+
+const POINT_LEN: usize = 33;
+
+const SCALAR_LEN: usize = 32;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Phase {
+    KeyGen,
+    Sign,
+    Refresh,
+    Presign,
+    BatchSign,
+    Reshare,
+    ToGg18,
+    ToLi17,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MessageKind {
+    pub protocol: Protocol,
+    pub phase: Phase,
+    pub sender: u8,
+    pub round: u16,
+}
+
+impl MessageKind {
+    pub const LI17_KEY_GEN_P1_MSG1: MessageKind = MessageKind::li17(Phase::KeyGen, 1, 1);
+    pub const LI17_KEY_GEN_P1_MSG2: MessageKind = MessageKind::li17(Phase::KeyGen, 1, 2);
+    pub const LI17_KEY_GEN_P2_MSG1: MessageKind = MessageKind::li17(Phase::KeyGen, 2, 1);
+    pub const LI17_KEY_GEN_P2_MSG2: MessageKind = MessageKind::li17(Phase::KeyGen, 2, 2);
+    pub const LI17_SIGN_P1_MSG1: MessageKind = MessageKind::li17(Phase::Sign, 1, 1);
+    pub const LI17_SIGN_P2_MSG1: MessageKind = MessageKind::li17(Phase::Sign, 2, 1);
+    pub const LI17_SIGN_P2_MSG2: MessageKind = MessageKind::li17(Phase::Sign, 2, 2);
+    pub const LI17_REFRESH_P1_MSG1: MessageKind = MessageKind::li17(Phase::Refresh, 1, 1);
+    pub const LI17_REFRESH_P1_MSG2: MessageKind = MessageKind::li17(Phase::Refresh, 1, 2);
+    pub const LI17_REFRESH_P2_MSG1: MessageKind = MessageKind::li17(Phase::Refresh, 2, 1);
+    pub const LI17_PRESIGN_P1_MSG1: MessageKind = MessageKind::li17(Phase::Presign, 1, 1);
+    pub const LI17_PRESIGN_P2_MSG1: MessageKind = MessageKind::li17(Phase::Presign, 2, 1);
+    pub const LI17_PRESIGN_P2_MSG2: MessageKind = MessageKind::li17(Phase::Presign, 2, 2);
+    pub const LI17_PRESIGN_P2_MSG3: MessageKind = MessageKind::li17(Phase::Presign, 2, 3);
+    pub const LI17_BATCH_SIGN_P1_MSG1: MessageKind = MessageKind::li17(Phase::BatchSign, 1, 1);
+    pub const LI17_BATCH_SIGN_P2_MSG1: MessageKind = MessageKind::li17(Phase::BatchSign, 2, 1);
+    pub const LI17_BATCH_SIGN_P2_MSG2: MessageKind = MessageKind::li17(Phase::BatchSign, 2, 2);
+    pub const LI17_TO_GG18_P1_MSG1: MessageKind = MessageKind::li17(Phase::ToGg18, 1, 1);
+    pub const LI17_TO_GG18_P2_MSG1: MessageKind = MessageKind::li17(Phase::ToGg18, 2, 1);
+    pub const GG18_TO_LI17_P1_MSG1: MessageKind = MessageKind::li17(Phase::ToLi17, 1, 1);
+    pub const GG18_TO_LI17_P1_MSG2: MessageKind = MessageKind::li17(Phase::ToLi17, 1, 2);
+    pub const GG18_TO_LI17_P2_MSG1: MessageKind = MessageKind::li17(Phase::ToLi17, 2, 1);
+
+    pub const GG18_KEY_GEN_MSG1: MessageKind = MessageKind::gg18(Phase::KeyGen, 1);
+    pub const GG18_KEY_GEN_MSG2: MessageKind = MessageKind::gg18(Phase::KeyGen, 2);
+    pub const GG18_KEY_GEN_MSG3: MessageKind = MessageKind::gg18(Phase::KeyGen, 3);
+    pub const GG18_KEY_GEN_MSG4: MessageKind = MessageKind::gg18(Phase::KeyGen, 4);
+    pub const GG18_KEY_GEN_MSG5: MessageKind = MessageKind::gg18(Phase::KeyGen, 5);
+    pub const GG18_SIGN_MSG1: MessageKind = MessageKind::gg18(Phase::Sign, 1);
+    pub const GG18_SIGN_MSG2: MessageKind = MessageKind::gg18(Phase::Sign, 2);
+    pub const GG18_SIGN_MSG3: MessageKind = MessageKind::gg18(Phase::Sign, 3);
+    pub const GG18_SIGN_MSG4: MessageKind = MessageKind::gg18(Phase::Sign, 4);
+    pub const GG18_SIGN_MSG5: MessageKind = MessageKind::gg18(Phase::Sign, 5);
+    pub const GG18_SIGN_MSG6: MessageKind = MessageKind::gg18(Phase::Sign, 6);
+    pub const GG18_SIGN_MSG7: MessageKind = MessageKind::gg18(Phase::Sign, 7);
+    pub const GG18_SIGN_MSG8: MessageKind = MessageKind::gg18(Phase::Sign, 8);
+    pub const GG18_SIGN_MSG9: MessageKind = MessageKind::gg18(Phase::Sign, 9);
+    // the online phase of a presignature sends GG18_SIGN_MSG5 to GG18_SIGN_MSG9
+    pub const GG18_PRESIGN_MSG1: MessageKind = MessageKind::gg18(Phase::Presign, 1);
+    pub const GG18_PRESIGN_MSG2: MessageKind = MessageKind::gg18(Phase::Presign, 2);
+    pub const GG18_PRESIGN_MSG3: MessageKind = MessageKind::gg18(Phase::Presign, 3);
+    pub const GG18_PRESIGN_MSG4: MessageKind = MessageKind::gg18(Phase::Presign, 4);
+    pub const GG18_BATCH_SIGN_MSG1: MessageKind = MessageKind::gg18(Phase::BatchSign, 1);
+    pub const GG18_BATCH_SIGN_MSG2: MessageKind = MessageKind::gg18(Phase::BatchSign, 2);
+    pub const GG18_BATCH_SIGN_MSG3: MessageKind = MessageKind::gg18(Phase::BatchSign, 3);
+    pub const GG18_BATCH_SIGN_MSG4: MessageKind = MessageKind::gg18(Phase::BatchSign, 4);
+    pub const GG18_BATCH_SIGN_MSG5: MessageKind = MessageKind::gg18(Phase::BatchSign, 5);
+    pub const GG18_BATCH_SIGN_MSG6: MessageKind = MessageKind::gg18(Phase::BatchSign, 6);
+    pub const GG18_BATCH_SIGN_MSG7: MessageKind = MessageKind::gg18(Phase::BatchSign, 7);
+    pub const GG18_BATCH_SIGN_MSG8: MessageKind = MessageKind::gg18(Phase::BatchSign, 8);
+    pub const GG18_BATCH_SIGN_MSG9: MessageKind = MessageKind::gg18(Phase::BatchSign, 9);
+    pub const GG18_REFRESH_MSG1: MessageKind = MessageKind::gg18(Phase::Refresh, 1);
+    pub const GG18_REFRESH_MSG2: MessageKind = MessageKind::gg18(Phase::Refresh, 2);
+    // MSG2 is also what the Li17 parties deal when converting to GG18
+    pub const GG18_RESHARE_MSG1: MessageKind = MessageKind::gg18(Phase::Reshare, 1);
+    pub const GG18_RESHARE_MSG2: MessageKind = MessageKind::gg18(Phase::Reshare, 2);
+
+    const fn li17(phase: Phase, sender: u8, round: u16) -> Self {
+        MessageKind {
+            protocol: Protocol::Li17,
+            phase,
+            sender,
+            round,
+        }
+    }
+
+    const fn gg18(phase: Phase, round: u16) -> Self {
+        MessageKind {
+            protocol: Protocol::Gg18,
+            phase,
+            sender: 0,
+            round,
+        }
+    }
+
+    // round 1 to 4 of a GG18 signing or presigning run
+    pub(crate) const fn gg18_sign_round(presign: bool, round: u16) -> Self {
+        let phase = if presign { Phase::Presign } else { Phase::Sign };
+        MessageKind::gg18(phase, round)
+    }
+
+    fn to_header(self) -> [u8; HEADER_LEN] {
+        let protocol = match self.protocol {
+            Protocol::Li17 => 1,
+            Protocol::Gg18 => 2,
+        };
+        let phase = match self.phase {
+            Phase::KeyGen => 1,
+            Phase::Sign => 2,
+            Phase::Refresh => 3,
+            Phase::Presign => 4,
+            Phase::BatchSign => 5,
+            Phase::Reshare => 6,
+            Phase::ToGg18 => 7,
+            Phase::ToLi17 => 8,
+        };
+        let version = WIRE_VERSION.to_be_bytes();
+        let round = self.round.to_be_bytes();
+        [
+            version[0],
+            version[1],
+            protocol,
+            phase,
+            self.sender,
+            round[0],
+            round[1],
+        ]
+    }
+}
+
+// reads the header of an encoded message, e.g. to route it before decoding
+pub fn message_kind(bytes: &[u8]) -> Result<MessageKind, Error> {
+    if bytes.len() < HEADER_LEN {
+        return Err(Error::InvalidEncoding);
+    }
+    let version = u16::from_be_bytes([bytes[0], bytes[1]]);
+    if version != WIRE_VERSION {
+        return Err(Error::UnsupportedVersion { version });
+    }
+    let protocol = match bytes[2] {
+        1 => Protocol::Li17,
+        2 => Protocol::Gg18,
+        _ => return Err(Error::InvalidEncoding),
+    };
+    let phase = match bytes[3] {
+        1 => Phase::KeyGen,
+        2 => Phase::Sign,
+        3 => Phase::Refresh,
+        4 => Phase::Presign,
+        5 => Phase::BatchSign,
+        6 => Phase::Reshare,
+        7 => Phase::ToGg18,
+        8 => Phase::ToLi17,
+        _ => return Err(Error::InvalidEncoding),
+    };
+    Ok(MessageKind {
+        protocol,
+        phase,
+        sender: bytes[4],
+        round: u16::from_be_bytes([bytes[5], bytes[6]]),
+    })
+}
+
+pub fn encode<T: Wire>(kind: MessageKind, message: &T) -> Result<Vec<u8>, Error> {
+    let mut bytes = Vec::new();
+    write_message(kind, message, &mut bytes)?;
+    if bytes.len() as u64 > MAX_MESSAGE_LEN {
+        return Err(Error::InvalidEncoding);
+    }
+    Ok(bytes)
+}
+
+pub fn decode<T: Wire>(kind: MessageKind, bytes: &[u8]) -> Result<T, Error> {
+    if message_kind(bytes)? != kind {
+        return Err(Error::InvalidEncoding);
+    }
+    from_bytes(&bytes[HEADER_LEN..])
+}
+
+// a value without header, e.g. a transport frame around a message
+pub(crate) fn to_bytes<T: Wire>(value: &T) -> Result<Vec<u8>, Error> {
+    let mut bytes = Vec::new();
+    value.write(&mut bytes)?;
+    Ok(bytes)
+}
+
+pub(crate) fn from_bytes<T: Wire>(bytes: &[u8]) -> Result<T, Error> {
+    if bytes.len() as u64 > MAX_MESSAGE_LEN {
+        return Err(Error::InvalidEncoding);
+    }
+    let mut input = Reader { bytes };
+    let value = T::read(&mut input)?;
+    if !input.bytes.is_empty() {
+        return Err(Error::InvalidEncoding);
+    }
+    // the upstream types are rebuilt from their fields, they must encode back to the input
+    if to_bytes(&value)? != bytes {
+        return Err(Error::InvalidEncoding);
+    }
+    Ok(value)
+}
+
+// the header and the message, for the enums over the messages of a protocol
+pub(crate) fn write_message<T: Wire>(
+    kind: MessageKind,
+    message: &T,
+    out: &mut Vec<u8>,
+) -> Result<(), Error> {
+    out.extend_from_slice(&kind.to_header());
+    message.write(out)
+}
+
+pub(crate) fn read_kind(input: &mut Reader<'_>) -> Result<MessageKind, Error> {
+    message_kind(input.take(HEADER_LEN)?)
+}
+
+// a value with an explicit encoding, see the table above
+pub trait Wire: Sized {
+    fn write(&self, out: &mut Vec<u8>) -> Result<(), Error>;
+
+    fn read(input: &mut Reader<'_>) -> Result<Self, Error>;
+}
+
+// the rest of the bytes to decode
+pub struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    pub fn take(&mut self, len: usize) -> Result<&'a [u8], Error> {
+        if len > self.bytes.len() {
+            return Err(Error::InvalidEncoding);
+        }
+        let (taken, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(taken)
+    }
+}
+
+macro_rules! wire_int {
+    ($($int:ty),*) => {$(
+        impl Wire for $int {
+            fn write(&self, out: &mut Vec<u8>) -> Result<(), Error> {
+                out.extend_from_slice(&self.to_be_bytes());
+                Ok(())
+            }
+
+            fn read(input: &mut Reader<'_>) -> Result<Self, Error> {
+                let mut bytes = [0u8; std::mem::size_of::<$int>()];
+                bytes.copy_from_slice(input.take(bytes.len())?);
+                Ok(<$int>::from_be_bytes(bytes))
+            }
+        }
+    )*};
+}
+
+wire_int!(u8, u16, u32, u64);
+
+impl Wire for bool {
+    fn write(&self, out: &mut Vec<u8>) -> Result<(), Error> {
+        out.push(*self as u8);
+        Ok(())
+    }
+
+    fn read(input: &mut Reader<'_>) -> Result<Self, Error> {
+        match u8::read(input)? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(Error::InvalidEncoding),
+        }
+    }
+}
+
+// the hash choice of the upstream proofs, it is not sent
+impl Wire for () {
+    fn write(&self, _: &mut Vec<u8>) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn read(_: &mut Reader<'_>) -> Result<Self, Error> {
+        Ok(())
+    }
+}
+
+impl Wire for [u8; 32] {
+    fn write(&self, out: &mut Vec<u8>) -> Result<(), Error> {
+        out.extend_from_slice(self);
+        Ok(())
+    }
+
+    fn read(input: &mut Reader<'_>) -> Result<Self, Error> {
+        let mut bytes = [0u8; 32];
+        bytes.copy_from_slice(input.take(32)?);
+        Ok(bytes)
+    }
+}
+
+fn write_len(len: usize, out: &mut Vec<u8>) -> Result<(), Error> {
+    u32::try_from(len)
+        .map_err(|_| Error::InvalidEncoding)?
+        .write(out)
+}
+
+// a count beyond the rest of the input is rejected before reading the elements
+fn read_len(input: &mut Reader<'_>) -> Result<usize, Error> {
+    let len = u32::read(input)? as usize;
+    if len > input.bytes.len() {
+        return Err(Error::InvalidEncoding);
+    }
+    Ok(len)
+}
+
+impl<T: Wire> Wire for Option<T> {
+    fn write(&self, out: &mut Vec<u8>) -> Result<(), Error> {
+        match self {
+            None => false.write(out),
+            Some(value) => {
+                true.write(out)?;
+                value.write(out)
+            }
+        }
+    }
+
+    fn read(input: &mut Reader<'_>) -> Result<Self, Error> {
+        if bool::read(input)? {
+            Ok(Some(T::read(input)?))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+impl<T: Wire> Wire for Vec<T> {
+    fn write(&self, out: &mut Vec<u8>) -> Result<(), Error> {
+        write_len(self.len(), out)?;
+        for value in self {
+            value.write(out)?;
+        }
+        Ok(())
+    }
+
+    fn read(input: &mut Reader<'_>) -> Result<Self, Error> {
+        let len = read_len(input)?;
+        let mut values = Vec::new();
+        for _ in 0..len {
+            values.push(T::read(input)?);
+        }
+        Ok(values)
+    }
+}
+
+impl<K: Wire + Ord, V: Wire> Wire for BTreeMap<K, V> {
+    fn write(&self, out: &mut Vec<u8>) -> Result<(), Error> {
+        write_len(self.len(), out)?;
+        for (key, value) in self {
+            key.write(out)?;
+            value.write(out)?;
+        }
+        Ok(())
+    }
+
+    fn read(input: &mut Reader<'_>) -> Result<Self, Error> {
+        let len = read_len(input)?;
+        let mut map = BTreeMap::new();
+        for _ in 0..len {
+            let key = K::read(input)?;
+            // keys in increasing order, there is one encoding per map
+            if map.keys().next_back().map_or(false, |last| last >= &key) {
+                return Err(Error::InvalidEncoding);
+            }
+            map.insert(key, V::read(input)?);
+        }
+        Ok(map)
+    }
+}
+
+macro_rules! wire_tuple {
+    ($($ty:ident $value:ident),+) => {
+        impl<$($ty: Wire),+> Wire for ($($ty,)+) {
+            fn write(&self, out: &mut Vec<u8>) -> Result<(), Error> {
+                let ($($value,)+) = self;
+                $($value.write(out)?;)+
+                Ok(())
+            }
+
+            fn read(input: &mut Reader<'_>) -> Result<Self, Error> {
+                Ok(($($ty::read(input)?,)+))
+            }
+        }
+    };
+}
+
+wire_tuple!(A a, B b);
+wire_tuple!(A a, B b, C c);
+wire_tuple!(A a, B b, C c, D d);
+wire_tuple!(A a, B b, C c, D d, E e);
+wire_tuple!(A a, B b, C c, D d, E e, F f);
+wire_tuple!(A a, B b, C c, D d, E e, F f, G g);
+
+impl Wire for BigInt {
+    fn write(&self, out: &mut Vec<u8>) -> Result<(), Error> {
+        let magnitude = self.abs().to_bytes();
+        let start = magnitude
+            .iter()
+            .position(|&b| b != 0)
+            .unwrap_or(magnitude.len());
+        (self < &BigInt::zero()).write(out)?;
+        write_len(magnitude.len() - start, out)?;
+        out.extend_from_slice(&magnitude[start..]);
+        Ok(())
+    }
+
+    fn read(input: &mut Reader<'_>) -> Result<Self, Error> {
+        let negative = bool::read(input)?;
+        let len = read_len(input)?;
+        let magnitude = input.take(len)?;
+        if magnitude.is_empty() {
+            // there is no negative zero
+            return if negative {
+                Err(Error::InvalidEncoding)
+            } else {
+                Ok(BigInt::zero())
+            };
+        }
+        if magnitude[0] == 0 {
+            return Err(Error::InvalidEncoding);
+        }
+        let magnitude = BigInt::from_bytes(magnitude);
+        if negative {
+            Ok(BigInt::zero() - magnitude)
+        } else {
+            Ok(magnitude)
+        }
+    }
+}
+
+impl Wire for Point<Secp256r1> {
+    fn write(&self, out: &mut Vec<u8>) -> Result<(), Error> {
+        if self.is_zero() {
+            out.extend_from_slice(&[0u8; POINT_LEN]);
+        } else {
+            out.extend_from_slice(self.to_bytes(true).as_ref());
+        }
+        Ok(())
+    }
+
+    fn read(input: &mut Reader<'_>) -> Result<Self, Error> {
+        let bytes = input.take(POINT_LEN)?;
+        if bytes.iter().all(|&b| b == 0) {
+            return Ok(Point::zero());
+        }
+        let point = Point::from_bytes(bytes).map_err(|_| Error::InvalidEncoding)?;
+        // x must be reduced modulo p
+        if point.to_bytes(true).as_ref() != bytes {
+            return Err(Error::InvalidEncoding);
+        }
+        Ok(point)
+    }
+}
+
+impl Wire for Scalar<Secp256r1> {
+    fn write(&self, out: &mut Vec<u8>) -> Result<(), Error> {
+        out.extend_from_slice(&to_fixed_bytes(&self.to_bigint()));
+        Ok(())
+    }
+
+    fn read(input: &mut Reader<'_>) -> Result<Self, Error> {
+        let x = BigInt::from_bytes(input.take(SCALAR_LEN)?);
+        if &x >= Scalar::<Secp256r1>::group_order() {
+            return Err(Error::InvalidEncoding);
+        }
+        Ok(Scalar::from_bigint(&x))
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct EncryptionKeyFields {
+    n: BigInt,
+    nn: BigInt,
+}
+
+impl Wire for EncryptionKey {
+    fn write(&self, out: &mut Vec<u8>) -> Result<(), Error> {
+        transcode::<_, EncryptionKeyFields>(self)?.n.write(out)
+    }
+
+    fn read(input: &mut Reader<'_>) -> Result<Self, Error> {
+        let n = BigInt::read(input)?;
+        let nn = &n * &n;
+        transcode(&EncryptionKeyFields { n, nn })
+    }
+}
+
+// moves the fields of a value into a type with fields of the same names
+fn transcode<A: Serialize, B: DeserializeOwned>(value: &A) -> Result<B, Error> {
+    serde_json::to_value(value)
+        .and_then(serde_json::from_value)
+        .map_err(|_| Error::InvalidEncoding)
+}
+
+/*
+Field lists of the upstream types. Fields{...} is a struct with the fields of
+the upstream type in encoding order, an upstream value is moved into it by
+field name and the fields are encoded one after the other. Nested types that
+are not sent on their own only have the struct.
+*/
+macro_rules! wire_fields {
+    (struct $fields:ident { $($(#[$attr:meta])* $field:ident: $ty:ty,)* }) => {
+        #[allow(non_snake_case)]
+        #[derive(Serialize, Deserialize)]
+        struct $fields {
+            $($(#[$attr])* $field: $ty,)*
+        }
+
+        impl Wire for $fields {
+            fn write(&self, out: &mut Vec<u8>) -> Result<(), Error> {
+                $(self.$field.write(out)?;)*
+                Ok(())
+            }
+
+            fn read(input: &mut Reader<'_>) -> Result<Self, Error> {
+                Ok($fields {
+                    $($field: Wire::read(input)?,)*
+                })
+            }
+        }
+    };
+    ($upstream:ty as $fields:ident { $($body:tt)* }) => {
+        wire_fields!(struct $fields { $($body)* });
+
+        impl Wire for $upstream {
+            fn write(&self, out: &mut Vec<u8>) -> Result<(), Error> {
+                transcode::<_, $fields>(self)?.write(out)
+            }
+
+            fn read(input: &mut Reader<'_>) -> Result<Self, Error> {
+                transcode(&$fields::read(input)?)
+            }
+        }
+    };
+}
+
+// proofs and secret sharing of curv
+
+wire_fields!(DLogProof<Secp256r1, Sha256> as DLogProofFields {
+    pk: Point<Secp256r1>,
+    pk_t_rand_commitment: Point<Secp256r1>,
+    challenge_response: Scalar<Secp256r1>,
+    #[serde(default)]
+    hash_choice: (),
+});
+
+wire_fields!(HomoELGamalProof<Secp256r1, Sha256> as HomoELGamalProofFields {
+    T: Point<Secp256r1>,
+    A3: Point<Secp256r1>,
+    z1: Scalar<Secp256r1>,
+    z2: Scalar<Secp256r1>,
+    #[serde(default)]
+    hash_choice: (),
+});
+
+wire_fields!(
+    struct ECDDHProofFields {
+        a1: Point<Secp256r1>,
+        a2: Point<Secp256r1>,
+        z: Scalar<Secp256r1>,
+        #[serde(default)]
+        hash_choice: (),
+    }
+);
+
+wire_fields!(
+    struct PedersenProofFields {
+        e: Scalar<Secp256r1>,
+        a1: Point<Secp256r1>,
+        a2: Point<Secp256r1>,
+        com: Point<Secp256r1>,
+        z1: Scalar<Secp256r1>,
+        z2: Scalar<Secp256r1>,
+        #[serde(default)]
+        hash_choice: (),
+    }
+);
+
+wire_fields!(
+    struct PedersenBlindingProofFields {
+        e: Scalar<Secp256r1>,
+        m: Scalar<Secp256r1>,
+        a: Point<Secp256r1>,
+        com: Point<Secp256r1>,
+        z: Scalar<Secp256r1>,
+        #[serde(default)]
+        hash_choice: (),
+    }
+);
+
+wire_fields!(
+    struct ShamirSecretSharingFields {
+        threshold: u16,
+        share_count: u16,
+    }
+);
+
+wire_fields!(VerifiableSS<Secp256r1, Sha256> as VerifiableSSFields {
+    parameters: ShamirSecretSharingFields,
+    commitments: Vec<Point<Secp256r1>>,
+    #[serde(default)]
+    hash_choice: (),
+});
+
+// Paillier proofs of zk-paillier and multi-party-ecdsa
+
+wire_fields!(NiCorrectKeyProof as NiCorrectKeyProofFields {
+    sigma_vec: Vec<BigInt>,
+});
+
+wire_fields!(CompositeDLogProof as CompositeDLogProofFields {
+    x: BigInt,
+    y: BigInt,
+});
+
+wire_fields!(
+    struct DLogStatementFields {
+        N: BigInt,
+        g: BigInt,
+        ni: BigInt,
+    }
+);
+
+wire_fields!(PDLwSlackStatement as PDLwSlackStatementFields {
+    ciphertext: BigInt,
+    ek: EncryptionKey,
+    Q: Point<Secp256r1>,
+    G: Point<Secp256r1>,
+    h1: BigInt,
+    h2: BigInt,
+    N_tilde: BigInt,
+});
+
+wire_fields!(PDLwSlackProof as PDLwSlackProofFields {
+    z: BigInt,
+    u1: Point<Secp256r1>,
+    u2: BigInt,
+    u3: BigInt,
+    s1: BigInt,
+    s2: BigInt,
+    s3: BigInt,
+});
+
+wire_fields!(
+    struct AliceProofFields {
+        z: BigInt,
+        e: BigInt,
+        s: BigInt,
+        s1: BigInt,
+        s2: BigInt,
+    }
+);
+
+wire_fields!(MessageA as MessageAFields {
+    c: BigInt,
+    range_proofs: Vec<AliceProofFields>,
+});
+
+wire_fields!(MessageB as MessageBFields {
+    c: BigInt,
+    b_proof: DLogProof<Secp256r1, Sha256>,
+    beta_tag_proof: DLogProof<Secp256r1, Sha256>,
+});
+
+// GG18 messages
+
+wire_fields!(KeyGenBroadcastMessage1 as KeyGenBroadcastMessage1Fields {
+    e: EncryptionKey,
+    dlog_statement: DLogStatementFields,
+    com: BigInt,
+    correct_key_proof: NiCorrectKeyProof,
+    composite_dlog_proof_base_h1: CompositeDLogProof,
+    composite_dlog_proof_base_h2: CompositeDLogProof,
+});
+
+wire_fields!(KeyGenDecommitMessage1 as KeyGenDecommitMessage1Fields {
+    blind_factor: BigInt,
+    y_i: Point<Secp256r1>,
+});
+
+wire_fields!(SignBroadcastPhase1 as SignBroadcastPhase1Fields {
+    com: BigInt,
+});
+
+wire_fields!(SignDecommitPhase1 as SignDecommitPhase1Fields {
+    blind_factor: BigInt,
+    g_gamma_i: Point<Secp256r1>,
+});
+
+wire_fields!(Phase5Com1 as Phase5Com1Fields {
+    com: BigInt,
+});
+
+wire_fields!(Phase5Com2 as Phase5Com2Fields {
+    com: BigInt,
+});
+
+wire_fields!(Phase5ADecom1 as Phase5ADecom1Fields {
+    V_i: Point<Secp256r1>,
+    A_i: Point<Secp256r1>,
+    B_i: Point<Secp256r1>,
+    blind_factor: BigInt,
+});
+
+wire_fields!(Phase5DDecom2 as Phase5DDecom2Fields {
+    u_i: Point<Secp256r1>,
+    t_i: Point<Secp256r1>,
+    blind_factor: BigInt,
+});
+
+// Li17 messages
+
+wire_fields!(party_one::KeyGenFirstMsg as P1KeyGenFirstMsgFields {
+    pk_commitment: BigInt,
+    zk_pok_commitment: BigInt,
+});
+
+wire_fields!(
+    struct P1CommWitnessFields {
+        pk_commitment_blind_factor: BigInt,
+        zk_pok_blind_factor: BigInt,
+        public_share: Point<Secp256r1>,
+        d_log_proof: DLogProof<Secp256r1, Sha256>,
+    }
+);
+
+wire_fields!(party_one::KeyGenSecondMsg as P1KeyGenSecondMsgFields {
+    comm_witness: P1CommWitnessFields,
+});
+
+wire_fields!(party_two::KeyGenFirstMsg as P2KeyGenFirstMsgFields {
+    d_log_proof: DLogProof<Secp256r1, Sha256>,
+    public_share: Point<Secp256r1>,
+});
+
+wire_fields!(party_one::EphKeyGenFirstMsg as P1EphKeyGenFirstMsgFields {
+    d_log_proof: ECDDHProofFields,
+    public_share: Point<Secp256r1>,
+    c: Point<Secp256r1>,
+});
+
+wire_fields!(party_two::EphKeyGenFirstMsg as P2EphKeyGenFirstMsgFields {
+    pk_commitment: BigInt,
+    zk_pok_commitment: BigInt,
+});
+
+wire_fields!(
+    struct P2EphCommWitnessFields {
+        pk_commitment_blind_factor: BigInt,
+        zk_pok_blind_factor: BigInt,
+        public_share: Point<Secp256r1>,
+        d_log_proof: ECDDHProofFields,
+        c: Point<Secp256r1>,
+    }
+);
+
+wire_fields!(party_two::EphKeyGenSecondMsg as P2EphKeyGenSecondMsgFields {
+    comm_witness: P2EphCommWitnessFields,
+});
+
+wire_fields!(party_two::PartialSig as PartialSigFields {
+    c3: BigInt,
+});
+
+wire_fields!(coin_flip_optimal_rounds::Party1FirstMessage<Secp256r1, Sha256> as CoinFlipParty1FirstMessageFields {
+    proof: PedersenProofFields,
+});
+
+wire_fields!(coin_flip_optimal_rounds::Party1SecondMessage<Secp256r1, Sha256> as CoinFlipParty1SecondMessageFields {
+    proof: PedersenBlindingProofFields,
+    seed: Scalar<Secp256r1>,
+});
+
+wire_fields!(coin_flip_optimal_rounds::Party2FirstMessage<Secp256r1> as CoinFlipParty2FirstMessageFields {
+    seed: Scalar<Secp256r1>,
+});
@@ -0,0 +1,464 @@
+use crate::conversion::*;
+use crate::gg18_batch_sign::*;
+use crate::gg18_key_gen::*;
+use crate::gg18_refresh::*;
+use crate::gg18_reshare::*;
+use crate::gg18_sign::*;
+use crate::gg18_test::{identities, one};
+use crate::li17_batch_sign::*;
+use crate::li17_key_gen::*;
+use crate::li17_refresh::*;
+use crate::li17_sign::*;
+use crate::persist::Protocol;
+use crate::secret::CloneSecret;
+use crate::session::SessionId;
+use crate::signature::Signature;
+use crate::wire::{decode, encode, message_kind, MessageKind, Phase, Wire, WIRE_VERSION};
+use crate::Error;
+use curv::elliptic::curves::{p256::Secp256r1, Point, Scalar};
+use curv::BigInt;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+
+const SID: SessionId = SessionId::new([3; 32]);
+
+const VECTORS: &str = include_str!("../testdata/wire_vectors.txt");
+
+// encodes and decodes a message, checking that the encoding is stable
+fn wire<T: Wire>(kind: MessageKind, message: T) -> T {
+    let bytes = encode(kind, &message).unwrap();
+    assert_eq!(message_kind(&bytes).unwrap(), kind);
+    let decoded: T = decode(kind, &bytes).unwrap();
+    assert_eq!(encode(kind, &decoded).unwrap(), bytes);
+    decoded
+}
+
+// decodes the golden vector of a message kind, which must encode back to the same bytes
+fn golden<T: Wire>(name: &str, kind: MessageKind) -> T {
+    let line = VECTORS
+        .lines()
+        .find(|line| line.split(' ').next() == Some(name))
+        .unwrap_or_else(|| panic!("no golden vector for {}", name));
+    let bytes = hex::decode(&line[name.len() + 1..]).unwrap();
+    let message: T = decode(kind, &bytes).unwrap_or_else(|e| panic!("{}: {:?}", name, e));
+    assert_eq!(encode(kind, &message).unwrap(), bytes, "{}", name);
+    message
+}
+
+macro_rules! golden {
+    ($checked:ident, $ty:ty, $kind:ident) => {{
+        $checked.push(stringify!($kind));
+        golden::<$ty>(stringify!($kind), MessageKind::$kind)
+    }};
+}
+
+fn scalar(x: u32) -> Scalar<Secp256r1> {
+    Scalar::from_bigint(&BigInt::from(x))
+}
+
+fn point(x: u32) -> Point<Secp256r1> {
+    Point::generator() * scalar(x)
+}
+
+#[test]
+fn golden_headers() {
+    let vectors = [
+        (
+            MessageKind::LI17_KEY_GEN_P1_MSG2,
+            [0x00, 0x01, 0x01, 0x01, 0x01, 0x00, 0x02],
+        ),
+        (
+            MessageKind::LI17_SIGN_P2_MSG1,
+            [0x00, 0x01, 0x01, 0x02, 0x02, 0x00, 0x01],
+        ),
+        (
+            MessageKind::LI17_REFRESH_P2_MSG1,
+            [0x00, 0x01, 0x01, 0x03, 0x02, 0x00, 0x01],
+        ),
+        (
+            MessageKind::LI17_PRESIGN_P2_MSG3,
+            [0x00, 0x01, 0x01, 0x04, 0x02, 0x00, 0x03],
+        ),
+        (
+            MessageKind::LI17_BATCH_SIGN_P1_MSG1,
+            [0x00, 0x01, 0x01, 0x05, 0x01, 0x00, 0x01],
+        ),
+        (
+            MessageKind::LI17_TO_GG18_P2_MSG1,
+            [0x00, 0x01, 0x01, 0x07, 0x02, 0x00, 0x01],
+        ),
+        (
+            MessageKind::GG18_TO_LI17_P1_MSG2,
+            [0x00, 0x01, 0x01, 0x08, 0x01, 0x00, 0x02],
+        ),
+        (
+            MessageKind::GG18_KEY_GEN_MSG5,
+            [0x00, 0x01, 0x02, 0x01, 0x00, 0x00, 0x05],
+        ),
+        (
+            MessageKind::GG18_SIGN_MSG9,
+            [0x00, 0x01, 0x02, 0x02, 0x00, 0x00, 0x09],
+        ),
+        (
+            MessageKind::GG18_REFRESH_MSG2,
+            [0x00, 0x01, 0x02, 0x03, 0x00, 0x00, 0x02],
+        ),
+        (
+            MessageKind::GG18_PRESIGN_MSG4,
+            [0x00, 0x01, 0x02, 0x04, 0x00, 0x00, 0x04],
+        ),
+        (
+            MessageKind::GG18_BATCH_SIGN_MSG9,
+            [0x00, 0x01, 0x02, 0x05, 0x00, 0x00, 0x09],
+        ),
+        (
+            MessageKind::GG18_RESHARE_MSG1,
+            [0x00, 0x01, 0x02, 0x06, 0x00, 0x00, 0x01],
+        ),
+    ];
+    for (kind, header) in vectors.iter() {
+        assert_eq!(encode(*kind, &()).unwrap(), header.to_vec());
+        assert_eq!(message_kind(header).unwrap(), *kind);
+    }
+    assert_eq!(
+        MessageKind::GG18_SIGN_MSG9,
+        MessageKind {
+            protocol: Protocol::Gg18,
+            phase: Phase::Sign,
+            sender: 0,
+            round: 9
+        }
+    );
+}
+
+#[test]
+fn golden_fields() {
+    let message = (
+        7u16,
+        vec![1u8, 2],
+        Some(true),
+        BigInt::from(-258),
+        BigInt::from(0),
+        scalar(5),
+        point(1),
+    );
+    let expected = hex::decode(concat!(
+        "00010202000003",                                                     // header
+        "0007",                                                               // u16
+        "000000020102",                                                       // Vec<u8>
+        "0101",                                                               // Option<bool>
+        "01000000020102",                                                     // BigInt -258
+        "0000000000",                                                         // BigInt 0
+        "0000000000000000000000000000000000000000000000000000000000000005",   // Scalar
+        "036b17d1f2e12c4247f8bce6e563a440f277037d812deb33a0f4a13945d898c296", // G
+    ))
+    .unwrap();
+    assert_eq!(
+        encode(MessageKind::GG18_SIGN_MSG3, &message).unwrap(),
+        expected
+    );
+    assert_eq!(
+        decode::<(
+            u16,
+            Vec<u8>,
+            Option<bool>,
+            BigInt,
+            BigInt,
+            Scalar<Secp256r1>,
+            Point<Secp256r1>
+        )>(MessageKind::GG18_SIGN_MSG3, &expected)
+        .unwrap(),
+        message
+    );
+
+    // the identity is 33 zero bytes
+    let identity = encode(MessageKind::GG18_SIGN_MSG3, &Point::<Secp256r1>::zero()).unwrap();
+    assert_eq!(identity[7..], [0u8; 33]);
+    assert!(
+        decode::<Point<Secp256r1>>(MessageKind::GG18_SIGN_MSG3, &identity)
+            .unwrap()
+            .is_zero()
+    );
+}
+
+#[test]
+fn golden_vectors() {
+    let mut checked = Vec::new();
+
+    let msg = golden!(checked, Li17KeyGenP1Msg1, LI17_KEY_GEN_P1_MSG1);
+    assert_eq!(msg.session, SID);
+    golden!(checked, Li17KeyGenP2Msg1, LI17_KEY_GEN_P2_MSG1);
+    golden!(checked, Li17KeyGenP1Msg2, LI17_KEY_GEN_P1_MSG2);
+    let msg = golden!(checked, Li17KeyGenP2Msg2, LI17_KEY_GEN_P2_MSG2);
+    assert_eq!(msg.body, point(1));
+    golden!(checked, Li17SignP2Msg1, LI17_SIGN_P2_MSG1);
+    golden!(checked, Li17SignP1Msg1, LI17_SIGN_P1_MSG1);
+    golden!(checked, Li17SignP2Msg2, LI17_SIGN_P2_MSG2);
+    golden!(checked, Li17RefreshP1Msg1, LI17_REFRESH_P1_MSG1);
+    golden!(checked, Li17RefreshP2Msg1, LI17_REFRESH_P2_MSG1);
+    golden!(checked, Li17RefreshP1Msg2, LI17_REFRESH_P1_MSG2);
+    golden!(checked, Li17PresignP2Msg1, LI17_PRESIGN_P2_MSG1);
+    golden!(checked, Li17PresignP1Msg1, LI17_PRESIGN_P1_MSG1);
+    golden!(checked, Li17PresignP2Msg2, LI17_PRESIGN_P2_MSG2);
+    let msg = golden!(checked, Li17PresignP2Msg3, LI17_PRESIGN_P2_MSG3);
+    assert_eq!(msg.body.c3, BigInt::from(0x010101));
+    golden!(checked, Li17BatchSignP2Msg1, LI17_BATCH_SIGN_P2_MSG1);
+    golden!(checked, Li17BatchSignP1Msg1, LI17_BATCH_SIGN_P1_MSG1);
+    let msg = golden!(checked, Li17BatchSignP2Msg2, LI17_BATCH_SIGN_P2_MSG2);
+    assert!(msg.body[0].is_some() && msg.body[1].is_none());
+
+    let msg = golden!(checked, Li17ToGg18Msg1, LI17_TO_GG18_P1_MSG1);
+    assert_eq!(msg.body.0.n, BigInt::from(0x010101));
+    assert_eq!(msg.body.0.nn, &msg.body.0.n * &msg.body.0.n);
+    golden!(checked, Li17ToGg18Msg2, LI17_TO_GG18_P2_MSG1);
+    golden!(checked, Gg18ToLi17Msg1, GG18_TO_LI17_P1_MSG1);
+    golden!(checked, Gg18ToLi17Msg2, GG18_TO_LI17_P2_MSG1);
+    let msg = golden!(checked, Gg18ToLi17Msg3, GG18_TO_LI17_P1_MSG2);
+    assert_eq!(msg.body, scalar(1));
+
+    golden!(checked, GG18KeyGenMsg1, GG18_KEY_GEN_MSG1);
+    golden!(checked, GG18KeyGenMsg2, GG18_KEY_GEN_MSG2);
+    let msg = golden!(checked, GG18KeyGenMsg3, GG18_KEY_GEN_MSG3);
+    assert_eq!(msg.body, vec![1, 2, 3, 4]);
+    let msg = golden!(checked, GG18KeyGenMsg4, GG18_KEY_GEN_MSG4);
+    assert_eq!(msg.body.parameters.threshold, 1);
+    assert_eq!(msg.body.parameters.share_count, 3);
+    assert_eq!(msg.body.commitments, vec![point(1), point(2)]);
+    golden!(checked, GG18KeyGenMsg5, GG18_KEY_GEN_MSG5);
+
+    golden!(checked, GG18SignMsg1, GG18_SIGN_MSG1);
+    golden!(checked, GG18SignMsg2, GG18_SIGN_MSG2);
+    let msg = golden!(checked, GG18SignMsg3, GG18_SIGN_MSG3);
+    assert_eq!(msg.body, scalar(1));
+    assert_eq!(
+        msg.signature,
+        Signature::from_scalars(&scalar(1), &scalar(2))
+    );
+    golden!(checked, GG18SignMsg4, GG18_SIGN_MSG4);
+    golden!(checked, GG18SignMsg5, GG18_SIGN_MSG5);
+    golden!(checked, GG18SignMsg6, GG18_SIGN_MSG6);
+    golden!(checked, GG18SignMsg7, GG18_SIGN_MSG7);
+    golden!(checked, GG18SignMsg8, GG18_SIGN_MSG8);
+    golden!(checked, GG18SignMsg9, GG18_SIGN_MSG9);
+    golden!(checked, GG18SignMsg1, GG18_PRESIGN_MSG1);
+    golden!(checked, GG18SignMsg2, GG18_PRESIGN_MSG2);
+    golden!(checked, GG18SignMsg3, GG18_PRESIGN_MSG3);
+    golden!(checked, GG18SignMsg4, GG18_PRESIGN_MSG4);
+    golden!(checked, GG18BatchSignMsg1, GG18_BATCH_SIGN_MSG1);
+    golden!(checked, GG18BatchSignMsg2, GG18_BATCH_SIGN_MSG2);
+    golden!(checked, GG18BatchSignMsg3, GG18_BATCH_SIGN_MSG3);
+    golden!(checked, GG18BatchSignMsg4, GG18_BATCH_SIGN_MSG4);
+    golden!(checked, GG18BatchSignMsg5, GG18_BATCH_SIGN_MSG5);
+    golden!(checked, GG18BatchSignMsg6, GG18_BATCH_SIGN_MSG6);
+    golden!(checked, GG18BatchSignMsg7, GG18_BATCH_SIGN_MSG7);
+    golden!(checked, GG18BatchSignMsg8, GG18_BATCH_SIGN_MSG8);
+    golden!(checked, GG18BatchSignMsg9, GG18_BATCH_SIGN_MSG9);
+    golden!(checked, GG18RefreshMsg1, GG18_REFRESH_MSG1);
+    let msg = golden!(checked, GG18RefreshMsg2, GG18_REFRESH_MSG2);
+    assert_eq!(msg.body.1.keys().copied().collect::<Vec<_>>(), vec![1, 2]);
+    golden!(checked, GG18ReshareMsg1, GG18_RESHARE_MSG1);
+    golden!(checked, GG18ReshareMsg2, GG18_RESHARE_MSG2);
+
+    // every vector of the file is checked
+    let names: Vec<&str> = VECTORS
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .filter_map(|line| line.split(' ').next())
+        .collect();
+    assert_eq!(names, checked);
+}
+
+#[test]
+fn strict_decoding() {
+    let kind = MessageKind::LI17_SIGN_P1_MSG1;
+    let bytes = encode(kind, &(1u16, 2u16)).unwrap();
+
+    let mut trailing = bytes.clone();
+    trailing.push(0);
+    assert_eq!(
+        decode::<(u16, u16)>(kind, &trailing),
+        Err(Error::InvalidEncoding)
+    );
+    assert_eq!(
+        decode::<(u16, u16)>(kind, &bytes[..bytes.len() - 1]),
+        Err(Error::InvalidEncoding)
+    );
+    assert_eq!(
+        decode::<(u16, u16)>(kind, &bytes[..3]),
+        Err(Error::InvalidEncoding)
+    );
+    assert_eq!(
+        decode::<(u16, u16)>(MessageKind::LI17_SIGN_P2_MSG1, &bytes),
+        Err(Error::InvalidEncoding)
+    );
+
+    let mut future = bytes.clone();
+    future[1] = 2;
+    assert_eq!(
+        decode::<(u16, u16)>(kind, &future),
+        Err(Error::UnsupportedVersion {
+            version: WIRE_VERSION + 1
+        })
+    );
+
+    let mut unknown_protocol = bytes.clone();
+    unknown_protocol[2] = 9;
+    assert_eq!(message_kind(&unknown_protocol), Err(Error::InvalidEncoding));
+    let mut unknown_phase = bytes;
+    unknown_phase[3] = 9;
+    assert_eq!(message_kind(&unknown_phase), Err(Error::InvalidEncoding));
+
+    // invalid bool and option tag
+    let mut bytes = encode(kind, &Some(true)).unwrap();
+    bytes[8] = 2;
+    assert_eq!(
+        decode::<Option<bool>>(kind, &bytes),
+        Err(Error::InvalidEncoding)
+    );
+    bytes[7] = 2;
+    assert_eq!(
+        decode::<Option<bool>>(kind, &bytes),
+        Err(Error::InvalidEncoding)
+    );
+
+    // a map with unsorted or repeated keys
+    let unsorted = encode(kind, &vec![(2u16, 0u8), (1u16, 0u8)]).unwrap();
+    assert_eq!(
+        decode::<BTreeMap<u16, u8>>(kind, &unsorted),
+        Err(Error::InvalidEncoding)
+    );
+    let repeated = encode(kind, &vec![(1u16, 0u8), (1u16, 0u8)]).unwrap();
+    assert_eq!(
+        decode::<BTreeMap<u16, u8>>(kind, &repeated),
+        Err(Error::InvalidEncoding)
+    );
+
+    // huge length prefix
+    let mut bytes = encode(kind, &vec![0u8; 4]).unwrap();
+    bytes[7] = 0xff;
+    assert_eq!(decode::<Vec<u8>>(kind, &bytes), Err(Error::InvalidEncoding));
+
+    // BigInt with a leading zero byte or negative zero
+    let mut bytes = encode(kind, &BigInt::from(0x0102)).unwrap();
+    bytes[11] = 3;
+    bytes.insert(12, 0);
+    assert_eq!(decode::<BigInt>(kind, &bytes), Err(Error::InvalidEncoding));
+    let mut bytes = encode(kind, &BigInt::from(0)).unwrap();
+    bytes[7] = 1;
+    assert_eq!(decode::<BigInt>(kind, &bytes), Err(Error::InvalidEncoding));
+
+    // scalar not less than the group order
+    let q = Scalar::<Secp256r1>::group_order();
+    let mut bytes = encode(kind, &scalar(1)).unwrap();
+    bytes[7..].copy_from_slice(&crate::signature::to_fixed_bytes(q));
+    assert_eq!(
+        decode::<Scalar<Secp256r1>>(kind, &bytes),
+        Err(Error::InvalidEncoding)
+    );
+
+    // point with an invalid prefix or off the curve
+    let good = encode(kind, &point(1)).unwrap();
+    let mut bytes = good.clone();
+    bytes[7] = 0x04;
+    assert_eq!(
+        decode::<Point<Secp256r1>>(kind, &bytes),
+        Err(Error::InvalidEncoding)
+    );
+    let mut bytes = good;
+    bytes[7] = 0x00;
+    assert_eq!(
+        decode::<Point<Secp256r1>>(kind, &bytes),
+        Err(Error::InvalidEncoding)
+    );
+}
+
+#[test]
+fn li17_over_the_wire() {
+    let (msg1p1, context1p1) = li17_p1_key_gen1(SID).unwrap();
+    let msg1p1 = wire(MessageKind::LI17_KEY_GEN_P1_MSG1, msg1p1);
+    let (msg1p2, context1p2) = li17_p2_key_gen1(msg1p1, SID).unwrap();
+    let msg1p2 = wire(MessageKind::LI17_KEY_GEN_P2_MSG1, msg1p2);
+    let (msg2p1, sign_context_p1) = li17_p1_key_gen2(msg1p2, context1p1).unwrap();
+    let msg2p1 = wire(MessageKind::LI17_KEY_GEN_P1_MSG2, msg2p1);
+    let (pk, sign_context_p2) = li17_p2_key_gen2(msg2p1, context1p2).unwrap();
+    let pk = wire(MessageKind::LI17_KEY_GEN_P2_MSG2, pk).body;
+    assert_eq!(pk, sign_context_p1.public);
+
+    let (rmsg1p1, rcontext1p1) = li17_p1_refresh1(sign_context_p1, SID).unwrap();
+    let rmsg1p1 = wire(MessageKind::LI17_REFRESH_P1_MSG1, rmsg1p1);
+    let (rmsg1p2, rcontext1p2) = li17_p2_refresh1(rmsg1p1, sign_context_p2, SID).unwrap();
+    let rmsg1p2 = wire(MessageKind::LI17_REFRESH_P2_MSG1, rmsg1p2);
+    let (rmsg2p1, sign_context_p1) = li17_p1_refresh2(rmsg1p2, rcontext1p1).unwrap();
+    let rmsg2p1 = wire(MessageKind::LI17_REFRESH_P1_MSG2, rmsg2p1);
+    let sign_context_p2 = li17_p2_refresh2(rmsg2p1, rcontext1p2).unwrap();
+
+    let message = b"over the wire";
+    let hash = Sha256::digest(message).to_vec();
+    let (smsg1p2, context1p2) = li17_p2_sign1(sign_context_p2, &hash, SID).unwrap();
+    let smsg1p2 = wire(MessageKind::LI17_SIGN_P2_MSG1, smsg1p2);
+    let (smsg1p1, context1p1) = li17_p1_sign1(smsg1p2, sign_context_p1, &hash, SID).unwrap();
+    let smsg1p1 = wire(MessageKind::LI17_SIGN_P1_MSG1, smsg1p1);
+    let smsg2p2 = li17_p2_sign2(smsg1p1, context1p2).unwrap();
+    let smsg2p2 = wire(MessageKind::LI17_SIGN_P2_MSG2, smsg2p2);
+    let sig = li17_p1_sign2(smsg2p2, context1p1).unwrap();
+    crate::li17_test::check_sig(&sig, message, &pk);
+}
+
+#[test]
+fn gg18_over_the_wire() {
+    let (keys, ids) = identities(2);
+    let (msg1_a, ctx1_a) = gg18_key_gen_1(2, 0, SID, keys[0].clone_secret(), ids.clone()).unwrap();
+    let (msg1_b, ctx1_b) = gg18_key_gen_1(2, 1, SID, keys[1].clone_secret(), ids).unwrap();
+    let kind = MessageKind::GG18_KEY_GEN_MSG1;
+    let (msg2_a, ctx2_a) = gg18_key_gen_2(one(1, wire(kind, msg1_b)), ctx1_a).unwrap();
+    let (msg2_b, ctx2_b) = gg18_key_gen_2(one(0, wire(kind, msg1_a)), ctx1_b).unwrap();
+    let kind = MessageKind::GG18_KEY_GEN_MSG2;
+    let (msg3_a, ctx3_a) = gg18_key_gen_3(one(1, wire(kind, msg2_b)), ctx2_a).unwrap();
+    let (msg3_b, ctx3_b) = gg18_key_gen_3(one(0, wire(kind, msg2_a)), ctx2_b).unwrap();
+    let kind = MessageKind::GG18_KEY_GEN_MSG3;
+    let (msg4_a, ctx4_a) = gg18_key_gen_4(one(1, wire(kind, msg3_b[&0].clone())), ctx3_a).unwrap();
+    let (msg4_b, ctx4_b) = gg18_key_gen_4(one(0, wire(kind, msg3_a[&1].clone())), ctx3_b).unwrap();
+    let kind = MessageKind::GG18_KEY_GEN_MSG4;
+    let (msg5_a, ctx5_a) = gg18_key_gen_5(one(1, wire(kind, msg4_b)), ctx4_a).unwrap();
+    let (msg5_b, ctx5_b) = gg18_key_gen_5(one(0, wire(kind, msg4_a)), ctx4_b).unwrap();
+    let kind = MessageKind::GG18_KEY_GEN_MSG5;
+    let sign_a = gg18_key_gen_6(one(1, wire(kind, msg5_b)), ctx5_a).unwrap();
+    let sign_b = gg18_key_gen_6(one(0, wire(kind, msg5_a)), ctx5_b).unwrap();
+    let pk = sign_a.pk.clone();
+
+    let message = b"over the wire";
+    let hash = Sha256::digest(message).to_vec();
+    let indices = vec![0u16, 1];
+    let (msg1_a, ctx1_a) = gg18_sign1(sign_a, indices.clone(), 0, hash.clone(), SID).unwrap();
+    let (msg1_b, ctx1_b) = gg18_sign1(sign_b, indices, 1, hash, SID).unwrap();
+    let kind = MessageKind::GG18_SIGN_MSG1;
+    let (msg2_a, ctx2_a) = gg18_sign2(one(1, wire(kind, msg1_b)), ctx1_a).unwrap();
+    let (msg2_b, ctx2_b) = gg18_sign2(one(0, wire(kind, msg1_a)), ctx1_b).unwrap();
+    let kind = MessageKind::GG18_SIGN_MSG2;
+    let (msg3_a, ctx3_a) = gg18_sign3(one(1, wire(kind, msg2_b[&0].clone())), ctx2_a).unwrap();
+    let (msg3_b, ctx3_b) = gg18_sign3(one(0, wire(kind, msg2_a[&1].clone())), ctx2_b).unwrap();
+    let kind = MessageKind::GG18_SIGN_MSG3;
+    let (msg4_a, ctx4_a) = gg18_sign4(one(1, wire(kind, msg3_b)), ctx3_a).unwrap();
+    let (msg4_b, ctx4_b) = gg18_sign4(one(0, wire(kind, msg3_a)), ctx3_b).unwrap();
+    let kind = MessageKind::GG18_SIGN_MSG4;
+    let (msg5_a, ctx5_a) = gg18_sign5(one(1, wire(kind, msg4_b)), ctx4_a).unwrap();
+    let (msg5_b, ctx5_b) = gg18_sign5(one(0, wire(kind, msg4_a)), ctx4_b).unwrap();
+    let kind = MessageKind::GG18_SIGN_MSG5;
+    let (msg6_a, ctx6_a) = gg18_sign6(one(1, wire(kind, msg5_b)), ctx5_a).unwrap();
+    let (msg6_b, ctx6_b) = gg18_sign6(one(0, wire(kind, msg5_a)), ctx5_b).unwrap();
+    let kind = MessageKind::GG18_SIGN_MSG6;
+    let (msg7_a, ctx7_a) = gg18_sign7(one(1, wire(kind, msg6_b)), ctx6_a).unwrap();
+    let (msg7_b, ctx7_b) = gg18_sign7(one(0, wire(kind, msg6_a)), ctx6_b).unwrap();
+    let kind = MessageKind::GG18_SIGN_MSG7;
+    let (msg8_a, ctx8_a) = gg18_sign8(one(1, wire(kind, msg7_b)), ctx7_a).unwrap();
+    let (msg8_b, ctx8_b) = gg18_sign8(one(0, wire(kind, msg7_a)), ctx7_b).unwrap();
+    let kind = MessageKind::GG18_SIGN_MSG8;
+    let (msg9_a, ctx9_a) = gg18_sign9(one(1, wire(kind, msg8_b)), ctx8_a).unwrap();
+    let (msg9_b, ctx9_b) = gg18_sign9(one(0, wire(kind, msg8_a)), ctx8_b).unwrap();
+    let kind = MessageKind::GG18_SIGN_MSG9;
+    let sig_a = gg18_sign10(one(1, wire(kind, msg9_b)), ctx9_a).unwrap();
+    let sig_b = gg18_sign10(one(0, wire(kind, msg9_a)), ctx9_b).unwrap();
+
+    assert_eq!(sig_a, sig_b);
+    crate::gg18_test::check_sig(&sig_a, message, &pk);
+}